@@ -0,0 +1,277 @@
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+
+use crate::{
+    appearance::page_annotations, pdf::text_string, permissions::Permission, xobject::as_number,
+    Pdf,
+};
+
+/// Where link annotation leads
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    /// Open URI with URI action
+    Uri(String),
+    /// Jump to explicit destination array in document, as built by `Destination::to_object`
+    Dest(Object),
+}
+
+/// Link annotation (Subtype Link)
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkAnnotation {
+    /// Clickable area as [x0, y0, x1, y1]
+    pub rect: [f32; 4],
+    pub target: LinkTarget,
+}
+
+/// Text annotation shown as note icon (Subtype Text)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextAnnotation {
+    /// Area of icon as [x0, y0, x1, y1]
+    pub rect: [f32; 4],
+    pub contents: String,
+    /// Whether popup is initially open
+    pub open: bool,
+    /// Name of icon, such as Comment, Note or Help
+    pub icon: Option<String>,
+}
+
+/// Annotation of page
+#[derive(Clone, Debug, PartialEq)]
+pub enum Annotation {
+    Link(LinkAnnotation),
+    Text(TextAnnotation),
+    /// Annotation of other subtype, or one which could not be parsed, kept as is
+    Other(Dictionary),
+}
+
+fn rect_object(rect: [f32; 4]) -> Object {
+    rect.iter()
+        .map(|&x| Object::Real(x))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn parse_rect(dict: &Dictionary) -> Option<[f32; 4]> {
+    let values: Vec<f32> = dict
+        .get(b"Rect")
+        .and_then(Object::as_array)
+        .ok()?
+        .iter()
+        .filter_map(as_number)
+        .collect();
+
+    values.try_into().ok()
+}
+
+/// Decode text string in PDFDocEncoding (taken as Latin-1) or UTF-16BE with BOM
+fn decode_text(obj: &Object) -> Option<String> {
+    let bytes = obj.as_str().ok()?;
+
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|x| u16::from_be_bytes([x[0], x[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => Some(bytes.iter().map(|&x| x as char).collect()),
+    }
+}
+
+/// Get entries of /Annots of page PAGE_ID, following reference to indirect array
+pub(crate) fn annots_array(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+    match doc.get_dictionary(page_id).and_then(|x| x.get(b"Annots")) {
+        Ok(Object::Reference(id)) => doc
+            .get_object(*id)
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        Ok(Object::Array(annots)) => annots.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Check whether ANNOT refers to link annotation
+pub(crate) fn is_link(doc: &Document, annot: &Object) -> bool {
+    let dict = match annot {
+        Object::Reference(id) => doc.get_dictionary(*id),
+        _ => annot.as_dict(),
+    };
+
+    dict.and_then(|x| x.get(b"Subtype"))
+        .and_then(Object::as_name)
+        .ok()
+        == Some(&b"Link"[..])
+}
+
+impl LinkAnnotation {
+    /// Parse link annotation DICT, resolving references in DOC. None if it is not a link
+    /// or leads to something other than URI or destination.
+    pub fn from_dict(doc: &Document, dict: &Dictionary) -> Option<Self> {
+        if dict.get(b"Subtype").and_then(Object::as_name).ok()? != b"Link" {
+            return None;
+        }
+        let rect = parse_rect(dict)?;
+
+        let resolve = |obj: &Object| match obj {
+            Object::Reference(id) => doc.get_object(*id).ok().cloned(),
+            _ => Some(obj.clone()),
+        };
+
+        let target = if let Ok(dest) = dict.get(b"Dest") {
+            LinkTarget::Dest(resolve(dest)?)
+        } else {
+            let action = resolve(dict.get(b"A").ok()?)?;
+            let action = action.as_dict().ok()?;
+            match action.get(b"S").and_then(Object::as_name).ok()? {
+                b"URI" => {
+                    let uri = action.get(b"URI").and_then(Object::as_str).ok()?;
+                    LinkTarget::Uri(String::from_utf8_lossy(uri).into_owned())
+                }
+                b"GoTo" => LinkTarget::Dest(resolve(action.get(b"D").ok()?)?),
+                _ => return None,
+            }
+        };
+
+        Some(Self { rect, target })
+    }
+
+    pub fn to_dict(&self) -> Dictionary {
+        let mut dict = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => rect_object(self.rect),
+            "Border" => vec![0.into(), 0.into(), 0.into()],
+            "F" => 4,
+        };
+
+        match &self.target {
+            LinkTarget::Uri(uri) => dict.set(
+                "A",
+                dictionary! {
+                    "S" => "URI",
+                    "URI" => Object::string_literal(uri.as_str()),
+                },
+            ),
+            LinkTarget::Dest(dest) => dict.set("Dest", dest.clone()),
+        }
+
+        dict
+    }
+}
+
+impl TextAnnotation {
+    /// Parse text annotation DICT. None if it is not a text annotation.
+    pub fn from_dict(dict: &Dictionary) -> Option<Self> {
+        if dict.get(b"Subtype").and_then(Object::as_name).ok()? != b"Text" {
+            return None;
+        }
+
+        Some(Self {
+            rect: parse_rect(dict)?,
+            contents: dict
+                .get(b"Contents")
+                .ok()
+                .and_then(decode_text)
+                .unwrap_or_default(),
+            open: dict.get(b"Open").and_then(Object::as_bool).unwrap_or(false),
+            icon: dict
+                .get(b"Name")
+                .and_then(Object::as_name_str)
+                .ok()
+                .map(str::to_string),
+        })
+    }
+
+    pub fn to_dict(&self) -> Dictionary {
+        let mut dict = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => rect_object(self.rect),
+            "Contents" => text_string(&self.contents),
+            "Open" => self.open,
+            "F" => 4,
+        };
+        if let Some(icon) = &self.icon {
+            dict.set("Name", Object::Name(icon.as_bytes().to_vec()));
+        }
+
+        dict
+    }
+}
+
+impl Annotation {
+    /// Parse annotation DICT, resolving references in DOC
+    pub fn from_dict(doc: &Document, dict: &Dictionary) -> Self {
+        if let Some(link) = LinkAnnotation::from_dict(doc, dict) {
+            Self::Link(link)
+        } else if let Some(text) = TextAnnotation::from_dict(dict) {
+            Self::Text(text)
+        } else {
+            Self::Other(dict.clone())
+        }
+    }
+
+    pub fn to_dict(&self) -> Dictionary {
+        match self {
+            Self::Link(link) => link.to_dict(),
+            Self::Text(text) => text.to_dict(),
+            Self::Other(dict) => dict.clone(),
+        }
+    }
+}
+
+impl From<LinkAnnotation> for Annotation {
+    fn from(link: LinkAnnotation) -> Self {
+        Self::Link(link)
+    }
+}
+
+impl From<TextAnnotation> for Annotation {
+    fn from(text: TextAnnotation) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl Pdf {
+    /// Get annotations of PAGE
+    pub fn annotations(&self, page: u32) -> crate::Result<Vec<Annotation>> {
+        let page_id = self.get_page_id(page)?;
+
+        Ok(page_annotations(&self.doc, page_id)
+            .into_iter()
+            .filter_map(|id| self.doc.get_dictionary(id).ok())
+            .map(|dict| Annotation::from_dict(&self.doc, dict))
+            .collect())
+    }
+
+    /// Add ANNOTATION to PAGE after existing ones, returning its id
+    pub fn add_annotation(
+        &mut self,
+        page: u32,
+        annotation: &Annotation,
+    ) -> crate::Result<ObjectId> {
+        self.check_permission(Permission::Annotate)?;
+
+        let page_id = self.get_page_id(page)?;
+        self.push_annotation(page_id, annotation)
+    }
+
+    /// Add ANNOTATION object to page PAGE_ID after existing ones, returning its id
+    pub(crate) fn push_annotation(
+        &mut self,
+        page_id: ObjectId,
+        annotation: &Annotation,
+    ) -> crate::Result<ObjectId> {
+        let mut dict = annotation.to_dict();
+        dict.set("P", page_id);
+        let annot_id = self.doc.add_object(dict);
+
+        // Annots may be indirect array shared with other pages, so it is copied into page
+        let mut annots = annots_array(&self.doc, page_id);
+        annots.push(annot_id.into());
+        self.doc.get_dictionary_mut(page_id)?.set("Annots", annots);
+
+        Ok(annot_id)
+    }
+}
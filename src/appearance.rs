@@ -0,0 +1,728 @@
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+
+use crate::{permissions::Permission, xobject::as_number, Pdf};
+
+/// Field flags (Ff) of button fields telling their kind
+const RADIO_FLAG: i64 = 1 << 15;
+const PUSHBUTTON_FLAG: i64 = 1 << 16;
+/// Field flag (Ff) of choice fields which are combo boxes rather than list boxes
+const COMBO_FLAG: i64 = 1 << 17;
+
+/// Get annotation ids of page, resolving referenced /Annots array
+pub(crate) fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let annots = doc
+        .get_dictionary(page_id)
+        .and_then(|x| x.get(b"Annots"))
+        .and_then(|x| match x {
+            Object::Reference(id) => doc.get_object(*id),
+            _ => Ok(x),
+        })
+        .and_then(Object::as_array);
+
+    match annots {
+        Ok(arr) => arr.iter().filter_map(|x| x.as_reference().ok()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn numbers(obj: Option<&Object>) -> Vec<f32> {
+    match obj {
+        Some(Object::Array(arr)) => arr.iter().filter_map(as_number).collect(),
+        _ => vec![],
+    }
+}
+
+pub(crate) fn rect(annot: &Dictionary) -> Option<[f32; 4]> {
+    match numbers(annot.get(b"Rect").ok())[..] {
+        [x0, y0, x1, y1] => Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]),
+        _ => None,
+    }
+}
+
+/// Operation setting color from /C or /IC array; None if transparent
+fn color_op(color: &[f32], stroke: bool) -> Option<Operation> {
+    let op = match (color.len(), stroke) {
+        (1, false) => "g",
+        (1, true) => "G",
+        (3, false) => "rg",
+        (3, true) => "RG",
+        (4, false) => "k",
+        (4, true) => "K",
+        _ => return None,
+    };
+
+    Some(Operation::new(
+        op,
+        color.iter().map(|&x| Object::Real(x)).collect(),
+    ))
+}
+
+fn border_width(annot: &Dictionary) -> f32 {
+    if let Ok(Object::Dictionary(bs)) = annot.get(b"BS") {
+        return bs.get(b"W").ok().and_then(as_number).unwrap_or(1.0);
+    }
+
+    match numbers(annot.get(b"Border").ok())[..] {
+        [_, _, w, ..] => w,
+        _ => 1.0,
+    }
+}
+
+fn op(operator: &str, operands: Vec<Object>) -> Operation {
+    Operation::new(operator, operands)
+}
+
+fn reals(values: &[f32]) -> Vec<Object> {
+    values.iter().map(|&x| Object::Real(x)).collect()
+}
+
+fn form_xobject(width: f32, height: f32, ops: Vec<Operation>) -> anyhow::Result<Stream> {
+    form_xobject_with_resources(width, height, ops, None)
+}
+
+fn form_xobject_with_resources(
+    width: f32,
+    height: f32,
+    ops: Vec<Operation>,
+    resources: Option<Dictionary>,
+) -> anyhow::Result<Stream> {
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => reals(&[0.0, 0.0, width, height]),
+    };
+    if let Some(resources) = resources {
+        dict.set("Resources", resources);
+    }
+
+    Ok(Stream::new(dict, Content { operations: ops }.encode()?))
+}
+
+/// Build rectangle border, as drawn by Link and Square annotation
+fn rectangle(annot: &Dictionary, width: f32, height: f32) -> Vec<Operation> {
+    let border = border_width(annot);
+    let stroke = color_op(&numbers(annot.get(b"C").ok()), true);
+    let fill = color_op(&numbers(annot.get(b"IC").ok()), false);
+
+    let mut ops = Vec::new();
+    if border <= 0.0 && fill.is_none() {
+        return ops;
+    }
+
+    let half = border / 2.0;
+    ops.push(op("w", reals(&[border])));
+    ops.extend(stroke.clone());
+    ops.extend(fill.clone());
+    ops.push(op(
+        "re",
+        reals(&[half, half, width - border, height - border]),
+    ));
+    ops.push(match (stroke.is_some() && border > 0.0, fill.is_some()) {
+        (true, true) => op("B", vec![]),
+        (false, true) => op("f", vec![]),
+        _ => op("S", vec![]),
+    });
+
+    ops
+}
+
+/// Build ellipse inscribed in rect, approximated by four bezier curves
+fn ellipse(annot: &Dictionary, width: f32, height: f32) -> Vec<Operation> {
+    let border = border_width(annot);
+    let stroke = color_op(&numbers(annot.get(b"C").ok()), true);
+    let fill = color_op(&numbers(annot.get(b"IC").ok()), false);
+
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let (rx, ry) = ((width - border) / 2.0, (height - border) / 2.0);
+    // control point distance for quarter circle
+    let (kx, ky) = (rx * 0.552_284_8, ry * 0.552_284_8);
+
+    let mut ops = vec![op("w", reals(&[border]))];
+    ops.extend(stroke.clone());
+    ops.extend(fill.clone());
+    ops.extend([
+        op("m", reals(&[cx + rx, cy])),
+        op(
+            "c",
+            reals(&[cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry]),
+        ),
+        op(
+            "c",
+            reals(&[cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy]),
+        ),
+        op(
+            "c",
+            reals(&[cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry]),
+        ),
+        op(
+            "c",
+            reals(&[cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy]),
+        ),
+    ]);
+    ops.push(match (stroke.is_some() && border > 0.0, fill.is_some()) {
+        (true, true) => op("b", vec![]),
+        (false, true) => op("f", vec![]),
+        _ => op("s", vec![]),
+    });
+
+    ops
+}
+
+/// Build Highlight, Underline, StrikeOut or Squiggly from /QuadPoints
+fn text_markup(annot: &Dictionary, subtype: &[u8], origin: (f32, f32)) -> Vec<Operation> {
+    let Some(color) = color_op(&numbers(annot.get(b"C").ok()), subtype != b"Highlight") else {
+        return vec![];
+    };
+
+    let points = numbers(annot.get(b"QuadPoints").ok());
+    let mut ops = vec![color];
+
+    if subtype == b"Highlight" {
+        // multiply blend mode keeps text under highlight readable
+        ops.push(op("gs", vec![Object::Name(b"GS0".to_vec())]));
+    }
+
+    for quad in points.chunks_exact(8) {
+        // QuadPoints order is upper-left, upper-right, lower-left, lower-right
+        let p: Vec<f32> = quad
+            .chunks_exact(2)
+            .flat_map(|x| [x[0] - origin.0, x[1] - origin.1])
+            .collect();
+        let height = (p[1] - p[5]).abs();
+
+        match subtype {
+            b"Highlight" => ops.extend([
+                op("m", reals(&[p[4], p[5]])),
+                op("l", reals(&[p[6], p[7]])),
+                op("l", reals(&[p[2], p[3]])),
+                op("l", reals(&[p[0], p[1]])),
+                op("f", vec![]),
+            ]),
+            b"Underline" | b"StrikeOut" => {
+                let offset = if subtype == b"Underline" {
+                    height * 0.1
+                } else {
+                    height * 0.5
+                };
+                ops.extend([
+                    op("w", reals(&[(height * 0.07).max(0.5)])),
+                    op("m", reals(&[p[4], p[5] + offset])),
+                    op("l", reals(&[p[6], p[7] + offset])),
+                    op("S", vec![]),
+                ]);
+            }
+            b"Squiggly" => {
+                let step = (height * 0.15).max(1.0);
+                let mut x = p[4];
+                ops.push(op("w", reals(&[(height * 0.05).max(0.5)])));
+                ops.push(op("m", reals(&[x, p[5]])));
+                let mut up = true;
+                while x < p[6] {
+                    x = (x + step).min(p[6]);
+                    ops.push(op("l", reals(&[x, p[5] + if up { step } else { 0.0 }])));
+                    up = !up;
+                }
+                ops.push(op("S", vec![]));
+            }
+            _ => {}
+        }
+    }
+
+    ops
+}
+
+/// Build Line and Ink annotation from their path coordinates
+fn polyline(annot: &Dictionary, subtype: &[u8], origin: (f32, f32)) -> Vec<Operation> {
+    let Some(color) = color_op(&numbers(annot.get(b"C").ok()), true) else {
+        return vec![];
+    };
+
+    let paths: Vec<Vec<f32>> = match subtype {
+        b"Line" => vec![numbers(annot.get(b"L").ok())],
+        _ => match annot.get(b"InkList") {
+            Ok(Object::Array(arr)) => arr.iter().map(|x| numbers(Some(x))).collect(),
+            _ => vec![],
+        },
+    };
+
+    let mut ops = vec![color, op("w", reals(&[border_width(annot)]))];
+    for path in paths {
+        for (i, point) in path.chunks_exact(2).enumerate() {
+            let operator = if i == 0 { "m" } else { "l" };
+            ops.push(op(
+                operator,
+                reals(&[point[0] - origin.0, point[1] - origin.1]),
+            ));
+        }
+        ops.push(op("S", vec![]));
+    }
+
+    ops
+}
+
+/// Encode S as literal string for simple font, replacing characters outside Latin-1
+fn latin1(s: &str) -> Object {
+    let bytes = s
+        .chars()
+        .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+        .collect();
+    Object::String(bytes, lopdf::StringFormat::Literal)
+}
+
+/// Parse font name, size and color operation from default appearance string DA
+fn parse_da(da: &[u8]) -> (Vec<u8>, f32, Option<Operation>) {
+    let tokens: Vec<&str> = std::str::from_utf8(da)
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+
+    let mut font = b"Helv".to_vec();
+    let mut size = 0.0;
+    let mut color = None;
+
+    for (i, &token) in tokens.iter().enumerate() {
+        let args = |n: usize| -> Vec<f32> {
+            tokens[i.saturating_sub(n)..i]
+                .iter()
+                .filter_map(|x| x.parse().ok())
+                .collect()
+        };
+
+        match token {
+            "Tf" if i >= 2 => {
+                font = tokens[i - 2].trim_start_matches('/').as_bytes().to_vec();
+                size = tokens[i - 1].parse().unwrap_or(0.0);
+            }
+            "g" => color = color_op(&args(1), false),
+            "rg" => color = color_op(&args(3), false),
+            "k" => color = color_op(&args(4), false),
+            _ => {}
+        }
+    }
+
+    (font, size, color)
+}
+
+pub(crate) fn field_value(doc: &Document, annot: &Dictionary, key: &[u8]) -> Option<Object> {
+    // inheritable field attribute, possibly defined on parent field
+    let mut dict = annot;
+    for _ in 0..32 {
+        if let Ok(value) = dict.get(key) {
+            return Some(value.clone());
+        }
+        dict = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|x| doc.get_dictionary(x))
+            .ok()?;
+    }
+    None
+}
+
+fn widget_resources(doc: &Document, font: &[u8]) -> Dictionary {
+    let dr_font = doc
+        .catalog()
+        .and_then(|x| x.get(b"AcroForm"))
+        .and_then(|x| match x {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            _ => x.as_dict(),
+        })
+        .and_then(|x| x.get(b"DR"))
+        .and_then(|x| match x {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            _ => x.as_dict(),
+        })
+        .and_then(|x| x.get(b"Font"))
+        .and_then(|x| match x {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            _ => x.as_dict(),
+        })
+        .and_then(|x| x.get(font))
+        .ok()
+        .cloned();
+
+    let font_obj = dr_font.unwrap_or_else(|| {
+        dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        }
+        .into()
+    });
+
+    let mut fonts = Dictionary::new();
+    fonts.set(font.to_vec(), font_obj);
+
+    dictionary! { "Font" => fonts }
+}
+
+/// Get font name, size and color operation of widget from its /DA, with size fitting
+/// HEIGHT if it is auto (0)
+fn widget_da(doc: &Document, annot: &Dictionary, height: f32) -> (Vec<u8>, f32, Option<Operation>) {
+    let da = field_value(doc, annot, b"DA")
+        .and_then(|x| x.as_str().ok().map(<[u8]>::to_vec))
+        .unwrap_or_else(|| b"/Helv 0 Tf 0 g".to_vec());
+    let (font, size, color) = parse_da(&da);
+    let size = if size > 0.0 {
+        size
+    } else {
+        (height * 0.7).max(4.0)
+    };
+
+    (font, size, color)
+}
+
+/// Get text strings of field value VALUE, which is single string or array of them
+fn text_values(value: Option<Object>) -> Vec<String> {
+    match value {
+        Some(Object::String(bytes, _)) => vec![crate::pdf::decode_text_string(&bytes)],
+        Some(Object::Array(arr)) => arr
+            .iter()
+            .filter_map(|x| x.as_str().ok())
+            .map(crate::pdf::decode_text_string)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Get options of choice field as pairs of export value and displayed text
+fn choice_options(doc: &Document, annot: &Dictionary) -> Vec<(String, String)> {
+    let opt = match field_value(doc, annot, b"Opt") {
+        Some(Object::Reference(id)) => doc.get_object(id).ok().cloned(),
+        x => x,
+    };
+    let Some(Object::Array(opt)) = opt else {
+        return vec![];
+    };
+
+    opt.iter()
+        .filter_map(|x| match x {
+            Object::String(bytes, _) => {
+                let text = crate::pdf::decode_text_string(bytes);
+                Some((text.clone(), text))
+            }
+            Object::Array(pair) => match &text_values(Some(Object::Array(pair.clone())))[..] {
+                [export, display] => Some((export.clone(), display.clone())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check whether S can be shown by simple font of form, which is encoded as Latin-1
+fn is_latin1(s: &str) -> bool {
+    s.chars().all(|c| (c as u32) < 256)
+}
+
+/// Build appearance of text field or combo box widget showing VALUE with its /DA.
+/// returns None if VALUE is out of Latin-1, as fonts of form can't show it.
+fn text_widget(
+    doc: &Document,
+    annot: &Dictionary,
+    width: f32,
+    height: f32,
+    value: &str,
+) -> anyhow::Result<Option<Stream>> {
+    if !is_latin1(value) {
+        return Ok(None);
+    }
+    let (font, size, color) = widget_da(doc, annot, height);
+
+    let mut ops = vec![
+        op("BMC", vec!["Tx".into()]),
+        op("q", vec![]),
+        op("re", reals(&[1.0, 1.0, width - 2.0, height - 2.0])),
+        op("W", vec![]),
+        op("n", vec![]),
+        op("BT", vec![]),
+        op("Tf", vec![Object::Name(font.clone()), Object::Real(size)]),
+    ];
+    ops.extend(color);
+    ops.extend([
+        op("Td", reals(&[2.0, (height - size) / 2.0 + size * 0.22])),
+        op("Tj", vec![latin1(value)]),
+        op("ET", vec![]),
+        op("Q", vec![]),
+        op("EMC", vec![]),
+    ]);
+
+    form_xobject_with_resources(width, height, ops, Some(widget_resources(doc, &font))).map(Some)
+}
+
+/// Build appearance of choice field widget. combo box shows its value, with displayed
+/// text of option if it has one, and list box shows options from /TI with selected ones
+/// highlighted. returns None if text to show is out of Latin-1.
+fn choice_widget(
+    doc: &Document,
+    annot: &Dictionary,
+    width: f32,
+    height: f32,
+) -> anyhow::Result<Option<Stream>> {
+    let flags = field_value(doc, annot, b"Ff")
+        .and_then(|x| x.as_i64().ok())
+        .unwrap_or(0);
+    let options = choice_options(doc, annot);
+    let selected = text_values(field_value(doc, annot, b"V"));
+
+    if flags & COMBO_FLAG != 0 {
+        let value = selected.first().cloned().unwrap_or_default();
+        let text = options
+            .iter()
+            .find(|(export, _)| *export == value)
+            .map_or(value.clone(), |(_, display)| display.clone());
+        return text_widget(doc, annot, width, height, &text);
+    }
+
+    // auto font size of list box is that of one line of 12 points at most
+    let (font, size, color) = widget_da(doc, annot, height.min(12.0 / 0.7));
+    let line = size * 1.15;
+    let top = annot
+        .get(b"TI")
+        .and_then(Object::as_i64)
+        .unwrap_or(0)
+        .max(0) as usize;
+    let visible: Vec<(f32, &(String, String))> = options
+        .iter()
+        .skip(top)
+        .enumerate()
+        .map(|(i, x)| (height - 1.0 - line * (i + 1) as f32, x))
+        .take_while(|&(y, _)| y + line > 0.0)
+        .collect();
+    if !visible.iter().all(|(_, (_, display))| is_latin1(display)) {
+        return Ok(None);
+    }
+
+    let mut ops = vec![
+        op("BMC", vec!["Tx".into()]),
+        op("q", vec![]),
+        op("re", reals(&[1.0, 1.0, width - 2.0, height - 2.0])),
+        op("W", vec![]),
+        op("n", vec![]),
+    ];
+    // highlight color viewers use for selected items
+    ops.push(op("rg", reals(&[0.6, 0.75, 0.86])));
+    for (y, (export, _)) in &visible {
+        if selected.contains(export) {
+            ops.push(op("re", reals(&[1.0, *y, width - 2.0, line])));
+            ops.push(op("f", vec![]));
+        }
+    }
+    ops.extend([
+        op("BT", vec![]),
+        op("Tf", vec![Object::Name(font.clone()), Object::Real(size)]),
+    ]);
+    ops.extend(color);
+    for (y, (_, display)) in &visible {
+        ops.push(op("Tm", reals(&[1.0, 0.0, 0.0, 1.0, 2.0, y + size * 0.22])));
+        ops.push(op("Tj", vec![latin1(display)]));
+    }
+    ops.extend([op("ET", vec![]), op("Q", vec![]), op("EMC", vec![])]);
+
+    form_xobject_with_resources(width, height, ops, Some(widget_resources(doc, &font))).map(Some)
+}
+
+/// Build on and off appearance of check box widget, or of radio button widget if RADIO.
+/// returns None for radio button without existing appearance, as its on state is not known.
+fn checkbox_widget(
+    doc: &Document,
+    annot: &Dictionary,
+    width: f32,
+    height: f32,
+    radio: bool,
+) -> anyhow::Result<Option<(Vec<u8>, Stream, Stream)>> {
+    let on_state = annot
+        .get(b"AP")
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"N"))
+        .and_then(Object::as_dict)
+        .ok()
+        .and_then(|x| x.iter().map(|(k, _)| k.clone()).find(|k| k != b"Off"));
+    let on_state = match on_state {
+        Some(state) => state,
+        None if radio => return Ok(None),
+        None => b"Yes".to_vec(),
+    };
+
+    let size = height.min(width) * 0.8;
+    let resources = dictionary! {
+        "Font" => dictionary! {
+            "ZaDb" => dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "ZapfDingbats",
+            },
+        },
+    };
+
+    let (_, _, color) = parse_da(
+        &field_value(doc, annot, b"DA")
+            .and_then(|x| x.as_str().ok().map(<[u8]>::to_vec))
+            .unwrap_or_default(),
+    );
+
+    let mut ops = vec![
+        op("q", vec![]),
+        op("BT", vec![]),
+        op("Tf", vec!["ZaDb".into(), Object::Real(size)]),
+    ];
+    ops.extend(color);
+    ops.extend([
+        op(
+            "Td",
+            reals(&[(width - size * 0.75) / 2.0, (height - size * 0.7) / 2.0]),
+        ),
+        // check mark and filled circle glyphs of ZapfDingbats
+        op(
+            "Tj",
+            vec![Object::string_literal(if radio { "l" } else { "4" })],
+        ),
+        op("ET", vec![]),
+        op("Q", vec![]),
+    ]);
+
+    let on = form_xobject_with_resources(width, height, ops, Some(resources))?;
+    let off = form_xobject(width, height, vec![])?;
+
+    Ok(Some((on_state, on, off)))
+}
+
+impl Pdf {
+    /// (Re)build normal appearance streams of annotations on all pages.
+    /// supports links, square and circle, text markup, line, ink, and text, choice, check
+    /// box and radio button widgets.
+    pub fn regenerate_appearances(&mut self) -> anyhow::Result<()> {
+        let annot_ids: Vec<ObjectId> = self
+            .doc
+            .get_pages()
+            .into_values()
+            .flat_map(|page_id| page_annotations(&self.doc, page_id))
+            .collect();
+
+        // check all permissions first so that nothing is changed on failure
+        for &annot_id in &annot_ids {
+            self.check_appearance_permission(annot_id)?;
+        }
+        for annot_id in annot_ids {
+            self.regenerate_appearance(annot_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that appearance of ANNOT_ID may be changed:
+    /// widgets need FillForms, other annotations need Annotate
+    fn check_appearance_permission(&self, annot_id: ObjectId) -> anyhow::Result<()> {
+        let annot = self.doc.get_dictionary(annot_id)?;
+        let permission = match annot.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Widget") => Permission::FillForms,
+            _ => Permission::Annotate,
+        };
+        Ok(self.check_permission(permission)?)
+    }
+
+    /// (Re)build normal appearance stream of annotation ANNOT_ID
+    pub fn regenerate_appearance(&mut self, annot_id: ObjectId) -> anyhow::Result<()> {
+        self.check_appearance_permission(annot_id)?;
+        let annot = self.doc.get_dictionary(annot_id)?;
+        let Some([x0, y0, x1, y1]) = rect(annot) else {
+            return Ok(());
+        };
+        let (width, height) = (x1 - x0, y1 - y0);
+        let subtype = annot.get(b"Subtype").and_then(Object::as_name)?.to_vec();
+
+        let appearance = match subtype.as_slice() {
+            b"Link" | b"Square" => form_xobject(width, height, rectangle(annot, width, height))?,
+            b"Circle" => form_xobject(width, height, ellipse(annot, width, height))?,
+            b"Highlight" | b"Underline" | b"StrikeOut" | b"Squiggly" => {
+                let ops = text_markup(annot, &subtype, (x0, y0));
+                let resources = (subtype == b"Highlight").then(|| {
+                    dictionary! {
+                        "ExtGState" => dictionary! {
+                            "GS0" => dictionary! {
+                                "Type" => "ExtGState",
+                                "BM" => "Multiply",
+                            },
+                        },
+                    }
+                });
+                form_xobject_with_resources(width, height, ops, resources)?
+            }
+            b"Line" | b"Ink" => form_xobject(width, height, polyline(annot, &subtype, (x0, y0)))?,
+            b"Widget" => {
+                let field_type = field_value(&self.doc, annot, b"FT");
+                let field_type = field_type.as_ref().and_then(|x| x.as_name().ok());
+
+                let widget = match field_type {
+                    Some(b"Tx") => {
+                        let value = text_values(field_value(&self.doc, annot, b"V"));
+                        let value = value.first().map_or("", String::as_str);
+                        text_widget(&self.doc, annot, width, height, value)?
+                    }
+                    Some(b"Ch") => choice_widget(&self.doc, annot, width, height)?,
+                    Some(b"Btn") => {
+                        let flags = field_value(&self.doc, annot, b"Ff")
+                            .and_then(|x| x.as_i64().ok())
+                            .unwrap_or(0);
+                        // push buttons show captions or icons which can't be made from field
+                        if flags & PUSHBUTTON_FLAG != 0 {
+                            return Ok(());
+                        }
+                        let radio = flags & RADIO_FLAG != 0;
+                        let Some((on_state, on, off)) =
+                            checkbox_widget(&self.doc, annot, width, height, radio)?
+                        else {
+                            return Ok(());
+                        };
+                        let on_id = self.doc.add_object(on);
+                        let off_id = self.doc.add_object(off);
+
+                        let mut states = Dictionary::new();
+                        states.set(on_state, on_id);
+                        states.set("Off", off_id);
+
+                        self.doc
+                            .get_dictionary_mut(annot_id)?
+                            .set("AP", dictionary! { "N" => states });
+                        return Ok(());
+                    }
+                    _ => return Ok(()),
+                };
+                match widget {
+                    Some(widget) => widget,
+                    None => return self.defer_appearance(annot_id),
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        let ap_id = self.doc.add_object(appearance);
+        self.doc
+            .get_dictionary_mut(annot_id)?
+            .set("AP", dictionary! { "N" => ap_id });
+
+        Ok(())
+    }
+
+    /// Leave appearance of widget ANNOT_ID to viewers by NeedAppearances, as its value
+    /// can't be shown by fonts of form. stale appearance is removed.
+    fn defer_appearance(&mut self, annot_id: ObjectId) -> anyhow::Result<()> {
+        log::warn!(
+            "value of widget {} {} is out of Latin-1, leaving its appearance to viewers",
+            annot_id.0,
+            annot_id.1
+        );
+        self.doc.get_dictionary_mut(annot_id)?.remove(b"AP");
+
+        let form_id = self.get_or_create_acro_form()?;
+        self.doc
+            .get_dictionary_mut(form_id)?
+            .set("NeedAppearances", true);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::path::Path;
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::{
+    pdf::{decode_text_string, text_string},
+    permissions::Permission,
+    redact::is_referenced,
+    Pdf,
+};
+
+/// File embedded in document
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    /// Key in /EmbeddedFiles name tree
+    pub name: String,
+    /// File name from /UF or /F of file specification
+    pub file_name: String,
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Guess MIME type from file extension, for /Subtype of embedded file stream
+fn mime_type(name: &str) -> Option<&'static str> {
+    let ext = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+
+    Some(match ext.as_str() {
+        "xml" => "text/xml",
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        _ => return None,
+    })
+}
+
+fn deref<'a>(doc: &'a Document, obj: &'a Object) -> anyhow::Result<&'a Object> {
+    Ok(match obj {
+        Object::Reference(id) => doc.get_object(*id)?,
+        _ => obj,
+    })
+}
+
+/// Collect (key, value) pairs of name tree rooted at NODE in key order
+pub(crate) fn name_tree_entries(
+    doc: &Document,
+    node: &Dictionary,
+) -> anyhow::Result<Vec<(Vec<u8>, Object)>> {
+    let mut result = vec![];
+
+    if let Ok(names) = node.get(b"Names") {
+        for pair in deref(doc, names)?.as_array()?.chunks_exact(2) {
+            result.push((pair[0].as_str()?.to_vec(), pair[1].clone()));
+        }
+    }
+
+    if let Ok(kids) = node.get(b"Kids") {
+        for kid in deref(doc, kids)?.as_array()? {
+            result.extend(name_tree_entries(doc, deref(doc, kid)?.as_dict()?)?);
+        }
+    }
+
+    Ok(result)
+}
+
+impl Pdf {
+    fn embedded_files(&self) -> anyhow::Result<Vec<(Vec<u8>, Object)>> {
+        let names = match self.doc.catalog()?.get(b"Names") {
+            Ok(names) => deref(&self.doc, names)?.as_dict()?,
+            Err(_) => return Ok(vec![]),
+        };
+
+        match names.get(b"EmbeddedFiles") {
+            Ok(tree) => name_tree_entries(&self.doc, deref(&self.doc, tree)?.as_dict()?),
+            Err(_) => Ok(vec![]),
+        }
+    }
+
+    /// Get dictionary of catalog /Names, creating it if missing
+    fn names_dict_mut(&mut self) -> anyhow::Result<&mut Dictionary> {
+        let names_id = match self.doc.catalog()?.get(b"Names") {
+            Ok(Object::Reference(id)) => Some(*id),
+            Ok(_) => None,
+            Err(_) => {
+                self.catalog_mut()?.set("Names", Dictionary::new());
+                None
+            }
+        };
+
+        match names_id {
+            Some(id) => Ok(self.doc.get_dictionary_mut(id)?),
+            None => Ok(self.catalog_mut()?.get_mut(b"Names")?.as_dict_mut()?),
+        }
+    }
+
+    /// Embed DATA as file NAME, registering it in /EmbeddedFiles and catalog /AF.
+    /// existing attachment with same name is replaced, and taken out of /AF.
+    pub fn add_attachment(&mut self, name: &str, data: &[u8]) -> anyhow::Result<ObjectId> {
+        self.check_permission(Permission::Modify)?;
+
+        let mut file_dict = dictionary! {
+            "Type" => "EmbeddedFile",
+            "Params" => dictionary! {
+                "Size" => data.len() as i64,
+                "ModDate" => self.now(),
+            },
+        };
+        if let Some(mime) = mime_type(name) {
+            file_dict.set("Subtype", Object::Name(mime.as_bytes().to_vec()));
+        }
+
+        let mut stream = Stream::new(file_dict, data.to_vec());
+        let _ = stream.compress();
+        let file_id = self.doc.add_object(stream);
+
+        // /F is for old readers which do not understand Unicode /UF
+        let ascii_name: String = name
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '_' })
+            .collect();
+        let spec_id = self.doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(ascii_name),
+            "UF" => text_string(name),
+            "EF" => dictionary! { "F" => file_id, "UF" => file_id },
+            "AFRelationship" => "Unspecified",
+        });
+
+        let key = text_string(name).as_str()?.to_vec();
+        let (replaced, mut entries): (Vec<_>, Vec<_>) = self
+            .embedded_files()?
+            .into_iter()
+            .partition(|(k, _)| decode_text_string(k) == name);
+        let replaced: Vec<ObjectId> = replaced
+            .iter()
+            .filter_map(|(_, v)| v.as_reference().ok())
+            .collect();
+        entries.push((key, spec_id.into()));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // rewrite tree as single leaf node
+        let names: Vec<Object> = entries
+            .into_iter()
+            .flat_map(|(k, v)| [Object::String(k, StringFormat::Hexadecimal), v])
+            .collect();
+        self.names_dict_mut()?
+            .set("EmbeddedFiles", dictionary! { "Names" => names });
+
+        let af_id = match self.doc.catalog()?.get(b"AF") {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        };
+        let af = match af_id {
+            Some(id) => self.doc.get_object_mut(id)?,
+            None => {
+                let catalog = self.catalog_mut()?;
+                if catalog.get(b"AF").and_then(Object::as_array).is_err() {
+                    catalog.set("AF", Vec::<Object>::new());
+                }
+                catalog.get_mut(b"AF")?
+            }
+        };
+        match af {
+            Object::Array(af) => {
+                af.retain(|x| x.as_reference().map_or(true, |id| !replaced.contains(&id)));
+                af.push(spec_id.into());
+            }
+            af => *af = vec![spec_id.into()].into(),
+        }
+
+        self.remove_file_specs(&replaced);
+        Ok(spec_id)
+    }
+
+    /// Remove file specifications SPECS and their embedded files, unless something
+    /// else still refers to them
+    fn remove_file_specs(&mut self, specs: &[ObjectId]) {
+        for &spec_id in specs {
+            let Ok(spec) = self.doc.get_dictionary(spec_id) else {
+                continue;
+            };
+            let files: Vec<ObjectId> = spec
+                .get(b"EF")
+                .ok()
+                .and_then(|x| deref(&self.doc, x).ok())
+                .and_then(|x| x.as_dict().ok())
+                .map(|ef| {
+                    ef.iter()
+                        .filter_map(|(_, x)| x.as_reference().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            if is_referenced(&self.doc, spec_id) {
+                continue;
+            }
+            self.doc.objects.remove(&spec_id);
+
+            for id in files {
+                if !is_referenced(&self.doc, id) {
+                    self.doc.objects.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// List files embedded in document through /EmbeddedFiles
+    pub fn attachments(&self) -> anyhow::Result<Vec<Attachment>> {
+        let mut result = vec![];
+
+        for (key, spec) in self.embedded_files()? {
+            let spec = deref(&self.doc, &spec)?.as_dict()?;
+
+            let text = |key: &[u8]| {
+                spec.get(key)
+                    .and_then(Object::as_str)
+                    .ok()
+                    .map(decode_text_string)
+            };
+
+            let ef = deref(&self.doc, spec.get(b"EF")?)?.as_dict()?;
+            let file = ef.get(b"UF").or_else(|_| ef.get(b"F"))?;
+            let stream = deref(&self.doc, file)?.as_stream()?;
+
+            let data = if stream.dict.has(b"Filter") {
+                stream.decompressed_content()?
+            } else {
+                stream.content.clone()
+            };
+
+            let name = decode_text_string(&key);
+            result.push(Attachment {
+                file_name: text(b"UF")
+                    .or_else(|| text(b"F"))
+                    .unwrap_or_else(|| name.clone()),
+                name,
+                mime_type: stream
+                    .dict
+                    .get(b"Subtype")
+                    .and_then(Object::as_name_str)
+                    .ok()
+                    .map(String::from),
+                description: text(b"Desc"),
+                data,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Write all attachments into directory DIR, returning written paths
+    pub fn extract_attachments<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        std::fs::create_dir_all(&dir)?;
+
+        let mut written = vec![];
+        for attachment in self.attachments()? {
+            // never let embedded name escape target directory
+            let Some(file_name) = Path::new(&attachment.file_name).file_name() else {
+                continue;
+            };
+
+            let path = dir.as_ref().join(file_name);
+            std::fs::write(&path, &attachment.data)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn af_entries(pdf: &Pdf) -> Vec<ObjectId> {
+        let af = pdf.doc.catalog().unwrap().get(b"AF").unwrap();
+        deref(&pdf.doc, af)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x.as_reference().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn add_attachment_replaces_spec_in_indirect_af() {
+        let mut pdf = Pdf::new();
+        let other = pdf.doc.add_object(dictionary! { "Type" => "Filespec" });
+        let af_id = pdf.doc.add_object(vec![Object::Reference(other)]);
+        pdf.catalog_mut().unwrap().set("AF", af_id);
+
+        let old = pdf.add_attachment("data.csv", b"a,b").unwrap();
+        let new = pdf.add_attachment("data.csv", b"c,d").unwrap();
+
+        let af = pdf.doc.catalog().unwrap().get(b"AF").unwrap();
+        assert_eq!(af.as_reference().unwrap(), af_id);
+        assert_eq!(af_entries(&pdf), [other, new]);
+        assert!(pdf.doc.get_object(old).is_err());
+
+        let attachments = pdf.attachments().unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].data, b"c,d");
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeSet;
+
+use lopdf::{Document, Object, ObjectId};
+
+use crate::xobject::as_number;
+
+/// Get page attribute KEY as stored, looking up page tree for inherited one.
+/// only Resources, MediaBox, CropBox and Rotate are inheritable (7.7.3.4 of ISO 32000-1).
+pub(crate) fn inherited_attribute<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut dict = doc.get_dictionary(page_id).ok()?;
+    let mut visited = BTreeSet::from([page_id]);
+
+    loop {
+        if let Ok(value) = dict.get(key) {
+            return Some(value);
+        }
+
+        // broken page tree may have cycle of parents
+        let parent = dict.get(b"Parent").and_then(Object::as_reference).ok()?;
+        if !visited.insert(parent) {
+            return None;
+        }
+        dict = doc.get_dictionary(parent).ok()?;
+    }
+}
+
+/// Get page attribute KEY like `inherited_attribute`, following reference to its value
+pub(crate) fn page_attribute<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    match inherited_attribute(doc, page_id, key)? {
+        Object::Reference(id) => doc.get_object(*id).ok(),
+        value => Some(value),
+    }
+}
+
+/// Get rectangle attribute KEY of page, like TrimBox, as [x0, y0, x1, y1]
+pub(crate) fn page_box(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<[f32; 4]> {
+    let values: Vec<f32> = page_attribute(doc, page_id, key)?
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|x| match x {
+            Object::Reference(id) => doc.get_object(*id).ok().and_then(as_number),
+            _ => as_number(x),
+        })
+        .collect();
+
+    match values[..] {
+        [x0, y0, x1, y1] => Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]),
+        _ => None,
+    }
+}
+
+/// Get MediaBox of page as [x0, y0, x1, y1]
+pub(crate) fn media_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    // US Letter, default of most viewers
+    page_box(doc, page_id, b"MediaBox").unwrap_or([0.0, 0.0, 612.0, 792.0])
+}
+
+/// Get CropBox of page as [x0, y0, x1, y1], which is MediaBox if not set
+pub(crate) fn crop_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    page_box(doc, page_id, b"CropBox").unwrap_or_else(|| media_box(doc, page_id))
+}
+
+/// Get clockwise rotation of page in degrees, one of 0, 90, 180 or 270
+pub(crate) fn rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    page_attribute(doc, page_id, b"Rotate")
+        .and_then(|x| x.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360)
+}
+
+/// Get visible area of page as it is displayed, after CropBox and Rotate, as
+/// [0, 0, width, height], with matrix mapping that space into default user space
+pub(crate) fn display_space(doc: &Document, page_id: ObjectId) -> ([f32; 4], [f32; 6]) {
+    let [x0, y0, x1, y1] = crop_box(doc, page_id);
+    let (width, height) = (x1 - x0, y1 - y0);
+
+    // top left corner of display is at lower left, upper left, upper right and lower right
+    // corner of crop box as page is turned
+    match rotation(doc, page_id) {
+        90 => ([0.0, 0.0, height, width], [0.0, 1.0, -1.0, 0.0, x1, y0]),
+        180 => ([0.0, 0.0, width, height], [-1.0, 0.0, 0.0, -1.0, x1, y1]),
+        270 => ([0.0, 0.0, height, width], [0.0, -1.0, 1.0, 0.0, x0, y1]),
+        _ => ([0.0, 0.0, width, height], [1.0, 0.0, 0.0, 1.0, x0, y0]),
+    }
+}
@@ -0,0 +1,164 @@
+use image::GrayImage;
+use lopdf::Object;
+
+use crate::{
+    attributes::crop_box, limits::LimitExceeded, permissions::Permission, selection::PageSelection,
+    xobject, Pdf,
+};
+
+/// Gray level below which pixel counts as ink or border
+const DARK_LEVEL: u8 = 128;
+
+/// Fraction of dark pixels over which line is taken as black scanner border
+const BORDER_RATIO: f32 = 0.6;
+
+/// Fraction of dark pixels under which line is taken as blank, allowing for dust
+const BLANK_RATIO: f32 = 0.005;
+
+/// Check whether line of PIXELS is black border or blank margin rather than content
+fn is_background(pixels: impl Iterator<Item = u8>) -> bool {
+    let (mut dark, mut total) = (0, 0);
+    for pixel in pixels {
+        dark += (pixel < DARK_LEVEL) as usize;
+        total += 1;
+    }
+
+    let ratio = dark as f32 / total.max(1) as f32;
+    ratio >= BORDER_RATIO || ratio <= BLANK_RATIO
+}
+
+/// Find box of content in IMAGE as [left, top, right, bottom] in pixels, trimming black
+/// borders and blank margins from each edge. None if image has no content.
+fn content_box(image: &GrayImage) -> Option<[u32; 4]> {
+    let (width, height) = image.dimensions();
+    let row = |y: u32| is_background((0..width).map(|x| image.get_pixel(x, y)[0]));
+    let column = |x: u32, top: u32, bottom: u32| {
+        is_background((top..bottom).map(|y| image.get_pixel(x, y)[0]))
+    };
+
+    let top = (0..height).find(|&y| !row(y))?;
+    let bottom = (top..height).rev().find(|&y| !row(y))? + 1;
+    let left = (0..width).find(|&x| !column(x, top, bottom))?;
+    let right = (left..width).rev().find(|&x| !column(x, top, bottom))? + 1;
+
+    Some([left, top, right, bottom])
+}
+
+impl Pdf {
+    /// Set CropBox of image pages of PAGES to content of their image, so that black
+    /// borders and blank margins of scans are hidden, leaving PADDING points around
+    /// content. pages without image or content are left as is. returns number of
+    /// cropped pages.
+    pub fn auto_crop(&mut self, pages: &PageSelection, padding: f32) -> anyhow::Result<usize> {
+        self.check_permission(Permission::Modify)?;
+        let page_ids = self.doc.get_pages();
+        let mut count = 0;
+
+        for page in pages.page_set(page_ids.len() as u32)? {
+            self.check_deadline()?;
+            let page_id = page_ids[&page];
+
+            let Some(placement) = xobject::largest_placement(&self.doc, page_id)? else {
+                log::info!("page {} has no image to crop to", page);
+                continue;
+            };
+            let Ok(Object::Stream(stream)) = self.doc.get_object(placement.id) else {
+                continue;
+            };
+            let image = match self.decode_image(stream) {
+                Ok(image) => image.to_luma8(),
+                Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                Err(err) => {
+                    log::warn!("skipping image of page {}: {}", page, err);
+                    continue;
+                }
+            };
+            let (width, height) = image.dimensions();
+            let Some([left, top, right, bottom]) = content_box(&image) else {
+                log::info!("image of page {} has no content", page);
+                continue;
+            };
+
+            // image is unit square in its space, with rows running from top down
+            let [a, b, c, d, e, f] = placement.ctm;
+            let corners =
+                [(left, top), (right, top), (left, bottom), (right, bottom)].map(|(x, y)| {
+                    let u = x as f32 / width as f32;
+                    let v = 1.0 - y as f32 / height as f32;
+                    (u * a + v * c + e, u * b + v * d + f)
+                });
+            let xs = corners.map(|(x, _)| x);
+            let ys = corners.map(|(_, y)| y);
+            let min = |v: [f32; 4]| v.into_iter().fold(f32::INFINITY, f32::min);
+            let max = |v: [f32; 4]| v.into_iter().fold(f32::NEG_INFINITY, f32::max);
+
+            // content never grows page beyond its current box
+            let [x0, y0, x1, y1] = crop_box(&self.doc, page_id);
+            let cropped = [
+                (min(xs) - padding).max(x0),
+                (min(ys) - padding).max(y0),
+                (max(xs) + padding).min(x1),
+                (max(ys) + padding).min(y1),
+            ];
+            if cropped[0] >= cropped[2] || cropped[1] >= cropped[3] {
+                continue;
+            }
+
+            self.doc.get_dictionary_mut(page_id)?.set(
+                "CropBox",
+                cropped.iter().map(|&x| x.into()).collect::<Vec<Object>>(),
+            );
+            log::info!("cropped page {} to {:?}", page, cropped);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn auto_crop_applies_inverted_decode() {
+        let mut pdf = Pdf::new();
+        let page_id = pdf.add_page(160, 160).unwrap();
+
+        // 16 x 16 bilevel scan stored with 1 as black, dark block at pixels 6..10
+        let data: Vec<u8> = (0..16)
+            .flat_map(|y| match y {
+                6..=9 => [0b0000_0011, 0b1100_0000],
+                _ => [0, 0],
+            })
+            .collect();
+        let image_id = pdf.doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 16,
+                "Height" => 16,
+                "BitsPerComponent" => 1,
+                "ColorSpace" => "DeviceGray",
+                "Decode" => vec![1.into(), 0.into()],
+            },
+            data,
+        ));
+        let contents_id = pdf.doc.add_object(Stream::new(
+            dictionary! {},
+            b"q 160 0 0 160 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        let page = pdf.doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", contents_id);
+        page.set(
+            "Resources",
+            dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+        );
+
+        assert_eq!(pdf.auto_crop(&PageSelection::default(), 0.0).unwrap(), 1);
+
+        let crop = crop_box(&pdf.doc, page_id);
+        assert_eq!(crop, [60.0, 60.0, 100.0, 100.0]);
+    }
+}
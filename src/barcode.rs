@@ -0,0 +1,358 @@
+use std::str::FromStr;
+
+use lopdf::ObjectId;
+
+use crate::{
+    attributes::display_space, font::Font, permissions::Permission, selection::PageSelection,
+    text::Position, Pdf,
+};
+
+/// Widths of bars and spaces of Code 128 symbols by value as ASCII digits, alternating
+/// from bar
+const CODE128: [[u8; 6]; 106] = [
+    *b"212222", *b"222122", *b"222221", *b"121223", *b"121322", *b"131222", *b"122213", *b"122312",
+    *b"132212", *b"221213", *b"221312", *b"231212", *b"112232", *b"122132", *b"122231", *b"113222",
+    *b"123122", *b"123221", *b"223211", *b"221132", *b"221231", *b"213212", *b"223112", *b"312131",
+    *b"311222", *b"321122", *b"321221", *b"312212", *b"322112", *b"322211", *b"212123", *b"212321",
+    *b"232121", *b"111323", *b"131123", *b"131321", *b"112313", *b"132113", *b"132311", *b"211313",
+    *b"231113", *b"231311", *b"112133", *b"112331", *b"132131", *b"113123", *b"113321", *b"133121",
+    *b"313121", *b"211331", *b"231131", *b"213113", *b"213311", *b"213131", *b"311123", *b"311321",
+    *b"331121", *b"312113", *b"312311", *b"332111", *b"314111", *b"221411", *b"431111", *b"111224",
+    *b"111422", *b"121124", *b"121421", *b"141122", *b"141221", *b"112214", *b"112412", *b"122114",
+    *b"122411", *b"142112", *b"142211", *b"241211", *b"221114", *b"413111", *b"241112", *b"134111",
+    *b"111242", *b"121142", *b"121241", *b"114212", *b"124112", *b"124211", *b"411212", *b"421112",
+    *b"421211", *b"212141", *b"214121", *b"412121", *b"111143", *b"111341", *b"131141", *b"114113",
+    *b"114311", *b"411113", *b"411311", *b"113141", *b"114131", *b"311141", *b"411131", *b"211412",
+    *b"211214", *b"211232",
+];
+
+/// Widths of Code 128 stop symbol, with final bar
+const CODE128_STOP: [u8; 7] = *b"2331112";
+
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+
+/// Left-hand odd parity (L) patterns of EAN digits. right-hand (R) ones are their
+/// complement, and even parity (G) ones reversed complement.
+const EAN_L: [&[u8; 7]; 10] = [
+    b"0001101", b"0011001", b"0010011", b"0111101", b"0100011", b"0110001", b"0101111", b"0111011",
+    b"0110111", b"0001011",
+];
+
+/// Parity of six left-hand digits of EAN-13 by first digit, G for even
+const EAN_PARITY: [&[u8; 6]; 10] = [
+    b"LLLLLL", b"LLGLGG", b"LLGGLG", b"LLGGGL", b"LGLLGG", b"LGGLLG", b"LGGGLL", b"LGLGLG",
+    b"LGLGGL", b"LGGLGL",
+];
+
+/// Kind of barcode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Symbology {
+    /// Code 128, encoding printable ASCII
+    #[default]
+    Code128,
+    /// EAN-13, encoding 12 digits and check digit
+    Ean13,
+}
+
+impl FromStr for Symbology {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "code128" => Ok(Self::Code128),
+            "ean13" | "ean" => Ok(Self::Ean13),
+            _ => anyhow::bail!("unknown barcode symbology: {}", s),
+        }
+    }
+}
+
+impl Symbology {
+    /// Width of blank space required on each side, in modules
+    fn quiet_zone(&self) -> usize {
+        match self {
+            Self::Code128 => 10,
+            Self::Ean13 => 11,
+        }
+    }
+}
+
+/// Append WIDTHS of alternating bars and spaces, starting from bar, to MODULES
+fn push_widths(modules: &mut Vec<bool>, widths: &[u8]) {
+    for (i, width) in widths.iter().enumerate() {
+        let bar = i % 2 == 0;
+        modules.extend(std::iter::repeat(bar).take((width - b'0') as usize));
+    }
+}
+
+/// Encode DATA as modules of Code 128, using code set C for data of even number of
+/// digits and code set B otherwise
+fn encode_code128(data: &str) -> anyhow::Result<Vec<bool>> {
+    anyhow::ensure!(!data.is_empty(), "barcode data is empty");
+
+    let digits = data.bytes().all(|x| x.is_ascii_digit());
+    let values: Vec<usize> = if digits && data.len() % 2 == 0 {
+        let pairs = data.as_bytes().chunks(2);
+        std::iter::once(CODE128_START_C)
+            .chain(pairs.map(|x| ((x[0] - b'0') * 10 + (x[1] - b'0')) as usize))
+            .collect()
+    } else {
+        let mut values = vec![CODE128_START_B];
+        for c in data.chars() {
+            anyhow::ensure!(
+                (' '..='~').contains(&c),
+                "character {:?} can't be encoded in Code 128",
+                c
+            );
+            values.push(c as usize - ' ' as usize);
+        }
+        values
+    };
+
+    // checksum weights start symbol by 1 as well as first data symbol
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| i.max(1) * value)
+        .sum::<usize>()
+        % 103;
+
+    let mut modules = Vec::new();
+    for value in values.into_iter().chain(std::iter::once(checksum)) {
+        push_widths(&mut modules, &CODE128[value]);
+    }
+    push_widths(&mut modules, &CODE128_STOP);
+
+    Ok(modules)
+}
+
+/// Compute check digit of 12 DIGITS of EAN-13
+fn ean_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| x as u32 * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Encode DATA of 12 digits, or 13 with valid check digit, as modules of EAN-13.
+/// returns modules and all 13 digits.
+fn encode_ean13(data: &str) -> anyhow::Result<(Vec<bool>, String)> {
+    anyhow::ensure!(
+        matches!(data.len(), 12 | 13) && data.bytes().all(|x| x.is_ascii_digit()),
+        "EAN-13 data must be 12 or 13 digits: {}",
+        data
+    );
+    let mut digits: Vec<u8> = data.bytes().map(|x| x - b'0').collect();
+    let check = ean_check_digit(&digits[..12]);
+    match digits.get(12) {
+        Some(&x) => anyhow::ensure!(x == check, "invalid check digit of EAN-13: {}", data),
+        None => digits.push(check),
+    }
+
+    let module = |x: u8| x == b'1';
+    let mut modules = vec![true, false, true];
+    let parity = EAN_PARITY[digits[0] as usize];
+    for (i, &digit) in digits[1..7].iter().enumerate() {
+        let pattern = EAN_L[digit as usize];
+        if parity[i] == b'G' {
+            modules.extend(pattern.iter().rev().map(|&x| !module(x)));
+        } else {
+            modules.extend(pattern.iter().map(|&x| module(x)));
+        }
+    }
+    modules.extend([false, true, false, true, false]);
+    for &digit in &digits[7..] {
+        modules.extend(EAN_L[digit as usize].iter().map(|&x| !module(x)));
+    }
+    modules.extend([true, false, true]);
+
+    let text = digits.iter().map(|x| (b'0' + x) as char).collect();
+    Ok((modules, text))
+}
+
+/// Encoded barcode, as sequence of narrowest bars (true) and spaces (false)
+#[derive(Clone, Debug)]
+pub struct Barcode {
+    pub symbology: Symbology,
+    pub modules: Vec<bool>,
+    /// Human-readable text printed below bars
+    pub caption: String,
+}
+
+impl Barcode {
+    /// Encode DATA in SYMBOLOGY, failing if DATA can't be encoded in it
+    pub fn new(symbology: Symbology, data: &str) -> anyhow::Result<Self> {
+        let (modules, caption) = match symbology {
+            Symbology::Code128 => (encode_code128(data)?, data.to_string()),
+            Symbology::Ean13 => encode_ean13(data)?,
+        };
+
+        Ok(Self {
+            symbology,
+            modules,
+            caption,
+        })
+    }
+}
+
+/// How barcode is placed by `stamp_barcode`
+#[derive(Clone, Debug)]
+pub struct BarcodeStyle {
+    pub position: Position,
+    /// Width of narrowest bar in points
+    pub module_width: f32,
+    /// Height of bars in points
+    pub height: f32,
+    /// Print caption below bars
+    pub caption: bool,
+    pub font: Font,
+    pub font_size: f32,
+    /// Distance from page edges in points
+    pub margin: f32,
+    /// Pages to draw barcode on
+    pub pages: PageSelection,
+}
+
+impl Default for BarcodeStyle {
+    fn default() -> Self {
+        Self {
+            position: Position::TopRight,
+            module_width: 1.0,
+            height: 36.0,
+            caption: true,
+            font: Font::default(),
+            font_size: 8.0,
+            margin: 36.0,
+            pages: PageSelection::default(),
+        }
+    }
+}
+
+impl Pdf {
+    /// Draw BARCODE on selected pages above existing content, on white background
+    /// including quiet zones so that it can be scanned over other content
+    pub fn stamp_barcode(&mut self, barcode: &Barcode, style: &BarcodeStyle) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        anyhow::ensure!(
+            style.module_width > 0.0 && style.height > 0.0,
+            "invalid size of barcode"
+        );
+
+        let page_ids = self.doc.get_pages();
+        let pages: Vec<ObjectId> = style
+            .pages
+            .pages(page_ids.len() as u32)
+            .iter()
+            .filter_map(|num| page_ids.get(num).copied())
+            .collect();
+
+        let quiet = barcode.symbology.quiet_zone() as f32 * style.module_width;
+        let bars_width = barcode.modules.len() as f32 * style.module_width;
+        let width = bars_width + 2.0 * quiet;
+        let caption_height = if style.caption {
+            style.font_size * 1.2
+        } else {
+            0.0
+        };
+        let height = style.height + caption_height;
+
+        for page_id in pages {
+            self.check_deadline()?;
+            // barcode is placed upright in visible area of page, even if page is rotated
+            let (page_box, ctm) = display_space(&self.doc, page_id);
+            let (x, y) = style.position.origin(page_box, width, height, style.margin);
+
+            let [a, b, c, d, e, f] = ctm;
+            let mut content = format!(
+                "q\n{} {} {} {} {} {} cm\n1 g\n{} {} {} {} re\nf\n0 g\n",
+                a, b, c, d, e, f, x, y, width, height
+            );
+            // adjacent bar modules are drawn as one rectangle
+            let mut start = None;
+            for (i, &bar) in barcode.modules.iter().chain([&false]).enumerate() {
+                match (bar, start) {
+                    (true, None) => start = Some(i),
+                    (false, Some(first)) => {
+                        content.push_str(&format!(
+                            "{} {} {} {} re\n",
+                            x + quiet + first as f32 * style.module_width,
+                            y + caption_height,
+                            (i - first) as f32 * style.module_width,
+                            style.height
+                        ));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            content.push_str("f\nQ\n");
+            self.append_page_content(page_id, content.into_bytes())?;
+
+            if style.caption {
+                let text_width = style.font.text_width(&barcode.caption, style.font_size);
+                let origin = (
+                    x + (width - text_width) / 2.0,
+                    y + caption_height - style.font_size,
+                );
+                self.draw_text_line(
+                    page_id,
+                    &barcode.caption,
+                    origin,
+                    &style.font,
+                    style.font_size,
+                    ctm,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Modules of symbols of VALUES followed by stop symbol
+    fn modules(values: &[usize]) -> Vec<bool> {
+        let mut modules = Vec::new();
+        for &value in values {
+            push_widths(&mut modules, &CODE128[value]);
+        }
+        push_widths(&mut modules, &CODE128_STOP);
+        modules
+    }
+
+    #[test]
+    fn code128_even_digits_use_code_set_c() {
+        // checksum: (105 + 1 * 12 + 2 * 34) % 103 = 82
+        assert_eq!(
+            encode_code128("1234").unwrap(),
+            modules(&[CODE128_START_C, 12, 34, 82])
+        );
+    }
+
+    #[test]
+    fn code128_text_uses_code_set_b() {
+        // checksum: (104 + 1 * 33 + 2 * 18) % 103 = 70
+        assert_eq!(
+            encode_code128("A2").unwrap(),
+            modules(&[CODE128_START_B, 33, 18, 70])
+        );
+    }
+
+    #[test]
+    fn code128_rejects_empty_and_non_ascii_data() {
+        assert!(encode_code128("").is_err());
+        assert!(encode_code128("café").is_err());
+    }
+
+    #[test]
+    fn ean_check_digits() {
+        assert_eq!(ean_check_digit(&[4, 0, 0, 6, 3, 8, 1, 3, 3, 3, 9, 3]), 1);
+        assert_eq!(ean_check_digit(&[5, 9, 0, 1, 2, 3, 4, 1, 2, 3, 4, 5]), 7);
+        assert_eq!(ean_check_digit(&[0; 12]), 0);
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+use lopdf::{dictionary, Stream};
+
+use crate::{writer, xobject, Pdf};
+
+/// Check whether data is PBM, plain (P1) or raw (P4)
+fn is_pbm(bytes: &[u8]) -> bool {
+    matches!(bytes, [b'P', b'1' | b'4', ..])
+}
+
+/// Pack bilevel IMG into rows of 1 bit samples, where 0 is black as in DeviceGray
+fn pack_bits(img: &DynamicImage) -> Vec<u8> {
+    let img = img.to_luma8();
+    let row = (img.width() as usize + 7) / 8;
+    let mut result = vec![0u8; row * img.height() as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if pixel.0[0] >= 128 {
+            result[y as usize * row + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+
+    result
+}
+
+impl Pdf {
+    /// Build image XObject from BMP or PNM (PBM, PGM, PPM) data by flate compressing
+    /// raw samples. PBM is kept 1 bit and 16-bit PGM/PPM keep their depth.
+    pub(crate) fn bitmap_xobject(
+        &mut self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> anyhow::Result<Stream> {
+        self.check_encoded_image(bytes)?;
+
+        let img = image::load_from_memory_with_format(bytes, format)?;
+
+        // let PNG path run preprocessors on decoded image
+        if !self.preprocessors.is_empty() {
+            let mut png = Vec::new();
+            img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            return Ok(self.png_xobject(&png)?);
+        }
+        let (width, height) = (img.width(), img.height());
+
+        let (cs, bpc, samples) = match &img {
+            _ if format == ImageFormat::Pnm && is_pbm(bytes) => ("DeviceGray", 1, pack_bits(&img)),
+            DynamicImage::ImageLuma16(buf) => (
+                "DeviceGray",
+                16,
+                buf.as_raw().iter().flat_map(|x| x.to_be_bytes()).collect(),
+            ),
+            DynamicImage::ImageRgb16(buf) => (
+                "DeviceRGB",
+                16,
+                buf.as_raw().iter().flat_map(|x| x.to_be_bytes()).collect(),
+            ),
+            _ => return xobject::flate_stream(&img),
+        };
+
+        if bpc == 16 && self.doc.version.as_str() < "1.5" {
+            self.doc.version = "1.5".into();
+        }
+
+        let data = writer::deflate(&samples)?;
+        Ok(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Filter" => "FlateDecode",
+                "BitsPerComponent" => bpc,
+                "ColorSpace" => cs,
+                "Length" => data.len() as u32,
+                "Width" => width,
+                "Height" => height,
+            },
+            data,
+        ))
+    }
+}
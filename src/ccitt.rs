@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use fax::{encoder::Encoder, Color, VecWriter};
+use image::GrayImage;
+use lopdf::{dictionary, Dictionary, Stream};
+
+use crate::{limits::LimitExceeded, optimize::filter_names, Pdf};
+
+/// Compression of page images converted to black and white
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bilevel {
+    /// CCITT Group 4, as used by fax machines and scanners
+    G4,
+}
+
+impl FromStr for Bilevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "g4" => Ok(Self::G4),
+            _ => anyhow::bail!("unknown bilevel compression: {}", s),
+        }
+    }
+}
+
+/// Build dictionary of CCITT G4 compressed image XObject of WIDTH x HEIGHT
+pub(crate) fn g4_image_dict(width: u32, height: u32, black_is_1: bool) -> Dictionary {
+    dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Filter" => "CCITTFaxDecode",
+        "DecodeParms" => dictionary! {
+            "K" => -1,
+            "Columns" => width,
+            "Rows" => height,
+            "BlackIs1" => black_is_1,
+        },
+        "BitsPerComponent" => 1,
+        "ColorSpace" => "DeviceGray",
+        "Width" => width,
+        "Height" => height,
+    }
+}
+
+/// Encode IMG with CCITT G4, taking pixels darker than middle gray as black
+pub(crate) fn g4_encode(img: &GrayImage) -> anyhow::Result<Vec<u8>> {
+    let width = u16::try_from(img.width())
+        .map_err(|_| anyhow::anyhow!("image is too wide for CCITT: {}", img.width()))?;
+
+    let mut encoder = Encoder::new(VecWriter::new());
+    for row in img.rows() {
+        let pels = row.map(|x| {
+            if x.0[0] < 128 {
+                Color::Black
+            } else {
+                Color::White
+            }
+        });
+        encoder.encode_line(pels, width)?;
+    }
+
+    Ok(encoder.finish()?.finish())
+}
+
+impl Pdf {
+    /// Convert page image STREAM to black and white with compression set by
+    /// `image_options.bilevel`. stream is returned as is if it is not set, stream is
+    /// already CCITT compressed or can't be decoded.
+    pub(crate) fn bilevel_stream(&self, stream: Stream) -> anyhow::Result<Stream> {
+        let Some(Bilevel::G4) = self.image_options.bilevel else {
+            return Ok(stream);
+        };
+        if filter_names(&stream.dict) == [b"CCITTFaxDecode"] {
+            return Ok(stream);
+        }
+
+        let img = match self.decode_image(&stream) {
+            Ok(img) => img,
+            Err(err) if err.is::<LimitExceeded>() => return Err(err),
+            Err(_) => return Ok(stream),
+        };
+
+        let data = g4_encode(&img.to_luma8())?;
+        let mut dict = g4_image_dict(img.width(), img.height(), false);
+        dict.set("Length", data.len() as u32);
+
+        Ok(Stream::new(dict, data))
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+/// Digest algorithm used for checksum manifest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn digest(&self, bytes: &[u8]) -> String {
+        match self {
+            Algorithm::Sha256 => format!("{:x}", Sha256::digest(bytes)),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            _ => Err(anyhow!("unsupported checksum algorithm: {}", s)),
+        }
+    }
+}
+
+/// One output file recorded in checksum manifest
+pub struct Entry {
+    pub file: PathBuf,
+    pub size: u64,
+    pub pages: usize,
+    pub digest: String,
+}
+
+impl Entry {
+    pub fn from_bytes<P: AsRef<Path>>(
+        file: P,
+        bytes: &[u8],
+        pages: usize,
+        algo: Algorithm,
+    ) -> Self {
+        Self {
+            file: file.as_ref().to_owned(),
+            size: bytes.len() as u64,
+            pages,
+            digest: algo.digest(bytes),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(
+        file: P,
+        pages: usize,
+        algo: Algorithm,
+    ) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(&file)?;
+        Ok(Self::from_bytes(file, &bytes, pages, algo))
+    }
+}
+
+/// Get path of sidecar manifest for OUTPUT, e.g. `out.pdf.sha256`
+pub fn manifest_path<P: AsRef<Path>>(output: P, algo: Algorithm) -> PathBuf {
+    let mut path = output.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(algo.name());
+    path.into()
+}
+
+/// Write manifest of ENTRIES next to OUTPUT.
+/// each line is tab separated file name, size in bytes, page count and digest.
+pub fn write_manifest<P: AsRef<Path>>(
+    output: P,
+    algo: Algorithm,
+    entries: &[Entry],
+) -> anyhow::Result<PathBuf> {
+    let mut manifest = String::new();
+    writeln!(manifest, "# file\tsize\tpages\t{}", algo.name())?;
+
+    for entry in entries {
+        let name = entry
+            .file
+            .file_name()
+            .unwrap_or(entry.file.as_os_str())
+            .to_string_lossy();
+
+        writeln!(
+            manifest,
+            "{}\t{}\t{}\t{}",
+            name, entry.size, entry.pages, entry.digest
+        )?;
+    }
+
+    let path = manifest_path(output, algo);
+    std::fs::write(&path, manifest)?;
+
+    Ok(path)
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use lopdf::{dictionary, ObjectId, Stream};
+
+use crate::{attributes::media_box, Pdf};
+
+/// Space around and between panels of comparison page in points
+const GAP: f32 = 18.0;
+
+/// Page of document being compared, imported into report as Form XObject
+struct Panel {
+    form: ObjectId,
+    /// lower left corner of MediaBox of source page
+    origin: (f32, f32),
+}
+
+impl Pdf {
+    /// Import page PAGE of OTHER into report, if it exists
+    fn import_panel(
+        &mut self,
+        other: &Pdf,
+        page: u32,
+        map: &mut BTreeMap<ObjectId, ObjectId>,
+    ) -> anyhow::Result<Option<(Panel, f32, f32)>> {
+        let Some(&page_id) = other.doc.get_pages().get(&page) else {
+            return Ok(None);
+        };
+
+        let [x0, y0, x1, y1] = media_box(&other.doc, page_id);
+        let form = self.import_page_as_form(&other.doc, page_id, map)?;
+
+        Ok(Some((
+            Panel {
+                form,
+                origin: (x0, y0),
+            },
+            x1 - x0,
+            y1 - y0,
+        )))
+    }
+
+    /// Wrap form of PANEL into isolated transparency group painted on white background,
+    /// so it can be blended as a whole
+    fn opaque_group(&mut self, panel: &Panel, width: f32, height: f32) -> ObjectId {
+        let (x0, y0) = panel.origin;
+        let content = format!("q 1 g {} {} {} {} re f Q /Page Do\n", x0, y0, width, height);
+
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![x0.into(), y0.into(), (x0 + width).into(), (y0 + height).into()],
+                "Group" => dictionary! {
+                    "S" => "Transparency",
+                    "I" => true,
+                },
+                "Resources" => dictionary! {
+                    "XObject" => dictionary! { "Page" => panel.form },
+                },
+            },
+            content.into_bytes(),
+        );
+        let _ = stream.compress();
+
+        self.doc.add_object(stream)
+    }
+
+    /// Build comparison document of A and B. each page shows page of A, page of B and
+    /// their difference side by side; unchanged areas of difference are white and
+    /// changed ones dark. difference is computed by viewer with Difference blend mode.
+    pub fn visual_diff(a: &Pdf, b: &Pdf) -> anyhow::Result<Pdf> {
+        let mut report = Pdf::new();
+        let mut map_a = BTreeMap::new();
+        let mut map_b = BTreeMap::new();
+
+        let difference = report.doc.add_object(dictionary! {
+            "Type" => "ExtGState",
+            "BM" => "Difference",
+        });
+
+        let count = a.page_count().max(b.page_count()) as u32;
+        for page in 1..=count {
+            report.check_deadline()?;
+
+            let panel_a = report.import_panel(a, page, &mut map_a)?;
+            let panel_b = report.import_panel(b, page, &mut map_b)?;
+
+            let (width, height) = [&panel_a, &panel_b]
+                .into_iter()
+                .flatten()
+                .fold((0.0f32, 0.0f32), |(w, h), (_, pw, ph)| {
+                    (w.max(*pw), h.max(*ph))
+                });
+
+            let page_width = width * 3.0 + GAP * 4.0;
+            let page_height = height + GAP * 2.0;
+            let page_id = report.add_page(page_width.ceil() as u32, page_height.ceil() as u32)?;
+            report.add_page_resource(page_id, "ExtGState", "Diff", difference)?;
+
+            // difference starts black, and pages of A and B (white if missing) are
+            // blended on it, leaving |A - B|
+            let diff_x = GAP * 3.0 + width * 2.0;
+            let mut content = format!("q 0 g {} {} {} {} re f Q\n", diff_x, GAP, width, height);
+
+            for (i, (panel, name)) in [(&panel_a, "A"), (&panel_b, "B")].into_iter().enumerate() {
+                let Some((panel, pw, ph)) = panel else {
+                    content.push_str(&format!(
+                        "q /Diff gs 1 g {} {} {} {} re f Q\n",
+                        diff_x, GAP, width, height
+                    ));
+                    continue;
+                };
+
+                let (x0, y0) = panel.origin;
+                report.add_page_resource(page_id, "XObject", name, panel.form)?;
+                let group = report.opaque_group(panel, *pw, *ph);
+                let group_name = format!("{}Group", name);
+                report.add_page_resource(page_id, "XObject", &group_name, group)?;
+
+                let x = GAP * (i as f32 + 1.0) + width * i as f32;
+                content.push_str(&format!(
+                    "q 1 0 0 1 {} {} cm /{} Do Q\n",
+                    x - x0,
+                    GAP - y0,
+                    name
+                ));
+                content.push_str(&format!(
+                    "q /Diff gs 1 0 0 1 {} {} cm /{} Do Q\n",
+                    diff_x - x0,
+                    GAP - y0,
+                    group_name
+                ));
+            }
+
+            // invert so that unchanged areas become white
+            content.push_str(&format!(
+                "q /Diff gs 1 g {} {} {} {} re f Q\n",
+                diff_x, GAP, width, height
+            ));
+
+            report
+                .doc
+                .change_page_content(page_id, content.into_bytes())?;
+        }
+
+        Ok(report)
+    }
+}
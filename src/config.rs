@@ -0,0 +1,93 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    optimize::{Optimization, Profile},
+    paper::PageSize,
+};
+
+/// Defaults of CLI read from config file, used where flags are not given.
+/// keys are named as flags, like
+///
+/// ```toml
+/// author = "Team"
+/// page-size = "a4"
+/// jpeg-quality = 80
+/// optimize = ["downsample:150", "streams"]
+/// profile = "ebook"
+/// object-streams = true
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Author set to documents which have none
+    pub author: Option<String>,
+    #[serde(deserialize_with = "parsed")]
+    pub page_size: Option<PageSize>,
+    pub jpeg_quality: Option<u8>,
+    /// Optimization passes run before saving if no --optimize is given
+    #[serde(deserialize_with = "parsed_vec")]
+    pub optimize: Vec<Optimization>,
+    #[serde(deserialize_with = "parsed")]
+    pub profile: Option<Profile>,
+    pub object_streams: bool,
+}
+
+/// Deserialize string into T by its FromStr
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|x| x.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+fn parsed_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|x| x.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+impl Config {
+    /// Default location of config file, pdftool/config.toml in $XDG_CONFIG_HOME or ~/.config
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(dir.join("pdftool").join("config.toml"))
+    }
+
+    /// Read config from TOML file at PATH
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        toml::from_str(&text).with_context(|| format!("Failed to parse config {}", path.display()))
+    }
+
+    /// Read config from default location, or empty config if file does not exist
+    pub fn load_default() -> anyhow::Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.is_file() => Self::load(path),
+            _ => Ok(Self::default()),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use sha2::{Digest, Sha256};
+
+use crate::{attributes::inherited_attribute, selection::PageSelection, writer, Pdf};
+
+/// Attributes page may inherit from page tree, which affect how it looks
+const INHERITED: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Resolution pages are rendered at for comparison
+const RENDER_DPI: f32 = 72.0;
+
+/// Hash OBJ of DOC by value, hashing objects in place of references to them, so that
+/// copies of same content in different objects hash same. MEMO caches hashes of
+/// referred objects. links to other pages are not followed, and parents are skipped.
+fn digest_object(doc: &Document, obj: &Object, memo: &mut BTreeMap<ObjectId, Vec<u8>>) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+
+    match obj {
+        Object::Reference(id) => {
+            if let Some(digest) = memo.get(id) {
+                return digest.clone();
+            }
+            let Ok(target) = doc.get_object(*id) else {
+                return Sha256::digest(b"null").to_vec();
+            };
+            let is_page = target
+                .as_dict()
+                .map_or(false, |x| matches!(x.type_name(), Ok("Page" | "Pages")));
+            if is_page {
+                return Sha256::digest(b"page").to_vec();
+            }
+
+            // placeholder ends cycles, like annotations referring to each other
+            memo.insert(*id, Sha256::digest(b"cycle").to_vec());
+            let digest = digest_object(doc, target, memo);
+            memo.insert(*id, digest.clone());
+            return digest;
+        }
+        Object::Array(arr) => {
+            hasher.update(b"[");
+            for x in arr {
+                hasher.update(digest_object(doc, x, memo));
+            }
+        }
+        Object::Dictionary(dict) => hasher.update(digest_dictionary(doc, dict, memo)),
+        Object::Stream(stream) => {
+            hasher.update(digest_dictionary(doc, &stream.dict, memo));
+            hasher.update(&stream.content);
+        }
+        _ => {
+            let mut bytes = Vec::new();
+            writer::write_object(&mut bytes, obj);
+            hasher.update(bytes);
+        }
+    }
+
+    hasher.finalize().to_vec()
+}
+
+/// Hash DICT like `digest_object`, regardless of order of its entries
+fn digest_dictionary(
+    doc: &Document,
+    dict: &Dictionary,
+    memo: &mut BTreeMap<ObjectId, Vec<u8>>,
+) -> Vec<u8> {
+    let mut entries: Vec<(&Vec<u8>, &Object)> = dict
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_slice(), b"Parent" | b"StructParents"))
+        .collect();
+    entries.sort_by_key(|(key, _)| *key);
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"<<");
+    for (key, value) in entries {
+        hasher.update(key);
+        hasher.update(digest_object(doc, value, memo));
+    }
+    hasher.finalize().to_vec()
+}
+
+impl Pdf {
+    /// Find pages identical to earlier ones, returning pairs of duplicate page and first
+    /// page it duplicates. pages are compared by their content and objects they use,
+    /// or with RENDERED, by how they look, which also matches pages made differently.
+    pub fn duplicate_pages(&self, rendered: bool) -> anyhow::Result<Vec<(u32, u32)>> {
+        let mut digests: Vec<(u32, Vec<u8>)> = Vec::new();
+
+        if rendered {
+            self.render_pages(&PageSelection::default(), RENDER_DPI, |page, image| {
+                let mut hasher = Sha256::new();
+                hasher.update(image.width().to_be_bytes());
+                hasher.update(image.height().to_be_bytes());
+                hasher.update(image.to_rgba8().as_raw());
+                digests.push((page, hasher.finalize().to_vec()));
+                Ok(())
+            })?;
+        } else {
+            let mut memo = BTreeMap::new();
+            for (page, page_id) in self.doc.get_pages() {
+                let mut dict = self.doc.get_dictionary(page_id)?.clone();
+                for key in INHERITED {
+                    if dict.has(key) {
+                        continue;
+                    }
+                    if let Some(value) = inherited_attribute(&self.doc, page_id, key) {
+                        dict.set(key, value.clone());
+                    }
+                }
+                let digest = digest_object(&self.doc, &Object::Dictionary(dict), &mut memo);
+                digests.push((page, digest));
+            }
+        }
+
+        let mut first: BTreeMap<Vec<u8>, u32> = BTreeMap::new();
+        let mut result = Vec::new();
+        for (page, digest) in digests {
+            match first.get(&digest) {
+                Some(&original) => result.push((page, original)),
+                None => {
+                    first.insert(digest, page);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Remove pages identical to earlier ones, found like `duplicate_pages`. returns
+    /// pairs of removed page and page it duplicated, numbered as before removal.
+    pub fn dedup_pages(&mut self, rendered: bool) -> anyhow::Result<Vec<(u32, u32)>> {
+        let duplicates = self.duplicate_pages(rendered)?;
+
+        let pages: Vec<u32> = duplicates.iter().map(|&(page, _)| page).collect();
+        self.remove_pages(&pages)?;
+
+        log::info!("removed {} duplicate pages", pages.len());
+        Ok(duplicates)
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::Cursor;
+
+use ::tiff::{decoder::ifd::Value, decoder::Decoder, tags::Tag};
+use image::ImageFormat;
+
+use crate::exif;
+
+/// Read resolution from JFIF header of JPEG data
+fn jfif(bytes: &[u8]) -> Option<(f32, f32)> {
+    // version (2), units (1), X density (2), Y density (2)
+    let [_, _, unit, xh, xl, yh, yl, ..] = *exif::jpeg_segment(bytes, 0xe0, b"JFIF\0")? else {
+        return None;
+    };
+    let x = u16::from_be_bytes([xh, xl]) as f32;
+    let y = u16::from_be_bytes([yh, yl]) as f32;
+
+    match unit {
+        1 => Some((x, y)),
+        2 => Some((x * 2.54, y * 2.54)),
+        // 0 gives only aspect ratio
+        _ => None,
+    }
+}
+
+/// Read resolution from BITMAPINFOHEADER of BMP data
+fn bmp(bytes: &[u8]) -> Option<(f32, f32)> {
+    let read = |pos: usize| -> Option<i32> {
+        Some(i32::from_le_bytes(
+            bytes.get(pos..pos + 4)?.try_into().ok()?,
+        ))
+    };
+
+    // older BITMAPCOREHEADER has no resolution
+    if read(14)? < 40 {
+        return None;
+    }
+    let (x, y) = (read(38)?, read(42)?);
+
+    Some((x as f32 * 0.0254, y as f32 * 0.0254))
+}
+
+/// Read resolution from first IFD of TIFF data
+fn tiff(bytes: &[u8]) -> Option<(f32, f32)> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).ok()?;
+    let mut rational = |tag| match decoder.find_tag(tag).ok()?? {
+        Value::Rational(n, d) if d != 0 => Some(n as f32 / d as f32),
+        _ => None,
+    };
+
+    let x = rational(Tag::XResolution)?;
+    let y = rational(Tag::YResolution).unwrap_or(x);
+    let unit = decoder
+        .find_tag(Tag::ResolutionUnit)
+        .ok()
+        .flatten()
+        .and_then(|x| x.into_u16().ok())
+        .unwrap_or(2);
+
+    match unit {
+        2 => Some((x, y)),
+        3 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+/// Get horizontal and vertical resolution in DPI recorded in image data,
+/// from pHYs of PNG, JFIF or EXIF of JPEG, and headers of BMP and TIFF
+pub(crate) fn density(bytes: &[u8]) -> Option<(f32, f32)> {
+    let dpi = match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => crate::png::get_info(bytes).ok()?.dpi,
+        ImageFormat::Jpeg => jfif(bytes).or_else(|| exif::resolution(bytes)),
+        ImageFormat::Bmp => bmp(bytes),
+        ImageFormat::Tiff => tiff(bytes),
+        _ => None,
+    };
+
+    dpi.filter(|&(x, y)| x > 0.0 && y > 0.0)
+}
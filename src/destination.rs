@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use lopdf::{Object, ObjectId};
+
+/// View of page to display when jumping to it (Table 151 of ISO 32000-1).
+/// None leaves the corresponding parameter unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Destination {
+    /// Position LEFT, TOP at upper-left corner of window with ZOOM factor
+    Xyz {
+        left: Option<f32>,
+        top: Option<f32>,
+        zoom: Option<f32>,
+    },
+    /// Fit whole page in window
+    #[default]
+    Fit,
+    /// Fit width of page, with TOP at top of window
+    FitH {
+        top: Option<f32>,
+    },
+    /// Fit height of page, with LEFT at left edge of window
+    FitV {
+        left: Option<f32>,
+    },
+    /// Fit rectangle in window
+    FitR {
+        left: f32,
+        bottom: f32,
+        right: f32,
+        top: f32,
+    },
+    /// Fit bounding box of page contents in window
+    FitB,
+    FitBH {
+        top: Option<f32>,
+    },
+    FitBV {
+        left: Option<f32>,
+    },
+}
+
+fn param(value: Option<f32>) -> Object {
+    value.map_or(Object::Null, Object::Real)
+}
+
+impl Destination {
+    /// Build explicit destination array targeting PAGE_ID
+    pub fn to_object(&self, page_id: ObjectId) -> Object {
+        let mut result: Vec<Object> = vec![page_id.into()];
+
+        match *self {
+            Destination::Xyz { left, top, zoom } => {
+                result.extend(["XYZ".into(), param(left), param(top), param(zoom)])
+            }
+            Destination::Fit => result.push("Fit".into()),
+            Destination::FitH { top } => result.extend(["FitH".into(), param(top)]),
+            Destination::FitV { left } => result.extend(["FitV".into(), param(left)]),
+            Destination::FitR {
+                left,
+                bottom,
+                right,
+                top,
+            } => result.extend([
+                "FitR".into(),
+                Object::Real(left),
+                Object::Real(bottom),
+                Object::Real(right),
+                Object::Real(top),
+            ]),
+            Destination::FitB => result.push("FitB".into()),
+            Destination::FitBH { top } => result.extend(["FitBH".into(), param(top)]),
+            Destination::FitBV { left } => result.extend(["FitBV".into(), param(left)]),
+        }
+
+        result.into()
+    }
+}
+
+/// Parse destination like `fit`, `fith:792`, `xyz:0,792,1.5` or `fitr:0,0,300,300`.
+/// empty parameter such as `xyz:,,2` is left unchanged.
+impl FromStr for Destination {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+
+        let params: Vec<Option<f32>> = if params.is_empty() {
+            vec![]
+        } else {
+            params
+                .split(',')
+                .map(|x| match x.trim() {
+                    "" | "null" => Ok(None),
+                    x => x.parse().map(Some),
+                })
+                .collect::<Result<_, _>>()?
+        };
+        let get = |i: usize| params.get(i).copied().flatten();
+        let require = |i: usize| get(i).ok_or(anyhow!("missing parameter in destination {}", s));
+
+        let dest = match kind.to_ascii_lowercase().as_str() {
+            "xyz" => Destination::Xyz {
+                left: get(0),
+                top: get(1),
+                zoom: get(2),
+            },
+            "fit" => Destination::Fit,
+            "fith" => Destination::FitH { top: get(0) },
+            "fitv" => Destination::FitV { left: get(0) },
+            "fitr" => Destination::FitR {
+                left: require(0)?,
+                bottom: require(1)?,
+                right: require(2)?,
+                top: require(3)?,
+            },
+            "fitb" => Destination::FitB,
+            "fitbh" => Destination::FitBH { top: get(0) },
+            "fitbv" => Destination::FitBV { left: get(0) },
+            _ => anyhow::bail!("unknown destination: {}", s),
+        };
+
+        Ok(dest)
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use lopdf::{Document, Object, ObjectId};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    appearance::page_annotations,
+    attributes::{media_box, rotation},
+    xobject::{self, as_number},
+    Pdf,
+};
+
+/// Document information keys which change whenever document is saved
+const VOLATILE_INFO: [&str; 3] = ["CreationDate", "ModDate", "Producer"];
+
+/// One field which differs between two documents
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Difference {
+    /// What differs, like "page 3 size" or "metadata Title"
+    pub field: String,
+    /// Value in first document, None if missing
+    pub a: Option<String>,
+    /// Value in second document, None if missing
+    pub b: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let a = self.a.as_deref().unwrap_or("(none)");
+        let b = self.b.as_deref().unwrap_or("(none)");
+        write!(f, "{}: {} -> {}", self.field, a, b)
+    }
+}
+
+/// Result of structural comparison of two documents
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StructuralDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl StructuralDiff {
+    /// Whether no difference was found
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    fn compare<T: PartialEq + ToString>(&mut self, field: String, a: Option<T>, b: Option<T>) {
+        if a != b {
+            self.differences.push(Difference {
+                field,
+                a: a.map(|x| x.to_string()),
+                b: b.map(|x| x.to_string()),
+            });
+        }
+    }
+}
+
+impl fmt::Display for StructuralDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identical() {
+            return write!(f, "documents are structurally identical");
+        }
+
+        for difference in &self.differences {
+            writeln!(f, "{}", difference)?;
+        }
+        write!(f, "{} differences found", self.differences.len())
+    }
+}
+
+/// Parts of page compared by `structural_diff`
+struct PageSummary {
+    /// Width and height of MediaBox, with rotation
+    size: String,
+    /// Subtype and Rect of each annotation
+    annotations: String,
+    /// SHA-256 of each image XObject stream, as stored
+    images: String,
+}
+
+fn summarize_page(doc: &Document, page_id: ObjectId) -> PageSummary {
+    let [x0, y0, x1, y1] = media_box(doc, page_id);
+    let size = match rotation(doc, page_id) {
+        0 => format!("{}x{}", x1 - x0, y1 - y0),
+        rotate => format!("{}x{} rotated {}", x1 - x0, y1 - y0, rotate),
+    };
+
+    let annotations = page_annotations(doc, page_id)
+        .into_iter()
+        .filter_map(|id| doc.get_dictionary(id).ok())
+        .map(|dict| {
+            let subtype = dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .unwrap_or(&b"?"[..]);
+            let rect: Vec<String> = match dict.get(b"Rect") {
+                Ok(Object::Array(arr)) => arr
+                    .iter()
+                    .filter_map(as_number)
+                    .map(|x| x.to_string())
+                    .collect(),
+                _ => vec![],
+            };
+            format!("{} [{}]", String::from_utf8_lossy(subtype), rect.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let images = xobject::page_xobjects(doc, page_id)
+        .into_values()
+        .filter(|&id| xobject::is_image(doc, id))
+        .filter_map(|id| doc.get_object(id).and_then(Object::as_stream).ok())
+        .map(|stream| format!("{:x}", Sha256::digest(&stream.content)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    PageSummary {
+        size,
+        annotations,
+        images,
+    }
+}
+
+impl Pdf {
+    /// Compare structure of A and B: page count, page sizes, document information,
+    /// outline, annotations and hashes of image streams. content streams are not
+    /// compared, use `visual_diff` to see changes in how pages look. dates and producer
+    /// of document information are compared only with INCLUDE_VOLATILE, since they
+    /// differ whenever document is saved.
+    pub fn structural_diff(a: &Pdf, b: &Pdf, include_volatile: bool) -> StructuralDiff {
+        let mut diff = StructuralDiff::default();
+
+        let pages_a = a.doc.get_pages();
+        let pages_b = b.doc.get_pages();
+        diff.compare(
+            "page count".into(),
+            Some(pages_a.len()),
+            Some(pages_b.len()),
+        );
+
+        let info_a = a.info_entries();
+        let info_b = b.info_entries();
+        let mut keys: Vec<&String> = info_a
+            .keys()
+            .chain(info_b.keys())
+            .filter(|key| include_volatile || !VOLATILE_INFO.contains(&key.as_str()))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            diff.compare(
+                format!("metadata {}", key),
+                info_a.get(key),
+                info_b.get(key),
+            );
+        }
+
+        let outline = |pdf: &Pdf| -> Vec<String> {
+            pdf.bookmarks()
+                .into_iter()
+                .map(|(depth, title)| format!("{}{}", "  ".repeat(depth - 1), title))
+                .collect()
+        };
+        let outline_a = outline(a);
+        let outline_b = outline(b);
+        for i in 0..outline_a.len().max(outline_b.len()) {
+            diff.compare(
+                format!("bookmark {}", i + 1),
+                outline_a.get(i),
+                outline_b.get(i),
+            );
+        }
+
+        for page in 1..=pages_a.len().max(pages_b.len()) as u32 {
+            let page_a = pages_a.get(&page).map(|&id| summarize_page(&a.doc, id));
+            let page_b = pages_b.get(&page).map(|&id| summarize_page(&b.doc, id));
+            // missing pages are reported by page count
+            let (Some(page_a), Some(page_b)) = (page_a, page_b) else {
+                continue;
+            };
+
+            diff.compare(
+                format!("page {} size", page),
+                Some(page_a.size),
+                Some(page_b.size),
+            );
+            diff.compare(
+                format!("page {} annotations", page),
+                Some(page_a.annotations),
+                Some(page_b.annotations),
+            );
+            diff.compare(
+                format!("page {} images", page),
+                Some(page_a.images),
+                Some(page_b.images),
+            );
+        }
+
+        diff
+    }
+}
@@ -0,0 +1,306 @@
+use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use anyhow::anyhow;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+use md5::{Digest, Md5};
+
+use crate::{permissions, writer};
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+const PADDING: [u8; 32] = [
+    0x28, 0xbf, 0x4e, 0x5e, 0x4e, 0x75, 0x8a, 0x41, 0x64, 0x00, 0x4e, 0x56, 0xff, 0xfa, 0x01, 0x08,
+    0x2e, 0x2e, 0x00, 0xb6, 0xd0, 0x68, 0x3e, 0x80, 0x2f, 0x0c, 0xa9, 0xfe, 0x64, 0x53, 0x69, 0x7a,
+];
+
+/// Key length in bytes of AESV2 crypt filter
+const KEY_LEN: usize = 16;
+
+/// All operations permitted
+const PERMISSIONS: i32 = -4;
+
+fn pad_password(password: &[u8]) -> [u8; 32] {
+    let len = password.len().min(32);
+    let mut result = PADDING;
+    result[..len].copy_from_slice(&password[..len]);
+    result[len..].copy_from_slice(&PADDING[..32 - len]);
+    result
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s: Vec<u8> = (0..=255).collect();
+
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            byte ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// RC4 DATA with KEY, then 19 more times with each byte of KEY xored by iteration count
+fn rc4_rounds(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut result = rc4(key, data);
+    for i in 1..=19u8 {
+        let round_key: Vec<u8> = key.iter().map(|x| x ^ i).collect();
+        result = rc4(&round_key, &result);
+    }
+    result
+}
+
+/// Reverse of `rc4_rounds`
+fn rc4_rounds_rev(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut result = data.to_vec();
+    for i in (0..=19u8).rev() {
+        let round_key: Vec<u8> = key.iter().map(|x| x ^ i).collect();
+        result = rc4(&round_key, &result);
+    }
+    result
+}
+
+/// Steps (a) to (d) of Algorithm 3 of ISO 32000-1: compute RC4 key from owner password
+fn owner_key(owner: &[u8], revision: i64, key_len: usize) -> Vec<u8> {
+    let mut hash = Md5::digest(pad_password(owner)).to_vec();
+    if revision >= 3 {
+        for _ in 0..50 {
+            hash = Md5::digest(&hash).to_vec();
+        }
+    }
+
+    hash.truncate(key_len);
+    hash
+}
+
+/// Algorithm 3 of ISO 32000-1: compute /O entry
+fn compute_owner_entry(owner: &[u8], user: &[u8]) -> Vec<u8> {
+    rc4_rounds(&owner_key(owner, 4, KEY_LEN), &pad_password(user))
+}
+
+/// Algorithm 2 of ISO 32000-1: compute file encryption key
+fn compute_key(
+    user: &[u8],
+    encrypt: &Dictionary,
+    id: &[u8],
+    revision: i64,
+    key_len: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let owner_entry = encrypt.get(b"O").and_then(Object::as_str)?;
+    let permissions = encrypt.get(b"P").and_then(Object::as_i64)? as i32;
+    let encrypt_metadata = encrypt
+        .get(b"EncryptMetadata")
+        .and_then(Object::as_bool)
+        .unwrap_or(true);
+
+    let mut md5 = Md5::new();
+    md5.update(pad_password(user));
+    md5.update(owner_entry);
+    md5.update(permissions.to_le_bytes());
+    md5.update(id);
+    if revision >= 4 && !encrypt_metadata {
+        md5.update([0xffu8; 4]);
+    }
+
+    let mut hash = md5.finalize().to_vec();
+    if revision >= 3 {
+        for _ in 0..50 {
+            hash = Md5::digest(&hash[..key_len]).to_vec();
+        }
+    }
+
+    hash.truncate(key_len);
+    Ok(hash)
+}
+
+/// Algorithm 4 and 5 of ISO 32000-1: compute /U entry
+fn compute_user_entry(key: &[u8], id: &[u8], revision: i64) -> Vec<u8> {
+    if revision == 2 {
+        return rc4(key, &PADDING);
+    }
+
+    let mut md5 = Md5::new();
+    md5.update(PADDING);
+    md5.update(id);
+
+    let mut result = rc4_rounds(key, &md5.finalize());
+    result.resize(32, 0);
+    result
+}
+
+/// Algorithm 7 of ISO 32000-1: check whether PASSWORD is owner password of DOC.
+/// only standard security handler of revision 2 to 4 is supported.
+pub(crate) fn authenticate_owner(doc: &Document, password: &str) -> anyhow::Result<bool> {
+    let Some(encrypt) = permissions::encrypt_dict(doc) else {
+        anyhow::bail!("document is not encrypted");
+    };
+
+    let revision = encrypt.get(b"R").and_then(Object::as_i64)?;
+    anyhow::ensure!(
+        (2..=4).contains(&revision),
+        "unsupported security handler revision: {}",
+        revision
+    );
+
+    let key_len = if revision == 2 {
+        5
+    } else {
+        encrypt
+            .get(b"Length")
+            .and_then(Object::as_i64)
+            .map_or(5, |x| x as usize / 8)
+    };
+
+    let id = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)?
+        .first()
+        .ok_or(anyhow!("document ID is empty"))?
+        .as_str()?;
+
+    let owner_entry = encrypt.get(b"O").and_then(Object::as_str)?;
+    let user_entry = encrypt.get(b"U").and_then(Object::as_str)?;
+
+    let key = owner_key(password.as_bytes(), revision, key_len);
+    let user = if revision == 2 {
+        rc4(&key, owner_entry)
+    } else {
+        rc4_rounds_rev(&key, owner_entry)
+    };
+
+    let file_key = compute_key(&user, encrypt, id, revision, key_len)?;
+    let expected = compute_user_entry(&file_key, id, revision);
+
+    // only first 16 bytes are significant for revision 3 or greater
+    let len = if revision == 2 { 32 } else { 16 };
+    Ok(user_entry.len() >= len && expected[..len] == user_entry[..len])
+}
+
+/// Algorithm 1 of ISO 32000-1: encrypt DATA of object ID with AES, prefixed by random IV
+fn encrypt_aes(key: &[u8], id: ObjectId, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut md5 = Md5::new();
+    md5.update(key);
+    md5.update(&id.0.to_le_bytes()[..3]);
+    md5.update(id.1.to_le_bytes());
+    md5.update(b"sAlT");
+    let object_key = md5.finalize();
+
+    let mut iv = [0u8; 16];
+    getrandom::getrandom(&mut iv)?;
+
+    let mut result = iv.to_vec();
+    result.extend(Aes128CbcEnc::new(&object_key, &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(data));
+    Ok(result)
+}
+
+/// Get first element of document ID, creating ID from content when missing
+fn document_id(doc: &mut Document) -> Vec<u8> {
+    let existing = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|x| x.first())
+        .and_then(|x| x.as_str().ok())
+        .map(<[u8]>::to_vec);
+
+    if let Some(id) = existing {
+        return id;
+    }
+
+    let mut md5 = Md5::new();
+    for (&id, obj) in &doc.objects {
+        let mut bytes = Vec::new();
+        writer::write_indirect_object(&mut bytes, id, obj);
+        md5.update(&bytes);
+    }
+    let id = md5.finalize().to_vec();
+
+    doc.trailer.set(
+        "ID",
+        vec![
+            Object::String(id.clone(), StringFormat::Hexadecimal),
+            Object::String(id.clone(), StringFormat::Hexadecimal),
+        ],
+    );
+
+    id
+}
+
+/// Encrypt only embedded file streams with PASSWORD (envelope mode).
+/// strings and other streams use Identity crypt filter, so the document itself opens without password.
+pub(crate) fn encrypt_attachments(doc: &mut Document, password: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !doc.trailer.has(b"Encrypt"),
+        "document is already encrypted"
+    );
+
+    let mut file_ids: Vec<ObjectId> = Vec::new();
+    for (&id, obj) in &doc.objects {
+        match obj {
+            Object::Stream(stream) if stream.dict.type_name().ok() == Some("EmbeddedFile") => {
+                file_ids.push(id)
+            }
+            Object::Dictionary(dict) => {
+                if let Ok(ef) = dict.get(b"EF").and_then(Object::as_dict) {
+                    file_ids.extend(ef.iter().filter_map(|(_, x)| x.as_reference().ok()));
+                }
+            }
+            _ => {}
+        }
+    }
+    file_ids.sort();
+    file_ids.dedup();
+
+    let password = password.as_bytes();
+    let id = document_id(doc);
+
+    let mut encrypt = dictionary! {
+        "Filter" => "Standard",
+        "V" => 4,
+        "R" => 4,
+        "Length" => (KEY_LEN * 8) as i64,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! {
+                "Type" => "CryptFilter",
+                "CFM" => "AESV2",
+                "AuthEvent" => "EFOpen",
+                "Length" => KEY_LEN as i64,
+            },
+        },
+        "StmF" => "Identity",
+        "StrF" => "Identity",
+        "EFF" => "StdCF",
+        "O" => Object::String(compute_owner_entry(password, password), StringFormat::Hexadecimal),
+        "P" => PERMISSIONS as i64,
+    };
+
+    let key = compute_key(password, &encrypt, &id, 4, KEY_LEN)?;
+    encrypt.set(
+        "U",
+        Object::String(compute_user_entry(&key, &id, 4), StringFormat::Hexadecimal),
+    );
+
+    for file_id in file_ids {
+        if let Ok(Object::Stream(stream)) = doc.get_object_mut(file_id) {
+            let content = encrypt_aes(&key, file_id, &stream.content)?;
+            stream.set_content(content);
+        }
+    }
+
+    let encrypt_id = doc.add_object(encrypt);
+    doc.trailer.set("Encrypt", encrypt_id);
+
+    if doc.version.as_str() < "1.6" {
+        doc.version = "1.6".into();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::{limits::LimitExceeded, permissions::PermissionDenied};
+
+/// Error returned by core document operations of `Pdf` (loading, saving, pages, links
+/// and bookmarks). operations added by other modules still return `anyhow::Result`,
+/// whose errors convert into this keeping kinds like `PermissionDenied`.
+#[derive(Debug)]
+pub enum Error {
+    /// Page of this number does not exist in document
+    PageNotFound(u32),
+    /// Image data is in format or layout which can't be embedded
+    UnsupportedImage(String),
+    /// Document is encrypted and needs password or operation can't handle it
+    Encrypted(String),
+    /// Operation is forbidden by permissions of encrypted document
+    PermissionDenied(PermissionDenied),
+    /// Document or operation exceeds resource limits
+    LimitExceeded(LimitExceeded),
+    /// Argument is out of range or inconsistent with document
+    InvalidArgument(String),
+    /// Document structure is broken in a way operation can't work around
+    InvalidDocument(String),
+    Io(std::io::Error),
+    Lopdf(lopdf::Error),
+    Image(image::ImageError),
+    /// Error raised by other modules of library, which don't tell its kind
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PageNotFound(page) => write!(f, "page {} not found in document", page),
+            Self::UnsupportedImage(reason) => write!(f, "unsupported image: {}", reason),
+            Self::Encrypted(reason) => write!(f, "encrypted document: {}", reason),
+            Self::PermissionDenied(err) => err.fmt(f),
+            Self::LimitExceeded(err) => err.fmt(f),
+            Self::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            Self::InvalidDocument(reason) => write!(f, "invalid document: {}", reason),
+            Self::Io(err) => err.fmt(f),
+            Self::Lopdf(err) => err.fmt(f),
+            Self::Image(err) => err.fmt(f),
+            Self::Other(err) => {
+                // causes follow message, as they are not reported by source
+                write!(f, "{}", err)?;
+                let mut source = err.source();
+                while let Some(cause) = source {
+                    write!(f, ": {}", cause)?;
+                    source = cause.source();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Lopdf(err) => Some(err),
+            Self::Image(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<PermissionDenied> for Error {
+    fn from(err: PermissionDenied) -> Self {
+        Self::PermissionDenied(err)
+    }
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(err: LimitExceeded) -> Self {
+        Self::LimitExceeded(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for Error {
+    fn from(err: lopdf::Error) -> Self {
+        Self::Lopdf(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        // keep kind of errors passed through anyhow by other modules
+        let err = match err.downcast::<Error>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<PermissionDenied>() {
+            Ok(err) => return Self::PermissionDenied(err),
+            Err(err) => err,
+        };
+        match err.downcast::<LimitExceeded>() {
+            Ok(err) => Self::LimitExceeded(err),
+            Err(err) => Self::Other(err.into()),
+        }
+    }
+}
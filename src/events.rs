@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lopdf::{Object, ObjectId};
+use serde_json::{json, Map, Value};
+
+use crate::Pdf;
+
+/// Format of event stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ndjson" => Ok(Self::Ndjson),
+            _ => anyhow::bail!("unknown event format: {}", s),
+        }
+    }
+}
+
+/// Callback receiving name of TASK, number of DONE items and TOTAL of them.
+/// it is Send, so that Pdf holding it can be moved to other threads.
+pub type ProgressFn = Box<dyn FnMut(&str, usize, usize) + Send>;
+
+impl Pdf {
+    /// Set CALLBACK notified of progress in long running operations, such as
+    /// re-encoding images of large documents
+    pub fn set_progress(&mut self, callback: ProgressFn) {
+        self.progress = Some(callback);
+    }
+
+    pub(crate) fn report_progress(&mut self, task: &str, done: usize, total: usize) {
+        if let Some(callback) = &mut self.progress {
+            callback(task, done, total);
+        }
+    }
+}
+
+/// Total size of stream data in document, as cheap estimate of output size
+pub fn stream_bytes(pdf: &Pdf) -> usize {
+    pdf.doc
+        .objects
+        .values()
+        .filter_map(|x| match x {
+            Object::Stream(stream) => Some(stream.content.len()),
+            _ => None,
+        })
+        .sum()
+}
+
+struct Running {
+    op: String,
+    started: Instant,
+    pages: usize,
+    bytes: usize,
+    warnings: Vec<String>,
+}
+
+/// Writer of machine readable progress events.
+/// every event has "event" kind and "time" in milliseconds since unix epoch.
+pub struct EventSink {
+    out: Box<dyn Write>,
+    running: Option<Running>,
+}
+
+impl EventSink {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Self { out, running: None }
+    }
+
+    pub fn stderr() -> Self {
+        Self::new(Box::new(io::stderr()))
+    }
+
+    pub fn file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Self::new(Box::new(File::create(path)?)))
+    }
+
+    /// Write event KIND with FIELDS as single line
+    pub fn emit(&mut self, kind: &str, fields: Value) -> anyhow::Result<()> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        let mut event = Map::new();
+        event.insert("event".into(), kind.into());
+        event.insert("time".into(), time.into());
+        if let Value::Object(fields) = fields {
+            event.extend(fields);
+        }
+
+        serde_json::to_writer(&mut self.out, &event)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    /// Emit "start" of operation OP
+    pub fn start(&mut self, op: &str, pdf: &Pdf) -> anyhow::Result<()> {
+        let running = Running {
+            op: op.to_string(),
+            started: Instant::now(),
+            pages: pdf.page_count(),
+            bytes: stream_bytes(pdf),
+            warnings: vec![],
+        };
+
+        self.emit("start", json!({ "op": op, "pages": running.pages }))?;
+        self.running = Some(running);
+
+        Ok(())
+    }
+
+    /// Record warning, reported with "finish" of running operation or immediately otherwise
+    pub fn warning(&mut self, message: &str) -> anyhow::Result<()> {
+        match &mut self.running {
+            Some(running) => {
+                running.warnings.push(message.to_string());
+                Ok(())
+            }
+            None => self.emit("warning", json!({ "message": message })),
+        }
+    }
+
+    /// Emit "finish" of running operation with page count and stream byte deltas
+    pub fn finish(&mut self, pdf: &Pdf) -> anyhow::Result<()> {
+        let Some(running) = self.running.take() else {
+            return Ok(());
+        };
+
+        let pages = pdf.page_count();
+        let bytes = stream_bytes(pdf);
+
+        self.emit(
+            "finish",
+            json!({
+                "op": running.op,
+                "duration_ms": running.started.elapsed().as_millis() as u64,
+                "pages": pages,
+                "pages_delta": pages as i64 - running.pages as i64,
+                "stream_bytes": bytes,
+                "stream_bytes_delta": bytes as i64 - running.bytes as i64,
+                "warnings": running.warnings,
+            }),
+        )
+    }
+
+    /// Emit "error" with MESSAGE, attributed to running operation if any
+    pub fn error(&mut self, message: &str) -> anyhow::Result<()> {
+        let op = self.running.take().map(|x| x.op);
+        self.emit("error", json!({ "op": op, "message": message }))
+    }
+}
+
+/// Operation recorded in `Summary`
+struct SummaryEntry {
+    op: String,
+    started: Instant,
+    pages_before: BTreeSet<ObjectId>,
+    warnings: Vec<String>,
+}
+
+/// Collector of structured summary of operations done in one run, reported at end
+/// unlike events streamed by `EventSink`
+#[derive(Default)]
+pub struct Summary {
+    operations: Vec<Value>,
+    warnings: Vec<String>,
+    running: Option<SummaryEntry>,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start recording operation OP
+    pub fn start(&mut self, op: &str, pdf: &Pdf) {
+        self.running = Some(SummaryEntry {
+            op: op.to_string(),
+            started: Instant::now(),
+            pages_before: pdf.doc.get_pages().into_values().collect(),
+            warnings: vec![],
+        });
+    }
+
+    /// Record warning MESSAGE, attributed to running operation if any
+    pub fn warning(&mut self, message: &str) {
+        if let Some(running) = &mut self.running {
+            running.warnings.push(message.to_string());
+        }
+        self.warnings.push(message.to_string());
+    }
+
+    /// Finish running operation, recording pages created by it
+    pub fn finish(&mut self, pdf: &Pdf) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+
+        let pages = pdf.doc.get_pages();
+        let created: Vec<Value> = pages
+            .iter()
+            .filter(|(_, id)| !running.pages_before.contains(id))
+            .map(|(num, id)| json!({ "page": num, "id": [id.0, id.1] }))
+            .collect();
+        let removed = running
+            .pages_before
+            .iter()
+            .filter(|id| !pages.values().any(|x| x == *id))
+            .count();
+
+        self.operations.push(json!({
+            "op": running.op,
+            "duration_ms": running.started.elapsed().as_millis() as u64,
+            "pages": pages.len(),
+            "pages_created": created,
+            "pages_removed": removed,
+            "warnings": running.warnings,
+        }));
+    }
+
+    /// Build summary object, with FIELDS describing result such as output path added
+    pub fn to_json(&self, fields: Value) -> Value {
+        let mut summary = Map::new();
+        summary.insert("operations".into(), self.operations.clone().into());
+        summary.insert("warnings".into(), self.warnings.clone().into());
+        if let Value::Object(fields) = fields {
+            summary.extend(fields);
+        }
+
+        Value::Object(summary)
+    }
+}
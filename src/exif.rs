@@ -0,0 +1,128 @@
+use image::DynamicImage;
+
+/// EXIF tag of image orientation
+const TAG_ORIENTATION: u16 = 0x0112;
+/// EXIF tags of horizontal and vertical resolution and their unit
+const TAG_X_RESOLUTION: u16 = 0x011a;
+const TAG_Y_RESOLUTION: u16 = 0x011b;
+const TAG_RESOLUTION_UNIT: u16 = 0x0128;
+
+/// Find content of first JPEG marker segment MARKER starting with PREFIX,
+/// with prefix stripped
+pub(crate) fn jpeg_segment<'a>(bytes: &'a [u8], marker: u8, prefix: &[u8]) -> Option<&'a [u8]> {
+    let mut pos = 2;
+
+    // walk marker segments until start of scan
+    while let [0xff, current, high, low, ..] = *bytes.get(pos..)? {
+        let length = u16::from_be_bytes([high, low]) as usize;
+        if current == 0xda {
+            return None;
+        }
+
+        let segment = bytes.get(pos + 4..pos + 2 + length)?;
+        if current == marker {
+            if let Some(content) = segment.strip_prefix(prefix) {
+                return Some(content);
+            }
+        }
+        pos += 2 + length;
+    }
+
+    None
+}
+
+/// First IFD of TIFF structure embedded in EXIF
+struct Ifd0<'a> {
+    tiff: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Ifd0<'a> {
+    fn from_jpeg(bytes: &'a [u8]) -> Option<Self> {
+        let tiff = jpeg_segment(bytes, 0xe1, b"Exif\0\0")?;
+        let big_endian = match tiff.get(..2)? {
+            b"MM" => true,
+            b"II" => false,
+            _ => return None,
+        };
+
+        Some(Self { tiff, big_endian })
+    }
+
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let b: [u8; 2] = self.tiff.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let b: [u8; 4] = self.tiff.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    /// Find position of value field of entry TAG
+    fn value(&self, tag: u16) -> Option<usize> {
+        let ifd = self.u32_at(4)? as usize;
+        let count = self.u16_at(ifd)? as usize;
+
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| self.u16_at(entry) == Some(tag))
+            .map(|entry| entry + 8)
+    }
+
+    /// Get SHORT value of TAG, which is stored in first 2 bytes of value field
+    fn short(&self, tag: u16) -> Option<u16> {
+        self.u16_at(self.value(tag)?)
+    }
+
+    /// Get RATIONAL value of TAG, which is stored at offset given in value field
+    fn rational(&self, tag: u16) -> Option<f32> {
+        let offset = self.u32_at(self.value(tag)?)? as usize;
+        let numerator = self.u32_at(offset)?;
+        let denominator = self.u32_at(offset + 4)?;
+
+        (denominator != 0).then(|| numerator as f32 / denominator as f32)
+    }
+}
+
+/// Read orientation tag (1 to 8) from EXIF segment of JPEG data
+pub(crate) fn orientation(bytes: &[u8]) -> Option<u16> {
+    Ifd0::from_jpeg(bytes)?
+        .short(TAG_ORIENTATION)
+        .filter(|x| (1..=8).contains(x))
+}
+
+/// Read horizontal and vertical resolution in DPI from EXIF segment of JPEG data
+pub(crate) fn resolution(bytes: &[u8]) -> Option<(f32, f32)> {
+    let ifd = Ifd0::from_jpeg(bytes)?;
+    let x = ifd.rational(TAG_X_RESOLUTION)?;
+    let y = ifd.rational(TAG_Y_RESOLUTION).unwrap_or(x);
+
+    match ifd.short(TAG_RESOLUTION_UNIT).unwrap_or(2) {
+        2 => Some((x, y)),
+        3 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+/// Transform IMG so that it looks upright according to EXIF ORIENTATION
+pub(crate) fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
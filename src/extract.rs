@@ -0,0 +1,113 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::ImageFormat;
+use lopdf::{Object, ObjectId};
+
+use crate::{
+    limits::LimitExceeded, optimize::filter_names, selection::PageSelection, xobject, Pdf,
+};
+
+/// Image XObject taken out of page, encoded as file
+#[derive(Clone, Debug)]
+pub struct ExtractedImage {
+    /// Page which image is in resources of
+    pub page: u32,
+    /// Index of image among images of page, starting from 1
+    pub index: usize,
+    pub id: ObjectId,
+    /// Jpeg for DCTDecode images copied as is, Png for others
+    pub format: ImageFormat,
+    pub data: Vec<u8>,
+}
+
+impl ExtractedImage {
+    /// File name by page and index, like page003-1.png
+    pub fn file_name(&self) -> String {
+        let extension = match self.format {
+            ImageFormat::Jpeg => "jpg",
+            _ => "png",
+        };
+
+        format!("page{:03}-{}.{}", self.page, self.index, extension)
+    }
+}
+
+impl Pdf {
+    /// Take out image XObjects in resources of PAGES as JPEG or PNG files, in order of
+    /// their resource names. DCTDecode images are copied as is and others are decoded
+    /// into PNG. images which can't be decoded are skipped with warning.
+    pub fn extract_images(&self, pages: &PageSelection) -> anyhow::Result<Vec<ExtractedImage>> {
+        let page_ids = self.doc.get_pages();
+        let mut result = Vec::new();
+
+        for page in pages.page_set(page_ids.len() as u32)? {
+            let page_id = page_ids[&page];
+            let mut index = 0;
+
+            for id in xobject::page_xobjects(&self.doc, page_id).into_values() {
+                if !xobject::is_image(&self.doc, id) {
+                    continue;
+                }
+                let stream = self.doc.get_object(id).and_then(Object::as_stream)?;
+                index += 1;
+
+                let (format, data) = if filter_names(&stream.dict) == [b"DCTDecode"] {
+                    (ImageFormat::Jpeg, stream.content.clone())
+                } else {
+                    let img = match self.decode_image(stream) {
+                        Ok(img) => img,
+                        Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                        Err(err) => {
+                            log::warn!(
+                                "skipping image {} {} on page {}: {}",
+                                id.0,
+                                id.1,
+                                page,
+                                err
+                            );
+                            continue;
+                        }
+                    };
+
+                    let mut png = Vec::new();
+                    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+                    (ImageFormat::Png, png)
+                };
+
+                log::debug!("extracted image {} {} on page {}", id.0, id.1, page);
+                result.push(ExtractedImage {
+                    page,
+                    index,
+                    id,
+                    format,
+                    data,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Write images of PAGES into directory DIR, named by page and index, returning paths
+    /// of written files
+    pub fn extract_images_to<P: AsRef<Path>>(
+        &self,
+        pages: &PageSelection,
+        dir: P,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::new();
+        for image in self.extract_images(pages)? {
+            let path = dir.join(image.file_name());
+            std::fs::write(&path, &image.data)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Context;
+use lopdf::{dictionary, Dictionary, Document, Object};
+
+use crate::{
+    forms::FieldValue,
+    metadata::unescape_xml,
+    pdf::{decode_text_string, text_string},
+    repair::rebuild_xref,
+    writer, Pdf,
+};
+
+/// Field of form data file, holding value or kids named by partial names
+struct FieldNode {
+    name: String,
+    value: Option<Object>,
+    kids: Vec<FieldNode>,
+}
+
+impl FieldNode {
+    /// Build tree of fields from fully qualified names in VALUES, keeping their order
+    fn tree(values: Vec<(String, Object)>) -> Vec<FieldNode> {
+        let mut roots: Vec<FieldNode> = vec![];
+
+        for (name, value) in values {
+            let mut nodes = &mut roots;
+            let parts: Vec<&str> = name.split('.').collect();
+
+            for (i, part) in parts.iter().enumerate() {
+                let pos = match nodes.iter().position(|x| x.name == *part) {
+                    Some(pos) => pos,
+                    None => {
+                        nodes.push(FieldNode {
+                            name: part.to_string(),
+                            value: None,
+                            kids: vec![],
+                        });
+                        nodes.len() - 1
+                    }
+                };
+                if i == parts.len() - 1 {
+                    nodes[pos].value = Some(value.clone());
+                }
+                nodes = &mut nodes[pos].kids;
+            }
+        }
+
+        roots
+    }
+
+    fn to_fdf(&self) -> Object {
+        let mut dict = Dictionary::new();
+        dict.set("T", text_string(&self.name));
+        if let Some(value) = &self.value {
+            dict.set("V", value.clone());
+        }
+        if !self.kids.is_empty() {
+            dict.set(
+                "Kids",
+                self.kids.iter().map(FieldNode::to_fdf).collect::<Vec<_>>(),
+            );
+        }
+
+        dict.into()
+    }
+
+    fn write_xfdf(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!(
+            "{}<field name=\"{}\">\n",
+            indent,
+            escape_xml(&self.name)
+        ));
+
+        let value = match &self.value {
+            Some(Object::String(bytes, _)) => Some(decode_text_string(bytes)),
+            Some(Object::Name(name)) => Some(String::from_utf8_lossy(name).into_owned()),
+            _ => None,
+        };
+        if let Some(value) = value {
+            out.push_str(&format!(
+                "{}  <value>{}</value>\n",
+                indent,
+                escape_xml(&value)
+            ));
+        }
+        for kid in &self.kids {
+            kid.write_xfdf(out, depth + 1);
+        }
+
+        out.push_str(&format!("{}</field>\n", indent));
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Find value of attribute NAME in start TAG
+pub(crate) fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let key = format!("{}={}", name, quote);
+        // attributes may be separated by any whitespace, like newline
+        let start = tag.match_indices(&key).map(|(i, _)| i).find(|&i| {
+            tag[..i]
+                .chars()
+                .next_back()
+                .map_or(false, char::is_whitespace)
+        });
+        if let Some(start) = start {
+            let value = &tag[start + key.len()..];
+            return Some(&value[..value.find(quote)?]);
+        }
+    }
+
+    None
+}
+
+/// Read field values from XFDF document XML, keyed by fully qualified names
+fn parse_xfdf(xml: &str) -> anyhow::Result<HashMap<String, FieldValue>> {
+    let mut names: Vec<String> = vec![];
+    let mut values = HashMap::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let end = rest.find('>').context("unterminated tag in XFDF")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let tag_name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .find(|x| !x.is_empty())
+            .unwrap_or_default();
+        let closing = tag.starts_with('/');
+        let empty = tag.ends_with('/');
+
+        match (tag_name, closing) {
+            ("field", false) if !empty => {
+                let name = attribute(tag, "name").context("field without name in XFDF")?;
+                names.push(unescape_xml(name));
+            }
+            ("field", true) => {
+                names.pop();
+            }
+            ("value", false) => {
+                let text = if empty {
+                    ""
+                } else {
+                    let end = rest
+                        .find("</value>")
+                        .context("unterminated value in XFDF")?;
+                    let text = &rest[..end];
+                    rest = &rest[end..];
+                    text
+                };
+                anyhow::ensure!(!names.is_empty(), "value outside of field in XFDF");
+                values.insert(names.join("."), FieldValue::Text(unescape_xml(text)));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(values)
+}
+
+/// Read field values from FDF document BYTES, keyed by fully qualified names
+fn parse_fdf(bytes: &[u8]) -> anyhow::Result<HashMap<String, FieldValue>> {
+    // FDF has same syntax as PDF but for header; files often lack valid xref table
+    let mut bytes = bytes.to_vec();
+    bytes[1..4].copy_from_slice(b"PDF");
+    let doc = match Document::load_mem(&bytes) {
+        Ok(doc) => doc,
+        Err(_) => Document::load_mem(&rebuild_xref(&bytes, &mut vec![])?)?,
+    };
+
+    let deref = |obj: &Object| -> Object {
+        match obj {
+            Object::Reference(id) => doc.get_object(*id).cloned().unwrap_or(Object::Null),
+            _ => obj.clone(),
+        }
+    };
+
+    let fdf = deref(
+        doc.catalog()?
+            .get(b"FDF")
+            .context("FDF dictionary not found")?,
+    );
+    let fields = match fdf.as_dict()?.get(b"Fields") {
+        Ok(fields) => deref(fields).as_array()?.clone(),
+        Err(_) => vec![],
+    };
+
+    let mut stack: Vec<(String, Object)> = fields
+        .into_iter()
+        .rev()
+        .map(|x| (String::new(), x))
+        .collect();
+    let mut visited = BTreeSet::new();
+    let mut values = HashMap::new();
+
+    while let Some((parent, field)) = stack.pop() {
+        if let Object::Reference(id) = field {
+            if !visited.insert(id) {
+                continue;
+            }
+        }
+        let field = deref(&field);
+        let Ok(dict) = field.as_dict() else {
+            continue;
+        };
+
+        let name = match dict.get(b"T").and_then(Object::as_str) {
+            Ok(partial) if parent.is_empty() => decode_text_string(partial),
+            Ok(partial) => format!("{}.{}", parent, decode_text_string(partial)),
+            Err(_) => parent,
+        };
+
+        // multiple selections of list box are given as array, of which first is used
+        let value = match dict.get(b"V").map(&deref) {
+            Ok(Object::Array(arr)) => arr.first().map(&deref),
+            Ok(value) => Some(value),
+            Err(_) => None,
+        };
+        match value {
+            Some(Object::String(bytes, _)) => {
+                values.insert(name.clone(), FieldValue::Text(decode_text_string(&bytes)));
+            }
+            Some(Object::Name(state)) => {
+                let state = String::from_utf8_lossy(&state).into_owned();
+                values.insert(name.clone(), FieldValue::Text(state));
+            }
+            _ => {}
+        }
+
+        if let Ok(kids) = dict.get(b"Kids").map(&deref) {
+            let kids = kids.as_array().cloned().unwrap_or_default();
+            stack.extend(kids.into_iter().rev().map(|x| (name.clone(), x)));
+        }
+    }
+
+    Ok(values)
+}
+
+impl Pdf {
+    /// Get values of filled terminal fields, keyed by fully qualified names
+    fn form_data(&self) -> Vec<(String, Object)> {
+        self.form_field_names()
+            .into_iter()
+            .filter_map(|(name, id)| {
+                let value = match self.doc.get_dictionary(id).ok()?.get(b"V").ok()? {
+                    Object::Reference(id) => self.doc.get_object(*id).ok()?,
+                    value => value,
+                };
+                // signature values and others which can't be filled are left out
+                matches!(value, Object::String(..) | Object::Name(_)).then(|| (name, value.clone()))
+            })
+            .collect()
+    }
+
+    /// Export values of form fields as FDF document
+    pub fn export_fdf(&self) -> Vec<u8> {
+        let fields: Vec<Object> = FieldNode::tree(self.form_data())
+            .iter()
+            .map(FieldNode::to_fdf)
+            .collect();
+
+        let mut fdf = Dictionary::new();
+        fdf.set("Fields", fields);
+        let mut catalog = Dictionary::new();
+        catalog.set("FDF", fdf);
+
+        let mut out = b"%FDF-1.2\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        writer::write_indirect_object(&mut out, (1, 0), &catalog.into());
+        out.extend_from_slice(b"trailer\n");
+        writer::write_dictionary(&mut out, &dictionary! { "Root" => (1, 0) });
+        out.extend_from_slice(b"\n%%EOF\n");
+
+        out
+    }
+
+    /// Export values of form fields as XFDF document
+    pub fn export_xfdf(&self) -> String {
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <xfdf xmlns=\"http://ns.adobe.com/xfdf/\" xml:space=\"preserve\">\n\
+             <fields>\n",
+        );
+        for node in FieldNode::tree(self.form_data()) {
+            node.write_xfdf(&mut out, 1);
+        }
+        out.push_str("</fields>\n</xfdf>\n");
+
+        out
+    }
+
+    /// Fill form fields with values of FDF or XFDF document BYTES, detected by content
+    pub fn import_form_data(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        let values = if bytes.starts_with(b"%FDF-") {
+            parse_fdf(bytes)?
+        } else {
+            let xml = std::str::from_utf8(bytes).context("form data is neither FDF nor XFDF")?;
+            anyhow::ensure!(xml.contains("<xfdf"), "form data is neither FDF nor XFDF");
+            parse_xfdf(xml)?
+        };
+
+        log::info!("importing values of {} fields", values.len());
+        Ok(self.fill_form(&values)?)
+    }
+}
@@ -0,0 +1,266 @@
+use lopdf::{
+    content::{Content, Operation},
+    Dictionary, Object, ObjectId,
+};
+
+use crate::{
+    appearance::{field_value, page_annotations},
+    permissions::Permission,
+    xobject::as_number,
+    Pdf,
+};
+
+/// Annotation flags which make annotation invisible (Hidden, NoView)
+const INVISIBLE_FLAGS: i64 = 0x02 | 0x20;
+
+fn numbers<const N: usize>(dict: &Dictionary, key: &[u8]) -> Option<[f32; N]> {
+    let arr = dict.get(key).and_then(Object::as_array).ok()?;
+    let values: Vec<f32> = arr.iter().filter_map(as_number).collect();
+    values.try_into().ok()
+}
+
+/// Transform rectangle by matrix M, returning bounding box of result
+pub(crate) fn transform_rect(rect: [f32; 4], m: [f32; 6]) -> [f32; 4] {
+    let points = [
+        (rect[0], rect[1]),
+        (rect[0], rect[3]),
+        (rect[2], rect[1]),
+        (rect[2], rect[3]),
+    ]
+    .map(|(x, y)| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]));
+
+    points.iter().skip(1).fold(
+        [points[0].0, points[0].1, points[0].0, points[0].1],
+        |[x0, y0, x1, y1], &(x, y)| [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+    )
+}
+
+impl Pdf {
+    /// Get normal appearance stream of annotation, selecting /AS state if there are several
+    fn normal_appearance(&self, annot: &Dictionary) -> Option<ObjectId> {
+        let ap = annot.get(b"AP").ok()?;
+        let ap = match ap {
+            Object::Reference(id) => self.doc.get_dictionary(*id).ok()?,
+            _ => ap.as_dict().ok()?,
+        };
+
+        match ap.get(b"N").ok()? {
+            Object::Reference(id) => match self.doc.get_object(*id).ok()? {
+                Object::Stream(_) => Some(*id),
+                Object::Dictionary(states) => {
+                    let state = annot.get(b"AS").and_then(Object::as_name).ok()?;
+                    states.get(state).and_then(Object::as_reference).ok()
+                }
+                _ => None,
+            },
+            Object::Dictionary(states) => {
+                let state = annot.get(b"AS").and_then(Object::as_name).ok()?;
+                states.get(state).and_then(Object::as_reference).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Register normal appearance of annotation ANNOT_ID, the INDEX-th on page PAGE_ID,
+    /// as XObject of page, returning operations drawing it at its rectangle.
+    /// None if annotation has no usable appearance.
+    fn stamp_appearance(
+        &mut self,
+        page_id: ObjectId,
+        annot_id: ObjectId,
+        index: usize,
+    ) -> anyhow::Result<Option<Vec<Operation>>> {
+        let annot = self.doc.get_dictionary(annot_id)?;
+        let (Some(ap_id), Some(rect)) = (self.normal_appearance(annot), numbers::<4>(annot, b"Rect")) else {
+            return Ok(None);
+        };
+        let rect = [
+            rect[0].min(rect[2]),
+            rect[1].min(rect[3]),
+            rect[0].max(rect[2]),
+            rect[1].max(rect[3]),
+        ];
+
+        let form = &self.doc.get_object(ap_id)?.as_stream()?.dict;
+        let matrix = numbers::<6>(form, b"Matrix").unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let Some(bbox) = numbers::<4>(form, b"BBox") else {
+            return Ok(None);
+        };
+
+        // map transformed bbox of form onto annotation rectangle
+        let [bx0, by0, bx1, by1] = transform_rect(bbox, matrix);
+        let (bw, bh) = (bx1 - bx0, by1 - by0);
+        let sx = if bw == 0.0 {
+            1.0
+        } else {
+            (rect[2] - rect[0]) / bw
+        };
+        let sy = if bh == 0.0 {
+            1.0
+        } else {
+            (rect[3] - rect[1]) / bh
+        };
+        let a = [sx, 0.0, 0.0, sy, rect[0] - bx0 * sx, rect[1] - by0 * sy];
+
+        let name = format!("FlatAnnot{}_{}", annot_id.0, index);
+        self.doc.add_xobject(page_id, name.as_bytes(), ap_id)?;
+
+        Ok(Some(vec![
+            Operation::new("q", vec![]),
+            Operation::new("cm", a.iter().map(|&x| Object::Real(x)).collect()),
+            Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+            Operation::new("Q", vec![]),
+        ]))
+    }
+
+    /// Draw appearance streams of annotations on page PAGE into its content, then remove them.
+    /// annotations without appearance get one generated first; those still lacking one and
+    /// form field widgets are kept.
+    pub fn flatten_annotations(&mut self, page: u32) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        let mut ops = vec![];
+        let mut flattened = vec![];
+
+        for (i, annot_id) in page_annotations(&self.doc, page_id).into_iter().enumerate() {
+            let annot = self.doc.get_dictionary(annot_id)?;
+            let flags = annot.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+            let subtype = annot.get(b"Subtype").and_then(Object::as_name).ok();
+
+            // form fields are still referenced from AcroForm, so leave them in place
+            if subtype == Some(&b"Widget"[..]) {
+                continue;
+            }
+
+            if flags & INVISIBLE_FLAGS != 0 || subtype == Some(&b"Popup"[..]) {
+                flattened.push(annot_id);
+                continue;
+            }
+
+            if self.normal_appearance(annot).is_none() {
+                self.regenerate_appearance(annot_id)?;
+            }
+
+            if let Some(stamp) = self.stamp_appearance(page_id, annot_id, i)? {
+                ops.extend(stamp);
+                flattened.push(annot_id);
+            }
+        }
+
+        if !ops.is_empty() {
+            let content = Content { operations: ops }.encode()?;
+            self.append_page_content(page_id, content)?;
+        }
+
+        self.remove_annotations(page_id, &flattened)
+    }
+
+    /// Draw appearance streams of form field widgets on all pages into their content,
+    /// then remove fields and interactive form, so values can no longer be edited.
+    /// appearances are rebuilt first where missing or if form asks viewers to do so.
+    pub fn flatten_form(&mut self) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let form = self.doc.catalog()?.get(b"AcroForm").ok().map(|x| match x {
+            Object::Reference(id) => self.doc.get_dictionary(*id).ok(),
+            _ => x.as_dict().ok(),
+        });
+        let Some(form) = form else {
+            return Ok(());
+        };
+        let need_appearances = form
+            .and_then(|x| x.get(b"NeedAppearances").and_then(Object::as_bool).ok())
+            .unwrap_or(false);
+
+        for page_id in self.doc.get_pages().into_values() {
+            let mut ops = vec![];
+            let mut widgets = vec![];
+
+            for (i, annot_id) in page_annotations(&self.doc, page_id).into_iter().enumerate() {
+                let annot = self.doc.get_dictionary(annot_id)?;
+                if annot.get(b"Subtype").and_then(Object::as_name).ok() != Some(&b"Widget"[..]) {
+                    continue;
+                }
+                widgets.push(annot_id);
+
+                let flags = annot.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+                if flags & INVISIBLE_FLAGS != 0 {
+                    continue;
+                }
+
+                // NeedAppearances is about fields showing their value as text; appearances
+                // of other fields are rebuilt only if missing
+                let field_type = field_value(&self.doc, annot, b"FT");
+                let shows_text = matches!(
+                    field_type.as_ref().and_then(|x| x.as_name().ok()),
+                    Some(b"Tx" | b"Ch")
+                );
+                if (need_appearances && shows_text) || self.normal_appearance(annot).is_none() {
+                    self.regenerate_appearance(annot_id)?;
+                    // value would be lost with fields, if no appearance could be made
+                    let annot = self.doc.get_dictionary(annot_id)?;
+                    anyhow::ensure!(
+                        !shows_text || self.normal_appearance(annot).is_some(),
+                        "value of widget {} {} can't be drawn with fonts of form to flatten",
+                        annot_id.0,
+                        annot_id.1
+                    );
+                }
+                ops.extend(
+                    self.stamp_appearance(page_id, annot_id, i)?
+                        .unwrap_or_default(),
+                );
+            }
+
+            if !ops.is_empty() {
+                let content = Content { operations: ops }.encode()?;
+                self.append_page_content(page_id, content)?;
+            }
+            self.remove_annotations(page_id, &widgets)?;
+        }
+
+        // non-terminal fields are not annotations, so they are removed separately
+        let mut stack = self.form_fields();
+        while let Some(id) = stack.pop() {
+            if let Ok(Object::Array(kids)) = self
+                .doc
+                .get_dictionary(id)
+                .and_then(|x| x.get(b"Kids"))
+                .cloned()
+            {
+                stack.extend(kids.iter().filter_map(|x| x.as_reference().ok()));
+            }
+            self.doc.objects.remove(&id);
+        }
+
+        self.catalog_mut()?.remove(b"AcroForm");
+        Ok(())
+    }
+
+    /// Remove annotations IDS from /Annots of page PAGE_ID and delete them
+    pub(crate) fn remove_annotations(
+        &mut self,
+        page_id: ObjectId,
+        ids: &[ObjectId],
+    ) -> anyhow::Result<()> {
+        let remaining: Vec<Object> = page_annotations(&self.doc, page_id)
+            .into_iter()
+            .filter(|id| !ids.contains(id))
+            .map(Object::Reference)
+            .collect();
+
+        let page = self.doc.get_dictionary_mut(page_id)?;
+        if remaining.is_empty() {
+            page.remove(b"Annots");
+        } else {
+            page.set("Annots", remaining);
+        }
+
+        for id in ids {
+            self.doc.objects.remove(id);
+        }
+
+        Ok(())
+    }
+}
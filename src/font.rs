@@ -0,0 +1,336 @@
+use std::{collections::BTreeMap, fmt, path::Path, str::FromStr, sync::Arc};
+
+use lopdf::{dictionary, Object, ObjectId, Stream, StringFormat};
+use ttf_parser::{name_id, Face, GlyphId};
+
+use crate::{
+    text::{win_ansi, StandardFont},
+    Pdf,
+};
+
+/// Number of mappings in each bfchar block of ToUnicode CMap, at most 100 by spec
+const BFCHAR_CHUNK: usize = 100;
+
+/// TrueType font embedded whole as composite font with Identity-H encoding, for text
+/// out of Latin-1 like Japanese, Chinese or Korean
+#[derive(Clone)]
+pub struct TrueTypeFont {
+    data: Arc<Vec<u8>>,
+    /// PostScript name of font
+    name: String,
+}
+
+impl fmt::Debug for TrueTypeFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrueTypeFont")
+            .field("name", &self.name)
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+impl PartialEq for TrueTypeFont {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.data == other.data
+    }
+}
+
+impl TrueTypeFont {
+    /// Load TrueType font file (.ttf) at PATH. collections (.ttc) are not supported.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Load TrueType font from DATA in memory. OpenType fonts with CFF outlines
+    /// are not supported, as their glyph ids don't match CIDs.
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        // whole file is embedded, which would carry every font of collection
+        anyhow::ensure!(
+            !data.starts_with(b"ttcf"),
+            "font collection (.ttc) is not supported, use single font (.ttf) of it"
+        );
+        let face = Face::parse(&data, 0)?;
+        anyhow::ensure!(
+            face.tables().glyf.is_some(),
+            "font has no TrueType outlines"
+        );
+
+        let name = face
+            .names()
+            .into_iter()
+            .filter(|x| x.name_id == name_id::POST_SCRIPT_NAME)
+            .find_map(|x| x.to_string())
+            .map(|x| x.chars().filter(|c| c.is_ascii_graphic()).collect())
+            .filter(|x: &String| !x.is_empty())
+            .unwrap_or_else(|| "PdftoolFont".to_string());
+
+        Ok(Self {
+            data: Arc::new(data),
+            name,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn face(&self) -> Face<'_> {
+        Face::parse(&self.data, 0).expect("font was checked when loaded")
+    }
+
+    /// Scale of font units to 1/1000 em used by PDF
+    fn scale(face: &Face) -> f32 {
+        1000.0 / face.units_per_em() as f32
+    }
+
+    /// Encode TEXT as 2 byte glyph ids for Identity-H. characters font doesn't have
+    /// are drawn as .notdef glyph.
+    pub(crate) fn encode(&self, text: &str) -> Object {
+        let face = self.face();
+        let bytes = text
+            .chars()
+            .flat_map(|c| face.glyph_index(c).map_or(0, |x| x.0).to_be_bytes())
+            .collect();
+
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+
+    /// Width of TEXT at SIZE points
+    pub(crate) fn text_width(&self, text: &str, size: f32) -> f32 {
+        let face = self.face();
+        let units: u32 = text
+            .chars()
+            .map(|c| {
+                let glyph = face.glyph_index(c).unwrap_or(GlyphId(0));
+                face.glyph_hor_advance(glyph).unwrap_or(0) as u32
+            })
+            .sum();
+
+        units as f32 * Self::scale(&face) * size / 1000.0
+    }
+
+    /// W array of CIDFont, listing ranges of glyphs sharing width other than DW of 1000
+    fn widths(face: &Face) -> Vec<Object> {
+        let scale = Self::scale(face);
+        let mut runs: Vec<(u16, u16, i64)> = vec![];
+
+        for glyph in 0..face.number_of_glyphs() {
+            let width =
+                (face.glyph_hor_advance(GlyphId(glyph)).unwrap_or(0) as f32 * scale).round() as i64;
+            match runs.last_mut() {
+                Some((_, last, w)) if *last + 1 == glyph && *w == width => *last = glyph,
+                _ => runs.push((glyph, glyph, width)),
+            }
+        }
+
+        runs.into_iter()
+            .filter(|(_, _, width)| *width != 1000)
+            .flat_map(|(first, last, width)| [first.into(), last.into(), width.into()])
+            .collect()
+    }
+
+    /// ToUnicode CMap mapping glyph ids back to characters, so text can be copied
+    fn to_unicode(face: &Face) -> Vec<u8> {
+        let mut chars: BTreeMap<u16, char> = BTreeMap::new();
+        let subtables = face.tables().cmap.into_iter().flat_map(|x| x.subtables);
+
+        for subtable in subtables.filter(|x| x.is_unicode()) {
+            subtable.codepoints(|code| {
+                if let (Some(c), Some(glyph)) = (char::from_u32(code), subtable.glyph_index(code)) {
+                    chars.entry(glyph.0).or_insert(c);
+                }
+            });
+        }
+
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n\
+             12 dict begin\n\
+             begincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n\
+             /CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+
+        let entries: Vec<(u16, char)> = chars.into_iter().collect();
+        for chunk in entries.chunks(BFCHAR_CHUNK) {
+            cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+            for (glyph, c) in chunk {
+                let unicode: String = c
+                    .encode_utf16(&mut [0; 2])
+                    .iter()
+                    .map(|x| format!("{:04X}", x))
+                    .collect();
+                cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, unicode));
+            }
+            cmap.push_str("endbfchar\n");
+        }
+
+        cmap.push_str(
+            "endcmap\n\
+             CMapName currentdict /CMap defineresource pop\n\
+             end\n\
+             end\n",
+        );
+
+        cmap.into_bytes()
+    }
+}
+
+/// Font text is drawn with
+#[derive(Clone, Debug, PartialEq)]
+pub enum Font {
+    Standard(StandardFont),
+    TrueType(TrueTypeFont),
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::Standard(StandardFont::default())
+    }
+}
+
+impl From<StandardFont> for Font {
+    fn from(font: StandardFont) -> Self {
+        Self::Standard(font)
+    }
+}
+
+impl From<TrueTypeFont> for Font {
+    fn from(font: TrueTypeFont) -> Self {
+        Self::TrueType(font)
+    }
+}
+
+impl FromStr for Font {
+    type Err = anyhow::Error;
+
+    /// Parse name of standard font like Times-Roman, or load TrueType font file at path S
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(font) => Ok(Self::Standard(font)),
+            Err(_) if Path::new(s).is_file() => Ok(Self::TrueType(TrueTypeFont::load(s)?)),
+            Err(_) => anyhow::bail!(
+                "unknown font: {}, expected standard font name or font file",
+                s
+            ),
+        }
+    }
+}
+
+impl Font {
+    /// Width of TEXT at SIZE points
+    pub(crate) fn text_width(&self, text: &str, size: f32) -> f32 {
+        match self {
+            Self::Standard(font) => font.text_width(text, size),
+            Self::TrueType(font) => font.text_width(text, size),
+        }
+    }
+}
+
+impl Pdf {
+    /// Get Type0 font object of FONT, embedding it if document does not have one yet.
+    /// font is embedded whole without subsetting, so output grows by size of font file.
+    pub(crate) fn embedded_font(&mut self, font: &TrueTypeFont) -> anyhow::Result<ObjectId> {
+        let existing = self.doc.objects.iter().find_map(|(id, obj)| {
+            let dict = obj.as_dict().ok()?;
+            let is_same = dict.type_name().ok() == Some("Font")
+                && dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Type0"[..])
+                && dict.get(b"BaseFont").and_then(Object::as_name).ok()
+                    == Some(font.name.as_bytes())
+                && dict.get(b"Encoding").and_then(Object::as_name).ok() == Some(&b"Identity-H"[..]);
+
+            is_same.then_some(*id)
+        });
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+
+        let face = font.face();
+        let scale = TrueTypeFont::scale(&face);
+        let bbox = face.global_bounding_box();
+
+        let mut file = Stream::new(
+            dictionary! { "Length1" => font.data.len() as i64 },
+            font.data.to_vec(),
+        );
+        let _ = file.compress();
+        let file_id = self.doc.add_object(file);
+
+        // symbolic, as glyphs are out of standard Latin character set
+        let mut flags: i64 = 4;
+        if face.is_monospaced() {
+            flags |= 1;
+        }
+        if face.is_italic() {
+            flags |= 64;
+        }
+
+        let descriptor_id = self.doc.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => font.name.as_str(),
+            "Flags" => flags,
+            "FontBBox" => vec![
+                (bbox.x_min as f32 * scale).into(),
+                (bbox.y_min as f32 * scale).into(),
+                (bbox.x_max as f32 * scale).into(),
+                (bbox.y_max as f32 * scale).into(),
+            ],
+            "ItalicAngle" => face.italic_angle().unwrap_or(0.0),
+            "Ascent" => face.ascender() as f32 * scale,
+            "Descent" => face.descender() as f32 * scale,
+            "CapHeight" => face.capital_height().unwrap_or_else(|| face.ascender()) as f32 * scale,
+            "StemV" => 80,
+            "FontFile2" => file_id,
+        });
+
+        let cid_font_id = self.doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "CIDFontType2",
+            "BaseFont" => font.name.as_str(),
+            "CIDSystemInfo" => dictionary! {
+                "Registry" => Object::string_literal("Adobe"),
+                "Ordering" => Object::string_literal("Identity"),
+                "Supplement" => 0,
+            },
+            "FontDescriptor" => descriptor_id,
+            "DW" => 1000,
+            "W" => TrueTypeFont::widths(&face),
+            "CIDToGIDMap" => "Identity",
+        });
+
+        let mut to_unicode = Stream::new(dictionary! {}, TrueTypeFont::to_unicode(&face));
+        let _ = to_unicode.compress();
+        let to_unicode_id = self.doc.add_object(to_unicode);
+
+        log::info!("embedded font {}", font.name);
+
+        Ok(self.doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => font.name.as_str(),
+            "Encoding" => "Identity-H",
+            "DescendantFonts" => vec![cid_font_id.into()],
+            "ToUnicode" => to_unicode_id,
+        }))
+    }
+
+    /// Get font object of FONT and its name in page resources, with TEXT encoded for it
+    pub(crate) fn font_for_text(
+        &mut self,
+        font: &Font,
+        text: &str,
+    ) -> anyhow::Result<(ObjectId, String, Object)> {
+        match font {
+            Font::Standard(font) => Ok((
+                self.standard_font(*font),
+                font.resource_name().to_string(),
+                win_ansi(text),
+            )),
+            Font::TrueType(font) => {
+                let id = self.embedded_font(font)?;
+                Ok((id, format!("PdftoolF{}", id.0), font.encode(text)))
+            }
+        }
+    }
+}
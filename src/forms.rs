@@ -0,0 +1,489 @@
+use std::collections::{BTreeSet, HashMap};
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::Deserialize;
+
+use crate::{
+    annotations::Annotation,
+    error::Error,
+    pdf::{decode_text_string, text_string},
+    permissions::Permission,
+    Pdf,
+};
+
+/// Print flag of annotation, so fields are printed with page
+const PRINT_FLAG: i64 = 4;
+
+/// Value filled into form field, as read from JSON like {"name": "Taro", "agree": true}
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    /// Check box state, on or off
+    Bool(bool),
+    Number(f64),
+    /// Text of text field, choice of choice field, or state name of button like
+    /// radio button
+    Text(String),
+}
+
+impl FieldValue {
+    fn text(&self) -> String {
+        match self {
+            Self::Bool(value) => value.to_string(),
+            Self::Number(value) => value.to_string(),
+            Self::Text(value) => value.clone(),
+        }
+    }
+}
+
+/// Widget annotations of field FIELD, which is widget itself if it has no kids
+fn field_widgets(doc: &Document, field: ObjectId) -> Vec<ObjectId> {
+    let kids = doc
+        .get_dictionary(field)
+        .and_then(|x| x.get(b"Kids"))
+        .and_then(Object::as_array);
+
+    match kids {
+        Ok(kids) => kids.iter().filter_map(|x| x.as_reference().ok()).collect(),
+        Err(_) => vec![field],
+    }
+}
+
+/// Names of appearance states of widget WIDGET other than Off
+fn on_states(doc: &Document, widget: ObjectId) -> Vec<Vec<u8>> {
+    doc.get_dictionary(widget)
+        .and_then(|x| x.get(b"AP"))
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"N"))
+        .and_then(Object::as_dict)
+        .map(|x| {
+            x.iter()
+                .map(|(k, _)| k.clone())
+                .filter(|k| k != b"Off")
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Pdf {
+    /// Get id of interactive form dictionary of catalog, creating it if missing and
+    /// moving it into its own object if it is direct
+    pub(crate) fn get_or_create_acro_form(&mut self) -> crate::Result<ObjectId> {
+        let existing = self.doc.catalog()?.get(b"AcroForm").ok().cloned();
+        let id = match existing {
+            Some(Object::Reference(id)) => id,
+            Some(Object::Dictionary(dict)) => self.doc.add_object(dict),
+            _ => self.doc.add_object(dictionary! { "Fields" => vec![] }),
+        };
+        self.catalog_mut()?.set("AcroForm", id);
+
+        // fonts referred to by default appearances of fields made here
+        let form = self.doc.get_dictionary_mut(id)?;
+        if !form.has(b"DA") {
+            form.set("DA", Object::string_literal("/Helv 0 Tf 0 g"));
+        }
+
+        // resources and their fonts may be indirect objects, which are modified in place
+        let dr_id = form
+            .get(b"DR")
+            .and_then(Object::as_reference)
+            .ok()
+            .filter(|&x| self.doc.get_dictionary(x).is_ok());
+        let mut resources = match dr_id {
+            Some(dr_id) => self.doc.get_dictionary(dr_id)?.clone(),
+            None => match self.doc.get_dictionary(id)?.get(b"DR") {
+                Ok(Object::Dictionary(dict)) => dict.clone(),
+                _ => Dictionary::new(),
+            },
+        };
+        let fonts_id = resources
+            .get(b"Font")
+            .and_then(Object::as_reference)
+            .ok()
+            .filter(|&x| self.doc.get_dictionary(x).is_ok());
+        let mut fonts = match fonts_id {
+            Some(fonts_id) => self.doc.get_dictionary(fonts_id)?.clone(),
+            None => match resources.get(b"Font") {
+                Ok(Object::Dictionary(dict)) => dict.clone(),
+                _ => Dictionary::new(),
+            },
+        };
+        if !fonts.has(b"Helv") {
+            fonts.set(
+                "Helv",
+                dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Helvetica",
+                    "Encoding" => "WinAnsiEncoding",
+                },
+            );
+        }
+        if !fonts.has(b"ZaDb") {
+            fonts.set(
+                "ZaDb",
+                dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "ZapfDingbats",
+                },
+            );
+        }
+        match fonts_id {
+            Some(fonts_id) => *self.doc.get_dictionary_mut(fonts_id)? = fonts,
+            None => resources.set("Font", fonts),
+        }
+        match dr_id {
+            Some(dr_id) => *self.doc.get_dictionary_mut(dr_id)? = resources,
+            None => self.doc.get_dictionary_mut(id)?.set("DR", resources),
+        }
+
+        Ok(id)
+    }
+
+    /// Get ids of top level fields of interactive form
+    pub(crate) fn form_fields(&self) -> Vec<ObjectId> {
+        let fields = self
+            .doc
+            .catalog()
+            .and_then(|x| x.get(b"AcroForm"))
+            .and_then(|x| match x {
+                Object::Reference(id) => self.doc.get_dictionary(*id),
+                _ => x.as_dict(),
+            })
+            .and_then(|x| x.get(b"Fields"))
+            .and_then(|x| match x {
+                Object::Reference(id) => self.doc.get_object(*id),
+                _ => Ok(x),
+            })
+            .and_then(Object::as_array);
+
+        match fields {
+            Ok(arr) => arr.iter().filter_map(|x| x.as_reference().ok()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Detach field or widget ID from kids of its parent field, or from fields of
+    /// interactive form if it is top level
+    pub(crate) fn remove_form_field(&mut self, id: ObjectId) -> crate::Result<()> {
+        let parent = self
+            .doc
+            .get_dictionary(id)?
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .ok();
+        if let Some(parent_id) = parent {
+            if let Ok(Object::Array(kids)) =
+                self.doc.get_dictionary_mut(parent_id)?.get_mut(b"Kids")
+            {
+                kids.retain(|x| x.as_reference().ok() != Some(id));
+            }
+            return Ok(());
+        }
+
+        // Fields may be indirect array, so it is copied into form dictionary
+        let fields: Vec<Object> = self
+            .form_fields()
+            .into_iter()
+            .filter(|&x| x != id)
+            .map(Object::from)
+            .collect();
+        let form_ref = self
+            .doc
+            .catalog()?
+            .get(b"AcroForm")
+            .and_then(Object::as_reference)
+            .ok();
+        let form = match form_ref {
+            Some(form_id) => self.doc.get_dictionary_mut(form_id).ok(),
+            None => match self.catalog_mut()?.get_mut(b"AcroForm") {
+                Ok(Object::Dictionary(dict)) => Some(dict),
+                _ => None,
+            },
+        };
+        if let Some(form) = form {
+            form.set("Fields", fields);
+        }
+
+        Ok(())
+    }
+
+    /// Get fully qualified names of terminal fields, like "address.city", with their ids
+    pub fn form_field_names(&self) -> Vec<(String, ObjectId)> {
+        let mut stack: Vec<(String, ObjectId)> = self
+            .form_fields()
+            .into_iter()
+            .rev()
+            .map(|x| (String::new(), x))
+            .collect();
+        let mut visited = BTreeSet::new();
+        let mut result = vec![];
+
+        while let Some((parent, id)) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let Ok(dict) = self.doc.get_dictionary(id) else {
+                continue;
+            };
+
+            let name = match dict.get(b"T").and_then(Object::as_str) {
+                Ok(partial) if parent.is_empty() => decode_text_string(partial),
+                Ok(partial) => format!("{}.{}", parent, decode_text_string(partial)),
+                Err(_) => parent,
+            };
+
+            // kids without name are widgets of this field, not fields
+            let kids: Vec<ObjectId> = field_widgets(&self.doc, id)
+                .into_iter()
+                .filter(|&x| x != id)
+                .filter(|&x| self.doc.get_dictionary(x).map_or(false, |x| x.has(b"T")))
+                .collect();
+            if kids.is_empty() {
+                result.push((name, id));
+            } else {
+                stack.extend(kids.into_iter().rev().map(|x| (name.clone(), x)));
+            }
+        }
+
+        result
+    }
+
+    /// Set value of fields named by keys of VALUES, and rebuild appearance of their
+    /// widgets. check boxes are turned on by true and off by false; buttons can also
+    /// be set to state by its name. fails without changing anything if field is not found.
+    pub fn fill_form(&mut self, values: &HashMap<String, FieldValue>) -> crate::Result<()> {
+        self.check_permission(Permission::FillForms)?;
+
+        let fields: HashMap<String, ObjectId> = self.form_field_names().into_iter().collect();
+        let mut unknown: Vec<&str> = values
+            .keys()
+            .filter(|x| !fields.contains_key(*x))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort_unstable();
+            return Err(Error::InvalidArgument(format!(
+                "form fields not found: {}",
+                unknown.join(", ")
+            )));
+        }
+
+        let mut need_appearances = false;
+        // filled in order of names, so objects are added in same order every time
+        let mut names: Vec<&String> = values.keys().collect();
+        names.sort_unstable();
+
+        for name in names {
+            let value = &values[name];
+            let field_id = fields[name];
+            let field_type = self.field_type(field_id);
+            let widgets = field_widgets(&self.doc, field_id);
+            log::debug!("filling field {}", name);
+
+            match field_type.as_deref() {
+                Some(b"Btn") => {
+                    let state = match value {
+                        FieldValue::Bool(false) => b"Off".to_vec(),
+                        FieldValue::Bool(true) => widgets
+                            .iter()
+                            .find_map(|&x| on_states(&self.doc, x).into_iter().next())
+                            .unwrap_or_else(|| b"Yes".to_vec()),
+                        _ => value.text().into_bytes(),
+                    };
+
+                    self.doc
+                        .get_dictionary_mut(field_id)?
+                        .set("V", Object::Name(state.clone()));
+                    for widget in widgets {
+                        if on_states(&self.doc, widget).is_empty() {
+                            self.regenerate_appearance(widget)?;
+                        }
+                        // each radio button shows on state only if it has state of value
+                        let shown = if on_states(&self.doc, widget).contains(&state) {
+                            state.clone()
+                        } else {
+                            b"Off".to_vec()
+                        };
+                        self.doc
+                            .get_dictionary_mut(widget)?
+                            .set("AS", Object::Name(shown));
+                    }
+                }
+                _ => {
+                    self.doc
+                        .get_dictionary_mut(field_id)?
+                        .set("V", text_string(&value.text()));
+
+                    // appearance of choice fields is left to viewer
+                    if field_type.as_deref() == Some(&b"Tx"[..]) {
+                        for widget in widgets {
+                            self.regenerate_appearance(widget)?;
+                        }
+                    } else {
+                        need_appearances = true;
+                    }
+                }
+            }
+        }
+
+        if need_appearances {
+            let form_id = self.get_or_create_acro_form()?;
+            self.doc
+                .get_dictionary_mut(form_id)?
+                .set("NeedAppearances", true);
+        }
+
+        Ok(())
+    }
+
+    /// Get field type (FT) of field ID, which may be inherited from parent field
+    fn field_type(&self, id: ObjectId) -> Option<Vec<u8>> {
+        let mut id = id;
+        let mut visited = BTreeSet::new();
+
+        while visited.insert(id) {
+            let dict = self.doc.get_dictionary(id).ok()?;
+            if let Ok(field_type) = dict.get(b"FT").and_then(Object::as_name) {
+                return Some(field_type.to_vec());
+            }
+            id = dict.get(b"Parent").and_then(Object::as_reference).ok()?;
+        }
+
+        None
+    }
+
+    /// Add widget annotation DICT as field NAME on PAGE at RECT, registering it in
+    /// interactive form
+    fn add_field(
+        &mut self,
+        page: u32,
+        name: &str,
+        rect: [f32; 4],
+        mut dict: Dictionary,
+    ) -> crate::Result<ObjectId> {
+        self.check_permission(Permission::Annotate)?;
+
+        if name.is_empty() || name.contains('.') {
+            return Err(Error::InvalidArgument(format!(
+                "field name must be non-empty and without '.': {}",
+                name
+            )));
+        }
+        let exists = self.form_fields().into_iter().any(|id| {
+            self.doc
+                .get_dictionary(id)
+                .and_then(|x| x.get(b"T"))
+                .and_then(Object::as_str)
+                .map_or(false, |x| decode_text_string(x) == name)
+        });
+        if exists {
+            return Err(Error::InvalidArgument(format!(
+                "field {} already exists",
+                name
+            )));
+        }
+
+        let [x0, y0, x1, y1] = rect;
+        dict.set("Type", "Annot");
+        dict.set("Subtype", "Widget");
+        dict.set("T", text_string(name));
+        dict.set(
+            "Rect",
+            vec![
+                x0.min(x1).into(),
+                y0.min(y1).into(),
+                x0.max(x1).into(),
+                y0.max(y1).into(),
+            ],
+        );
+        dict.set("F", PRINT_FLAG);
+
+        let page_id = self.get_page_id(page)?;
+        let form_id = self.get_or_create_acro_form()?;
+        let field_id = self.push_annotation(page_id, &Annotation::Other(dict))?;
+
+        // Fields may be indirect array, so it is copied into form dictionary
+        let mut fields: Vec<Object> = self.form_fields().into_iter().map(Object::from).collect();
+        fields.push(field_id.into());
+        self.doc.get_dictionary_mut(form_id)?.set("Fields", fields);
+
+        Ok(field_id)
+    }
+
+    /// Add single line text field NAME on PAGE at RECT, with default appearance of
+    /// Helvetica auto sized to field. returns id of field.
+    pub fn add_text_field(
+        &mut self,
+        page: u32,
+        name: &str,
+        rect: [f32; 4],
+    ) -> crate::Result<ObjectId> {
+        let dict = dictionary! {
+            "FT" => "Tx",
+            "DA" => Object::string_literal("/Helv 0 Tf 0 g"),
+            "V" => text_string(""),
+        };
+
+        let id = self.add_field(page, name, rect, dict)?;
+        self.regenerate_appearance(id)?;
+        Ok(id)
+    }
+
+    /// Add check box field NAME on PAGE at RECT, checked if CHECKED. its on state is
+    /// named Yes. returns id of field.
+    pub fn add_checkbox(
+        &mut self,
+        page: u32,
+        name: &str,
+        rect: [f32; 4],
+        checked: bool,
+    ) -> crate::Result<ObjectId> {
+        let state = if checked { "Yes" } else { "Off" };
+        let dict = dictionary! {
+            "FT" => "Btn",
+            "DA" => Object::string_literal("/ZaDb 0 Tf 0 g"),
+            "V" => state,
+            "AS" => state,
+        };
+
+        let id = self.add_field(page, name, rect, dict)?;
+        self.regenerate_appearance(id)?;
+        Ok(id)
+    }
+
+    /// Add empty signature field NAME on PAGE at RECT, to be signed later by other
+    /// tools. returns id of field.
+    pub fn add_signature_field(
+        &mut self,
+        page: u32,
+        name: &str,
+        rect: [f32; 4],
+    ) -> crate::Result<ObjectId> {
+        let [x0, y0, x1, y1] = rect;
+        let (width, height) = ((x1 - x0).abs(), (y1 - y0).abs());
+        let appearance = self.doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![Object::Real(0.0), Object::Real(0.0), width.into(), height.into()],
+            },
+            vec![],
+        ));
+        let dict = dictionary! {
+            "FT" => "Sig",
+            "AP" => dictionary! { "N" => appearance },
+        };
+
+        let id = self.add_field(page, name, rect, dict)?;
+
+        // signature fields require SigFlags telling viewers that document has them
+        let form_id = self.get_or_create_acro_form()?;
+        let form = self.doc.get_dictionary_mut(form_id)?;
+        let flags = form.get(b"SigFlags").and_then(Object::as_i64).unwrap_or(0);
+        form.set("SigFlags", flags | 1);
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, io::Cursor};
+
+use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageDecoder, RgbaImage};
+use lopdf::{dictionary, Object, ObjectId, Stream, StringFormat};
+
+use crate::{writer, Pdf};
+
+/// Build image XObject with Indexed color space from composed GIF frame.
+/// transparent pixels get their own palette entry masked out by /Mask.
+/// returns None if frame has more colors than palette can hold.
+fn indexed_stream(frame: &RgbaImage) -> anyhow::Result<Option<Stream>> {
+    let mut palette: BTreeMap<[u8; 3], u8> = BTreeMap::new();
+    let mut colors: Vec<u8> = Vec::new();
+    let mut transparent: Option<u8> = None;
+    let mut indices = Vec::with_capacity((frame.width() * frame.height()) as usize);
+
+    for pixel in frame.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let key = if a < 128 { None } else { Some([r, g, b]) };
+
+        let next = (colors.len() / 3) as u16 + transparent.is_some() as u16;
+        let index = match key {
+            Some(rgb) => match palette.get(&rgb) {
+                Some(&index) => index,
+                None => {
+                    if next > 255 {
+                        return Ok(None);
+                    }
+                    palette.insert(rgb, next as u8);
+                    colors.extend(rgb);
+                    next as u8
+                }
+            },
+            None => match transparent {
+                Some(index) => index,
+                None => {
+                    if next > 255 {
+                        return Ok(None);
+                    }
+                    transparent = Some(next as u8);
+                    // color of masked entry does not matter
+                    colors.extend([255, 255, 255]);
+                    next as u8
+                }
+            },
+        };
+        indices.push(index);
+    }
+
+    let count = colors.len() / 3;
+    let data = writer::deflate(&indices)?;
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Filter" => "FlateDecode",
+        "BitsPerComponent" => 8,
+        "ColorSpace" => vec![
+            "Indexed".into(),
+            "DeviceRGB".into(),
+            (count as i64 - 1).into(),
+            Object::String(colors, StringFormat::Hexadecimal),
+        ],
+        "Length" => data.len() as u32,
+        "Width" => frame.width(),
+        "Height" => frame.height(),
+    };
+    if let Some(index) = transparent {
+        dict.set("Mask", vec![index.into(), index.into()]);
+    }
+
+    Ok(Some(Stream::new(dict, data)))
+}
+
+impl Pdf {
+    /// Build image XObject for first frame of GIF data, or for every frame if
+    /// `image_options.all_frames` is set. frames are composed as animation shows them.
+    pub(crate) fn gif_xobjects(&mut self, bytes: &[u8]) -> anyhow::Result<Vec<Stream>> {
+        let decoder = GifDecoder::new(Cursor::new(bytes))?;
+        let (width, height) = decoder.dimensions();
+        self.check_image_pixels(width, height)?;
+
+        let count = if self.image_options.all_frames {
+            usize::MAX
+        } else {
+            1
+        };
+
+        let mut result = Vec::new();
+        for frame in decoder.into_frames().take(count) {
+            self.check_deadline()?;
+            let frame = frame?.into_buffer();
+
+            // frames composed from local palettes can exceed 256 colors
+            let stream = match indexed_stream(&frame)? {
+                Some(stream) => stream,
+                None => {
+                    let mut png = Vec::new();
+                    frame.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+                    self.png_xobject(&png)?
+                }
+            };
+            result.push(stream);
+        }
+
+        Ok(result)
+    }
+
+    /// Add page for first frame of GIF data, or every frame if `image_options.all_frames`
+    /// is set, returning ids of added pages
+    pub fn add_gif(&mut self, bytes: &[u8]) -> anyhow::Result<Vec<ObjectId>> {
+        Ok(self
+            .gif_xobjects(bytes)?
+            .into_iter()
+            .map(|stream| self.add_image_page(stream, None))
+            .collect::<Result<_, _>>()?)
+    }
+}
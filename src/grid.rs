@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use lopdf::{
+    content::{Content, Operation},
+    ObjectId,
+};
+
+use crate::{
+    density::density,
+    paper::PageSize,
+    placement::{draw_clipped, Rect},
+    text::{text_width, win_ansi, StandardFont},
+    Pdf,
+};
+
+/// Font size of captions in points
+const CAPTION_SIZE: f32 = 8.0;
+/// Padding between cell edges, image and caption in points
+const PADDING: f32 = 6.0;
+
+/// Layout of contact sheet, COLUMNS x ROWS images per page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl FromStr for Grid {
+    type Err = anyhow::Error;
+
+    /// Parse COLUMNSxROWS, e.g. 3x4
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (columns, rows) = s
+            .split_once('x')
+            .ok_or_else(|| anyhow::anyhow!("invalid grid: {}", s))?;
+        let columns: u32 = columns.parse()?;
+        let rows: u32 = rows.parse()?;
+        anyhow::ensure!(columns > 0 && rows > 0, "invalid grid: {}", s);
+
+        Ok(Self { columns, rows })
+    }
+}
+
+/// Shorten TEXT with trailing "..." so that it fits in WIDTH at caption size
+fn fit_caption(text: &str, width: f32) -> String {
+    if text_width(text, CAPTION_SIZE) <= width {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let shortened = format!("{}...", chars.iter().collect::<String>());
+        if text_width(&shortened, CAPTION_SIZE) <= width {
+            return shortened;
+        }
+    }
+
+    String::new()
+}
+
+impl Pdf {
+    /// Lay out IMAGES, pairs of caption and image data, in GRID on pages of
+    /// `image_options.page_size` (A4 if not set) as contact sheet, returning ids of
+    /// added pages. images are scaled into cells by `image_options.fit`, with
+    /// captions below them.
+    pub fn add_image_grid(
+        &mut self,
+        images: &[(String, Vec<u8>)],
+        grid: Grid,
+    ) -> anyhow::Result<Vec<ObjectId>> {
+        let size = self.image_options.page_size.unwrap_or(PageSize::A4);
+        let margin = self.image_options.margin;
+
+        let cell_width = (size.width - margin * 2.0) / grid.columns as f32;
+        let cell_height = (size.height - margin * 2.0) / grid.rows as f32;
+        anyhow::ensure!(
+            cell_width > PADDING * 2.0 && cell_height > PADDING * 3.0 + CAPTION_SIZE,
+            "grid {}x{} does not fit in page size {}x{}",
+            grid.columns,
+            grid.rows,
+            size.width,
+            size.height
+        );
+
+        let font_id = self.standard_font(StandardFont::Helvetica);
+        let mut pages = Vec::new();
+
+        for chunk in images.chunks((grid.columns * grid.rows) as usize) {
+            let page_id = self.add_page(size.width.round() as u32, size.height.round() as u32)?;
+            self.add_page_resource(page_id, "Font", "PdftoolHelv", font_id)?;
+
+            let mut content = Vec::new();
+            for (i, (caption, bytes)) in chunk.iter().enumerate() {
+                let column = i as u32 % grid.columns;
+                let row = i as u32 / grid.columns;
+                let x = margin + cell_width * column as f32;
+                let y = size.height - margin - cell_height * (row + 1) as f32;
+
+                // image above caption, both inside padding of cell
+                let area = Rect::new(
+                    x + PADDING,
+                    y + PADDING * 2.0 + CAPTION_SIZE,
+                    cell_width - PADDING * 2.0,
+                    cell_height - PADDING * 3.0 - CAPTION_SIZE,
+                );
+
+                let img_stream = self.image_xobject(bytes)?;
+                let img_stream = self.bilevel_stream(img_stream)?;
+                let (width, height) = self.image_size(&img_stream, density(bytes))?;
+                let placed = self.image_options.fit.place(width, height, area);
+
+                let name = format!("Image{}", i);
+                let image_id = self.doc.add_object(img_stream);
+                self.add_page_resource(page_id, "XObject", &name, image_id)?;
+                content.extend(draw_clipped(&name, placed, area).into_bytes());
+
+                let caption = fit_caption(caption, area.width);
+                let caption_x = x + (cell_width - text_width(&caption, CAPTION_SIZE)) / 2.0;
+                let caption_y = y + PADDING;
+                let text = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["PdftoolHelv".into(), CAPTION_SIZE.into()]),
+                        Operation::new("Td", vec![caption_x.into(), caption_y.into()]),
+                        Operation::new("Tj", vec![win_ansi(&caption)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                content.extend(text.encode()?);
+            }
+
+            self.doc.change_page_content(page_id, content)?;
+            pages.push(page_id);
+        }
+
+        Ok(pages)
+    }
+}
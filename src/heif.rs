@@ -0,0 +1,86 @@
+use image::DynamicImage;
+use lopdf::Stream;
+
+use crate::{xobject, Pdf};
+
+/// Image formats based on HEIF container
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Heif {
+    /// AV1 coded, from AVIF files
+    Avif,
+    /// HEVC coded, from HEIC files of phone cameras
+    Heic,
+}
+
+/// Detect HEIF based format from major brand of ftyp box
+pub(crate) fn detect(bytes: &[u8]) -> Option<Heif> {
+    if bytes.get(4..8)? != b"ftyp" {
+        return None;
+    }
+
+    match bytes.get(8..12)? {
+        b"avif" | b"avis" => Some(Heif::Avif),
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"mif1" | b"msf1" => {
+            Some(Heif::Heic)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "avif")]
+fn decode_avif(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    Ok(image::load_from_memory_with_format(
+        bytes,
+        image::ImageFormat::Avif,
+    )?)
+}
+
+#[cfg(not(feature = "avif"))]
+fn decode_avif(_: &[u8]) -> anyhow::Result<DynamicImage> {
+    anyhow::bail!("AVIF support is not enabled, build with feature \"avif\"")
+}
+
+#[cfg(feature = "heic")]
+fn decode_heic(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(bytes)?;
+    let handle = context.primary_image_handle()?;
+    let decoded = handle.decode(ColorSpace::Rgb(RgbChroma::Rgb), false)?;
+
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or_else(|| anyhow::anyhow!("HEIC decoder returned no RGB plane"))?;
+    let (width, height) = (plane.width, plane.height);
+
+    // rows of plane may be padded beyond 3 * width bytes
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        rgb.extend_from_slice(&row[..width as usize * 3]);
+    }
+
+    image::RgbImage::from_raw(width, height, rgb)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| anyhow::anyhow!("invalid HEIC image data"))
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heic(_: &[u8]) -> anyhow::Result<DynamicImage> {
+    anyhow::bail!("HEIC support is not enabled, build with feature \"heic\"")
+}
+
+impl Pdf {
+    /// Build image XObject from AVIF or HEIC data. PDF has no filter for them,
+    /// so photos are decoded and re-encoded as JPEG.
+    pub(crate) fn heif_xobject(&mut self, bytes: &[u8], kind: Heif) -> anyhow::Result<Stream> {
+        let img = match kind {
+            Heif::Avif => decode_avif(bytes)?,
+            Heif::Heic => decode_heic(bytes)?,
+        };
+        self.check_image_pixels(img.width(), img.height())?;
+
+        let jpeg = xobject::jpeg_stream(&img, self.image_options.quality())?.content;
+        Ok(self.jpeg_xobject(&jpeg)?)
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use lopdf::{Document, Object, ObjectId};
+
+use crate::{
+    writer::{self, XrefEntry},
+    Pdf,
+};
+
+/// Bytes of document as it was loaded, with per-object digests computed from them
+/// when first needed
+pub(crate) struct Original {
+    bytes: Vec<u8>,
+    digests: Option<BTreeMap<ObjectId, u64>>,
+}
+
+impl Original {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            digests: None,
+        }
+    }
+
+    /// Get digests of objects loaded, parsing original bytes again on first call
+    fn digests(&mut self) -> anyhow::Result<&BTreeMap<ObjectId, u64>> {
+        if self.digests.is_none() {
+            let doc = Document::load_mem(&self.bytes)?;
+            let digests = doc
+                .objects
+                .iter()
+                .map(|(&id, obj)| (id, digest(obj)))
+                .collect();
+            self.digests = Some(digests);
+        }
+
+        Ok(self.digests.as_ref().unwrap())
+    }
+
+    /// Whether objects of DOC differ from those loaded
+    pub(crate) fn is_modified(&mut self, doc: &Document) -> bool {
+        let Ok(digests) = self.digests() else {
+            return true;
+        };
+
+        doc.objects.len() != digests.len()
+            || doc
+                .objects
+                .iter()
+                .any(|(id, obj)| digests.get(id) != Some(&digest(obj)))
+    }
+
+    fn startxref(&self) -> anyhow::Result<usize> {
+        let tail = &self.bytes[self.bytes.len().saturating_sub(1024)..];
+        let pos = tail
+            .windows(9)
+            .rposition(|x| x == b"startxref")
+            .ok_or(anyhow!("startxref not found in original document"))?;
+
+        let offset = String::from_utf8_lossy(&tail[pos + 9..])
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .parse()?;
+
+        Ok(offset)
+    }
+}
+
+fn digest(obj: &Object) -> u64 {
+    let mut bytes = Vec::new();
+    writer::write_object(&mut bytes, obj);
+
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Pdf {
+    /// Save document as incremental update appended to the loaded file.
+    /// only changed objects are written, so existing signatures stay valid.
+    pub fn save_incremental<P: AsRef<Path>>(self, path: P) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_bytes_incremental()?)?;
+        Ok(())
+    }
+
+    pub fn to_bytes_incremental(mut self) -> anyhow::Result<Vec<u8>> {
+        self.update_mod_date()?;
+
+        let Some(mut original) = self.original.take() else {
+            anyhow::bail!("incremental save requires document loaded from file");
+        };
+
+        let prev = original.startxref()?;
+        anyhow::ensure!(
+            original
+                .bytes
+                .get(prev..)
+                .map_or(false, |x| x.starts_with(b"xref")),
+            "incremental update of document with xref stream is not supported"
+        );
+
+        let mut out = original.bytes.clone();
+        if !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+
+        let mut entries = BTreeMap::new();
+        let digests = original.digests()?;
+
+        for (&id, obj) in &self.doc.objects {
+            if digests.get(&id) != Some(&digest(obj)) {
+                entries.insert(
+                    id.0,
+                    XrefEntry::InUse {
+                        offset: out.len(),
+                        generation: id.1,
+                    },
+                );
+                writer::write_indirect_object(&mut out, id, obj);
+            }
+        }
+
+        let deleted: Vec<ObjectId> = digests
+            .keys()
+            .filter(|id| !self.doc.objects.contains_key(id) && !entries.contains_key(&id.0))
+            .copied()
+            .collect();
+
+        if entries.is_empty() && deleted.is_empty() {
+            return Ok(original.bytes.clone());
+        }
+
+        // chain deleted objects into free list starting from object 0
+        let mut next = 0;
+        for id in deleted.iter().rev() {
+            entries.insert(
+                id.0,
+                XrefEntry::Free {
+                    next,
+                    generation: id.1.saturating_add(1),
+                },
+            );
+            next = id.0;
+        }
+        if !deleted.is_empty() {
+            entries.insert(
+                0,
+                XrefEntry::Free {
+                    next,
+                    generation: 65535,
+                },
+            );
+        }
+
+        let mut trailer = self.doc.trailer.clone();
+        let size = trailer
+            .get(b"Size")
+            .and_then(Object::as_i64)
+            .unwrap_or(0)
+            .max(self.doc.max_id as i64 + 1);
+        trailer.set("Size", size);
+        trailer.set("Prev", prev as i64);
+        trailer.remove(b"XRefStm");
+
+        let startxref = out.len();
+        writer::write_xref_table(&mut out, &entries);
+
+        out.extend_from_slice(b"trailer\n");
+        writer::write_dictionary(&mut out, &trailer);
+        out.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", startxref).as_bytes());
+
+        self.check_output_size(out.len())?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    cmp::Ordering,
+    io::{Cursor, Read},
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use crate::limits::LimitExceeded;
+
+/// Extensions of image files picked up from directories
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "tif", "tiff", "webp", "bmp", "pbm", "pgm", "ppm", "pnm", "jp2",
+    "j2k", "jpx", "avif", "heic", "heif",
+];
+
+/// Consume run of digits at start of CHARS, returning it without leading zeros
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+
+    digits.trim_start_matches('0').to_string()
+}
+
+/// Compare A and B in natural order, so that runs of digits are compared by their
+/// numeric value (page2 before page10)
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+
+                // without leading zeros, longer number is larger
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Sort PATHS in natural order of their string form
+pub fn natural_sort(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|x| x.to_string_lossy().to_ascii_lowercase());
+
+    matches!(extension, Some(x) if IMAGE_EXTENSIONS.contains(&x.as_str()))
+}
+
+fn is_image_file(path: &Path) -> bool {
+    path.is_file() && has_image_extension(path)
+}
+
+/// Expand ARG of image input into files in natural order. directory gives image
+/// files directly in it, and glob pattern (containing *, ? or [) gives files
+/// matching it. other arguments are returned as is.
+pub fn expand_images(arg: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = Path::new(arg);
+
+    let mut files = if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            if is_image_file(&file) {
+                files.push(file);
+            }
+        }
+        anyhow::ensure!(!files.is_empty(), "No image files found in {}", arg);
+        files
+    } else if !path.exists() && arg.contains(['*', '?', '[']) {
+        let mut files = Vec::new();
+        for file in glob::glob(arg)? {
+            let file = file?;
+            if file.is_file() {
+                files.push(file);
+            }
+        }
+        anyhow::ensure!(!files.is_empty(), "No files match {}", arg);
+        files
+    } else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+
+    natural_sort(&mut files);
+    Ok(files)
+}
+
+/// Check whether data is ZIP archive, such as CBZ comic book
+pub fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"PK\x03\x04")
+}
+
+/// Extract image files in ZIP archive in natural order of their names,
+/// returning pairs of file name and data. directories and metadata of macOS are skipped.
+/// with LIMIT, fails if any entry or all entries together extract to more bytes.
+pub fn zip_images(bytes: &[u8], limit: Option<u64>) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut images = Vec::new();
+    let mut total = 0;
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let Some(path) = file.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if !file.is_file() || path.starts_with("__MACOSX") || !has_image_extension(&path) {
+            continue;
+        }
+
+        // declared size may lie, so read at most one byte over limit to detect it
+        let remaining = limit.map_or(u64::MAX, |x| x - total);
+        let mut data = Vec::with_capacity(file.size().min(remaining) as usize);
+        file.take(remaining.saturating_add(1))
+            .read_to_end(&mut data)?;
+        total += data.len() as u64;
+        if let Some(limit) = limit.filter(|&x| total > x) {
+            return Err(LimitExceeded::InputBytes {
+                bytes: total,
+                limit,
+            }
+            .into());
+        }
+        images.push((path.to_string_lossy().into_owned(), data));
+    }
+    anyhow::ensure!(!images.is_empty(), "No image files found in archive");
+
+    images.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+    Ok(images)
+}
@@ -0,0 +1,141 @@
+use lopdf::{dictionary, Stream};
+
+use crate::Pdf;
+
+/// Signature box at start of JP2 file
+const JP2_SIGNATURE: &[u8] = b"\x00\x00\x00\x0cjP  \r\n\x87\n";
+/// SOC marker followed by SIZ marker at start of J2K codestream
+const J2K_SIGNATURE: &[u8] = b"\xff\x4f\xff\x51";
+
+/// Check whether data is JPEG 2000, either JP2 file or bare codestream
+pub(crate) fn is_jpx(bytes: &[u8]) -> bool {
+    bytes.starts_with(JP2_SIGNATURE) || bytes.starts_with(J2K_SIGNATURE)
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Find contents of contiguous codestream box (jp2c) in JP2 file
+fn codestream(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+
+    while pos + 8 <= bytes.len() {
+        let length = read_u32(bytes, pos)? as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+
+        let (header, length) = match length {
+            // box extends to end of file
+            0 => (8, bytes.len() - pos),
+            // 64 bit length follows type
+            1 => {
+                let high = read_u32(bytes, pos + 8)? as u64;
+                let low = read_u32(bytes, pos + 12)? as u64;
+                (16, usize::try_from((high << 32) | low).ok()?)
+            }
+            _ => (8, length),
+        };
+        if length < header {
+            return None;
+        }
+        // length of crafted box may run past any possible offset
+        let end = pos.checked_add(length)?;
+
+        if kind == b"jp2c" {
+            return bytes.get(pos + header..end.min(bytes.len()));
+        }
+        pos = end;
+    }
+
+    None
+}
+
+/// Get width and height of image from SIZ marker segment of codestream
+fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let stream = if bytes.starts_with(JP2_SIGNATURE) {
+        codestream(bytes)?
+    } else {
+        bytes
+    };
+    if !stream.starts_with(J2K_SIGNATURE) {
+        return None;
+    }
+
+    // SOC (2), SIZ marker (2), Lsiz (2), Rsiz (2), then Xsiz, Ysiz, XOsiz, YOsiz
+    let [x, y, x0, y0] = [8, 12, 16, 20].map(|pos| read_u32(stream, pos));
+    Some((x?.checked_sub(x0?)?, y?.checked_sub(y0?)?))
+}
+
+impl Pdf {
+    /// Build image XObject embedding JPEG 2000 data as is with JPXDecode.
+    /// color space and depth are taken from data by reader, so they are left out.
+    pub(crate) fn jpx_xobject(&mut self, bytes: &[u8]) -> anyhow::Result<Stream> {
+        let (width, height) =
+            dimensions(bytes).ok_or_else(|| anyhow::anyhow!("invalid JPEG 2000 data"))?;
+        self.check_image_pixels(width, height)?;
+
+        // JPXDecode is introduced in PDF 1.5
+        if self.doc.version.as_str() < "1.5" {
+            self.doc.version = "1.5".into();
+        }
+
+        Ok(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Filter" => "JPXDecode",
+                "Length" => bytes.len() as u32,
+                "Width" => width,
+                "Height" => height,
+            },
+            bytes.to_vec(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make box of KIND with CONTENT, using 32 bit length
+    fn jp2_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let length = (content.len() + 8) as u32;
+        [&length.to_be_bytes()[..], kind, content].concat()
+    }
+
+    #[test]
+    fn codestream_finds_jp2c_box() {
+        let bytes = [
+            JP2_SIGNATURE.to_vec(),
+            jp2_box(b"ftyp", b"jp2 \0\0\0\0jp2 "),
+            jp2_box(b"jp2c", b"\xff\x4f\xff\x51data"),
+        ]
+        .concat();
+
+        assert_eq!(codestream(&bytes), Some(&b"\xff\x4f\xff\x51data"[..]));
+    }
+
+    #[test]
+    fn codestream_box_to_end_of_file() {
+        let bytes = [&JP2_SIGNATURE[..], b"\0\0\0\0jp2cdata"].concat();
+
+        assert_eq!(codestream(&bytes), Some(&b"data"[..]));
+    }
+
+    #[test]
+    fn codestream_rejects_overflowing_length() {
+        // 64 bit length of u64::MAX after signature box
+        let bytes = [&JP2_SIGNATURE[..], b"\0\0\0\x01junk", &[0xffu8; 8], b"rest"].concat();
+
+        assert_eq!(codestream(&bytes), None);
+    }
+
+    #[test]
+    fn codestream_rejects_length_shorter_than_header() {
+        let bytes = [&JP2_SIGNATURE[..], b"\0\0\0\x04jp2c"].concat();
+
+        assert_eq!(codestream(&bytes), None);
+    }
+}
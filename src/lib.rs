@@ -1,4 +1,76 @@
+pub mod error;
 pub mod pdf;
+pub use error::{Error, Result};
 pub use pdf::Pdf;
 
 pub mod png;
+
+pub mod annotations;
+pub mod attachment;
+pub mod barcode;
+pub mod bitmap;
+pub mod ccitt;
+pub mod checksum;
+pub mod compare;
+pub mod config;
+pub mod destination;
+pub mod diff;
+pub mod events;
+pub mod extract;
+pub mod font;
+pub mod forms;
+pub mod gif;
+pub mod grid;
+pub mod input;
+pub mod limits;
+pub mod locale;
+pub mod manifest;
+pub mod metadata;
+pub mod ocr;
+pub mod operation;
+pub mod optimize;
+pub mod outline;
+pub mod overlay;
+pub mod page;
+pub mod paper;
+pub mod permissions;
+pub mod placement;
+pub mod preflight;
+pub mod preprocess;
+pub mod provenance;
+pub mod render;
+pub mod review;
+pub mod selection;
+pub mod stamp;
+pub mod testing;
+pub mod text;
+pub mod thumbnail;
+pub mod tiff;
+pub mod transition;
+pub mod validate;
+pub mod version;
+pub mod viewer;
+pub mod webp;
+pub mod writer;
+
+mod appearance;
+mod attributes;
+mod autocrop;
+mod dedup;
+mod density;
+mod encryption;
+mod exif;
+mod fdf;
+mod flatten;
+mod heif;
+mod incremental;
+mod jpx;
+mod merge;
+mod prune;
+mod redact;
+mod repair;
+mod reproducible;
+mod sanitize;
+mod split;
+mod tagged;
+mod xobject;
@@ -0,0 +1,185 @@
+use std::{
+    fmt,
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use image::DynamicImage;
+use lopdf::Stream;
+
+use crate::{xobject, Pdf};
+
+/// Resource limits for processing untrusted documents. None means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// Max width * height of image decoded or embedded
+    pub max_image_pixels: Option<u64>,
+    /// Max size of serialized output in bytes
+    pub max_output_bytes: Option<u64>,
+    /// Max size of data extracted from archive such as CBZ, per entry and in total
+    pub max_input_bytes: Option<u64>,
+    /// Max number of objects in document
+    pub max_objects: Option<usize>,
+    /// Max time spent on document, counted from load or `restart_timer`
+    pub timeout: Option<Duration>,
+}
+
+/// Error returned when document or operation exceeds one of `Limits`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    ImagePixels { pixels: u64, limit: u64 },
+    OutputBytes { bytes: u64, limit: u64 },
+    InputBytes { bytes: u64, limit: u64 },
+    Objects { count: usize, limit: usize },
+    Timeout { limit: Duration },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ImagePixels { pixels, limit } => {
+                write!(f, "image has {} pixels, exceeding limit {}", pixels, limit)
+            }
+            Self::OutputBytes { bytes, limit } => {
+                write!(f, "output is {} bytes, exceeding limit {}", bytes, limit)
+            }
+            Self::InputBytes { bytes, limit } => {
+                write!(
+                    f,
+                    "extracted data is over {} bytes, exceeding limit {}",
+                    bytes, limit
+                )
+            }
+            Self::Objects { count, limit } => {
+                write!(
+                    f,
+                    "document has {} objects, exceeding limit {}",
+                    count, limit
+                )
+            }
+            Self::Timeout { limit } => {
+                write!(
+                    f,
+                    "processing took longer than {} seconds",
+                    limit.as_secs_f32()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Start time of processing, used for `Limits::timeout`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timer(Instant);
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Pdf {
+    /// Restart time counted for `Limits::timeout`, e.g. before processing next request
+    pub fn restart_timer(&mut self) {
+        self.timer = Timer::default();
+    }
+
+    /// Put LIMITS in effect for later operations, failing if document already has
+    /// more objects than they allow, as when loaded with them
+    pub fn set_limits(&mut self, limits: Limits) -> crate::Result<()> {
+        self.limits = limits;
+        self.check_object_count()?;
+
+        Ok(())
+    }
+
+    /// Fail if timeout of limits elapsed or document has more objects than they allow,
+    /// for checking limits between operations
+    pub fn check_limits(&self) -> anyhow::Result<()> {
+        self.check_deadline()
+    }
+
+    /// Fail if timeout of limits elapsed, or object count went over limit while operation
+    /// is adding objects. long operations call this for each page or image.
+    pub(crate) fn check_deadline(&self) -> anyhow::Result<()> {
+        self.check_object_count()?;
+
+        match self.limits.timeout {
+            Some(limit) if self.timer.0.elapsed() > limit => {
+                Err(LimitExceeded::Timeout { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if image of WIDTH x HEIGHT is larger than limits allow, or timeout elapsed
+    pub(crate) fn check_image_pixels(&self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.check_deadline()?;
+
+        let pixels = width as u64 * height as u64;
+        match self.limits.max_image_pixels {
+            Some(limit) if pixels > limit => {
+                Err(LimitExceeded::ImagePixels { pixels, limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode image XObject STREAM, failing first if it is larger than limits allow.
+    /// images of document are all decoded through this.
+    pub(crate) fn decode_image(&self, stream: &Stream) -> anyhow::Result<DynamicImage> {
+        let (width, height) = xobject::image_size(&self.doc, &stream.dict)?;
+        self.check_image_pixels(width, height)?;
+
+        xobject::decode_image(&self.doc, stream)
+    }
+
+    /// Check size of encoded image BYTES from its header, before decoding it
+    pub(crate) fn check_encoded_image(&self, bytes: &[u8]) -> anyhow::Result<()> {
+        let (width, height) = image::io::Reader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .into_dimensions()?;
+
+        self.check_image_pixels(width, height)
+    }
+
+    /// Fail if document has more objects than limits allow
+    pub(crate) fn check_object_count(&self) -> anyhow::Result<()> {
+        let count = self.doc.objects.len();
+        match self.limits.max_objects {
+            Some(limit) if count > limit => Err(LimitExceeded::Objects { count, limit }.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if serialized output of LEN bytes is larger than limits allow
+    pub(crate) fn check_output_size(&self, len: usize) -> anyhow::Result<()> {
+        let bytes = len as u64;
+        match self.limits.max_output_bytes {
+            Some(limit) if bytes > limit => Err(LimitExceeded::OutputBytes { bytes, limit }.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::sample_document;
+
+    #[test]
+    fn object_limit_stops_operation_adding_objects() {
+        let mut pdf = sample_document(1, 200, 200).unwrap();
+        let other = sample_document(5, 200, 200).unwrap();
+        let limits = Limits {
+            max_objects: Some(pdf.doc.objects.len() + 2),
+            ..Limits::default()
+        };
+        pdf.set_limits(limits).unwrap();
+
+        let err = pdf.append_document(&other, None, None).unwrap_err();
+        assert!(err.is::<LimitExceeded>());
+    }
+}
@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate};
+
+/// Conventions for numbers and dates drawn by stamps.
+/// characters out of Latin-1 (e.g. Japanese date or Arabic-Indic digits) need font supporting them,
+/// so stamps of Ja and Ar take TrueType font.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+    It,
+    Ja,
+    Ar,
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // accept tags like en-US or ja_JP by language part
+        let lang = s.split(|c| c == '-' || c == '_').next().unwrap_or(s);
+
+        match lang.to_ascii_lowercase().as_str() {
+            "en" => Ok(Self::En),
+            "de" => Ok(Self::De),
+            "fr" => Ok(Self::Fr),
+            "es" => Ok(Self::Es),
+            "it" => Ok(Self::It),
+            "ja" => Ok(Self::Ja),
+            "ar" => Ok(Self::Ar),
+            _ => anyhow::bail!("unsupported locale: {}", s),
+        }
+    }
+}
+
+const MONTHS_EN: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const MONTHS_DE: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+const MONTHS_FR: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+const MONTHS_ES: [&str; 12] = [
+    "enero",
+    "febrero",
+    "marzo",
+    "abril",
+    "mayo",
+    "junio",
+    "julio",
+    "agosto",
+    "septiembre",
+    "octubre",
+    "noviembre",
+    "diciembre",
+];
+const MONTHS_IT: [&str; 12] = [
+    "gennaio",
+    "febbraio",
+    "marzo",
+    "aprile",
+    "maggio",
+    "giugno",
+    "luglio",
+    "agosto",
+    "settembre",
+    "ottobre",
+    "novembre",
+    "dicembre",
+];
+const MONTHS_AR: [&str; 12] = [
+    "يناير",
+    "فبراير",
+    "مارس",
+    "أبريل",
+    "مايو",
+    "يونيو",
+    "يوليو",
+    "أغسطس",
+    "سبتمبر",
+    "أكتوبر",
+    "نوفمبر",
+    "ديسمبر",
+];
+
+impl Locale {
+    /// Replace ASCII digits in TEXT with digit shapes of locale
+    pub fn digits(&self, text: &str) -> String {
+        match self {
+            // Arabic-Indic digits
+            Self::Ar => text
+                .chars()
+                .map(|c| match c.to_digit(10) {
+                    Some(d) => char::from_u32(0x0660 + d).unwrap_or(c),
+                    None => c,
+                })
+                .collect(),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Whether text of locale has characters out of Latin-1
+    pub fn needs_unicode_font(&self) -> bool {
+        matches!(self, Self::Ja | Self::Ar)
+    }
+
+    /// Thousands separator of locale. En is not grouped, keeping numbers as stamped
+    /// before locales were supported.
+    fn group_separator(&self) -> Option<&'static str> {
+        match self {
+            Self::En => None,
+            Self::Ja => Some(","),
+            Self::De | Self::Es | Self::It => Some("."),
+            Self::Fr => Some(" "),
+            Self::Ar => Some("٬"),
+        }
+    }
+
+    /// Format NUMBER with thousands separator and digit shapes of locale
+    pub fn format_number(&self, number: u64) -> String {
+        let plain = number.to_string();
+        let mut grouped = String::new();
+
+        for (i, c) in plain.chars().enumerate() {
+            if i > 0 && (plain.len() - i) % 3 == 0 {
+                grouped.push_str(self.group_separator().unwrap_or_default());
+            }
+            grouped.push(c);
+        }
+
+        self.digits(&grouped)
+    }
+
+    /// Format DATE in long form of locale, like "October 16, 2026" or "16. Oktober 2026"
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        let (day, month, year) = (date.day(), date.month0() as usize, date.year());
+
+        let text = match self {
+            Self::En => format!("{} {}, {}", MONTHS_EN[month], day, year),
+            Self::De => format!("{}. {} {}", day, MONTHS_DE[month], year),
+            Self::Fr => format!("{} {} {}", day, MONTHS_FR[month], year),
+            Self::Es => format!("{} de {} de {}", day, MONTHS_ES[month], year),
+            Self::It => format!("{} {} {}", day, MONTHS_IT[month], year),
+            Self::Ja => format!("{}年{}月{}日", year, month + 1, day),
+            Self::Ar => format!("{} {} {}", day, MONTHS_AR[month], year),
+        };
+
+        self.digits(&text)
+    }
+}
@@ -1,90 +1,1542 @@
 use anyhow::Context;
 
 use argorder;
-use clap::{ArgAction, Parser};
+use chrono::{DateTime, TimeZone, Utc};
+use clap::{ArgAction, Parser, Subcommand};
+use indicatif::{ProgressBar, ProgressStyle};
 
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use pdftool::Pdf;
+use pdftool::{
+    barcode::{Barcode, BarcodeStyle, Symbology},
+    ccitt::Bilevel,
+    checksum,
+    config::Config,
+    destination::Destination,
+    events::{self, EventSink, Summary},
+    font::Font,
+    forms::FieldValue,
+    grid::Grid,
+    input,
+    limits::Limits,
+    locale::Locale,
+    manifest::Manifest,
+    ocr::parse_text_layout,
+    operation,
+    optimize::{self, Optimization},
+    outline::BookmarkStyle,
+    overlay::Layer,
+    paper::PageSize,
+    placement::Fit,
+    preflight::Profile,
+    selection::PageSelection,
+    stamp::{PageNumberStyle, TextStamp},
+    text::Position,
+    transition::TransitionStyle,
+    validate::Conformance,
+    version::PdfVersion,
+    viewer::{PageLayout, PageMode, ViewerPreference},
+    writer::SaveOptions,
+    Error, Pdf,
+};
 
 /// CLI app to manipulate URLs and images in PDF
 #[derive(Parser)]
 #[clap(author, about, version)]
 struct Arg {
-    /// Set input file to INPUT. if not defined, make new PDF document.
+    /// Set input file to INPUT ("-" reads from stdin). if not defined, make new PDF document.
     #[clap(short, long)]
     input: Option<PathBuf>,
 
-    /// Set output file to OUTPUT. if not defined, overwrite input file.
+    /// Rebuild cross-reference table of broken INPUT by scanning for objects, reporting fixes
+    #[clap(long, action = ArgAction::SetTrue)]
+    repair: bool,
+
+    /// Set output file to OUTPUT ("-" writes to stdout). if not defined, overwrite input file.
     #[clap(short, long)]
     output: Option<PathBuf>,
 
-    /// Set PDF author as AUTHOR
-    #[clap(short, long)]
-    author: Option<String>,
+    /// Set PDF author as AUTHOR
+    #[clap(short, long)]
+    author: Option<String>,
+
+    /// Set entry KEY of document information to VALUE, like Department=Legal. empty VALUE
+    /// removes entry
+    #[clap(long, value_name = "KEY=VALUE")]
+    meta: Vec<String>,
+
+    /// Set language of document as LANG like ja-JP, used by screen readers
+    #[clap(long, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Add LINK to PAGE
+    #[clap(short = 'l', long, num_args = 2, value_names = ["LINK", "PAGE"])]
+    add_link: Vec<String>,
+
+    /// Add FILE to pdf. directory, glob pattern or ZIP archive (CBZ) adds images in
+    /// natural order, and "-" reads image from stdin
+    #[clap(short = 'p', long, num_args = 0.. , value_name = "FILE")]
+    add_page: Vec<String>,
+
+    /// Append pages of PDF FILE, along with its bookmarks
+    #[clap(long, num_args = 0.., value_name = "FILE")]
+    append: Vec<PathBuf>,
+
+    /// Add top-level bookmark to first page of each file of --append, named from file
+    /// name, with bookmarks of file moved beneath it
+    #[clap(long, action = ArgAction::SetTrue)]
+    file_bookmarks: bool,
+
+    /// Name bookmark of next file of --append LABEL instead of its file name. implies
+    /// --file-bookmarks
+    #[clap(long, value_name = "LABEL")]
+    bookmark_label: Vec<String>,
+
+    /// Remove links of PAGES, given as ranges like 1-5,8,10-
+    #[clap(short = 'L', long, num_args = 0.. , value_name = "PAGES")]
+    remove_link: Vec<PageSelection>,
+
+    /// Remove PAGES, given as ranges like 1-5,8,10-
+    #[clap(short = 'P', long, num_args = 0.. , value_name = "PAGES")]
+    remove_page: Vec<PageSelection>,
+
+    /// Move link from FROM to TO
+    #[clap(short = 'm', long, num_args = 2, value_names = ["FROM", "TO"])]
+    move_link: Vec<u32>,
+
+    /// Move page from FROM to TO
+    #[clap(short = 'M', long, num_args = 2, value_names = ["FROM", "TO"])]
+    move_page: Vec<u32>,
+
+    /// Reorder pages as ORDER like 3,1,2,5-10,4; unlisted pages follow in original order
+    #[clap(long, value_name = "ORDER")]
+    order: Vec<PageSelection>,
+
+    /// Collapse nested page tree nodes so that all pages are kids of root
+    #[clap(long, action = ArgAction::SetTrue)]
+    flatten_page_tree: bool,
+
+    /// Rebuild page tree as balanced tree of nodes with at most FANOUT (default: 32) kids
+    #[clap(long, value_name = "FANOUT", num_args = 0..=1, default_missing_value = "32")]
+    rebalance_page_tree: Option<usize>,
+
+    /// Prune unused object, merge duplicate images and renumber
+    #[clap(short = 'c', long, action = ArgAction::Count)]
+    prune: u8,
+
+    /// Add bookmark TITLE pointing to PAGE. PAGE may be followed by destination like 3:xyz:0,792,2
+    #[clap(short = 'b', long, num_args = 2, value_names = ["TITLE", "PAGE"])]
+    add_bookmark: Vec<String>,
+
+    /// Add link on PAGE jumping to TARGET. TARGET may be followed by destination like 3:fith:792
+    #[clap(short = 'g', long, num_args = 2, value_names = ["PAGE", "TARGET"])]
+    add_goto_link: Vec<String>,
+
+    /// Collapse outline so that only bookmarks shallower than DEPTH are shown
+    #[clap(long, value_name = "DEPTH")]
+    collapse_outline_depth: Option<usize>,
+
+    /// Open document at PAGE. PAGE may be followed by destination like 3:xyz:,,1.5 to
+    /// zoom to 150%
+    #[clap(long, value_name = "PAGE")]
+    open_at: Option<String>,
+
+    /// Arrange pages as LAYOUT when document is opened (single-page, one-column,
+    /// two-column-left, two-column-right, two-page-left, two-page-right)
+    #[clap(long, value_name = "LAYOUT")]
+    page_layout: Option<PageLayout>,
+
+    /// Show panel of MODE when document is opened (use-none, use-outlines, use-thumbs,
+    /// full-screen, use-oc, use-attachments)
+    #[clap(long, value_name = "MODE")]
+    page_mode: Option<PageMode>,
+
+    /// Turn on viewer preference PREF (hide-toolbar, hide-menubar, hide-window-ui,
+    /// fit-window, center-window, display-doc-title)
+    #[clap(long, num_args = 0.. , value_name = "PREF")]
+    viewer_pref: Vec<ViewerPreference>,
+
+    /// Remove metadata, document ID and annotation authors
+    #[clap(long, action = ArgAction::SetTrue)]
+    sanitize: bool,
+
+    /// Remove JavaScript, launch actions and additional actions
+    #[clap(long, action = ArgAction::SetTrue)]
+    strip_actions: bool,
+
+    /// Rebuild appearance streams of annotations
+    #[clap(long, action = ArgAction::SetTrue)]
+    regenerate_appearances: bool,
+
+    /// Draw image FILE on PAGE at POSITION given as X,Y or X,Y,SCALE in points
+    #[clap(long, num_args = 3, value_names = ["PAGE", "FILE", "POSITION"])]
+    stamp_image: Vec<String>,
+
+    /// Draw pages of PDF file FILE below content of each page, like letterhead
+    #[clap(long, value_name = "FILE")]
+    background: Vec<PathBuf>,
+
+    /// Draw pages of PDF file FILE above content of each page
+    #[clap(long, value_name = "FILE")]
+    overlay: Vec<PathBuf>,
+
+    /// Select pages for following --page-numbers, --header, --footer, --barcode,
+    /// --transition and --auto-crop, like 1-4,7,10-
+    #[clap(long, value_name = "RANGE")]
+    pages: Vec<PageSelection>,
+
+    /// Stamp page numbers on selected pages in FORMAT
+    /// (%d: number, %06d: zero padded, %r/%R: roman, %t: total)
+    #[clap(long, value_name = "FORMAT")]
+    page_numbers: Vec<String>,
+
+    /// Text put before page numbers, like Bates prefix
+    #[clap(long, value_name = "PREFIX", default_value = "")]
+    page_number_prefix: String,
+
+    /// Number of first page for --page-numbers
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    page_number_start: u32,
+
+    /// Position of page numbers (top-left, top, top-right, bottom-left, bottom, bottom-right)
+    #[clap(long, value_name = "POSITION", default_value = "bottom")]
+    page_number_position: Position,
+
+    /// Font size of page numbers in points
+    #[clap(long, value_name = "SIZE", default_value_t = 10.0)]
+    page_number_size: f32,
+
+    /// Format numbers and dates of stamps for LOCALE (en, de, fr, es, it, ja, ar).
+    /// ja and ar need TrueType font given by --font
+    #[clap(long, value_name = "LOCALE", default_value = "en")]
+    locale: Locale,
+
+    /// Font of page numbers, headers and footers: standard font like Times-Roman, or
+    /// TrueType font file (.ttf) for text like Japanese, embedded whole without subsetting
+    #[clap(long, value_name = "FONT", default_value = "Helvetica")]
+    font: Font,
+
+    /// Draw TEXT at top of selected pages. {page}, {pages}, {file}, {date}, {title}, {author}
+    /// and {meta:KEY} are substituted
+    #[clap(long, value_name = "TEXT")]
+    header: Vec<String>,
+
+    /// Draw TEXT at bottom of selected pages. {page}, {pages}, {file}, {date}, {title}, {author}
+    /// and {meta:KEY} are substituted
+    #[clap(long, value_name = "TEXT")]
+    footer: Vec<String>,
+
+    /// Draw barcode of DATA in SYMBOLOGY (code128, ean13) with caption on selected pages
+    #[clap(long, num_args = 2, value_names = ["SYMBOLOGY", "DATA"])]
+    barcode: Vec<String>,
+
+    /// Position of barcodes (top-left, top, top-right, bottom-left, bottom, bottom-right)
+    #[clap(long, value_name = "POSITION", default_value = "top-right")]
+    barcode_position: Position,
+
+    /// Height of bars of barcodes in points
+    #[clap(long, value_name = "HEIGHT", default_value_t = 36.0)]
+    barcode_height: f32,
+
+    /// Draw annotations of PAGES into page content and remove them, given as ranges like 1-5,8,10-
+    #[clap(long, num_args = 0.. , value_name = "PAGES")]
+    flatten_annotations: Vec<PageSelection>,
+
+    /// Draw form fields into page content and remove interactive form
+    #[clap(long, action = ArgAction::SetTrue)]
+    flatten_form: bool,
+
+    /// Crop selected image pages to content of their image, hiding black borders and
+    /// blank margins of scans, leaving PADDING points (default: 0) around content
+    #[clap(long, value_name = "PADDING", num_args = 0..=1, default_missing_value = "0")]
+    auto_crop: Vec<f32>,
+
+    /// Show STYLE effect when moving to selected pages in presentation mode (replace,
+    /// fade, dissolve, wipe, split, blinds, box, glitter, push, cover, uncover)
+    #[clap(long, value_name = "STYLE")]
+    transition: Vec<TransitionStyle>,
+
+    /// Duration of --transition effect in seconds
+    #[clap(long, value_name = "SECONDS", default_value_t = 1.0)]
+    transition_duration: f32,
+
+    /// Embed FILE as attachment
+    #[clap(long, num_args = 0.. , value_name = "FILE")]
+    attach: Vec<PathBuf>,
+
+    /// Print name, type and size of attachments
+    #[clap(long, action = ArgAction::SetTrue)]
+    list_attachments: bool,
+
+    /// Write attachments into directory DIR
+    #[clap(long, value_name = "DIR")]
+    extract_attachments: Option<PathBuf>,
+
+    /// Write thumbnail of PAGE into FILE as PNG
+    #[clap(long, num_args = 2, value_names = ["PAGE", "FILE"])]
+    export_thumbnail: Vec<String>,
+
+    /// Embed thumbnail of at most PIXELS (default: 106) wide and high in each page
+    /// without one, made from largest image of page
+    #[clap(long, value_name = "PIXELS", num_args = 0..=1, default_missing_value = "106")]
+    generate_thumbnails: Option<u32>,
+
+    /// Max width and height of thumbnails in pixels
+    #[clap(long = "max", value_name = "PIXELS", default_value_t = 512)]
+    thumbnail_max: u32,
+
+    /// Report scanned pages differing drastically in size or brightness from neighbors,
+    /// like double feeds and skipped pages
+    #[clap(long, action = ArgAction::SetTrue)]
+    review: bool,
+
+    /// Write report of --review into FILE instead of stdout
+    #[clap(long, value_name = "FILE")]
+    review_report: Option<PathBuf>,
+
+    /// Add bookmark to each page reported by --review
+    #[clap(long, action = ArgAction::SetTrue)]
+    review_bookmarks: bool,
+
+    /// Check document against PROFILE (print) and print violations with page numbers
+    #[clap(long, value_name = "PROFILE")]
+    preflight: Vec<Profile>,
+
+    /// Run registered operation NAME with its ARGS (see --list-operations)
+    #[clap(long, num_args = 1.., value_names = ["NAME", "ARGS"])]
+    op: Vec<String>,
+
+    /// Append blank page of SIZE (a4, letter, WxH, ...; default: size of last page)
+    /// before saving if page count is odd, and before each --append source likewise
+    #[clap(long, num_args = 0..=1, value_name = "SIZE")]
+    pad_even: Option<Option<PageSize>>,
+
+    /// Record pdftool version in output, embedding manifest FILE used to generate it if given
+    #[clap(long, num_args = 0..=1, value_name = "FILE")]
+    provenance: Option<Option<PathBuf>>,
+
+    /// Print pdftool version and manifest recorded by --provenance
+    #[clap(long, action = ArgAction::SetTrue)]
+    show_provenance: bool,
+
+    /// Fail on images larger than PIXELS (width * height)
+    #[clap(long, value_name = "PIXELS")]
+    max_image_pixels: Option<u64>,
+
+    /// Fail if output would be larger than BYTES
+    #[clap(long, value_name = "BYTES")]
+    max_output_bytes: Option<u64>,
+
+    /// Fail if images extracted from archive (CBZ) are larger than BYTES
+    #[clap(long, value_name = "BYTES")]
+    max_input_bytes: Option<u64>,
+
+    /// Fail if document has more than COUNT objects
+    #[clap(long, value_name = "COUNT")]
+    max_objects: Option<usize>,
+
+    /// Fail if processing takes longer than SECONDS
+    #[clap(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    /// Emit progress events in FORMAT (ndjson) to stderr or --events-file
+    #[clap(long, value_name = "FORMAT")]
+    events: Option<events::Format>,
+
+    /// Write progress events to FILE instead of stderr
+    #[clap(long, value_name = "FILE")]
+    events_file: Option<PathBuf>,
+
+    /// Read defaults from config FILE instead of ~/.config/pdftool/config.toml
+    #[clap(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print JSON summary of operations, created pages and warnings to stdout.
+    /// other reports, like --list-attachments, go to stderr then
+    #[clap(long, action = ArgAction::SetTrue)]
+    json: bool,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Write comparison of PDF files A and B into OUTPUT, showing pages side by side
+    /// with their difference, and exit
+    #[clap(long, num_args = 2, value_names = ["A", "B"])]
+    visual_diff: Vec<PathBuf>,
+
+    /// Print registered operations and exit
+    #[clap(long, action = ArgAction::SetTrue)]
+    list_operations: bool,
+
+    /// Run optimization pass KIND (streams, downsample:DPI, jpeg:QUALITY[:all])
+    #[clap(long, value_name = "KIND")]
+    optimize: Vec<Optimization>,
+
+    /// Apply preset PROFILE (screen, ebook, print) of image downsampling, compression,
+    /// metadata handling and object streams before saving
+    #[clap(long, value_name = "PROFILE")]
+    profile: Option<optimize::Profile>,
+
+    /// Write checksum manifest of output file using ALGO (sha256)
+    #[clap(long, value_name = "ALGO")]
+    emit_checksums: Option<checksum::Algorithm>,
+
+    /// Write same bytes for same inputs: dates are set to TIMESTAMP (RFC 3339 or Unix
+    /// seconds; default: SOURCE_DATE_EPOCH or 1970-01-01), objects are renumbered and
+    /// document ID is derived from content
+    #[clap(long, num_args = 0..=1, value_name = "TIMESTAMP")]
+    deterministic: Option<Option<String>>,
+
+    /// Append changes to input file as incremental update instead of rewriting it
+    #[clap(long, action = ArgAction::SetTrue)]
+    incremental: bool,
+
+    /// Pack objects into object streams and write xref stream (PDF 1.5+)
+    #[clap(long, action = ArgAction::SetTrue)]
+    object_streams: bool,
+
+    /// Write output as PDF VERSION (1.4, 1.5, 1.7, 2.0), failing if document uses
+    /// features it lacks
+    #[clap(long, value_name = "VERSION")]
+    pdf_version: Option<PdfVersion>,
+
+    /// Encrypt embedded files with PASSWORD, leaving document itself readable
+    #[clap(long, value_name = "PASSWORD")]
+    encrypt_attachments: Option<String>,
+
+    /// Ignore permission restrictions of encrypted input using owner PASSWORD
+    #[clap(long, value_name = "PASSWORD")]
+    override_permissions: Option<String>,
+
+    /// Reduce 16-bit PNG images to DEPTH bits (only 8 supported) with dithering
+    #[clap(long, value_name = "DEPTH", value_parser = clap::value_parser!(u8).range(8..=8))]
+    depth_reduce: Option<u8>,
+
+    /// Convert added page images to black and white, compressed with KIND (g4)
+    #[clap(long, value_name = "KIND")]
+    bilevel: Option<Bilevel>,
+
+    /// Tag pages added by preceding --add-page as figures described by TEXT, one for
+    /// each page in order, for screen readers
+    #[clap(long, num_args = 0.. , value_name = "TEXT")]
+    alt_text: Vec<String>,
+
+    /// Tag pages of each added image as figure described by text of sidecar file
+    /// with same name and extension .txt, like photo.txt for photo.jpg. page of --grid
+    /// is described by texts of all its images
+    #[clap(long, action = ArgAction::SetTrue)]
+    alt_text_sidecar: bool,
+
+    /// Add every frame of animated GIF as page instead of first one
+    #[clap(long, action = ArgAction::SetTrue)]
+    all_frames: bool,
+
+    /// Size pages of added images assuming resolution of DPI, instead of one recorded in them
+    #[clap(long, value_name = "DPI")]
+    dpi: Option<f32>,
+
+    /// Re-encode converted and downsampled images as JPEG of QUALITY (1-100, default: 85)
+    #[clap(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: Option<u8>,
+
+    /// Place added images on pages of SIZE (a4, letter, WxH, ...) instead of pages of image size
+    #[clap(long, value_name = "SIZE")]
+    page_size: Option<PageSize>,
+
+    /// Scale images onto pages of --page-size or cells of --grid by MODE
+    /// (contain, cover, fill, center)
+    #[clap(long, value_name = "MODE", default_value = "contain")]
+    fit: Fit,
+
+    /// Leave margin of POINTS around images on pages of --page-size or --grid
+    #[clap(long, value_name = "POINTS", default_value_t = 0.0)]
+    margin: f32,
+
+    /// Lay out images of each --add-page in COLUMNSxROWS grid on pages of --page-size
+    /// (default: a4) with file names as captions, as contact sheet
+    #[clap(long, value_name = "COLUMNSxROWS")]
+    grid: Option<Grid>,
+}
+
+trait IterNextN: Iterator {
+    fn nextn(&mut self, count: u32) -> Vec<Self::Item> {
+        let mut result = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let Some(val) = self.next() else {
+                break;
+            };
+            result.push(val);
+        }
+
+        result
+    }
+}
+
+impl<T: ?Sized> IterNextN for T where T: Iterator {}
+
+/// Parse TIMESTAMP given as RFC 3339 like 2024-01-31T09:00:00Z, or as Unix seconds
+fn parse_timestamp(timestamp: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(seconds) = timestamp.trim().parse::<i64>() {
+        return Utc
+            .timestamp_opt(seconds, 0)
+            .single()
+            .with_context(|| format!("Invalid timestamp {}", timestamp));
+    }
+
+    let time = DateTime::parse_from_rfc3339(timestamp.trim())
+        .with_context(|| format!("Invalid timestamp {}", timestamp))?;
+    Ok(time.with_timezone(&Utc))
+}
+
+fn parse_rect(arg: &str) -> Result<[f32; 4], String> {
+    let values = arg
+        .split(',')
+        .map(|x| x.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    match values[..] {
+        [x0, y0, x1, y1] => Ok([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]),
+        _ => Err("expected 4 numbers like x0,y0,x1,y1".into()),
+    }
+}
+
+/// Parse SIZE like 10MB, 500k or 2MiB into bytes. units without i are powers of 1000
+fn parse_size(arg: &str) -> Result<u64, String> {
+    let arg = arg.trim();
+    let split = arg
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size: {}", arg))?;
+    let unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000 * 1000,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        _ => return Err(format!("unknown unit of size: {}", unit)),
+    };
+
+    Ok((number * unit as f64) as u64)
+}
+
+/// Parse SECONDS like 2.5 into duration, rejecting values duration can't hold
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs: f32 = arg
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid seconds: {}", arg))?;
+    if !(0.0..=u32::MAX as f32).contains(&secs) {
+        return Err(format!("seconds out of range: {}", arg));
+    }
+
+    Ok(Duration::from_secs_f32(secs))
+}
+
+/// Parse PAGE[:DESTINATION] argument of option OP
+fn parse_page_dest(arg: &str, op: &str) -> anyhow::Result<(u32, Destination)> {
+    let (page_str, dest_str) = arg.split_once(':').unwrap_or((arg, "fit"));
+
+    let page = page_str
+        .parse()
+        .with_context(|| format!("Invalid argument {} found in option \"{}\"", arg, op))?;
+    let dest = dest_str
+        .parse()
+        .with_context(|| format!("Invalid destination {} found in option \"{}\"", arg, op))?;
+
+    Ok((page, dest))
+}
+
+/// Expand SELECTIONS into sorted page numbers of PDF without duplicates
+fn page_set(selections: &[PageSelection], pdf: &Pdf) -> anyhow::Result<Vec<u32>> {
+    let total = pdf.page_count() as u32;
+
+    let mut pages = Vec::new();
+    for selection in selections {
+        pages.extend(selection.page_set(total)?);
+    }
+    pages.sort_unstable();
+    pages.dedup();
+
+    Ok(pages)
+}
+
+/// Expand SELECTION into page numbers of PDF in given order, failing on pages out of document
+fn page_order(selection: &PageSelection, pdf: &Pdf) -> anyhow::Result<Vec<u32>> {
+    let total = pdf.page_count() as u32;
+
+    for range in &selection.0 {
+        let last = range.end.unwrap_or(range.start);
+        if range.start > total || last > total {
+            return Err(Error::InvalidArgument(format!(
+                "page {} in order is out of document with {} pages",
+                last.max(range.start),
+                total
+            ))
+            .into());
+        }
+    }
+
+    Ok(selection.pages(total))
+}
+
+/// Read alternate text of image FILE from sidecar file with extension .txt, if it exists
+fn read_sidecar(file: &Path) -> anyhow::Result<Option<String>> {
+    let sidecar = file.with_extension("txt");
+    if !sidecar.is_file() {
+        return Ok(None);
+    }
+
+    let alt = std::fs::read_to_string(&sidecar)
+        .with_context(|| format!("Failed to read {}", sidecar.display()))?;
+    Ok(Some(alt.trim().to_string()))
+}
+
+/// Read image FILE, or stdin if FILE is "-", returning pairs of file name and data.
+/// ZIP archive (CBZ) gives images in it, extracting no more than LIMIT bytes.
+fn read_images(file: &Path, limit: Option<u64>) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let (name, bytes) = if file == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .context("Failed to read image from stdin")?;
+        anyhow::ensure!(!bytes.is_empty(), "No image data in stdin");
+
+        ("stdin".to_string(), bytes)
+    } else {
+        let bytes =
+            std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let name = file
+            .file_name()
+            .unwrap_or(file.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+
+        (name, bytes)
+    };
+
+    if input::is_zip(&bytes) {
+        return input::zip_images(&bytes, limit)
+            .with_context(|| format!("Failed to extract {}", name));
+    }
+
+    Ok(vec![(name, bytes)])
+}
+
+/// Progress bar of LEN steps on stderr, hidden if HIDDEN or stderr is not terminal
+fn progress_bar(len: usize, hidden: bool) -> ProgressBar {
+    if hidden {
+        return ProgressBar::hidden();
+    }
+
+    let bar = ProgressBar::new(len as u64);
+    if let Ok(style) = ProgressStyle::with_template("{msg:20} [{bar:40}] {pos}/{len} {eta}") {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+}
+
+/// Build documents declared in manifest file
+#[derive(Parser)]
+#[clap(name = "pdftool apply")]
+struct ApplyArg {
+    /// TOML file declaring inputs, image pages, links, metadata and outputs
+    manifest: PathBuf,
+
+    /// Record pdftool version in each output, embedding manifest as source attachment
+    #[clap(long, action = ArgAction::SetTrue)]
+    provenance: bool,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Work with interactive form of PDF
+#[derive(Parser)]
+#[clap(name = "pdftool form")]
+struct FormArg {
+    #[clap(subcommand)]
+    command: FormCommand,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+}
+
+#[derive(Subcommand)]
+enum FormCommand {
+    /// Fill fields with values of JSON object, keyed by full field names like "address.city"
+    Fill {
+        /// PDF file with form ("-" reads from stdin)
+        input: PathBuf,
+
+        /// JSON file of field values: strings for text, true or false for check boxes
+        #[clap(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Output PDF file
+        #[clap(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Write values of fields into FDF file, or XFDF if output ends with .xfdf
+    Export {
+        /// PDF file with form ("-" reads from stdin)
+        input: PathBuf,
+
+        /// FDF or XFDF file to write
+        #[clap(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Fill fields with values of FDF or XFDF file
+    Import {
+        /// PDF file with form ("-" reads from stdin)
+        input: PathBuf,
+
+        /// FDF or XFDF file to read
+        #[clap(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Output PDF file
+        #[clap(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+}
+
+/// Work with files attached to PDF
+#[derive(Parser)]
+#[clap(name = "pdftool attach")]
+struct AttachArg {
+    #[clap(subcommand)]
+    command: AttachCommand,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+}
+
+#[derive(Subcommand)]
+enum AttachCommand {
+    /// Embed files, replacing attachments of same name
+    Add {
+        /// PDF file to attach files to ("-" reads from stdin)
+        input: PathBuf,
+
+        /// Files to embed, named by their file name
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output PDF file
+        #[clap(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Write attachments into directory, printing their paths
+    Extract {
+        /// PDF file to extract attachments from ("-" reads from stdin)
+        input: PathBuf,
+
+        /// Directory to write attachments into
+        #[clap(short, long, value_name = "DIR")]
+        output: PathBuf,
+    },
+}
+
+/// Work with images in PDF
+#[derive(Parser)]
+#[clap(name = "pdftool images")]
+struct ImagesArg {
+    #[clap(subcommand)]
+    command: ImagesCommand,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+}
+
+#[derive(Subcommand)]
+enum ImagesCommand {
+    /// Write images of pages into directory as JPEG or PNG files named by page and index
+    Extract {
+        /// PDF file to extract images from ("-" reads from stdin)
+        input: PathBuf,
+
+        /// Directory to write images into
+        #[clap(short, long, value_name = "DIR")]
+        output: PathBuf,
+
+        /// Extract images of PAGES only, like 1-5,8,10-
+        #[clap(long, value_name = "PAGES", default_value = "all")]
+        pages: PageSelection,
+    },
+}
+
+/// Compare structure of two PDFs, exiting with error if they differ
+#[derive(Parser)]
+#[clap(name = "pdftool diff")]
+struct DiffArg {
+    /// First PDF file
+    a: PathBuf,
+
+    /// Second PDF file
+    b: PathBuf,
+
+    /// Print differences as JSON to stdout
+    #[clap(long)]
+    json: bool,
+
+    /// Also compare CreationDate, ModDate and Producer of document information
+    #[clap(long)]
+    include_volatile: bool,
+}
+
+/// Add invisible text layer to scanned pages, recognized by tesseract (needs feature "ocr")
+/// or read from hOCR or ALTO files
+#[derive(Parser)]
+#[clap(name = "pdftool ocr")]
+struct OcrArg {
+    /// PDF file to recognize ("-" reads from stdin)
+    #[clap(short, long, value_name = "FILE")]
+    input: PathBuf,
+
+    /// Output PDF file
+    #[clap(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Languages of text as tesseract names them, like eng or eng+jpn
+    #[clap(long, value_name = "LANG", default_value = "eng")]
+    lang: String,
+
+    /// Resolution pages are rendered at (needs feature "render"), otherwise page images
+    /// are recognized as they are
+    #[clap(long, value_name = "DPI", default_value_t = 300.0)]
+    dpi: f32,
+
+    /// Recognize PAGES only, like 1-5,8,10-
+    #[clap(long, value_name = "PAGES", default_value = "all")]
+    pages: PageSelection,
+
+    /// hOCR or ALTO file of text recognized by other OCR engine, instead of running
+    /// tesseract. pages of files are laid on selected pages in order. can be repeated
+    #[clap(long, value_name = "FILE")]
+    layout: Vec<PathBuf>,
+
+    /// Font of text layer: standard font like Times-Roman, or TrueType font file (.ttf)
+    /// for text like Japanese, embedded whole without subsetting
+    #[clap(long, value_name = "FONT", default_value = "Helvetica")]
+    font: Font,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Rasterize pages into PNG files (needs feature "render")
+#[derive(Parser)]
+#[clap(name = "pdftool render")]
+struct RenderArg {
+    /// PDF file to render ("-" reads from stdin)
+    input: PathBuf,
+
+    /// Directory to write images into, named like page003.png
+    #[clap(short, long, value_name = "DIR")]
+    output: PathBuf,
+
+    /// Resolution of images
+    #[clap(long, value_name = "DPI", default_value_t = 150.0)]
+    dpi: f32,
+
+    /// Render PAGES only, like 1-5,8,10-
+    #[clap(long, value_name = "PAGES", default_value = "all")]
+    pages: PageSelection,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Remove pages identical to earlier ones, keeping first of each, and print what was removed
+#[derive(Parser)]
+#[clap(name = "pdftool dedup-pages")]
+struct DedupPagesArg {
+    /// PDF file to remove duplicate pages from ("-" reads from stdin)
+    input: PathBuf,
+
+    /// Compare rendered pages instead of their content, to find pages looking same
+    /// though made differently
+    #[clap(long, action = ArgAction::SetTrue)]
+    rendered: bool,
+
+    /// Output PDF file
+    #[clap(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Remove text and images under rectangles of page and cover them with black boxes
+#[derive(Parser)]
+#[clap(name = "pdftool redact")]
+struct RedactArg {
+    /// PDF file to redact ("-" reads from stdin)
+    input: PathBuf,
+
+    /// Page to redact
+    #[clap(long, value_name = "PAGE")]
+    page: u32,
+
+    /// Area to redact in default user space, like 72,700,300,720. can be repeated
+    #[clap(long, value_name = "X0,Y0,X1,Y1", value_parser = parse_rect, required = true)]
+    rect: Vec<[f32; 4]>,
+
+    /// Output PDF file
+    #[clap(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Split PDF into consecutively numbered parts
+#[derive(Parser)]
+#[clap(name = "pdftool split")]
+struct SplitArg {
+    /// PDF file to split ("-" reads from stdin)
+    input: PathBuf,
+
+    /// Make parts estimated to be at most SIZE, like 10MB, 500KB or 2MiB
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, required_unless_present = "every")]
+    max_size: Option<u64>,
+
+    /// Make parts of N pages each, last one having rest
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "max_size"
+    )]
+    every: Option<u32>,
+
+    /// Write parts as PREFIX-1.pdf, PREFIX-2.pdf and so on [default: input without
+    /// extension]
+    #[clap(short, long, value_name = "PREFIX")]
+    output: Option<PathBuf>,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Interleave pages of two PDFs, like fronts and backs scanned in separate passes
+#[derive(Parser)]
+#[clap(name = "pdftool zip")]
+struct ZipArg {
+    /// PDF file of odd pages (front sides)
+    odd: PathBuf,
+
+    /// PDF file of even pages (back sides)
+    even: PathBuf,
+
+    /// Take pages of EVEN from last to first, as when stack is flipped over for backs
+    #[clap(short, long, action = ArgAction::SetTrue)]
+    reverse: bool,
+
+    /// Output PDF file
+    #[clap(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Log what is done to stderr, with more details if repeated (-vv)
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+/// Check PDF against conformance requirements and print rule-by-rule report
+#[derive(Parser)]
+#[clap(name = "pdftool validate")]
+struct ValidateArg {
+    /// PDF file to check ("-" reads from stdin)
+    input: PathBuf,
+
+    /// Conformance level to check against (pdfa-2b)
+    #[clap(long, value_name = "PROFILE", default_value = "pdfa-2b")]
+    profile: Conformance,
+}
+
+fn init_logger(verbose: u8) {
+    let level = match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        _ => log::LevelFilter::Debug,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .init();
+}
+
+fn apply(args: ApplyArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let manifest = Manifest::load(&args.manifest)?;
+    let source = if args.provenance {
+        Some(std::fs::read(&args.manifest)?)
+    } else {
+        None
+    };
+    let name = args
+        .manifest
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    for target in &manifest.outputs {
+        let path = manifest.resolve(&target.path);
+        log::info!("building {}", path.display());
+
+        let mut pdf = manifest
+            .build(target)
+            .with_context(|| format!("Failed to build {}", path.display()))?;
+        if let Some(source) = &source {
+            pdf.embed_provenance(Some((&name, source)))?;
+        }
+        pdf.save(&path)?;
+    }
+
+    Ok(())
+}
+
+fn form(args: FormArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let load = |input: &Path| -> anyhow::Result<Pdf> {
+        Ok(if input == Path::new("-") {
+            Pdf::load_from(std::io::stdin().lock())?
+        } else {
+            Pdf::load(input)?
+        })
+    };
+
+    match args.command {
+        FormCommand::Fill {
+            input,
+            data,
+            output,
+        } => {
+            let mut pdf = load(&input)?;
+
+            let file = std::fs::File::open(&data)
+                .with_context(|| format!("Failed to read {}", data.display()))?;
+            let values: HashMap<String, FieldValue> = serde_json::from_reader(file)
+                .with_context(|| format!("Failed to parse {}", data.display()))?;
+
+            pdf.fill_form(&values)?;
+            pdf.save(&output)?;
+        }
+        FormCommand::Export { input, output } => {
+            let pdf = load(&input)?;
+
+            let bytes = match output.extension() {
+                Some(ext) if ext.eq_ignore_ascii_case("xfdf") => pdf.export_xfdf().into_bytes(),
+                _ => pdf.export_fdf(),
+            };
+            std::fs::write(&output, bytes)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+        }
+        FormCommand::Import {
+            input,
+            data,
+            output,
+        } => {
+            let mut pdf = load(&input)?;
+
+            let bytes = std::fs::read(&data)
+                .with_context(|| format!("Failed to read {}", data.display()))?;
+            pdf.import_form_data(&bytes)?;
+            pdf.save(&output)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn attach(args: AttachArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let load = |input: &Path| -> anyhow::Result<Pdf> {
+        Ok(if input == Path::new("-") {
+            Pdf::load_from(std::io::stdin().lock())?
+        } else {
+            Pdf::load(input)?
+        })
+    };
+
+    match args.command {
+        AttachCommand::Add {
+            input,
+            files,
+            output,
+        } => {
+            let mut pdf = load(&input)?;
+
+            for file in files {
+                let name = file
+                    .file_name()
+                    .with_context(|| format!("Invalid file name {}", file.display()))?
+                    .to_string_lossy()
+                    .into_owned();
+                let data = std::fs::read(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                pdf.add_attachment(&name, &data)?;
+            }
+            pdf.save(&output)?;
+        }
+        AttachCommand::Extract { input, output } => {
+            let pdf = load(&input)?;
+
+            for path in pdf.extract_attachments(&output)? {
+                println!("{}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn images(args: ImagesArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    match args.command {
+        ImagesCommand::Extract {
+            input,
+            output,
+            pages,
+        } => {
+            let pdf = if input == Path::new("-") {
+                Pdf::load_from(std::io::stdin().lock())?
+            } else {
+                Pdf::load(&input)?
+            };
+
+            for path in pdf.extract_images_to(&pages, &output)? {
+                println!("{}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn ocr(args: OcrArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let mut pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
+
+    let count = if args.layout.is_empty() {
+        pdf.ocr(&args.pages, &args.lang, args.dpi, &args.font)?
+    } else {
+        let mut layouts = vec![];
+        for path in &args.layout {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            layouts.extend(
+                parse_text_layout(&bytes)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?,
+            );
+        }
+        pdf.add_text_layers(&args.pages, &layouts, &args.font)?
+    };
+    println!("{} words added to text layer", count);
+    pdf.save(&args.output)?;
+
+    Ok(())
+}
 
-    /// Add LINK to PAGE
-    #[clap(short = 'l', long, num_args = 2, value_names = ["LINK", "PAGE"])]
-    add_link: Vec<String>,
+fn render(args: RenderArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
 
-    /// Add FILE to pdf
-    #[clap(short = 'p', long, num_args = 0.. , value_name = "FILE")]
-    add_page: Vec<String>,
+    let pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
+
+    for path in pdf.render_pages_to(&args.pages, args.dpi, &args.output)? {
+        println!("{}", path.display());
+    }
 
-    /// Remove link of PAGE
-    #[clap(short = 'L', long, num_args = 0.. , value_name = "PAGE")]
-    remove_link: Vec<u32>,
+    Ok(())
+}
 
-    /// Remove PAGE
-    #[clap(short = 'P', long, num_args = 0.. , value_name = "PAGE")]
-    remove_page: Vec<u32>,
+fn redact(args: RedactArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
 
-    /// Move link from FROM to TO
-    #[clap(short = 'm', long, num_args = 2, value_names = ["FROM", "TO"])]
-    move_link: Vec<u32>,
+    let mut pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
 
-    /// Move page from FROM to TO
-    #[clap(short = 'M', long, num_args = 2, value_names = ["FROM", "TO"])]
-    move_page: Vec<u32>,
+    pdf.redact(args.page, &args.rect)?;
+    pdf.save(&args.output)?;
 
-    /// Prune unused object and renumber
-    #[clap(short = 'c', long, action = ArgAction::Count)]
-    prune: u8,
+    Ok(())
 }
 
-trait IterNextN: Iterator {
-    fn nextn(&mut self, count: u32) -> Vec<Self::Item> {
-        let mut result = Vec::with_capacity(count as usize);
+fn dedup_pages(args: DedupPagesArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
 
-        for _ in 0..count {
-            let Some(val) = self.next() else {
-                break;
-            };
-            result.push(val);
+    let mut pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
+
+    let removed = pdf.dedup_pages(args.rendered)?;
+    for (page, original) in &removed {
+        println!("removed page {} (duplicate of page {})", page, original);
+    }
+    if removed.is_empty() {
+        println!("no duplicate pages");
+    }
+    pdf.save(&args.output)?;
+
+    Ok(())
+}
+
+fn split(args: SplitArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let prefix = match args.output {
+        Some(prefix) => prefix,
+        None if args.input == Path::new("-") => {
+            anyhow::bail!("--output is required for input from stdin")
         }
+        None => args.input.with_extension(""),
+    };
+    let pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
 
-        result
+    let parts = match (args.every, args.max_size) {
+        (Some(every), _) => pdf.split_every(every),
+        (None, max_size) => pdf.split_by_size(max_size.unwrap()),
+    };
+    // numbers are padded to same width, so that parts sort in order
+    let width = parts.len().to_string().len();
+    for (i, pages) in parts.iter().enumerate() {
+        let mut name = prefix.clone().into_os_string();
+        name.push(format!("-{:0width$}.pdf", i + 1, width = width));
+        let path = PathBuf::from(name);
+
+        let data = pdf.extract_pages(pages)?.to_bytes()?;
+        if let Some(max_size) = args.max_size.filter(|&x| data.len() as u64 > x) {
+            log::warn!(
+                "{} takes {} bytes, over limit of {}",
+                path.display(),
+                data.len(),
+                max_size
+            );
+        }
+        std::fs::write(&path, &data)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("{}", path.display());
     }
+
+    Ok(())
 }
 
-impl<T: ?Sized> IterNextN for T where T: Iterator {}
+fn zip(args: ZipArg) -> anyhow::Result<()> {
+    init_logger(args.verbose);
+
+    let mut pdf =
+        Pdf::load(&args.odd).with_context(|| format!("Failed to load {}", args.odd.display()))?;
+    let even =
+        Pdf::load(&args.even).with_context(|| format!("Failed to load {}", args.even.display()))?;
+
+    pdf.interleave(&even, args.reverse)?;
+    pdf.save(&args.output)?;
+
+    Ok(())
+}
+
+fn diff(args: DiffArg) -> anyhow::Result<()> {
+    let a = Pdf::load(&args.a).with_context(|| format!("Failed to load {}", args.a.display()))?;
+    let b = Pdf::load(&args.b).with_context(|| format!("Failed to load {}", args.b.display()))?;
+
+    let diff = Pdf::structural_diff(&a, &b, args.include_volatile);
+    if args.json {
+        let result = serde_json::json!({
+            "identical": diff.is_identical(),
+            "differences": diff.differences,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", diff);
+    }
+    anyhow::ensure!(diff.is_identical(), "documents differ");
+
+    Ok(())
+}
+
+fn validate(args: ValidateArg) -> anyhow::Result<()> {
+    let pdf = if args.input == Path::new("-") {
+        Pdf::load_from(std::io::stdin().lock())?
+    } else {
+        Pdf::load(&args.input)?
+    };
+
+    let report = pdf.validate(args.profile)?;
+    println!("{}", report);
+    anyhow::ensure!(
+        report.is_valid(),
+        "document does not conform to {}",
+        args.profile
+    );
+
+    Ok(())
+}
 
 fn main() -> anyhow::Result<()> {
+    // ordered options can't be mixed with subcommands, so they are dispatched by hand
+    match std::env::args_os().nth(1) {
+        Some(x) if x == "apply" => return apply(ApplyArg::parse_from(std::env::args_os().skip(1))),
+        Some(x) if x == "attach" => {
+            return attach(AttachArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "dedup-pages" => {
+            return dedup_pages(DedupPagesArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "diff" => return diff(DiffArg::parse_from(std::env::args_os().skip(1))),
+        Some(x) if x == "form" => return form(FormArg::parse_from(std::env::args_os().skip(1))),
+        Some(x) if x == "images" => {
+            return images(ImagesArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "ocr" => return ocr(OcrArg::parse_from(std::env::args_os().skip(1))),
+        Some(x) if x == "redact" => {
+            return redact(RedactArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "render" => {
+            return render(RenderArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "split" => return split(SplitArg::parse_from(std::env::args_os().skip(1))),
+        Some(x) if x == "validate" => {
+            return validate(ValidateArg::parse_from(std::env::args_os().skip(1)))
+        }
+        Some(x) if x == "zip" => return zip(ZipArg::parse_from(std::env::args_os().skip(1))),
+        _ => {}
+    }
+
     let (args, order) = argorder::parse::<Arg>();
 
+    init_logger(args.verbose);
+
+    let mut events = match args.events {
+        Some(events::Format::Ndjson) => Some(match &args.events_file {
+            Some(path) => EventSink::file(path)?,
+            None => EventSink::stderr(),
+        }),
+        None => None,
+    };
+
+    let result = run(args, order, &mut events);
+    if let (Err(err), Some(sink)) = (&result, &mut events) {
+        sink.error(&format!("{:#}", err))?;
+    }
+
+    result
+}
+
+fn run(args: Arg, order: Vec<(String, u32)>, events: &mut Option<EventSink>) -> anyhow::Result<()> {
+    let registry = operation::Registry::with_builtins();
+
+    if args.list_operations {
+        for operation in registry.iter() {
+            println!(
+                "{} {}\t{}",
+                operation.name,
+                operation.value_names.join(" "),
+                operation.help
+            );
+        }
+        return Ok(());
+    }
+
+    if let [a, b] = &args.visual_diff[..] {
+        let output = args
+            .output
+            .as_ref()
+            .context("output file is required for --visual-diff")?;
+        Pdf::visual_diff(&Pdf::load(a)?, &Pdf::load(b)?)?.save(output)?;
+        return Ok(());
+    }
+
+    // flags take precedence over config file
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
+    let profile = args.profile.or(config.profile);
+
     // check if input or output is avail
     anyhow::ensure!(
         args.input.is_some() || args.output.is_some(),
         "both input and output file not provided"
     );
 
-    let mut pdf = if let Some(file) = &args.input {
-        Pdf::load(file)?
+    // stdin is buffered here so that its size is known for save event
+    let stdin_input = match &args.input {
+        Some(file) if file == Path::new("-") => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .lock()
+                .read_to_end(&mut bytes)
+                .context("Failed to read input from stdin")?;
+            Some(bytes)
+        }
+        _ => None,
+    };
+
+    let input_bytes = match (&stdin_input, &args.input) {
+        (Some(bytes), _) => bytes.len() as u64,
+        (None, Some(file)) => std::fs::metadata(file)?.len(),
+        (None, None) => 0,
+    };
+
+    let limits = Limits {
+        max_image_pixels: args.max_image_pixels,
+        max_output_bytes: args.max_output_bytes,
+        max_input_bytes: args.max_input_bytes,
+        max_objects: args.max_objects,
+        timeout: args.timeout,
+    };
+
+    let mut pdf = if args.repair && args.input.is_some() {
+        let (mut pdf, fixes) = match &stdin_input {
+            Some(bytes) => Pdf::from_bytes_lenient(bytes)?,
+            None => Pdf::load_lenient(args.input.as_ref().unwrap())?,
+        };
+        for fix in fixes {
+            eprintln!("repair: {}", fix);
+        }
+        pdf.set_limits(limits)?;
+        pdf
+    } else if let Some(bytes) = &stdin_input {
+        Pdf::load_from_with_limits(bytes.as_slice(), limits)?
+    } else if let Some(file) = &args.input {
+        Pdf::load_with_limits(file, limits)?
+    } else {
+        let mut pdf = args.pdf_version.map_or_else(Pdf::new, Pdf::with_version);
+        pdf.limits = limits;
+        pdf
+    };
+
+    if let Some(password) = &args.override_permissions {
+        pdf.override_permissions(password)?;
+    }
+
+    if let Some(timestamp) = &args.deterministic {
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp.clone(),
+            None => std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| "0".into()),
+        };
+        pdf.set_timestamp(parse_timestamp(&timestamp)?)?;
+    }
+
+    pdf.image_options.depth_reduce = args.depth_reduce;
+    pdf.image_options.all_frames = args.all_frames;
+    pdf.image_options.bilevel = args.bilevel;
+    pdf.image_options.dpi = args.dpi;
+    pdf.image_options.page_size = args.page_size.or(config.page_size);
+    pdf.image_options.jpeg_quality = args.jpeg_quality.or(config.jpeg_quality);
+    pdf.image_options.fit = args.fit;
+    pdf.image_options.margin = args.margin;
+
+    let output = args.output.or(args.input).unwrap();
+    let to_stdout = output == Path::new("-");
+    anyhow::ensure!(
+        !(to_stdout && args.emit_checksums.is_some()),
+        "--emit-checksums can't be used with output to stdout"
+    );
+    anyhow::ensure!(
+        !(to_stdout && args.json),
+        "--json can't be used with output to stdout"
+    );
+    anyhow::ensure!(
+        !(args.incremental && args.deterministic.is_some()),
+        "--deterministic can't be used with --incremental"
+    );
+    anyhow::ensure!(
+        !(args.incremental && args.pdf_version.is_some()),
+        "--pdf-version can't be used with --incremental"
+    );
+    anyhow::ensure!(
+        !(args.incremental && args.encrypt_attachments.is_some()),
+        "--encrypt-attachments can't be used with --incremental"
+    );
+    anyhow::ensure!(
+        !(args.incremental && args.object_streams),
+        "--object-streams can't be used with --incremental"
+    );
+    let mut summary = args.json.then(Summary::new);
+    // reports of operations go to stderr when stdout carries output PDF or JSON summary
+    let mut report_out: Box<dyn Write> = if to_stdout || args.json {
+        Box::new(std::io::stderr())
     } else {
-        Pdf::new()
+        Box::new(std::io::stdout())
     };
 
-    let output = args.input.or(args.output).unwrap();
+    let file_name = output
+        .file_name()
+        .filter(|_| !to_stdout)
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // progress bar would garble logs and events written to stderr
+    let hide_progress = args.verbose > 0 || (args.events.is_some() && args.events_file.is_none());
+    if !hide_progress {
+        let bar = progress_bar(0, false);
+        pdf.set_progress(Box::new(move |task, done, total| {
+            bar.set_message(task.to_string());
+            bar.set_length(total as u64);
+            bar.set_position(done as u64);
+            if done == total {
+                bar.finish_and_clear();
+            }
+        }));
+    }
+
+    if let (None, Some(author)) = (&args.author, &config.author) {
+        if pdf.info("Author").map_or(true, |x| x.is_empty()) {
+            pdf.set_author(author)?;
+        }
+    }
+    let default_optimize = if args.optimize.is_empty() {
+        config.optimize
+    } else {
+        Vec::new()
+    };
 
     let mut ali = args.add_link.into_iter();
     let mut api = args.add_page.into_iter();
@@ -92,14 +1544,59 @@ fn main() -> anyhow::Result<()> {
     let mut rpi = args.remove_page.into_iter();
     let mut mli = args.move_link.into_iter();
     let mut mpi = args.move_page.into_iter();
+    let mut opi = args.optimize.into_iter();
+    let mut abi = args.add_bookmark.into_iter();
+    let mut agi = args.add_goto_link.into_iter();
+    let mut fai = args.flatten_annotations.into_iter();
+    let mut ati = args.attach.into_iter();
+    let mut sii = args.stamp_image.into_iter();
+    let mut bgi = args.background.into_iter();
+    let mut ovi = args.overlay.into_iter();
+    let mut pni = args.page_numbers.into_iter();
+    let mut hdi = args.header.into_iter();
+    let mut fti = args.footer.into_iter();
+    let mut pgi = args.pages.into_iter();
+    let mut ori = args.order.into_iter();
+    let mut selection = PageSelection::default();
+    let mut added_pages: Vec<u32> = Vec::new();
+    let mut ooi = args.op.into_iter();
+    let mut pfi = args.preflight.into_iter();
+    let mut eti = args.export_thumbnail.into_iter();
+    let mut vpi = args.viewer_pref.into_iter();
+    let mut tri = args.transition.into_iter();
+    let mut alti = args.alt_text.into_iter();
+    let mut mti = args.meta.into_iter();
+    let mut aci = args.auto_crop.into_iter();
+    let mut bci = args.barcode.into_iter();
+    let file_bookmarks = args.file_bookmarks || !args.bookmark_label.is_empty();
+    let mut appi = args.append.into_iter();
+    let mut bli = args.bookmark_label.into_iter();
 
     for (op, argc) in order {
         let op = op.as_str();
+        log::info!("running {}", op);
+
+        if let Some(sink) = events {
+            sink.start(op, &pdf)?;
+        }
+        if let Some(summary) = &mut summary {
+            summary.start(op, &pdf);
+        }
 
         match op {
             "author" => {
                 pdf.set_author(args.author.as_ref().unwrap())?;
             }
+            "meta" => {
+                let entry = mti.next().unwrap();
+                let (key, value) = entry.split_once('=').with_context(|| {
+                    format!("Invalid argument {} found in option \"{}\"", entry, op)
+                })?;
+                pdf.set_info_entry(key, value)?;
+            }
+            "lang" => {
+                pdf.set_language(args.lang.as_ref().unwrap())?;
+            }
             "add_link" => {
                 let link = ali.next().unwrap();
                 let page_str = ali.next().unwrap();
@@ -110,18 +1607,96 @@ fn main() -> anyhow::Result<()> {
                 pdf.add_link(&link, page)?;
             }
             "add_page" => {
-                for file in api.nextn(argc) {
-                    let bytes = std::fs::read(file)?;
-                    let _ = pdf.add_image(&bytes)?;
+                let mut files = Vec::new();
+                for arg in api.nextn(argc) {
+                    files.extend(input::expand_images(&arg)?);
+                }
+
+                let bar = progress_bar(files.len(), hide_progress);
+                bar.set_message("add_page");
+                let first = pdf.page_count() as u32 + 1;
+
+                if let Some(grid) = args.grid {
+                    let mut images = Vec::new();
+                    let mut alts = Vec::new();
+                    for file in files {
+                        let frames = read_images(&file, pdf.limits.max_input_bytes)?;
+                        let alt = if args.alt_text_sidecar {
+                            read_sidecar(&file)?
+                        } else {
+                            None
+                        };
+                        alts.extend(std::iter::repeat(alt).take(frames.len()));
+                        images.extend(frames);
+                        bar.inc(1);
+                    }
+                    let pages = pdf.add_image_grid(&images, grid)?;
+
+                    // page of grid is tagged as one figure described by texts of its images
+                    let per_page = (grid.columns * grid.rows) as usize;
+                    for (page, alts) in (first..).zip(alts.chunks(per_page)).take(pages.len()) {
+                        let alt: Vec<&str> = alts.iter().flatten().map(String::as_str).collect();
+                        if !alt.is_empty() {
+                            pdf.tag_figure(page, &alt.join("\n"))?;
+                        }
+                    }
+                } else {
+                    for file in files {
+                        log::info!("adding {}", file.display());
+                        let start = pdf.page_count() as u32 + 1;
+                        for (_, bytes) in read_images(&file, pdf.limits.max_input_bytes)? {
+                            let _ = pdf.add_image(&bytes)?;
+                        }
+
+                        let alt = if args.alt_text_sidecar {
+                            read_sidecar(&file)?
+                        } else {
+                            None
+                        };
+                        if let Some(alt) = alt {
+                            for page in start..=pdf.page_count() as u32 {
+                                pdf.tag_figure(page, &alt)?;
+                            }
+                        }
+                        bar.inc(1);
+                    }
+                }
+                bar.finish_and_clear();
+                added_pages = (first..=pdf.page_count() as u32).collect();
+            }
+            "append" => {
+                for file in appi.nextn(argc) {
+                    let other = Pdf::load(&file)
+                        .with_context(|| format!("Failed to load {}", file.display()))?;
+                    let label = bli.next().or_else(|| {
+                        let name = file.file_stem()?.to_string_lossy().into_owned();
+                        file_bookmarks.then_some(name)
+                    });
+                    let _ = pdf.append_document(&other, label.as_deref(), args.pad_even)?;
+                }
+            }
+            "alt_text" => {
+                let texts = alti.nextn(argc);
+                anyhow::ensure!(!added_pages.is_empty(), "--alt-text must follow --add-page");
+                anyhow::ensure!(
+                    texts.len() == added_pages.len(),
+                    "--alt-text got {} texts for {} pages added by preceding --add-page",
+                    texts.len(),
+                    added_pages.len()
+                );
+                for (&page, text) in added_pages.iter().zip(&texts) {
+                    pdf.tag_figure(page, text)?;
                 }
             }
             "remove_link" => {
-                for page in rli.nextn(argc) {
+                for page in page_set(&rli.nextn(argc), &pdf)? {
                     pdf.remove_link(page)?;
                 }
             }
             "remove_page" => {
-                pdf.remove_pages(&rpi.nextn(argc));
+                pdf.remove_pages(&page_set(&rpi.nextn(argc), &pdf)?)?;
+                // numbers of added pages are no longer valid once page list changes
+                added_pages.clear();
             }
             "move_link" => {
                 let from = mli.next().unwrap();
@@ -134,15 +1709,413 @@ fn main() -> anyhow::Result<()> {
                 let to: usize = mpi.next().unwrap().try_into()?;
 
                 pdf.move_page(from, to)?;
+                added_pages.clear();
+            }
+            "order" => {
+                let order = page_order(&ori.next().unwrap(), &pdf)?;
+                pdf.reorder_pages(&order)?;
+                added_pages.clear();
             }
             "prune" => {
                 pdf.prune();
             }
+            "add_bookmark" => {
+                let title = abi.next().unwrap();
+                let (page, dest) = parse_page_dest(&abi.next().unwrap(), op)?;
+
+                pdf.add_bookmark(&title, page, &dest, None, &BookmarkStyle::default())?;
+            }
+            "add_goto_link" => {
+                let page_str = agi.next().unwrap();
+                let page: u32 = page_str.parse().with_context(|| {
+                    format!("Invalid argument {} found in option \"{}\"", page_str, op)
+                })?;
+                let (target, dest) = parse_page_dest(&agi.next().unwrap(), op)?;
+
+                pdf.add_goto_link(page, target, &dest)?;
+            }
+            "collapse_outline_depth" => {
+                pdf.collapse_outline(args.collapse_outline_depth.unwrap())?;
+            }
+            "open_at" => {
+                let (page, dest) = parse_page_dest(args.open_at.as_ref().unwrap(), op)?;
+                pdf.set_open_destination(page, &dest)?;
+            }
+            "page_layout" => {
+                pdf.set_page_layout(args.page_layout.unwrap())?;
+            }
+            "page_mode" => {
+                pdf.set_page_mode(args.page_mode.unwrap())?;
+            }
+            "viewer_pref" => {
+                for preference in vpi.nextn(argc) {
+                    pdf.set_viewer_preference(preference, true)?;
+                }
+            }
+            "sanitize" => {
+                pdf.strip_metadata()?;
+            }
+            "strip_actions" => {
+                pdf.strip_actions()?;
+            }
+            "regenerate_appearances" => {
+                pdf.regenerate_appearances()?;
+            }
+            "flatten_annotations" => {
+                for page in page_set(&fai.nextn(argc), &pdf)? {
+                    pdf.flatten_annotations(page)?;
+                }
+            }
+            "flatten_form" => {
+                pdf.flatten_form()?;
+            }
+            "stamp_image" => {
+                let page = sii.next().unwrap();
+                let file = sii.next().unwrap();
+                let position = sii.next().unwrap();
+                let invalid =
+                    |arg: &str| format!("Invalid argument {} found in option \"{}\"", arg, op);
+
+                let page: u32 = page.parse().with_context(|| invalid(&page))?;
+                let values = position
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<Vec<f32>, _>>()
+                    .with_context(|| invalid(&position))?;
+                let (x, y, scale) = match values[..] {
+                    [x, y] => (x, y, 1.0),
+                    [x, y, scale] => (x, y, scale),
+                    _ => anyhow::bail!(invalid(&position)),
+                };
+
+                pdf.stamp_image(page, &std::fs::read(file)?, (x, y), scale)?;
+            }
+            "background" => {
+                let other = Pdf::load(bgi.next().unwrap())?;
+                pdf.overlay(&other, Layer::Underlay)?;
+            }
+            "overlay" => {
+                let other = Pdf::load(ovi.next().unwrap())?;
+                pdf.overlay(&other, Layer::Overlay)?;
+            }
+            "pages" => {
+                selection = pgi.next().unwrap();
+            }
+            "page_numbers" => {
+                let style = PageNumberStyle {
+                    format: pni.next().unwrap(),
+                    prefix: args.page_number_prefix.clone(),
+                    start: args.page_number_start,
+                    position: args.page_number_position,
+                    font_size: args.page_number_size,
+                    font: args.font.clone(),
+                    locale: args.locale,
+                    pages: selection.clone(),
+                    ..Default::default()
+                };
+
+                pdf.stamp_page_numbers(&style)?;
+            }
+            "barcode" => {
+                let symbology: Symbology = bci.next().unwrap().parse()?;
+                let barcode = Barcode::new(symbology, &bci.next().unwrap())?;
+                let style = BarcodeStyle {
+                    position: args.barcode_position,
+                    height: args.barcode_height,
+                    font: args.font.clone(),
+                    pages: selection.clone(),
+                    ..Default::default()
+                };
+
+                pdf.stamp_barcode(&barcode, &style)?;
+            }
+            "auto_crop" => {
+                let count = pdf.auto_crop(&selection, aci.next().unwrap())?;
+                log::info!("auto-cropped {} pages", count);
+            }
+            "transition" => {
+                let style = tri.next().unwrap();
+                for page in selection.page_set(pdf.page_count() as u32)? {
+                    pdf.set_transition(page, style, args.transition_duration)?;
+                }
+            }
+            "header" => {
+                let style = TextStamp {
+                    file_name: file_name.clone(),
+                    font: args.font.clone(),
+                    locale: args.locale,
+                    pages: selection.clone(),
+                    ..TextStamp::header()
+                };
+                pdf.stamp_text(&hdi.next().unwrap(), &style)?;
+            }
+            "footer" => {
+                let style = TextStamp {
+                    file_name: file_name.clone(),
+                    font: args.font.clone(),
+                    locale: args.locale,
+                    pages: selection.clone(),
+                    ..TextStamp::footer()
+                };
+                pdf.stamp_text(&fti.next().unwrap(), &style)?;
+            }
+            "attach" => {
+                for file in ati.nextn(argc) {
+                    let name = file
+                        .file_name()
+                        .with_context(|| {
+                            format!("Invalid file {} found in option \"{}\"", file.display(), op)
+                        })?
+                        .to_string_lossy();
+                    pdf.add_attachment(&name, &std::fs::read(&file)?)?;
+                }
+            }
+            "list_attachments" => {
+                for attachment in pdf.attachments()? {
+                    writeln!(
+                        report_out,
+                        "{}\t{}\t{}",
+                        attachment.file_name,
+                        attachment.mime_type.as_deref().unwrap_or("-"),
+                        attachment.data.len()
+                    )?;
+                }
+            }
+            "export_thumbnail" => {
+                let page_str = eti.next().unwrap();
+                let file = eti.next().unwrap();
+                let page: u32 = page_str.parse().with_context(|| {
+                    format!("Invalid argument {} found in option \"{}\"", page_str, op)
+                })?;
+
+                pdf.export_thumbnail(page, &file, args.thumbnail_max)?;
+            }
+            "flatten_page_tree" => {
+                pdf.flatten_page_tree()?;
+            }
+            "rebalance_page_tree" => {
+                pdf.rebalance_page_tree(args.rebalance_page_tree.unwrap())?;
+            }
+            "generate_thumbnails" => {
+                pdf.generate_thumbnails(args.generate_thumbnails.unwrap())?;
+            }
+            "review" => {
+                let items = pdf.review_scans()?;
+                let report: String = items.iter().map(|x| format!("{}\n", x)).collect();
+
+                match &args.review_report {
+                    Some(path) => std::fs::write(path, report)?,
+                    None => report_out.write_all(report.as_bytes())?,
+                }
+                if args.review_bookmarks {
+                    pdf.add_review_bookmarks(&items)?;
+                }
+            }
+            "show_provenance" => match pdf.provenance()? {
+                Some(provenance) => {
+                    writeln!(report_out, "version: {}", provenance.version)?;
+                    if let Some(name) = provenance.manifest_name {
+                        writeln!(report_out, "manifest: {}", name)?;
+                    }
+                    if let Some(manifest) = provenance.manifest {
+                        writeln!(report_out, "{}", String::from_utf8_lossy(&manifest))?;
+                    }
+                }
+                None => writeln!(report_out, "no provenance recorded")?,
+            },
+            "preflight" => {
+                for violation in pdf.preflight(pfi.next().unwrap())? {
+                    // violations are reported in summary instead with --json
+                    match &mut summary {
+                        Some(summary) => summary.warning(&violation.to_string()),
+                        None => writeln!(report_out, "{}", violation)?,
+                    }
+
+                    if let Some(sink) = events {
+                        sink.warning(&violation.to_string())?;
+                    }
+                }
+            }
+            "op" => {
+                let mut values = ooi.nextn(argc).into_iter();
+                let name = values.next().unwrap();
+                let values: Vec<String> = values.collect();
+
+                registry.run(&mut pdf, &name, &values)?;
+                // operations of plugins may change page list
+                added_pages.clear();
+            }
+            "extract_attachments" => {
+                for path in pdf.extract_attachments(args.extract_attachments.as_ref().unwrap())? {
+                    eprintln!("extracted {}", path.display());
+                }
+            }
+            "optimize" => match opi.next().unwrap() {
+                Optimization::Jpeg { quality, lossless } => {
+                    for saving in pdf.recompress_jpeg(quality, lossless)? {
+                        eprintln!(
+                            "image {} {}: {} -> {} bytes ({} saved)",
+                            saving.id.0,
+                            saving.id.1,
+                            saving.before,
+                            saving.after,
+                            saving.before - saving.after
+                        );
+                    }
+                }
+                optimization => pdf.optimize(&optimization)?,
+            },
             _ => {}
         }
+        pdf.check_limits()?;
+
+        if let Some(sink) = events {
+            sink.finish(&pdf)?;
+        }
+        if let Some(summary) = &mut summary {
+            summary.finish(&pdf);
+        }
+    }
+
+    for optimization in &default_optimize {
+        pdf.optimize(optimization)?;
+    }
+
+    if let Some(size) = args.pad_even {
+        pdf.pad_even(size)?;
+    }
+
+    if let Some(manifest) = &args.provenance {
+        let data = match manifest {
+            Some(path) => Some(std::fs::read(path)?),
+            None => None,
+        };
+        let name = manifest
+            .as_ref()
+            .and_then(|x| x.file_name())
+            .map(|x| x.to_string_lossy().into_owned());
+
+        pdf.embed_provenance(name.as_deref().zip(data.as_deref()))?;
+    }
+
+    if let Some(profile) = profile {
+        if let Some(sink) = events {
+            sink.start("profile", &pdf)?;
+        }
+
+        if let Some(summary) = &mut summary {
+            summary.start("profile", &pdf);
+        }
+
+        pdf.apply_profile(profile)?;
+
+        if let Some(sink) = events {
+            sink.finish(&pdf)?;
+        }
+        if let Some(summary) = &mut summary {
+            summary.finish(&pdf);
+        }
+    }
+
+    let pages = pdf.page_count();
+    let data = if args.incremental {
+        pdf.to_bytes_incremental()?
+    } else {
+        let defaults = profile.map(|x| x.save_options()).unwrap_or_default();
+        // object streams of profile are dropped for versions lacking them, while those
+        // asked for explicitly are refused
+        let profile_object_streams =
+            defaults.object_streams && args.pdf_version.map_or(true, |x| x >= PdfVersion::V1_5);
+        let options = SaveOptions {
+            object_streams: args.object_streams || config.object_streams || profile_object_streams,
+            attachment_password: args.encrypt_attachments,
+            deterministic: args.deterministic.is_some(),
+            version: args.pdf_version,
+        };
+        pdf.to_bytes_with_options(&options)?
+    };
+
+    if to_stdout {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    } else {
+        std::fs::write(&output, &data)?;
+    }
+
+    if let Some(sink) = events {
+        let bytes = data.len() as u64;
+        sink.emit(
+            "save",
+            serde_json::json!({
+                "path": output.display().to_string(),
+                "pages": pages,
+                "bytes": bytes,
+                "bytes_delta": bytes as i64 - input_bytes as i64,
+            }),
+        )?;
     }
 
-    pdf.save(output)?;
+    if let Some(summary) = &summary {
+        let result = summary.to_json(serde_json::json!({
+            "output": output.display().to_string(),
+            "pages": pages,
+            "bytes": data.len(),
+        }));
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    }
+
+    if let Some(algo) = args.emit_checksums {
+        let entry = checksum::Entry::from_bytes(&output, &data, pages, algo);
+        checksum::write_manifest(&output, algo, &[entry])?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("123"), Ok(123));
+        assert_eq!(parse_size("500k"), Ok(500_000));
+        assert_eq!(parse_size("10MB"), Ok(10_000_000));
+        assert_eq!(parse_size("1.5 GB"), Ok(1_500_000_000));
+        assert_eq!(parse_size("2MiB"), Ok(2 << 20));
+        assert_eq!(parse_size("4kib"), Ok(4096));
+    }
+
+    #[test]
+    fn parse_size_rejects_invalid_sizes() {
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10XB").is_err());
+        assert!(parse_size("1.2.3k").is_err());
+    }
+
+    #[test]
+    fn parse_rect_normalizes_corners() {
+        assert_eq!(parse_rect("10, 20,5,0"), Ok([5.0, 0.0, 10.0, 20.0]));
+        assert!(parse_rect("1,2,3").is_err());
+        assert!(parse_rect("1,2,3,x").is_err());
+    }
+
+    #[test]
+    fn parse_seconds_range() {
+        assert_eq!(parse_seconds("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("1e20").is_err());
+        assert!(parse_seconds("NaN").is_err());
+    }
+
+    #[test]
+    fn parse_timestamp_epoch_or_rfc3339() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+        assert_eq!(parse_timestamp("1700000000").unwrap(), time);
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20Z").unwrap(), time);
+        assert_eq!(parse_timestamp("2023-11-15T07:13:20+09:00").unwrap(), time);
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+    annotations::{LinkAnnotation, LinkTarget},
+    destination::Destination,
+    input,
+    operation::Registry,
+    Pdf,
+};
+
+/// Batch of documents to build, read from TOML file like
+///
+/// ```toml
+/// [[output]]
+/// path = "book.pdf"
+/// input = "cover.pdf"
+/// pages = ["scans/", "appendix/*.png"]
+///
+/// [output.metadata]
+/// Title = "Book"
+/// Author = "Me"
+///
+/// [[output.links]]
+/// page = 1
+/// uri = "https://example.com"
+///
+/// [[output.links]]
+/// page = 2
+/// target = "10:fith:792"
+/// rect = [0, 0, 100, 50]
+///
+/// [[output.operations]]
+/// name = "flatten-annotations"
+/// args = ["1-"]
+/// ```
+///
+/// relative paths are resolved from directory of manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<Target>,
+    /// Directory which relative paths are resolved from
+    #[serde(skip)]
+    pub base: PathBuf,
+}
+
+/// One output document of manifest
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// File to write document to
+    pub path: PathBuf,
+    /// Document to start from. new document is made if not set.
+    pub input: Option<PathBuf>,
+    /// Image files appended as pages, expanded like `-p` (directory, glob or ZIP archive)
+    #[serde(default)]
+    pub pages: Vec<String>,
+    /// Entries of document information dictionary, like Title and Author
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Links added after pages
+    #[serde(default)]
+    pub links: Vec<Link>,
+    /// Operations of registry run last, in order
+    #[serde(default)]
+    pub operations: Vec<OperationCall>,
+}
+
+/// Operation of `Registry` run on document, like `--op NAME ARGS...`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationCall {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Link of manifest, with either URI or target page
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    /// Page which link is on
+    pub page: u32,
+    /// URI to open
+    pub uri: Option<String>,
+    /// Page to jump to, optionally followed by destination like 3:xyz:0,792,2
+    pub target: Option<String>,
+    /// Clickable area as [x0, y0, x1, y1]. whole page if not set.
+    pub rect: Option<[f32; 4]>,
+}
+
+impl Manifest {
+    /// Read manifest from TOML file at PATH
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut manifest: Self = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+        manifest.base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        Ok(manifest)
+    }
+
+    /// Resolve PATH relative to directory of manifest
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.base.join(path)
+    }
+
+    /// Build document of TARGET, with operations built into pdftool
+    pub fn build(&self, target: &Target) -> anyhow::Result<Pdf> {
+        self.build_with(target, &Registry::with_builtins())
+    }
+
+    /// Build document of TARGET, running its operations from REGISTRY, which may have
+    /// operations registered by downstream crates
+    pub fn build_with(&self, target: &Target, registry: &Registry) -> anyhow::Result<Pdf> {
+        let mut pdf = match &target.input {
+            Some(input) => Pdf::load(self.resolve(input))?,
+            None => Pdf::new(),
+        };
+
+        for arg in &target.pages {
+            let arg = self.resolve(Path::new(arg));
+            for file in input::expand_images(&arg.to_string_lossy())? {
+                log::info!("adding {}", file.display());
+                let bytes = std::fs::read(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+
+                if input::is_zip(&bytes) {
+                    for (_, bytes) in input::zip_images(&bytes, pdf.limits.max_input_bytes)? {
+                        pdf.add_image(&bytes)?;
+                    }
+                } else {
+                    pdf.add_image(&bytes)?;
+                }
+            }
+        }
+
+        for (key, value) in &target.metadata {
+            pdf.set_info(key, value)?;
+        }
+
+        for link in &target.links {
+            add_link(&mut pdf, link)
+                .with_context(|| format!("Failed to add link on page {}", link.page))?;
+        }
+
+        for operation in &target.operations {
+            registry
+                .run(&mut pdf, &operation.name, &operation.args)
+                .with_context(|| format!("Failed to run operation \"{}\"", operation.name))?;
+        }
+
+        Ok(pdf)
+    }
+}
+
+fn add_link(pdf: &mut Pdf, link: &Link) -> anyhow::Result<()> {
+    let target = match (&link.uri, &link.target) {
+        (Some(uri), None) => LinkTarget::Uri(uri.clone()),
+        (None, Some(target)) => {
+            let (page, dest) = target.split_once(':').unwrap_or((target, "fit"));
+            let page: u32 = page
+                .parse()
+                .with_context(|| format!("Invalid target {}", target))?;
+            let dest: Destination = dest.parse()?;
+            LinkTarget::Dest(dest.to_object(pdf.page(page)?.id()))
+        }
+        _ => anyhow::bail!("link must have either uri or target"),
+    };
+
+    let rect = match link.rect {
+        Some(rect) => rect,
+        None => pdf.page(link.page)?.media_box(),
+    };
+
+    pdf.add_annotation(link.page, &LinkAnnotation { rect, target }.into())?;
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use lopdf::{Object, ObjectId};
+
+use crate::{
+    attributes::inherited_attribute, destination::Destination, outline::BookmarkStyle,
+    overlay::import_object, paper::PageSize, pdf::decode_text_string, pdf::text_string,
+    permissions::Permission, Pdf,
+};
+
+/// Attributes pages may inherit from page tree, which is not copied along with them
+const INHERITED: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+impl Pdf {
+    /// Append pages of OTHER after last page, along with its outline and form fields.
+    /// with BOOKMARK, top-level bookmark of that title to first appended page is added,
+    /// and outline of OTHER is moved beneath it. with PAD_EVEN, document is padded by
+    /// `pad_even` with that size first, so OTHER starts on front side in duplex printing.
+    /// appended fields whose name is already used get suffix like "_2".
+    /// returns numbers of appended pages.
+    pub fn append_document(
+        &mut self,
+        other: &Pdf,
+        bookmark: Option<&str>,
+        pad_even: Option<Option<PageSize>>,
+    ) -> anyhow::Result<Vec<u32>> {
+        self.check_permission(Permission::Assemble)?;
+        other.check_permission(Permission::Assemble)?;
+
+        let other_pages: Vec<ObjectId> = other.doc.get_pages().into_values().collect();
+        anyhow::ensure!(!other_pages.is_empty(), "appended document has no pages");
+
+        if let Some(size) = pad_even {
+            if self.pad_even(size)? {
+                log::info!("padded document to even page count before appending");
+            }
+        }
+
+        let first = self.page_count() as u32 + 1;
+        // shared by all pages, so that links between them point to copies
+        let mut map = BTreeMap::new();
+
+        for page_id in other_pages {
+            self.check_deadline()?;
+
+            let new_id = import_object(
+                &mut self.doc,
+                &other.doc,
+                &Object::Reference(page_id),
+                &mut map,
+            )?
+            .as_reference()?;
+
+            let own = other.doc.get_dictionary(page_id)?;
+            let mut inherited = Vec::new();
+            for key in INHERITED {
+                if own.has(key) {
+                    continue;
+                }
+                if let Some(value) = inherited_attribute(&other.doc, page_id, key) {
+                    inherited.push((
+                        key,
+                        import_object(&mut self.doc, &other.doc, value, &mut map)?,
+                    ));
+                }
+            }
+
+            let pages_id = self.pages_id;
+            let page = self.doc.get_dictionary_mut(new_id)?;
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            page.set("Parent", pages_id);
+            self.push_page(new_id)?;
+        }
+        let last = self.page_count() as u32;
+
+        let parent = match bookmark {
+            Some(title) => Some(self.add_bookmark(
+                title,
+                first,
+                &Destination::Fit,
+                None,
+                &BookmarkStyle::default(),
+            )?),
+            None => None,
+        };
+        self.import_outline(&other.doc, parent, &mut map)?;
+
+        // widgets are copied along with pages, but their fields are reached from form only
+        let other_fields = other.form_fields();
+        if !other_fields.is_empty() {
+            let form_id = self.get_or_create_acro_form()?;
+            let own_fields = self.form_fields();
+            let mut names: BTreeSet<String> = own_fields
+                .iter()
+                .filter_map(|&id| self.field_name(id))
+                .collect();
+            let mut fields: Vec<Object> = own_fields.into_iter().map(Object::from).collect();
+            for id in other_fields {
+                let field = Object::Reference(id);
+                let field = import_object(&mut self.doc, &other.doc, &field, &mut map)?;
+                if let Ok(new_id) = field.as_reference() {
+                    self.rename_colliding_field(new_id, &mut names)?;
+                }
+                fields.push(field);
+            }
+            self.doc.get_dictionary_mut(form_id)?.set("Fields", fields);
+        }
+
+        log::info!("appended pages {} to {}", first, last);
+        Ok((first..=last).collect())
+    }
+
+    /// Get partial name /T of field ID
+    fn field_name(&self, id: ObjectId) -> Option<String> {
+        let name = self.doc.get_dictionary(id).ok()?.get(b"T").ok()?;
+        Some(decode_text_string(name.as_str().ok()?))
+    }
+
+    /// Rename root field ID appended from other document, if its name is already in
+    /// NAMES, by adding suffix like "_2", so that both fields keep their own value
+    fn rename_colliding_field(
+        &mut self,
+        id: ObjectId,
+        names: &mut BTreeSet<String>,
+    ) -> anyhow::Result<()> {
+        let Some(name) = self.field_name(id) else {
+            return Ok(());
+        };
+        if names.insert(name.clone()) {
+            return Ok(());
+        }
+
+        let renamed = (2..)
+            .map(|n| format!("{}_{}", name, n))
+            .find(|x| !names.contains(x))
+            .unwrap();
+        log::warn!("renamed appended form field {} to {}", name, renamed);
+        self.doc
+            .get_dictionary_mut(id)?
+            .set("T", text_string(&renamed));
+        names.insert(renamed);
+        Ok(())
+    }
+
+    /// Interleave pages of OTHER with pages of document, so that page N of document is
+    /// followed by page N of OTHER, or by page N from last of OTHER with REVERSE. this
+    /// restores double-sided document scanned as front sides then back sides, which
+    /// come in reverse order when stack is flipped over.
+    pub fn interleave(&mut self, other: &Pdf, reverse: bool) -> anyhow::Result<()> {
+        let count = self.page_count() as u32;
+        let other_count = other.page_count() as u32;
+        if count != other_count && count != other_count + 1 {
+            log::warn!(
+                "{} front pages and {} back pages, remaining ones are put at end",
+                count,
+                other_count
+            );
+        }
+
+        let _ = self.append_document(other, None, None)?;
+
+        // appended pages of OTHER follow those of document
+        let back_page = |i: u32| {
+            if reverse {
+                count + other_count - i
+            } else {
+                count + 1 + i
+            }
+        };
+        let order: Vec<u32> = (0..count.max(other_count))
+            .flat_map(|i| {
+                let front = (i < count).then_some(i + 1);
+                let back = (i < other_count).then(|| back_page(i));
+                front.into_iter().chain(back)
+            })
+            .collect();
+
+        self.reorder_pages(&order)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::sample_document;
+
+    #[test]
+    fn append_document_renames_colliding_fields() {
+        let mut pdf = sample_document(1, 200, 200).unwrap();
+        pdf.add_text_field(1, "name", [10.0, 10.0, 100.0, 30.0])
+            .unwrap();
+        let mut other = sample_document(1, 200, 200).unwrap();
+        other
+            .add_text_field(1, "name", [10.0, 10.0, 100.0, 30.0])
+            .unwrap();
+
+        pdf.append_document(&other, None, None).unwrap();
+
+        let names: Vec<String> = pdf.form_field_names().into_iter().map(|x| x.0).collect();
+        assert_eq!(names, ["name", "name_2"]);
+    }
+}
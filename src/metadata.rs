@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+
+use chrono::{offset::Utc, SecondsFormat};
+use lopdf::{Dictionary, Object};
+
+use crate::{
+    error::Error,
+    pdf::{decode_text_string, text_string},
+    permissions::Permission,
+    Pdf,
+};
+
+/// Replace predefined XML entities and character references like &#233; or &#xE9; in
+/// TEXT. unknown or malformed references are kept as is.
+pub(crate) fn unescape_xml(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('&') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let decoded = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "amp" => Some('&'),
+            name => {
+                let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => name.strip_prefix('#').and_then(|x| x.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        };
+
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Find value of property NAME in XMP packet, written either as element or attribute.
+/// NAME is local name with any prefix, or qualified name like pdfaid:part to match that
+/// prefix only. for arrays (rdf:Alt, rdf:Seq, rdf:Bag), first item is returned.
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let (prefix, name) = match name.split_once(':') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, name),
+    };
+    let mut rest = xmp;
+
+    while let Some(pos) = rest.find(&format!(":{}", name)) {
+        let after = &rest[pos + name.len() + 1..];
+        let before = &rest[..pos];
+        rest = after;
+
+        // name must be whole local name, preceded by prefix
+        let prefix_ok = match prefix {
+            Some(prefix) => before.strip_suffix(prefix).map_or(false, |x| {
+                x.ends_with(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            }),
+            None => before
+                .chars()
+                .next_back()
+                .map_or(false, |c| c.is_alphanumeric()),
+        };
+        if !prefix_ok {
+            continue;
+        }
+
+        if let Some(value) = after.strip_prefix("=\"") {
+            let end = value.find('"')?;
+            return Some(unescape_xml(&value[..end]));
+        }
+
+        let is_element = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .map_or(false, |i| before[i..].starts_with('<'));
+        if !is_element || !after.starts_with(|c| c == '>' || c == ' ') {
+            continue;
+        }
+
+        let content = &after[after.find('>')? + 1..];
+        let content = match content.trim_start().strip_prefix("<rdf:") {
+            Some(array) => {
+                let item = &array[array.find("<rdf:li")?..];
+                &item[item.find('>')? + 1..]
+            }
+            None => content,
+        };
+
+        let end = content.find('<')?;
+        return Some(unescape_xml(content[..end].trim()));
+    }
+
+    None
+}
+
+/// Set property NAME (qualified like "xmp:ModifyDate") of NAMESPACE in XMP packet to
+/// VALUE, written either as element or attribute. missing property is added as attribute
+/// of first rdf:Description, and XMP is left unchanged if it has none.
+fn set_xmp_property(xmp: &str, name: &str, namespace: &str, value: &str) -> String {
+    let open = format!("<{}>", name);
+    if let Some(start) = xmp.find(&open).map(|i| i + open.len()) {
+        if let Some(end) = xmp[start..].find(&format!("</{}>", name)) {
+            return [&xmp[..start], value, &xmp[start + end..]].concat();
+        }
+    }
+
+    for quote in ['"', '\''] {
+        let key = format!("{}={}", name, quote);
+        if let Some(start) = xmp.find(&key).map(|i| i + key.len()) {
+            if let Some(end) = xmp[start..].find(quote) {
+                return [&xmp[..start], value, &xmp[start + end..]].concat();
+            }
+        }
+    }
+
+    let Some(start) = xmp.find("<rdf:Description") else {
+        return xmp.to_string();
+    };
+    let start = start + "<rdf:Description".len();
+    let tag_end = xmp[start..].find('>').map_or(xmp.len(), |i| start + i);
+    let prefix = name.split(':').next().unwrap_or_default();
+
+    let mut attributes = format!(" {}=\"{}\"", name, value);
+    if !xmp[start..tag_end].contains(&format!("xmlns:{}=", prefix)) {
+        attributes.push_str(&format!(" xmlns:{}=\"{}\"", prefix, namespace));
+    }
+
+    [&xmp[..start], &attributes, &xmp[start..]].concat()
+}
+
+impl Pdf {
+    pub(crate) fn info_dict(&self) -> Option<&Dictionary> {
+        match self.doc.trailer.get(b"Info").ok()? {
+            Object::Reference(id) => self.doc.get_dictionary(*id).ok(),
+            Object::Dictionary(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Get document information dictionary, creating it if document has none and
+    /// moving it into its own object if it is direct
+    pub(crate) fn info_dict_mut(&mut self) -> crate::Result<&mut Dictionary> {
+        let id = match self.doc.trailer.get(b"Info").ok().cloned() {
+            Some(Object::Reference(id)) if self.doc.get_dictionary(id).is_ok() => id,
+            Some(Object::Dictionary(dict)) => self.doc.add_object(dict),
+            _ => self.doc.add_object(Dictionary::new()),
+        };
+        self.doc.trailer.set("Info", id);
+
+        Ok(self.doc.get_dictionary_mut(id)?)
+    }
+
+    /// Record current time as modification date in document information and XMP
+    /// metadata, if document was loaded and has been changed since. missing ones are
+    /// not created, so that document stripped of metadata stays without it.
+    pub(crate) fn update_mod_date(&mut self) -> crate::Result<()> {
+        let is_modified = match &mut self.original {
+            Some(original) => original.is_modified(&self.doc),
+            None => false,
+        };
+        if !is_modified {
+            return Ok(());
+        }
+
+        let now = self.now();
+        if self.info_dict().is_some() {
+            self.info_dict_mut()?.set("ModDate", now);
+        }
+
+        let Some(xmp) = self.xmp() else {
+            return Ok(());
+        };
+        let date = now.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let xmp_ns = "http://ns.adobe.com/xap/1.0/";
+        let xmp = set_xmp_property(&xmp, "xmp:ModifyDate", xmp_ns, &date);
+        let xmp = set_xmp_property(&xmp, "xmp:MetadataDate", xmp_ns, &date);
+
+        let id = self
+            .doc
+            .catalog()?
+            .get(b"Metadata")
+            .and_then(Object::as_reference)?;
+        let stream = self
+            .doc
+            .get_object_mut(id)
+            .and_then(Object::as_stream_mut)?;
+        // XMP is kept uncompressed, so that it can be read without PDF parser
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(xmp.into_bytes());
+
+        log::info!("updated modification date to {}", date);
+        Ok(())
+    }
+
+    /// Get text entry KEY of document information dictionary
+    pub fn info(&self, key: &str) -> Option<String> {
+        let value = self.info_dict()?.get(key.as_bytes()).ok()?;
+        let value = match value {
+            Object::Reference(id) => self.doc.get_object(*id).ok()?,
+            _ => value,
+        };
+
+        value.as_str().ok().map(decode_text_string)
+    }
+
+    /// Set text entry KEY of document information dictionary to VALUE
+    pub fn set_info(&mut self, key: &str, value: &str) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        self.info_dict_mut()?.set(key, text_string(value));
+
+        Ok(())
+    }
+
+    /// Get natural language of document (/Lang of catalog) like "ja-JP"
+    pub fn language(&self) -> Option<String> {
+        let value = self.doc.catalog().ok()?.get(b"Lang").ok()?;
+        value.as_str().ok().map(decode_text_string)
+    }
+
+    /// Set natural language of document to language tag LANG like "ja-JP" (BCP 47),
+    /// which screen readers use to pronounce text and accessibility checkers require
+    pub fn set_language(&mut self, lang: &str) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        if lang.is_empty() || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(Error::InvalidArgument(format!(
+                "invalid language tag: {}",
+                lang
+            )));
+        }
+
+        self.catalog_mut()?.set("Lang", text_string(lang));
+
+        Ok(())
+    }
+
+    /// Get text entries of document information dictionary, both standard ones like
+    /// Title and custom ones like Department
+    pub fn info_entries(&self) -> BTreeMap<String, String> {
+        let Some(info) = self.info_dict() else {
+            return BTreeMap::new();
+        };
+
+        info.iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    Object::Reference(id) => self.doc.get_object(*id).ok()?,
+                    _ => value,
+                };
+                let value = decode_text_string(value.as_str().ok()?);
+                Some((String::from_utf8_lossy(key).into_owned(), value))
+            })
+            .collect()
+    }
+
+    /// Set custom text entry KEY of document information dictionary, like Department,
+    /// to VALUE. empty VALUE removes entry. dates and Trapped, which are not text, can't
+    /// be set this way.
+    pub fn set_info_entry(&mut self, key: &str, value: &str) -> crate::Result<()> {
+        let is_name = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(c));
+        if !is_name || matches!(key, "CreationDate" | "ModDate" | "Trapped") {
+            return Err(Error::InvalidArgument(format!(
+                "invalid Info entry key: {}",
+                key
+            )));
+        }
+
+        if value.is_empty() {
+            self.check_permission(Permission::Modify)?;
+            self.info_dict_mut()?.remove(key.as_bytes());
+            return Ok(());
+        }
+
+        self.set_info(key, value)
+    }
+
+    /// Get XMP metadata packet of document
+    pub fn xmp(&self) -> Option<String> {
+        let id = self
+            .doc
+            .catalog()
+            .ok()?
+            .get(b"Metadata")
+            .and_then(Object::as_reference)
+            .ok()?;
+        let stream = self.doc.get_object(id).and_then(Object::as_stream).ok()?;
+
+        let data = if stream.dict.has(b"Filter") {
+            stream.decompressed_content().ok()?
+        } else {
+            stream.content.clone()
+        };
+
+        Some(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// Get XMP property with local NAME, such as "title" or "Department", or qualified
+    /// name like "pdfaid:part"
+    pub fn xmp_value(&self, name: &str) -> Option<String> {
+        xmp_property(&self.xmp()?, name)
+    }
+
+    /// Resolve metadata template variable NAME: standard names like title and author
+    /// look up Info and then XMP, and meta:KEY looks up custom Info entry and then XMP.
+    pub(crate) fn metadata_var(&self, name: &str) -> Option<String> {
+        if let Some(key) = name.strip_prefix("meta:") {
+            return self.info(key).or_else(|| self.xmp_value(key));
+        }
+
+        let (info_key, xmp_name) = match name {
+            "title" => ("Title", "title"),
+            "author" => ("Author", "creator"),
+            "subject" => ("Subject", "description"),
+            "keywords" => ("Keywords", "Keywords"),
+            "creator" => ("Creator", "CreatorTool"),
+            "producer" => ("Producer", "Producer"),
+            _ => return None,
+        };
+
+        self.info(info_key)
+            .filter(|x| !x.is_empty())
+            .or_else(|| self.xmp_value(xmp_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xmp_property_matches_prefix_of_qualified_name() {
+        let xmp = r#"<rdf:Description xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/"
+            xmp:part="9" pdfaid:part="2"><pdfaid:conformance>U</pdfaid:conformance>
+            </rdf:Description>"#;
+
+        assert_eq!(xmp_property(xmp, "pdfaid:part").as_deref(), Some("2"));
+        assert_eq!(xmp_property(xmp, "part").as_deref(), Some("9"));
+        assert_eq!(
+            xmp_property(xmp, "pdfaid:conformance").as_deref(),
+            Some("U")
+        );
+        assert_eq!(xmp_property(xmp, "xpdfaid:part"), None);
+    }
+
+    #[test]
+    fn set_xmp_property_replaces_element_or_attribute() {
+        let xmp = r#"<rdf:Description xmp:CreatorTool="a"><xmp:ModifyDate>old</xmp:ModifyDate>"#;
+
+        assert_eq!(
+            set_xmp_property(xmp, "xmp:ModifyDate", "ns:", "new"),
+            r#"<rdf:Description xmp:CreatorTool="a"><xmp:ModifyDate>new</xmp:ModifyDate>"#
+        );
+        assert_eq!(
+            set_xmp_property(xmp, "xmp:CreatorTool", "ns:", "b"),
+            r#"<rdf:Description xmp:CreatorTool="b"><xmp:ModifyDate>old</xmp:ModifyDate>"#
+        );
+    }
+
+    #[test]
+    fn set_xmp_property_adds_attribute_with_namespace() {
+        assert_eq!(
+            set_xmp_property(
+                "<rdf:Description rdf:about=\"\">",
+                "pdf:Producer",
+                "ns:",
+                "p"
+            ),
+            "<rdf:Description pdf:Producer=\"p\" xmlns:pdf=\"ns:\" rdf:about=\"\">"
+        );
+        assert_eq!(
+            set_xmp_property(
+                "<rdf:Description xmlns:pdf=\"ns:\">",
+                "pdf:Producer",
+                "ns:",
+                "p"
+            ),
+            "<rdf:Description pdf:Producer=\"p\" xmlns:pdf=\"ns:\">"
+        );
+        assert_eq!(
+            set_xmp_property("<x:xmpmeta/>", "pdf:Producer", "ns:", "p"),
+            "<x:xmpmeta/>"
+        );
+    }
+
+    #[test]
+    fn saving_sanitized_document_adds_no_info() {
+        let mut pdf = crate::testing::sample_document(1, 100, 100).unwrap();
+        pdf.set_info("Title", "secret").unwrap();
+        let mut pdf = Pdf::from_bytes(&pdf.to_bytes().unwrap()).unwrap();
+        pdf.set_info("Title", "changed").unwrap();
+        pdf.strip_metadata().unwrap();
+
+        let pdf = Pdf::from_bytes(&pdf.to_bytes().unwrap()).unwrap();
+        assert!(pdf.info_dict().is_none());
+        assert!(pdf.xmp().is_none());
+    }
+
+    #[test]
+    fn unescape_xml_predefined_entities() {
+        assert_eq!(
+            unescape_xml("a &lt;b&gt; &amp; &quot;c&apos;"),
+            "a <b> & \"c'"
+        );
+    }
+
+    #[test]
+    fn unescape_xml_character_references() {
+        assert_eq!(unescape_xml("caf&#233; &#x65E5;&#X672C;"), "café 日本");
+    }
+
+    #[test]
+    fn unescape_xml_decodes_once() {
+        assert_eq!(unescape_xml("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn unescape_xml_keeps_malformed_references() {
+        assert_eq!(
+            unescape_xml("a & b &#xZZ; &unknown; &"),
+            "a & b &#xZZ; &unknown; &"
+        );
+    }
+}
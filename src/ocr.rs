@@ -0,0 +1,454 @@
+use anyhow::Context;
+use image::DynamicImage;
+use lopdf::{
+    content::{Content, Operation},
+    Document, Object, ObjectId,
+};
+
+use crate::{
+    attributes::{crop_box, rotation},
+    fdf::attribute,
+    font::Font,
+    limits::LimitExceeded,
+    metadata::unescape_xml,
+    permissions::Permission,
+    selection::PageSelection,
+    xobject, Pdf,
+};
+
+/// Word recognized in page image
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrWord {
+    pub text: String,
+    /// Left, top, right and bottom of word in pixels, from top left of image
+    pub bbox: [f32; 4],
+}
+
+/// Words recognized in page image of WIDTH x HEIGHT pixels, as page is displayed
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrPage {
+    pub width: f32,
+    pub height: f32,
+    pub words: Vec<OcrWord>,
+}
+
+/// Read words from TSV output of tesseract, which has row for each page, block,
+/// paragraph, line and word
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.splitn(12, '\t').collect();
+            // level 5 is word
+            if cols.len() < 12 || cols[0] != "5" {
+                return None;
+            }
+            let text = cols[11].trim();
+            let [left, top, width, height] =
+                [cols[6], cols[7], cols[8], cols[9]].map(|x| x.parse::<f32>().unwrap_or_default());
+            (!text.is_empty() && width > 0.0 && height > 0.0).then(|| OcrWord {
+                text: text.to_string(),
+                bbox: [left, top, left + width, top + height],
+            })
+        })
+        .collect()
+}
+
+/// Local name of element in start or end TAG, without namespace prefix
+fn tag_name(tag: &str) -> &str {
+    let name = tag
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default();
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Call CALLBACK with each tag of XML and text following it
+fn for_each_tag<F>(xml: &str, mut callback: F) -> anyhow::Result<()>
+where
+    F: FnMut(&str, &str) -> anyhow::Result<()>,
+{
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let end = rest.find('>').context("unterminated tag")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        callback(tag, rest)?;
+    }
+
+    Ok(())
+}
+
+/// Get numbers of bbox property in hOCR TITLE like "bbox 10 20 30 40; x_wconf 95"
+fn hocr_bbox(title: &str) -> Option<[f32; 4]> {
+    let values: Vec<f32> = title
+        .split(';')
+        .find_map(|x| x.trim().strip_prefix("bbox "))?
+        .split_whitespace()
+        .map(|x| x.parse().ok())
+        .collect::<Option<_>>()?;
+
+    values.try_into().ok()
+}
+
+/// Read pages of hOCR document XML, as written by tesseract or other OCR engines.
+/// words are read from elements of class ocrx_word.
+pub fn parse_hocr(xml: &str) -> anyhow::Result<Vec<OcrPage>> {
+    let mut pages: Vec<OcrPage> = vec![];
+
+    for_each_tag(xml, |tag, rest| {
+        let Some(class) = attribute(tag, "class") else {
+            return Ok(());
+        };
+        let classes: Vec<&str> = class.split_whitespace().collect();
+        let title = attribute(tag, "title").unwrap_or_default();
+
+        if classes.contains(&"ocr_page") {
+            let [x0, y0, x1, y1] = hocr_bbox(title).context("page without bbox in hOCR")?;
+            pages.push(OcrPage {
+                width: x1 - x0,
+                height: y1 - y0,
+                words: vec![],
+            });
+        } else if classes.contains(&"ocrx_word") {
+            let page = pages.last_mut().context("word outside of page in hOCR")?;
+            let Some(bbox) = hocr_bbox(title) else {
+                return Ok(());
+            };
+
+            // text may be marked up inside word, like <strong>
+            let end = tag_name(tag);
+            let end = rest.find(&format!("</{}", end)).unwrap_or(rest.len());
+            let mut text = String::new();
+            let mut in_tag = false;
+            for c in rest[..end].chars() {
+                match c {
+                    '<' => in_tag = true,
+                    '>' => in_tag = false,
+                    c if !in_tag => text.push(c),
+                    _ => {}
+                }
+            }
+
+            let text = unescape_xml(text.trim());
+            if !text.is_empty() {
+                page.words.push(OcrWord { text, bbox });
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(pages)
+}
+
+/// Read pages of ALTO document XML. positions may be in any measurement unit, as
+/// they are taken relative to size of page.
+pub fn parse_alto(xml: &str) -> anyhow::Result<Vec<OcrPage>> {
+    let mut pages: Vec<OcrPage> = vec![];
+
+    for_each_tag(xml, |tag, _| {
+        if tag.starts_with('/') {
+            return Ok(());
+        }
+        let number = |name: &str| attribute(tag, name).and_then(|x| x.trim().parse::<f32>().ok());
+
+        match tag_name(tag) {
+            "Page" => {
+                let (Some(width), Some(height)) = (number("WIDTH"), number("HEIGHT")) else {
+                    anyhow::bail!("page without WIDTH and HEIGHT in ALTO");
+                };
+                pages.push(OcrPage {
+                    width,
+                    height,
+                    words: vec![],
+                });
+            }
+            "String" => {
+                let page = pages.last_mut().context("word outside of page in ALTO")?;
+                let text = unescape_xml(attribute(tag, "CONTENT").unwrap_or_default().trim());
+                let position = [
+                    number("HPOS"),
+                    number("VPOS"),
+                    number("WIDTH"),
+                    number("HEIGHT"),
+                ];
+                if let [Some(x), Some(y), Some(width), Some(height)] = position {
+                    if !text.is_empty() {
+                        page.words.push(OcrWord {
+                            text,
+                            bbox: [x, y, x + width, y + height],
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    Ok(pages)
+}
+
+/// Read pages of hOCR or ALTO document BYTES, detected by content
+pub fn parse_text_layout(bytes: &[u8]) -> anyhow::Result<Vec<OcrPage>> {
+    let xml = std::str::from_utf8(bytes).context("text layout is not UTF-8 XML")?;
+
+    if xml.contains("<alto") || xml.contains(":alto") {
+        parse_alto(xml)
+    } else if xml.contains("ocr_page") {
+        parse_hocr(xml)
+    } else {
+        anyhow::bail!("text layout is neither hOCR nor ALTO")
+    }
+}
+
+/// Recognize words in IMAGE scanned at DPI with tesseract, using languages LANG like
+/// "eng" or "eng+jpn"
+#[cfg(feature = "ocr")]
+fn recognize(image: &DynamicImage, lang: &str, dpi: f32) -> anyhow::Result<Vec<OcrWord>> {
+    use std::io::Cursor;
+    use tesseract::Tesseract;
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageOutputFormat::Png)?;
+
+    let mut tess = Tesseract::new(None, Some(lang))?
+        .set_image_from_mem(png.get_ref())?
+        .set_source_resolution(dpi.round() as i32)
+        .recognize()?;
+
+    Ok(parse_tsv(&tess.get_tsv_text(0)?))
+}
+
+#[cfg(not(feature = "ocr"))]
+fn recognize(_: &DynamicImage, _: &str, _: f32) -> anyhow::Result<Vec<OcrWord>> {
+    anyhow::bail!("OCR is not enabled, build with feature \"ocr\"")
+}
+
+/// Crop box of page with its rotation, mapping displayed page into default user space
+struct PageGeometry {
+    bbox: [f32; 4],
+    rotate: i64,
+}
+
+impl PageGeometry {
+    fn new(doc: &Document, page_id: ObjectId) -> Self {
+        Self {
+            bbox: crop_box(doc, page_id),
+            rotate: rotation(doc, page_id).rem_euclid(360),
+        }
+    }
+
+    /// Width and height of page as displayed, in points
+    fn display_size(&self) -> (f32, f32) {
+        let [x0, y0, x1, y1] = self.bbox;
+        match self.rotate {
+            90 | 270 => (y1 - y0, x1 - x0),
+            _ => (x1 - x0, y1 - y0),
+        }
+    }
+
+    /// Map point at S, T of displayed page, as fractions of width and height from top
+    /// left, into default user space
+    fn point(&self, s: f32, t: f32) -> (f32, f32) {
+        let [x0, y0, x1, y1] = self.bbox;
+        let (w, h) = (x1 - x0, y1 - y0);
+        match self.rotate {
+            90 => (x0 + t * w, y0 + s * h),
+            180 => (x1 - s * w, y0 + t * h),
+            270 => (x1 - t * w, y1 - s * h),
+            _ => (x0 + s * w, y1 - t * h),
+        }
+    }
+
+    /// Rightward and upward unit vectors of displayed page in default user space
+    fn axes(&self) -> ([f32; 2], [f32; 2]) {
+        match self.rotate {
+            90 => ([0.0, 1.0], [-1.0, 0.0]),
+            180 => ([-1.0, 0.0], [0.0, -1.0]),
+            270 => ([0.0, -1.0], [1.0, 0.0]),
+            _ => ([1.0, 0.0], [0.0, 1.0]),
+        }
+    }
+}
+
+impl Pdf {
+    /// Get image of PAGE to recognize at DPI, as displayed. page is rendered if feature
+    /// "render" is enabled, otherwise largest image of page is used, assuming it covers
+    /// whole page as in scanned documents.
+    fn ocr_image(&self, page: u32, dpi: f32) -> anyhow::Result<(DynamicImage, f32)> {
+        if cfg!(feature = "render") {
+            return Ok((self.render_page(page, dpi)?, dpi));
+        }
+
+        let page_id = self.get_page_id(page)?;
+        let id = xobject::largest_image(&self.doc, page_id)?
+            .ok_or_else(|| anyhow::anyhow!("page {} has no image to recognize", page))?;
+        let stream = self.doc.get_object(id).and_then(Object::as_stream)?;
+        let img = self.decode_image(stream)?;
+        let img = match rotation(&self.doc, page_id).rem_euclid(360) {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+
+        // resolution of scan, to tell tesseract size of characters
+        let (display_width, _) = PageGeometry::new(&self.doc, page_id).display_size();
+        let dpi = img.width() as f32 / display_width.abs() * 72.0;
+
+        Ok((img, dpi))
+    }
+
+    /// Draw WORDS recognized in page image as invisible text (render mode 3) on PAGE,
+    /// each scaled to fit its bounding box, so scanned page can be searched and copied.
+    /// with standard fonts, characters out of Latin-1 are replaced with '?'; use
+    /// TrueType font for other scripts.
+    pub fn add_text_layer(
+        &mut self,
+        page: u32,
+        words: &OcrPage,
+        font: &Font,
+    ) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        anyhow::ensure!(
+            words.width > 0.0 && words.height > 0.0,
+            "invalid image size: {}x{}",
+            words.width,
+            words.height
+        );
+
+        let page_id = self.get_page_id(page)?;
+        let geometry = PageGeometry::new(&self.doc, page_id);
+        let (display_width, display_height) = geometry.display_size();
+        let ([ux, uy], [vx, vy]) = geometry.axes();
+
+        let mut operations = vec![
+            Operation::new("q", vec![]),
+            Operation::new("BT", vec![]),
+            Operation::new("Tr", vec![3.into()]),
+        ];
+        let mut has_text = false;
+
+        for word in &words.words {
+            let [left, top, right, bottom] = word.bbox;
+            let width = (right - left) / words.width * display_width.abs();
+            let size = (bottom - top) / words.height * display_height.abs();
+            if word.text.trim().is_empty() || width <= 0.0 || size <= 0.0 {
+                continue;
+            }
+
+            let (font_id, name, encoded) = self.font_for_text(font, &word.text)?;
+            if !has_text {
+                self.add_page_resource(page_id, "Font", &name, font_id)?;
+                has_text = true;
+            }
+
+            let natural = font.text_width(&word.text, size);
+            let scale = if natural > 0.0 {
+                width / natural * 100.0
+            } else {
+                100.0
+            };
+            let (x, y) = geometry.point(left / words.width, bottom / words.height);
+
+            operations.extend([
+                Operation::new("Tf", vec![name.into(), size.into()]),
+                Operation::new("Tz", vec![scale.into()]),
+                Operation::new(
+                    "Tm",
+                    vec![
+                        ux.into(),
+                        uy.into(),
+                        vx.into(),
+                        vy.into(),
+                        x.into(),
+                        y.into(),
+                    ],
+                ),
+                Operation::new("Tj", vec![encoded]),
+            ]);
+        }
+
+        if !has_text {
+            log::info!("no text to add on page {}", page);
+            return Ok(());
+        }
+
+        operations.extend([Operation::new("ET", vec![]), Operation::new("Q", vec![])]);
+        let content = Content { operations };
+        self.append_page_content(page_id, content.encode()?)?;
+
+        log::info!(
+            "added text layer of {} words on page {}",
+            words.words.len(),
+            page
+        );
+        Ok(())
+    }
+
+    /// Add text layers of LAYOUTS, recognized by OCR engine of user's choice, on PAGES
+    /// in order. returns number of words added.
+    pub fn add_text_layers(
+        &mut self,
+        pages: &PageSelection,
+        layouts: &[OcrPage],
+        font: &Font,
+    ) -> anyhow::Result<usize> {
+        let pages = pages.page_set(self.doc.get_pages().len() as u32)?;
+        anyhow::ensure!(
+            pages.len() == layouts.len(),
+            "{} pages selected, but text layout has {} pages",
+            pages.len(),
+            layouts.len()
+        );
+
+        for (&page, layout) in pages.iter().zip(layouts) {
+            self.add_text_layer(page, layout, font)?;
+        }
+
+        Ok(layouts.iter().map(|x| x.words.len()).sum())
+    }
+
+    /// Recognize text of PAGES with tesseract in languages LANG like "eng" or "eng+jpn",
+    /// and add it as invisible text layer drawn in FONT. pages are rendered at DPI if
+    /// feature "render" is enabled. pages without usable image are skipped with warning.
+    /// returns number of recognized words.
+    pub fn ocr(
+        &mut self,
+        pages: &PageSelection,
+        lang: &str,
+        dpi: f32,
+        font: &Font,
+    ) -> anyhow::Result<usize> {
+        self.check_permission(Permission::Modify)?;
+
+        let mut count = 0;
+        for page in pages.page_set(self.doc.get_pages().len() as u32)? {
+            let (image, dpi) = match self.ocr_image(page, dpi) {
+                Ok(image) => image,
+                Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                Err(err) => {
+                    log::warn!("skipping page {}: {}", page, err);
+                    continue;
+                }
+            };
+            let words = OcrPage {
+                width: image.width() as f32,
+                height: image.height() as f32,
+                words: recognize(&image, lang, dpi)?,
+            };
+            log::info!("recognized {} words on page {}", words.words.len(), page);
+
+            count += words.words.len();
+            self.add_text_layer(page, &words, font)?;
+        }
+
+        Ok(count)
+    }
+}
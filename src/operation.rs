@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use crate::{selection::PageSelection, Pdf};
+
+type Handler = Box<dyn Fn(&mut Pdf, &[String]) -> anyhow::Result<()>>;
+
+/// Named operation over document, run with its string arguments
+pub struct Operation {
+    pub name: String,
+    /// Names of arguments, used both for help and arity check
+    pub value_names: Vec<String>,
+    pub help: String,
+    handler: Handler,
+}
+
+impl Operation {
+    pub fn new<F>(name: &str, value_names: &[&str], help: &str, handler: F) -> Self
+    where
+        F: Fn(&mut Pdf, &[String]) -> anyhow::Result<()> + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            value_names: value_names.iter().map(|x| x.to_string()).collect(),
+            help: help.to_string(),
+            handler: Box::new(handler),
+        }
+    }
+
+    pub fn run(&self, pdf: &mut Pdf, args: &[String]) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            args.len() == self.value_names.len(),
+            "operation \"{}\" takes {} argument(s) ({}), but {} given",
+            self.name,
+            self.value_names.len(),
+            self.value_names.join(" "),
+            args.len()
+        );
+
+        (self.handler)(pdf, args)
+    }
+}
+
+/// Set of operations available by name, which downstream crates can extend by
+/// `register` and run in order by `run` or from manifest by `Manifest::build_with`
+#[derive(Default)]
+pub struct Registry {
+    operations: BTreeMap<String, Operation>,
+}
+
+impl Registry {
+    /// Registry with operations built into pdftool
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+
+        let builtins = [
+            Operation::new("prune", &[], "Prune unused objects", |pdf, _| {
+                pdf.prune();
+                Ok(())
+            }),
+            Operation::new("sanitize", &[], "Remove metadata", |pdf, _| {
+                pdf.strip_metadata()
+            }),
+            Operation::new(
+                "strip-actions",
+                &[],
+                "Remove JavaScript and launch actions",
+                |pdf, _| pdf.strip_actions(),
+            ),
+            Operation::new(
+                "regenerate-appearances",
+                &[],
+                "Rebuild appearance streams of annotations",
+                |pdf, _| pdf.regenerate_appearances(),
+            ),
+            Operation::new(
+                "flatten-annotations",
+                &["PAGES"],
+                "Draw annotations of PAGES (like 1-5,8,10-) into page content",
+                |pdf, args| {
+                    let selection: PageSelection = args[0].parse()?;
+                    for page in selection.page_set(pdf.page_count() as u32)? {
+                        pdf.flatten_annotations(page)?;
+                    }
+                    Ok(())
+                },
+            ),
+            Operation::new(
+                "flatten-form",
+                &[],
+                "Draw form fields into page content and remove form",
+                |pdf, _| pdf.flatten_form(),
+            ),
+        ];
+
+        for operation in builtins {
+            registry
+                .operations
+                .insert(operation.name.clone(), operation);
+        }
+
+        registry
+    }
+
+    /// Add OPERATION; fails if operation with same name already exists
+    pub fn register(&mut self, operation: Operation) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.operations.contains_key(&operation.name),
+            "operation \"{}\" is already registered",
+            operation.name
+        );
+
+        self.operations.insert(operation.name.clone(), operation);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Operation> {
+        self.operations.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.values()
+    }
+
+    /// Run operation NAME on PDF with ARGS
+    pub fn run(&self, pdf: &mut Pdf, name: &str, args: &[String]) -> anyhow::Result<()> {
+        let operation = self
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("unknown operation \"{}\"", name))?;
+
+        operation.run(pdf, args)?;
+        pdf.check_limits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn registry_with_title() -> Registry {
+        let mut registry = Registry::with_builtins();
+        let set_title = Operation::new("set-title", &["TITLE"], "Set title", |pdf, args| {
+            Ok(pdf.set_info("Title", &args[0])?)
+        });
+        registry.register(set_title).unwrap();
+        registry
+    }
+
+    #[test]
+    fn registered_operation_runs_with_checked_arguments() {
+        let registry = registry_with_title();
+        let mut pdf = Pdf::new();
+
+        registry
+            .run(&mut pdf, "set-title", &["Report".to_string()])
+            .unwrap();
+        assert_eq!(pdf.info("Title").as_deref(), Some("Report"));
+        assert!(registry.run(&mut pdf, "set-title", &[]).is_err());
+        assert!(registry.run(&mut pdf, "unknown", &[]).is_err());
+    }
+
+    #[test]
+    fn duplicate_operation_is_rejected() {
+        let mut registry = registry_with_title();
+        let prune = Operation::new("prune", &[], "Prune again", |_, _| Ok(()));
+
+        assert!(registry.register(prune).is_err());
+    }
+
+    #[test]
+    fn manifest_runs_registered_operation() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[output]]
+            path = "out.pdf"
+
+            [[output.operations]]
+            name = "set-title"
+            args = ["From manifest"]
+            "#,
+        )
+        .unwrap();
+
+        let pdf = manifest
+            .build_with(&manifest.outputs[0], &registry_with_title())
+            .unwrap();
+        assert_eq!(pdf.info("Title").as_deref(), Some("From manifest"));
+    }
+}
@@ -0,0 +1,436 @@
+use std::{collections::BTreeMap, io::Read, str::FromStr};
+
+use anyhow::anyhow;
+use flate2::read::ZlibDecoder;
+use image::imageops::FilterType;
+use lopdf::{Dictionary, Object, ObjectId};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    limits::LimitExceeded,
+    writer::{self, SaveOptions},
+    xobject, Pdf,
+};
+
+/// JPEG quality used when re-encoding DCTDecode images
+pub(crate) const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// Optimization pass selectable by `--optimize`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Optimization {
+    /// Re-flate compressed streams at max level and compress uncompressed ones
+    Streams,
+    /// Downsample images placed above given DPI
+    Downsample(f32),
+    /// Re-encode JPEG images at quality, also lossless ones if flag is set
+    Jpeg { quality: u8, lossless: bool },
+}
+
+/// Preset of optimizations and save options for typical use of output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Viewing on screen: 72 DPI images, lossy JPEG everywhere, no metadata
+    Screen,
+    /// E-book readers and tablets: 150 DPI images, medium JPEG quality
+    Ebook,
+    /// Printing: 300 DPI images, only lossless compression
+    Print,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "screen" => Ok(Profile::Screen),
+            "ebook" => Ok(Profile::Ebook),
+            "print" => Ok(Profile::Print),
+            _ => Err(anyhow!("unknown profile: {}", s)),
+        }
+    }
+}
+
+impl Profile {
+    /// Optimization passes of profile, in order they are run
+    pub fn optimizations(&self) -> Vec<Optimization> {
+        match self {
+            Profile::Screen => vec![
+                Optimization::Downsample(72.0),
+                Optimization::Jpeg {
+                    quality: 60,
+                    lossless: true,
+                },
+                Optimization::Streams,
+            ],
+            Profile::Ebook => vec![
+                Optimization::Downsample(150.0),
+                Optimization::Jpeg {
+                    quality: 75,
+                    lossless: false,
+                },
+                Optimization::Streams,
+            ],
+            Profile::Print => vec![Optimization::Downsample(300.0), Optimization::Streams],
+        }
+    }
+
+    /// Whether Info, XMP and other metadata are removed
+    pub fn strips_metadata(&self) -> bool {
+        *self == Profile::Screen
+    }
+
+    /// Save options of profile. object streams are used unless output goes to print shops,
+    /// whose older RIPs may not read them.
+    pub fn save_options(&self) -> SaveOptions {
+        SaveOptions {
+            object_streams: *self != Profile::Print,
+            ..SaveOptions::default()
+        }
+    }
+}
+
+/// Size change of one image by recompression
+#[derive(Clone, Debug)]
+pub struct ImageSaving {
+    pub id: ObjectId,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl FromStr for Optimization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "streams" => Ok(Optimization::Streams),
+            _ => match s.split_once(':') {
+                Some(("downsample", dpi)) => Ok(Optimization::Downsample(dpi.parse()?)),
+                Some(("jpeg", params)) => {
+                    let (quality, lossless) = match params.split_once(':') {
+                        Some((quality, "all")) => (quality, true),
+                        Some(_) => anyhow::bail!("unknown optimization: {}", s),
+                        None => (params, false),
+                    };
+                    let quality: u8 = quality.parse()?;
+                    anyhow::ensure!(
+                        (1..=100).contains(&quality),
+                        "JPEG quality must be 1 to 100"
+                    );
+
+                    Ok(Optimization::Jpeg { quality, lossless })
+                }
+                _ => Err(anyhow!("unknown optimization: {}", s)),
+            },
+        }
+    }
+}
+
+pub(crate) fn filter_names(dict: &Dictionary) -> Vec<&[u8]> {
+    match dict.get(b"Filter") {
+        Ok(Object::Name(name)) => vec![name],
+        Ok(Object::Array(arr)) => arr.iter().filter_map(|x| x.as_name().ok()).collect(),
+        _ => vec![],
+    }
+}
+
+pub(crate) fn inflate(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    ZlibDecoder::new(bytes).read_to_end(&mut result)?;
+    Ok(result)
+}
+
+/// Replace references in OBJ according to MAP
+pub(crate) fn replace_references(obj: &mut Object, map: &BTreeMap<ObjectId, ObjectId>) {
+    match obj {
+        Object::Reference(id) => {
+            if let Some(new) = map.get(id) {
+                *id = *new;
+            }
+        }
+        Object::Array(arr) => arr.iter_mut().for_each(|x| replace_references(x, map)),
+        Object::Dictionary(dict) => dict
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, map)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, map)),
+        _ => {}
+    }
+}
+
+impl Pdf {
+    /// Merge image XObjects with identical dictionary and data into one object,
+    /// and point all references to it. returns number of removed duplicates.
+    pub fn dedup_images(&mut self) -> usize {
+        let mut total = 0;
+
+        // repeat since merging soft masks can make their parent images identical
+        loop {
+            let mut seen: BTreeMap<Vec<u8>, ObjectId> = BTreeMap::new();
+            let mut duplicates: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+
+            for (&id, obj) in &self.doc.objects {
+                if !xobject::is_image(&self.doc, id) {
+                    continue;
+                }
+
+                let mut bytes = Vec::new();
+                writer::write_object(&mut bytes, obj);
+                let digest = Sha256::digest(&bytes).to_vec();
+
+                match seen.get(&digest) {
+                    Some(&original) => {
+                        duplicates.insert(id, original);
+                    }
+                    None => {
+                        seen.insert(digest, id);
+                    }
+                }
+            }
+
+            if duplicates.is_empty() {
+                return total;
+            }
+            total += duplicates.len();
+
+            for id in duplicates.keys() {
+                self.doc.objects.remove(id);
+            }
+            for obj in self.doc.objects.values_mut() {
+                replace_references(obj, &duplicates);
+            }
+        }
+    }
+
+    /// Run optimizations of PROFILE, strip metadata if profile says so, and remove
+    /// unused objects. save with `profile.save_options()` to finish.
+    pub fn apply_profile(&mut self, profile: Profile) -> anyhow::Result<()> {
+        for optimization in profile.optimizations() {
+            self.optimize(&optimization)?;
+        }
+
+        if profile.strips_metadata() {
+            self.strip_metadata()?;
+        }
+
+        self.prune();
+        Ok(())
+    }
+
+    pub fn optimize(&mut self, optimization: &Optimization) -> anyhow::Result<()> {
+        log::info!("optimizing with {:?}", optimization);
+
+        match optimization {
+            Optimization::Streams => self.recompress_streams(),
+            Optimization::Downsample(dpi) => self.downsample_images(*dpi),
+            Optimization::Jpeg { quality, lossless } => {
+                self.recompress_jpeg(*quality, *lossless).map(|_| ())
+            }
+        }
+    }
+
+    /// Re-encode DCTDecode images with QUALITY, and also FlateDecode or
+    /// uncompressed ones if LOSSLESS is set. CMYK images and masks, along with images
+    /// having them, are kept. images are only replaced when result is smaller.
+    pub fn recompress_jpeg(
+        &mut self,
+        quality: u8,
+        lossless: bool,
+    ) -> anyhow::Result<Vec<ImageSaving>> {
+        let mut result = Vec::new();
+
+        let ids: Vec<ObjectId> = self
+            .doc
+            .objects
+            .keys()
+            .copied()
+            .filter(|&id| xobject::is_image(&self.doc, id))
+            .collect();
+        let masks = xobject::mask_images(&self.doc);
+
+        let total = ids.len();
+        for (i, id) in ids.into_iter().enumerate() {
+            self.report_progress("recompress_jpeg", i, total);
+            let stream = self.doc.get_object(id).and_then(Object::as_stream)?;
+
+            let is_jpeg = match filter_names(&stream.dict).as_slice() {
+                [b"DCTDecode"] => true,
+                [] | [b"FlateDecode"] if lossless => false,
+                _ => continue,
+            };
+            let bpc = stream
+                .dict
+                .get(b"BitsPerComponent")
+                .and_then(Object::as_i64)
+                .unwrap_or(8);
+            let cmyk = xobject::is_cmyk(&self.doc, &stream.dict);
+            // masks must keep exact shape, so images with them or used as them are kept
+            let masked = [&b"SMask"[..], b"Mask", b"ImageMask"]
+                .iter()
+                .any(|&key| stream.dict.has(key))
+                || masks.contains(&id);
+
+            // JPEG is poor fit for bilevel images, and converting CMYK would shift colors
+            if (!is_jpeg && bpc < 8) || cmyk || masked {
+                continue;
+            }
+
+            let img = match self.decode_image(stream) {
+                Ok(img) => img,
+                Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                Err(err) => {
+                    log::warn!("skipping image {} {}: {}", id.0, id.1, err);
+                    continue;
+                }
+            };
+
+            let before = stream.content.len();
+            let new = xobject::jpeg_stream(&img, quality)?;
+            let after = new.content.len();
+
+            if after < before {
+                log::debug!(
+                    "re-encoded image {} {}: {} -> {} bytes",
+                    id.0,
+                    id.1,
+                    before,
+                    after
+                );
+                xobject::replace_image(&mut self.doc, id, new)?;
+                result.push(ImageSaving { id, before, after });
+            }
+        }
+        self.report_progress("recompress_jpeg", total, total);
+
+        Ok(result)
+    }
+
+    /// Rescale images whose effective resolution on page exceeds TARGET_DPI on both axes.
+    /// images drawn more than once are judged by their largest placement.
+    pub fn downsample_images(&mut self, target_dpi: f32) -> anyhow::Result<()> {
+        // largest placed size in points for each image
+        let mut sizes: BTreeMap<ObjectId, (f32, f32)> = BTreeMap::new();
+
+        for page_id in self.doc.get_pages().into_values() {
+            for placement in xobject::image_placements(&self.doc, page_id)? {
+                let (w, h) = placement.size();
+                let size = sizes.entry(placement.id).or_insert((0.0, 0.0));
+                *size = (size.0.max(w), size.1.max(h));
+            }
+        }
+
+        let total = sizes.len();
+        for (i, (id, (w_pt, h_pt))) in sizes.into_iter().enumerate() {
+            self.report_progress("downsample", i, total);
+            let Ok(stream) = self.doc.get_object(id).and_then(Object::as_stream) else {
+                continue;
+            };
+
+            let (width, height) = match xobject::image_size(&self.doc, &stream.dict) {
+                Ok(size) => size,
+                Err(err) => {
+                    log::warn!("skipping image {} {}: {}", id.0, id.1, err);
+                    continue;
+                }
+            };
+            if w_pt <= 0.0 || h_pt <= 0.0 {
+                continue;
+            }
+            // image stretched on one axis is judged by its lower resolution
+            let dpi_x = width as f32 / (w_pt / 72.0);
+            let dpi_y = height as f32 / (h_pt / 72.0);
+            if dpi_x.min(dpi_y) <= target_dpi {
+                continue;
+            }
+
+            // skip images which can't be decoded, e.g. CCITT or JPX
+            let img = match self.decode_image(stream) {
+                Ok(img) => img,
+                Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                Err(_) => continue,
+            };
+
+            let new_w = ((w_pt / 72.0 * target_dpi).round() as u32).max(1);
+            let new_h = ((h_pt / 72.0 * target_dpi).round() as u32).max(1);
+            let is_jpeg = filter_names(&stream.dict) == [b"DCTDecode"];
+            let smask = stream
+                .dict
+                .get(b"SMask")
+                .and_then(Object::as_reference)
+                .ok();
+
+            log::debug!(
+                "downsampling image {} {} from {}x{} to {}x{}",
+                id.0,
+                id.1,
+                width,
+                height,
+                new_w,
+                new_h
+            );
+            let resized = img.resize_exact(new_w, new_h, FilterType::Lanczos3);
+            let new = if is_jpeg {
+                xobject::jpeg_stream(&resized, self.image_options.quality())?
+            } else {
+                xobject::flate_stream(&resized)?
+            };
+            xobject::replace_image(&mut self.doc, id, new)?;
+
+            if let Some(smask_id) = smask {
+                let Ok(mask) = self
+                    .doc
+                    .get_object(smask_id)
+                    .and_then(Object::as_stream)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|x| self.decode_image(x))
+                else {
+                    continue;
+                };
+
+                let resized = mask
+                    .resize_exact(new_w, new_h, FilterType::Lanczos3)
+                    .grayscale();
+                xobject::replace_image(&mut self.doc, smask_id, xobject::flate_stream(&resized)?)?;
+            }
+        }
+        self.report_progress("downsample", total, total);
+
+        Ok(())
+    }
+
+    /// Decompress and re-flate all FlateDecode streams at max compression,
+    /// and compress streams which have no filter.
+    /// streams are only replaced when result is smaller.
+    pub fn recompress_streams(&mut self) -> anyhow::Result<()> {
+        for obj in self.doc.objects.values_mut() {
+            let Object::Stream(stream) = obj else {
+                continue;
+            };
+
+            if matches!(stream.dict.type_name(), Ok("ObjStm" | "XRef" | "Metadata")) {
+                continue;
+            }
+
+            let filters = filter_names(&stream.dict);
+            let compressed = match filters.as_slice() {
+                [] => writer::deflate(&stream.content)?,
+                [b"FlateDecode"] => {
+                    // leave streams we can't decode untouched
+                    let Ok(raw) = inflate(&stream.content) else {
+                        continue;
+                    };
+                    writer::deflate(&raw)?
+                }
+                _ => continue,
+            };
+
+            if compressed.len() < stream.content.len() {
+                stream.dict.set("Filter", "FlateDecode");
+                stream.set_content(compressed);
+            }
+        }
+
+        Ok(())
+    }
+}
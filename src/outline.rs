@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+use crate::{
+    attachment::name_tree_entries,
+    destination::Destination,
+    overlay::import_object,
+    pdf::{decode_text_string, text_string},
+    permissions::Permission,
+    preflight::deref,
+    Pdf,
+};
+
+/// Appearance of bookmark in outline
+#[derive(Clone, Debug)]
+pub struct BookmarkStyle {
+    pub bold: bool,
+    pub italic: bool,
+    /// RGB color, each component in 0.0 to 1.0
+    pub color: Option<[f32; 3]>,
+    /// Show children when document is opened
+    pub open: bool,
+}
+
+impl Default for BookmarkStyle {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            italic: false,
+            color: None,
+            open: true,
+        }
+    }
+}
+
+impl BookmarkStyle {
+    fn flags(&self) -> i64 {
+        (self.italic as i64) | (self.bold as i64) << 1
+    }
+}
+
+fn children(doc: &Document, id: ObjectId) -> Vec<ObjectId> {
+    let mut result = Vec::new();
+    let mut next = doc
+        .get_dictionary(id)
+        .and_then(|x| x.get(b"First"))
+        .and_then(Object::as_reference)
+        .ok();
+
+    while let Some(child) = next {
+        // guard against cyclic sibling chain
+        if result.contains(&child) {
+            break;
+        }
+        result.push(child);
+
+        next = doc
+            .get_dictionary(child)
+            .and_then(|x| x.get(b"Next"))
+            .and_then(Object::as_reference)
+            .ok();
+    }
+
+    result
+}
+
+/// Name of named destination DEST, which is name or string
+fn destination_name(dest: &Object) -> Option<&[u8]> {
+    match dest {
+        Object::Name(name) | Object::String(name, _) => Some(name.as_slice()),
+        _ => None,
+    }
+}
+
+/// Destinations named in DOC by catalog /Dests and /Names/Dests, as arrays
+fn named_destinations(doc: &Document) -> anyhow::Result<BTreeMap<Vec<u8>, Object>> {
+    let catalog = doc.catalog()?;
+    let mut entries = Vec::new();
+
+    if let Ok(dests) = catalog.get(b"Dests").and_then(|x| deref(doc, x).as_dict()) {
+        entries.extend(
+            dests
+                .iter()
+                .map(|(name, dest)| (name.clone(), dest.clone())),
+        );
+    }
+    let tree = catalog
+        .get(b"Names")
+        .and_then(|x| deref(doc, x).as_dict())
+        .and_then(|x| x.get(b"Dests"))
+        .and_then(|x| deref(doc, x).as_dict());
+    if let Ok(tree) = tree {
+        entries.extend(name_tree_entries(doc, tree)?);
+    }
+
+    // destination may be given by dictionary of /D (12.3.2.3 of ISO 32000-1)
+    Ok(entries
+        .into_iter()
+        .map(|(name, dest)| {
+            let dest = match deref(doc, &dest) {
+                Object::Dictionary(dict) => dict.get(b"D").unwrap_or(&dest).clone(),
+                other => other.clone(),
+            };
+            (name, dest)
+        })
+        .collect())
+}
+
+impl Pdf {
+    fn outlines_id(&self) -> Option<ObjectId> {
+        self.doc
+            .catalog()
+            .and_then(|x| x.get(b"Outlines"))
+            .and_then(Object::as_reference)
+            .ok()
+    }
+
+    fn get_or_create_outlines(&mut self) -> anyhow::Result<ObjectId> {
+        if let Some(id) = self.outlines_id() {
+            return Ok(id);
+        }
+
+        let id = self.doc.add_object(dictionary! {
+            "Type" => "Outlines",
+            "Count" => 0,
+        });
+        self.catalog_mut()?.set("Outlines", id);
+
+        Ok(id)
+    }
+
+    /// Add bookmark to PAGE showing DEST as last child of PARENT, or of outline root if None
+    pub fn add_bookmark(
+        &mut self,
+        title: &str,
+        page: u32,
+        dest: &Destination,
+        parent: Option<ObjectId>,
+        style: &BookmarkStyle,
+    ) -> anyhow::Result<ObjectId> {
+        self.check_permission(Permission::Assemble)?;
+
+        let page_id = self.get_page_id(page)?;
+        let parent_id = match parent {
+            Some(id) => id,
+            None => self.get_or_create_outlines()?,
+        };
+
+        let mut item = dictionary! {
+            "Title" => text_string(title),
+            "Parent" => parent_id,
+            "Dest" => dest.to_object(page_id),
+        };
+        if style.flags() != 0 {
+            item.set("F", style.flags());
+        }
+        if let Some(color) = style.color {
+            item.set(
+                "C",
+                color.iter().map(|&x| x.into()).collect::<Vec<Object>>(),
+            );
+        }
+
+        let last = self
+            .doc
+            .get_dictionary(parent_id)?
+            .get(b"Last")
+            .and_then(Object::as_reference)
+            .ok();
+        if let Some(last) = last {
+            item.set("Prev", last);
+        }
+
+        let id = self.doc.add_object(item);
+
+        match last {
+            Some(last) => self.doc.get_dictionary_mut(last)?.set("Next", id),
+            None => self.doc.get_dictionary_mut(parent_id)?.set("First", id),
+        }
+        self.doc.get_dictionary_mut(parent_id)?.set("Last", id);
+
+        if !style.open {
+            self.closed_bookmarks.insert(id);
+        }
+        self.refresh_outline_counts()?;
+
+        Ok(id)
+    }
+
+    /// Copy bookmarks of document OTHER as last children of PARENT, or of outline root if
+    /// None, keeping their hierarchy. MAP records objects of OTHER already copied, such
+    /// as its pages, so that copied bookmarks point to copies.
+    pub(crate) fn import_outline(
+        &mut self,
+        other: &Document,
+        parent: Option<ObjectId>,
+        map: &mut BTreeMap<ObjectId, ObjectId>,
+    ) -> anyhow::Result<()> {
+        let Some(other_root) = other
+            .catalog()
+            .and_then(|x| x.get(b"Outlines"))
+            .and_then(Object::as_reference)
+            .ok() else {
+            return Ok(());
+        };
+        let items = children(other, other_root);
+        if items.is_empty() {
+            return Ok(());
+        }
+        let parent_id = match parent {
+            Some(id) => id,
+            None => self.get_or_create_outlines()?,
+        };
+
+        // siblings and descendants are copied along, but without their Parent
+        let mut top = Vec::new();
+        for item in items {
+            let id = import_object(&mut self.doc, other, &Object::Reference(item), map)?;
+            top.push(id.as_reference()?);
+        }
+
+        let mut stack: Vec<(ObjectId, ObjectId)> = top.iter().map(|&x| (x, parent_id)).collect();
+        let named = named_destinations(other)?;
+        let mut visited = BTreeSet::new();
+        while let Some((id, parent_id)) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            self.doc.get_dictionary_mut(id)?.set("Parent", parent_id);
+            self.resolve_named_destination(other, id, &named, map)?;
+            stack.extend(children(&self.doc, id).into_iter().map(|x| (x, id)));
+        }
+
+        let (first, last) = (top[0], top[top.len() - 1]);
+        let prev = self
+            .doc
+            .get_dictionary(parent_id)?
+            .get(b"Last")
+            .and_then(Object::as_reference)
+            .ok();
+        match prev {
+            Some(prev) => {
+                self.doc.get_dictionary_mut(prev)?.set("Next", first);
+                self.doc.get_dictionary_mut(first)?.set("Prev", prev);
+            }
+            None => self.doc.get_dictionary_mut(parent_id)?.set("First", first),
+        }
+        self.doc.get_dictionary_mut(parent_id)?.set("Last", last);
+
+        self.refresh_outline_counts()
+    }
+
+    /// Replace destination of imported bookmark ID named in OTHER, given by /Dest or
+    /// GoTo action, with destination it names in NAMED, since names are not copied
+    fn resolve_named_destination(
+        &mut self,
+        other: &Document,
+        id: ObjectId,
+        named: &BTreeMap<Vec<u8>, Object>,
+        map: &mut BTreeMap<ObjectId, ObjectId>,
+    ) -> anyhow::Result<()> {
+        let item = self.doc.get_dictionary(id)?;
+        let name = match item.get(b"Dest") {
+            Ok(dest) => destination_name(dest),
+            Err(_) => item
+                .get(b"A")
+                .and_then(|x| deref(&self.doc, x).as_dict())
+                .ok()
+                .filter(|x| {
+                    x.get(b"S")
+                        .and_then(Object::as_name)
+                        .map_or(false, |s| s == b"GoTo")
+                })
+                .and_then(|x| x.get(b"D").ok())
+                .and_then(destination_name),
+        };
+        let Some(name) = name.map(<[u8]>::to_vec) else {
+            return Ok(());
+        };
+        let Some(dest) = named.get(&name) else {
+            log::warn!(
+                "bookmark to unknown destination {} is kept as is",
+                String::from_utf8_lossy(&name)
+            );
+            return Ok(());
+        };
+
+        let dest = import_object(&mut self.doc, other, dest, map)?;
+        let item = self.doc.get_dictionary_mut(id)?;
+        item.remove(b"A");
+        item.set("Dest", dest);
+
+        Ok(())
+    }
+
+    fn is_bookmark_open(&self, id: ObjectId) -> bool {
+        match self
+            .doc
+            .get_dictionary(id)
+            .and_then(|x| x.get(b"Count"))
+            .and_then(Object::as_i64)
+        {
+            Ok(count) if count != 0 => count > 0,
+            _ => !self.closed_bookmarks.contains(&id),
+        }
+    }
+
+    /// Set whether children of bookmark ID are shown when document is opened
+    pub fn set_bookmark_open(&mut self, id: ObjectId, open: bool) -> anyhow::Result<()> {
+        if let Ok(count) = self
+            .doc
+            .get_dictionary(id)?
+            .get(b"Count")
+            .and_then(Object::as_i64)
+        {
+            let count = if open { count.abs() } else { -count.abs() };
+            self.doc.get_dictionary_mut(id)?.set("Count", count);
+        }
+
+        if open {
+            self.closed_bookmarks.remove(&id);
+        } else {
+            self.closed_bookmarks.insert(id);
+        }
+
+        self.refresh_outline_counts()
+    }
+
+    /// Get titles of all bookmarks in document order, with depth of each.
+    /// depth of top level bookmarks is 1.
+    pub fn bookmarks(&self) -> Vec<(usize, String)> {
+        let Some(root) = self.outlines_id() else {
+            return vec![];
+        };
+
+        let mut stack: Vec<(ObjectId, usize)> = children(&self.doc, root)
+            .into_iter()
+            .rev()
+            .map(|x| (x, 1))
+            .collect();
+        let mut visited = Vec::new();
+        let mut result = Vec::new();
+
+        while let Some((id, level)) = stack.pop() {
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+
+            let title = self
+                .doc
+                .get_dictionary(id)
+                .and_then(|x| x.get(b"Title"))
+                .and_then(Object::as_str)
+                .map(decode_text_string)
+                .unwrap_or_default();
+            result.push((level, title));
+
+            stack.extend(
+                children(&self.doc, id)
+                    .into_iter()
+                    .rev()
+                    .map(|x| (x, level + 1)),
+            );
+        }
+
+        result
+    }
+
+    /// Close all bookmarks at DEPTH or deeper, and open shallower ones.
+    /// depth of top level bookmarks is 1.
+    pub fn collapse_outline(&mut self, depth: usize) -> anyhow::Result<()> {
+        let Some(root) = self.outlines_id() else {
+            return Ok(());
+        };
+
+        let mut stack: Vec<(ObjectId, usize)> = children(&self.doc, root)
+            .into_iter()
+            .map(|x| (x, 1))
+            .collect();
+        let mut visited = Vec::new();
+
+        while let Some((id, level)) = stack.pop() {
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+
+            self.set_bookmark_open(id, level < depth)?;
+            stack.extend(children(&self.doc, id).into_iter().map(|x| (x, level + 1)));
+        }
+
+        Ok(())
+    }
+
+    /// Recompute /Count of every outline item from open state of its descendants
+    fn refresh_outline_counts(&mut self) -> anyhow::Result<()> {
+        let Some(root) = self.outlines_id() else {
+            return Ok(());
+        };
+
+        let total = self.refresh_count(root, &mut Vec::new())?;
+        self.doc.get_dictionary_mut(root)?.set("Count", total);
+
+        Ok(())
+    }
+
+    /// Update /Count of descendants of ID and return number of items visible when ID is open
+    fn refresh_count(&mut self, id: ObjectId, visited: &mut Vec<ObjectId>) -> anyhow::Result<i64> {
+        visited.push(id);
+        let mut total = 0;
+
+        for child in children(&self.doc, id) {
+            if visited.contains(&child) {
+                continue;
+            }
+
+            let open = self.is_bookmark_open(child);
+            let count = self.refresh_count(child, visited)?;
+
+            let dict = self.doc.get_dictionary_mut(child)?;
+            if count == 0 {
+                dict.remove(b"Count");
+            } else {
+                dict.set("Count", if open { count } else { -count });
+            }
+
+            total += 1 + if open { count } else { 0 };
+        }
+
+        Ok(total)
+    }
+}
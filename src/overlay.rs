@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::{
+    attributes::{inherited_attribute, media_box},
+    permissions::Permission,
+    Pdf,
+};
+
+/// Where pages of other document are drawn relative to existing content
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    /// Above existing content, like stamp
+    Overlay,
+    /// Below existing content, like letterhead
+    Underlay,
+}
+
+impl FromStr for Layer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overlay" => Ok(Self::Overlay),
+            "underlay" => Ok(Self::Underlay),
+            _ => anyhow::bail!("unknown layer: {}", s),
+        }
+    }
+}
+
+/// Copy OBJ of document FROM into document TO, following references.
+/// MAP records already copied objects so shared ones are copied once.
+pub(crate) fn import_object(
+    to: &mut Document,
+    from: &Document,
+    obj: &Object,
+    map: &mut BTreeMap<ObjectId, ObjectId>,
+) -> anyhow::Result<Object> {
+    Ok(match obj {
+        Object::Reference(id) => {
+            if let Some(new_id) = map.get(id) {
+                return Ok(Object::Reference(*new_id));
+            }
+
+            let new_id = to.new_object_id();
+            map.insert(*id, new_id);
+
+            let copied = import_object(to, from, from.get_object(*id)?, map)?;
+            to.objects.insert(new_id, copied);
+
+            Object::Reference(new_id)
+        }
+        Object::Dictionary(dict) => Object::Dictionary(import_dictionary(to, from, dict, map)?),
+        Object::Stream(stream) => {
+            let dict = import_dictionary(to, from, &stream.dict, map)?;
+            Object::Stream(Stream::new(dict, stream.content.clone()))
+        }
+        Object::Array(arr) => Object::Array(
+            arr.iter()
+                .map(|x| import_object(to, from, x, map))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        _ => obj.clone(),
+    })
+}
+
+fn import_dictionary(
+    to: &mut Document,
+    from: &Document,
+    dict: &Dictionary,
+    map: &mut BTreeMap<ObjectId, ObjectId>,
+) -> anyhow::Result<Dictionary> {
+    let mut result = Dictionary::new();
+
+    for (key, value) in dict.iter() {
+        // never pull in page tree of other document
+        if key == b"Parent" {
+            continue;
+        }
+
+        result.set(key.clone(), import_object(to, from, value, map)?);
+    }
+
+    Ok(result)
+}
+
+impl Pdf {
+    /// Import page PAGE_ID of OTHER as Form XObject
+    pub(crate) fn import_page_as_form(
+        &mut self,
+        other: &Document,
+        page_id: ObjectId,
+        map: &mut BTreeMap<ObjectId, ObjectId>,
+    ) -> anyhow::Result<ObjectId> {
+        let content = other.get_page_content(page_id)?;
+        let [x0, y0, x1, y1] = media_box(other, page_id);
+
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![x0.into(), y0.into(), x1.into(), y1.into()],
+        };
+
+        if let Some(resources) = inherited_attribute(other, page_id, b"Resources") {
+            dict.set(
+                "Resources",
+                import_object(&mut self.doc, other, resources, map)?,
+            );
+        }
+
+        let mut stream = Stream::new(dict, content);
+        let _ = stream.compress();
+
+        Ok(self.doc.add_object(stream))
+    }
+
+    /// Draw pages of OTHER on each page of document as LAYER.
+    /// page N gets page N of OTHER, or its last page if OTHER is shorter.
+    pub fn overlay(&mut self, other: &Pdf, layer: Layer) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let other_pages: Vec<ObjectId> = other.doc.get_pages().into_values().collect();
+        anyhow::ensure!(!other_pages.is_empty(), "overlay document has no pages");
+
+        let mut map = BTreeMap::new();
+        let mut forms: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+
+        for (i, page_id) in self.doc.get_pages().into_values().enumerate() {
+            self.check_deadline()?;
+            let other_page = other_pages[i.min(other_pages.len() - 1)];
+
+            let form_id = match forms.get(&other_page) {
+                Some(id) => *id,
+                None => {
+                    let id = self.import_page_as_form(&other.doc, other_page, &mut map)?;
+                    forms.insert(other_page, id);
+                    id
+                }
+            };
+
+            let name = format!("Layer{}", form_id.0);
+            self.doc.add_xobject(page_id, name.as_bytes(), form_id)?;
+
+            // align lower left corners of both pages
+            let [px, py, ..] = media_box(&self.doc, page_id);
+            let [ox, oy, ..] = media_box(&other.doc, other_page);
+            let draw =
+                format!("q 1 0 0 1 {} {} cm /{} Do Q\n", px - ox, py - oy, name).into_bytes();
+
+            match layer {
+                Layer::Underlay => {
+                    let original = self.doc.get_page_content(page_id)?;
+                    self.doc
+                        .change_page_content(page_id, [draw, original].concat())?;
+                }
+                Layer::Overlay => self.append_page_content(page_id, draw)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use lopdf::ObjectId;
+
+use crate::{
+    appearance::page_annotations,
+    attributes::{crop_box, media_box, rotation},
+    destination::Destination,
+    error::Error,
+    permissions::Permission,
+    transition::TransitionStyle,
+    Pdf,
+};
+
+/// Handle of one page of document, returned by `Pdf::page`
+pub struct Page<'a> {
+    pdf: &'a mut Pdf,
+    number: u32,
+    id: ObjectId,
+}
+
+impl<'a> Page<'a> {
+    /// Page number, starting from 1
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Object id of page dictionary
+    pub fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    /// MediaBox as [x0, y0, x1, y1], inherited from page tree if page has none
+    pub fn media_box(&self) -> [f32; 4] {
+        media_box(&self.pdf.doc, self.id)
+    }
+
+    /// CropBox as [x0, y0, x1, y1], which is visible area of page. MediaBox if not set
+    pub fn crop_box(&self) -> [f32; 4] {
+        crop_box(&self.pdf.doc, self.id)
+    }
+
+    /// Clockwise rotation of page in degrees, one of 0, 90, 180 or 270
+    pub fn rotation(&self) -> i64 {
+        rotation(&self.pdf.doc, self.id)
+    }
+
+    /// Turn page clockwise by DEGREES, which must be multiple of 90
+    pub fn rotate(&mut self, degrees: i64) -> crate::Result<()> {
+        self.pdf.check_permission(Permission::Assemble)?;
+        if degrees % 90 != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "rotation must be multiple of 90 degrees: {}",
+                degrees
+            )));
+        }
+
+        let rotation = (self.rotation() + degrees).rem_euclid(360);
+        self.pdf
+            .doc
+            .get_dictionary_mut(self.id)?
+            .set("Rotate", rotation);
+
+        Ok(())
+    }
+
+    /// Ids of annotations on page
+    pub fn annotations(&self) -> Vec<ObjectId> {
+        page_annotations(&self.pdf.doc, self.id)
+    }
+
+    /// Add link to URI covering whole page, see `Pdf::add_link`
+    pub fn add_link(&mut self, uri: &str) -> crate::Result<()> {
+        self.pdf.add_link(uri, self.number)
+    }
+
+    /// Add link on page which jumps to TARGET page showing DEST
+    pub fn add_goto_link(&mut self, target: u32, dest: &Destination) -> crate::Result<()> {
+        self.pdf.add_goto_link(self.number, target, dest)
+    }
+
+    /// Show STYLE effect taking DURATION seconds when moving to page, see
+    /// `Pdf::set_transition`
+    pub fn set_transition(&mut self, style: TransitionStyle, duration: f32) -> crate::Result<()> {
+        self.pdf.set_transition(self.number, style, duration)
+    }
+}
+
+impl Pdf {
+    /// Get handle of page NUMBER (starting from 1)
+    pub fn page(&mut self, number: u32) -> crate::Result<Page<'_>> {
+        let id = self.get_page_id(number)?;
+
+        Ok(Page {
+            pdf: self,
+            number,
+            id,
+        })
+    }
+}
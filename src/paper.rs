@@ -0,0 +1,59 @@
+use std::str::FromStr;
+
+/// Page size in points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PageSize {
+    pub const A3: Self = Self::new(841.89, 1190.55);
+    pub const A4: Self = Self::new(595.28, 841.89);
+    pub const A5: Self = Self::new(419.53, 595.28);
+    pub const LETTER: Self = Self::new(612.0, 792.0);
+    pub const LEGAL: Self = Self::new(612.0, 1008.0);
+
+    pub const fn new(width: f32, height: f32) -> Self {
+        Self { width, height }
+    }
+
+    /// Same size turned 90 degrees
+    pub fn landscape(&self) -> Self {
+        Self::new(self.height, self.width)
+    }
+}
+
+impl FromStr for PageSize {
+    type Err = anyhow::Error;
+
+    /// Parse paper name (a3, a4, a5, letter, legal, optionally followed by -landscape)
+    /// or WIDTHxHEIGHT in points
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let (name, landscape) = match lower.strip_suffix("-landscape") {
+            Some(name) => (name, true),
+            None => (lower.as_str(), false),
+        };
+
+        let size = match name {
+            "a3" => Self::A3,
+            "a4" => Self::A4,
+            "a5" => Self::A5,
+            "letter" => Self::LETTER,
+            "legal" => Self::LEGAL,
+            _ => {
+                let (w, h) = name
+                    .split_once('x')
+                    .ok_or_else(|| anyhow::anyhow!("unknown page size: {}", s))?;
+                let width: f32 = w.parse()?;
+                let height: f32 = h.parse()?;
+                anyhow::ensure!(width > 0.0 && height > 0.0, "invalid page size: {}", s);
+
+                Self::new(width, height)
+            }
+        };
+
+        Ok(if landscape { size.landscape() } else { size })
+    }
+}
@@ -1,23 +1,57 @@
-use std::{io::Cursor, path::Path};
+use std::{
+    collections::BTreeSet,
+    io::{Cursor, Read},
+    path::Path,
+};
 
-use anyhow::anyhow;
-use chrono::offset::Utc;
+use chrono::{offset::Utc, DateTime};
 use image::{DynamicImage, GenericImageView, ImageFormat};
 use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
 
+use crate::{
+    annotations::{annots_array, is_link, LinkAnnotation, LinkTarget},
+    attributes::{inherited_attribute, media_box, page_attribute},
+    ccitt::Bilevel,
+    density::density,
+    destination::Destination,
+    encryption,
+    error::Error,
+    events::ProgressFn,
+    exif, heif,
+    incremental::Original,
+    jpx,
+    limits::{Limits, Timer},
+    optimize::DEFAULT_JPEG_QUALITY,
+    paper::PageSize,
+    permissions::{Permission, PermissionDenied, Permissions},
+    placement::Fit,
+    preprocess::ImagePreprocessor,
+    writer::{self, SaveOptions},
+    xobject,
+};
+
 struct PagesDict<'a> {
     dict: &'a Dictionary,
 }
 
 impl<'a> PagesDict<'a> {
-    fn new(doc: &'a Document, pages_id: ObjectId) -> Self {
-        Self {
-            dict: doc.get_dictionary(pages_id).unwrap(),
-        }
+    fn new(doc: &'a Document, pages_id: ObjectId) -> crate::Result<Self> {
+        Ok(Self {
+            dict: doc.get_dictionary(pages_id)?,
+        })
     }
 
-    fn kids(&self) -> &Vec<Object> {
-        self.dict.get(b"Kids").unwrap().as_array().unwrap()
+    /// Get ids of kids with their positions in Kids, so they can be used to modify it.
+    /// missing Kids is taken as empty and non-reference entries are skipped.
+    fn kids(&self) -> Vec<(usize, ObjectId)> {
+        match self.dict.get(b"Kids").and_then(Object::as_array) {
+            Ok(kids) => kids
+                .iter()
+                .enumerate()
+                .filter_map(|(i, x)| Some((i, x.as_reference().ok()?)))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
     }
 }
 
@@ -26,38 +60,58 @@ struct PagesDictMut<'a> {
 }
 
 impl<'a> PagesDictMut<'a> {
-    fn new(doc: &'a mut Document, pages_id: ObjectId) -> Self {
-        Self {
-            dict: doc.get_dictionary_mut(pages_id).unwrap(),
-        }
+    fn new(doc: &'a mut Document, pages_id: ObjectId) -> crate::Result<Self> {
+        Ok(Self {
+            dict: doc.get_dictionary_mut(pages_id)?,
+        })
     }
 
-    fn count(&self) -> i64 {
-        self.dict.get(b"Count").unwrap().as_i64().unwrap()
+    /// Get Count, falling back to number of kids if it is missing or invalid
+    fn count(&mut self) -> crate::Result<i64> {
+        match self.dict.get(b"Count").and_then(Object::as_i64) {
+            Ok(count) => Ok(count),
+            Err(_) => Ok(self.kids_mut()?.len() as i64),
+        }
     }
 
     fn set_count(&mut self, count: i64) {
         self.dict.set("Count", count);
     }
 
-    fn kids_mut(&mut self) -> &mut Vec<Object> {
-        self.dict.get_mut(b"Kids").unwrap().as_array_mut().unwrap()
+    /// Get Kids for modification, replacing it with empty array if it is missing or invalid
+    fn kids_mut(&mut self) -> crate::Result<&mut Vec<Object>> {
+        if !matches!(self.dict.get(b"Kids"), Ok(Object::Array(_))) {
+            self.dict.set("Kids", Vec::<Object>::new());
+        }
+
+        Ok(self.dict.get_mut(b"Kids")?.as_array_mut()?)
     }
 
-    fn push(&mut self, page_id: ObjectId) {
-        self.kids_mut().push(page_id.into());
-        self.set_count(self.count() + 1);
+    fn push(&mut self, page_id: ObjectId) -> crate::Result<()> {
+        let count = self.count()?;
+        self.kids_mut()?.push(page_id.into());
+        self.set_count(count + 1);
+        Ok(())
     }
 
-    fn insert(&mut self, index: usize, page_id: ObjectId) {
-        self.kids_mut().insert(index, page_id.into());
-        self.set_count(self.count() + 1);
+    fn insert(&mut self, index: usize, page_id: ObjectId) -> crate::Result<()> {
+        let count = self.count()?;
+        let kids = self.kids_mut()?;
+        kids.insert(index.min(kids.len()), page_id.into());
+        self.set_count(count + 1);
+        Ok(())
     }
 
-    fn remove(&mut self, index: usize) -> Option<ObjectId> {
-        let result = self.kids_mut().remove(index).as_reference().unwrap();
-        self.set_count(self.count() - 1);
-        Some(result)
+    fn remove(&mut self, index: usize) -> crate::Result<Option<ObjectId>> {
+        let count = self.count()?;
+        let kids = self.kids_mut()?;
+        if index >= kids.len() {
+            return Ok(None);
+        }
+
+        let result = kids.remove(index).as_reference().ok();
+        self.set_count((count - 1).max(0));
+        Ok(result)
     }
 }
 
@@ -74,53 +128,259 @@ impl<'a> Pages<'a> {
         }
     }
 
-    fn push(&mut self, page_id: ObjectId) {
-        PagesDictMut::new(self.doc, self.root_id).push(page_id);
+    fn push(&mut self, page_id: ObjectId) -> crate::Result<()> {
+        PagesDictMut::new(self.doc, self.root_id)?.push(page_id)
     }
 
-    fn find_pages(&self, index: &mut usize, pages_id: ObjectId) -> Option<(ObjectId, usize)> {
-        for (i, kid) in PagesDict::new(self.doc, pages_id)
-            .kids()
-            .iter()
-            .map(|x| x.as_reference().unwrap())
-            .enumerate()
-        {
-            match self.doc.get_dictionary(kid).unwrap().type_name().unwrap() {
-                "Pages" => {
-                    let result = self.find_pages(index, kid);
-                    if result.is_some() {
-                        return result;
-                    }
+    /// Find node containing INDEX-th (1-based) page of subtree PAGES_ID and position of
+    /// page in its kids, counting down INDEX by pages passed. kids which are missing or
+    /// of unknown type are skipped, and pages nodes already in VISITED are reported as
+    /// cycle.
+    fn find_pages(
+        &self,
+        index: &mut usize,
+        pages_id: ObjectId,
+        visited: &mut BTreeSet<ObjectId>,
+    ) -> crate::Result<Option<(ObjectId, usize)>> {
+        if !visited.insert(pages_id) {
+            return Err(Error::InvalidDocument(format!(
+                "page tree has cycle at object {} {}",
+                pages_id.0, pages_id.1
+            )));
+        }
+
+        for (i, kid) in PagesDict::new(self.doc, pages_id)?.kids() {
+            let Ok(dict) = self.doc.get_dictionary(kid) else {
+                continue;
+            };
+
+            // Type may be missing in broken documents, so guess it from Kids
+            let is_pages = match dict.type_name() {
+                Ok(name) => name == "Pages",
+                Err(_) => dict.has(b"Kids"),
+            };
+
+            if is_pages {
+                let result = self.find_pages(index, kid, visited)?;
+                if result.is_some() {
+                    return Ok(result);
                 }
-                "Page" => {
-                    *index -= 1;
-                    if *index == 0 {
-                        return Some((pages_id, i));
-                    }
+            } else if dict.type_name().map_or(true, |x| x == "Page") {
+                *index -= 1;
+                if *index == 0 {
+                    return Ok(Some((pages_id, i)));
                 }
-                _ => {}
-            };
+            }
         }
-        return None;
+
+        Ok(None)
     }
 
-    fn insert(&mut self, index: usize, page_id: ObjectId) {
+    /// Find node containing INDEX-th (1-based) page and position of page in its kids
+    fn find(&self, index: usize) -> crate::Result<Option<(ObjectId, usize)>> {
+        if index == 0 {
+            return Ok(None);
+        }
+
         let mut index = index;
-        if let Some((id, idx)) = self.find_pages(&mut index, self.root_id) {
-            PagesDictMut::new(self.doc, id).insert(idx, page_id);
+        self.find_pages(&mut index, self.root_id, &mut BTreeSet::new())
+    }
+
+    fn insert(&mut self, index: usize, page_id: ObjectId) -> crate::Result<()> {
+        if let Some((id, idx)) = self.find(index)? {
+            PagesDictMut::new(self.doc, id)?.insert(idx, page_id)?;
         }
+        Ok(())
     }
 
-    fn remove(&mut self, index: usize) -> Option<ObjectId> {
-        let mut index = index;
-        self.find_pages(&mut index, self.root_id)
-            .and_then(|(id, idx)| PagesDictMut::new(self.doc, id).remove(idx))
+    fn remove(&mut self, index: usize) -> crate::Result<Option<ObjectId>> {
+        match self.find(index)? {
+            Some((id, idx)) => PagesDictMut::new(self.doc, id)?.remove(idx),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Encode S as PDF text string in UTF-16BE with byte order mark
+pub(crate) fn text_string(s: &str) -> Object {
+    let str_iter = s.encode_utf16();
+
+    let mut utfbe_str: Vec<u8> = Vec::with_capacity((str_iter.count() + 1) * 2);
+    utfbe_str.push(0xfe);
+    utfbe_str.push(0xff);
+
+    for byte in s.encode_utf16() {
+        let u8_2 = byte.to_be_bytes();
+        utfbe_str.push(u8_2[0]);
+        utfbe_str.push(u8_2[1]);
+    }
+
+    Object::String(utfbe_str, StringFormat::Hexadecimal)
+}
+
+/// Decode PDF text string, either UTF-16BE with byte order mark or PDFDocEncoding
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|x| u16::from_be_bytes([x[0], x[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        // PDFDocEncoding matches Latin-1 for printable characters
+        _ => bytes.iter().map(|&x| x as char).collect(),
+    }
+}
+
+/// Chromaticities of sRGB: D65 white point and Rec. 709 primaries
+const SRGB_CHROMATICITIES: [f32; 8] = [0.3127, 0.329, 0.64, 0.33, 0.3, 0.6, 0.15, 0.06];
+
+/// Convert chromaticity x, y into XYZ with Y = 1
+fn xyz(x: f32, y: f32) -> [f32; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// Build CalRGB or CalGray color space from gAMA, cHRM and sRGB chunks of PNG
+fn calibrated_color_space(info: &crate::png::PngInfo) -> Option<Object> {
+    // sRGB chunk overrides gAMA and cHRM
+    let (gamma, chrm) = if info.srgb.is_some() {
+        (Some(1.0 / 2.2), Some(SRGB_CHROMATICITIES))
+    } else {
+        (info.gamma, info.chromaticities)
+    };
+
+    if gamma.is_none() && chrm.is_none() {
+        return None;
+    }
+
+    let [wx, wy, rx, ry, gx, gy, bx, by] = chrm.unwrap_or(SRGB_CHROMATICITIES);
+    // y of zero cannot be converted into XYZ, leave the image in the device space
+    if [wy, ry, gy, by].iter().any(|&y| y <= 0.0) {
+        return None;
+    }
+    let white = xyz(wx, wy);
+    // PNG stores encoding gamma, PDF wants decoding gamma
+    let gamma = gamma.filter(|x| *x > 0.0).map_or(1.0, |x| 1.0 / x);
+
+    if let 0 | 4 = info.color_type {
+        return Some(
+            vec![
+                "CalGray".into(),
+                dictionary! {
+                    "WhitePoint" => white.iter().map(|&x| x.into()).collect::<Vec<Object>>(),
+                    "Gamma" => gamma,
+                }
+                .into(),
+            ]
+            .into(),
+        );
+    }
+
+    // scale primaries so that they sum up to white point
+    let [r, g, b] = [xyz(rx, ry), xyz(gx, gy), xyz(bx, by)];
+    let det = |m: [[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let columns = |a: [f32; 3], b: [f32; 3], c: [f32; 3]| {
+        [[a[0], b[0], c[0]], [a[1], b[1], c[1]], [a[2], b[2], c[2]]]
+    };
+
+    let d = det(columns(r, g, b));
+    if d.abs() < f32::EPSILON {
+        return None;
+    }
+    let sr = det(columns(white, g, b)) / d;
+    let sg = det(columns(r, white, b)) / d;
+    let sb = det(columns(r, g, white)) / d;
+
+    let matrix: Vec<Object> = [r.map(|x| x * sr), g.map(|x| x * sg), b.map(|x| x * sb)]
+        .iter()
+        .flatten()
+        .map(|&x| x.into())
+        .collect();
+
+    Some(
+        vec![
+            "CalRGB".into(),
+            dictionary! {
+                "WhitePoint" => white.iter().map(|&x| x.into()).collect::<Vec<Object>>(),
+                "Gamma" => vec![gamma.into(), gamma.into(), gamma.into()],
+                "Matrix" => matrix,
+            }
+            .into(),
+        ]
+        .into(),
+    )
+}
+
+/// Options applied to images added with add_image and friends
+#[derive(Clone, Debug, Default)]
+pub struct ImageOptions {
+    /// Reduce 16-bit samples to this depth (only 8 supported) with ordered dithering
+    pub depth_reduce: Option<u8>,
+    /// Add every frame of animated GIF instead of first one
+    pub all_frames: bool,
+    /// Convert page images to black and white with this compression
+    pub bilevel: Option<Bilevel>,
+    /// Resolution used to size pages of images, instead of one recorded in them
+    pub dpi: Option<f32>,
+    /// Place images on pages of this size instead of pages of image size
+    pub page_size: Option<PageSize>,
+    /// How images are scaled onto pages of `page_size`
+    pub fit: Fit,
+    /// Margin in points around images on pages of `page_size`
+    pub margin: f32,
+    /// Quality of images re-encoded as JPEG, 85 if not set
+    pub jpeg_quality: Option<u8>,
+}
+
+impl ImageOptions {
+    pub(crate) fn quality(&self) -> u8 {
+        self.jpeg_quality.unwrap_or(DEFAULT_JPEG_QUALITY)
+    }
+}
+
+/// Reduce 16-bit image to 8-bit using 4x4 ordered dithering
+fn dither_to_8bit(img: &DynamicImage) -> DynamicImage {
+    const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+    let dither = |x: u32, y: u32, v: u16| {
+        let threshold = BAYER[(y % 4) as usize][(x % 4) as usize] * 16 + 8;
+        ((v as u32 + threshold) >> 8).min(255) as u8
+    };
+
+    match img {
+        DynamicImage::ImageLuma16(buf) => DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(
+            buf.width(),
+            buf.height(),
+            |x, y| image::Luma([dither(x, y, buf.get_pixel(x, y)[0])]),
+        )),
+        DynamicImage::ImageRgb16(buf) => DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
+            buf.width(),
+            buf.height(),
+            |x, y| image::Rgb(buf.get_pixel(x, y).0.map(|v| dither(x, y, v))),
+        )),
+        _ => img.clone(),
     }
 }
 
 pub struct Pdf {
     pub doc: Document,
     pub pages_id: ObjectId,
+    pub(crate) original: Option<Original>,
+    permissions: Option<Permissions>,
+    /// Bookmarks without children yet which should be closed once they get some
+    pub(crate) closed_bookmarks: BTreeSet<ObjectId>,
+    pub image_options: ImageOptions,
+    pub(crate) preprocessors: Vec<Box<dyn ImagePreprocessor>>,
+    pub limits: Limits,
+    pub(crate) timer: Timer,
+    pub(crate) progress: Option<ProgressFn>,
+    /// Time recorded in dates instead of current time, see `set_timestamp`
+    pub(crate) timestamp: Option<DateTime<Utc>>,
 }
 
 impl Pdf {
@@ -152,98 +412,240 @@ impl Pdf {
             .into(),
         );
 
-        Self { doc, pages_id }
+        Self {
+            doc,
+            pages_id,
+            original: None,
+            permissions: None,
+            closed_bookmarks: BTreeSet::new(),
+            image_options: ImageOptions::default(),
+            preprocessors: Vec::new(),
+            limits: Limits::default(),
+            timer: Timer::default(),
+            progress: None,
+            timestamp: None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        Self::from_buffer(std::fs::read(path)?)
     }
 
-    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let doc = Document::load(path)?;
+    /// Load document from READER, such as stdin, buffering all of it in memory
+    pub fn load_from<R: Read>(mut reader: R) -> crate::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
 
+        Self::from_buffer(bytes)
+    }
+
+    /// Load document from BYTES in memory, counterpart of to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        Self::from_buffer(bytes.to_vec())
+    }
+
+    /// Load document from remaining data of CURSOR, like `Document::load_mem`
+    pub fn load_mem<T: AsRef<[u8]>>(cursor: Cursor<T>) -> crate::Result<Self> {
+        let start = (cursor.position() as usize).min(cursor.get_ref().as_ref().len());
+        Self::from_bytes(&cursor.get_ref().as_ref()[start..])
+    }
+
+    pub(crate) fn from_buffer(bytes: Vec<u8>) -> crate::Result<Self> {
+        let doc = Document::load_mem(&bytes)?;
+        let original = Original::new(bytes);
+        log::info!(
+            "loaded PDF {} with {} pages and {} objects",
+            doc.version,
+            doc.get_pages().len(),
+            doc.objects.len()
+        );
+
+        let mut pdf = Self::from_document(doc)?;
+        pdf.original = Some(original);
+        Ok(pdf)
+    }
+
+    /// Wrap DOC built in memory, like part of other document, as new document
+    pub(crate) fn from_document(doc: Document) -> crate::Result<Self> {
         let pages_id = doc
             .catalog()?
             .get(b"Pages")
-            .and_then(Object::as_reference)
-            .unwrap()
-            .to_owned();
+            .and_then(Object::as_reference)?;
+        let permissions = Permissions::from_document(&doc);
 
-        Ok(Self { doc, pages_id })
+        Ok(Self {
+            doc,
+            pages_id,
+            original: None,
+            permissions,
+            closed_bookmarks: BTreeSet::new(),
+            image_options: ImageOptions::default(),
+            preprocessors: Vec::new(),
+            limits: Limits::default(),
+            timer: Timer::default(),
+            progress: None,
+            timestamp: None,
+        })
     }
 
-    fn get_pages(&mut self) -> Pages {
-        Pages::new(&mut self.doc, self.pages_id)
+    /// Load document at PATH, failing if it already exceeds LIMITS.
+    /// LIMITS stay in effect for later operations.
+    pub fn load_with_limits<P: AsRef<Path>>(path: P, limits: Limits) -> crate::Result<Self> {
+        Self::load_from_with_limits(std::fs::File::open(path)?, limits)
     }
 
-    fn get_page_id(&self, num: u32) -> anyhow::Result<ObjectId> {
-        self.doc
-            .get_pages()
-            .get(&num)
-            .ok_or(anyhow!("Page {} not found in document.", num))
-            .copied()
+    /// Load document from READER like load_from, failing if it already exceeds LIMITS.
+    /// LIMITS stay in effect for later operations.
+    pub fn load_from_with_limits<R: Read>(reader: R, limits: Limits) -> crate::Result<Self> {
+        let mut pdf = Self::load_from(reader)?;
+        pdf.limits = limits;
+        pdf.check_object_count()?;
+
+        Ok(pdf)
     }
 
-    pub fn set_author(&mut self, author: &str) -> anyhow::Result<()> {
-        let author_iter = author.encode_utf16();
+    /// Get permissions of loaded document, or None if unrestricted
+    pub fn permissions(&self) -> Option<Permissions> {
+        self.permissions
+    }
+
+    /// Lift permission restrictions after checking OWNER_PASSWORD
+    pub fn override_permissions(&mut self, owner_password: &str) -> crate::Result<()> {
+        if !encryption::authenticate_owner(&self.doc, owner_password)? {
+            return Err(Error::Encrypted("incorrect owner password".into()));
+        }
+        self.permissions = None;
 
-        let mut utfbe_str: Vec<u8> = Vec::with_capacity((author_iter.count() + 1) * 2);
-        utfbe_str.push(0xfe);
-        utfbe_str.push(0xff);
+        Ok(())
+    }
 
-        for byte in author.encode_utf16() {
-            let u8_2 = byte.to_be_bytes();
-            utfbe_str.push(u8_2[0]);
-            utfbe_str.push(u8_2[1]);
+    pub(crate) fn check_permission(&self, permission: Permission) -> crate::Result<()> {
+        match self.permissions {
+            Some(permissions) if !permissions.allows(permission) => {
+                Err(PermissionDenied(permission).into())
+            }
+            _ => Ok(()),
         }
+    }
+
+    fn get_pages(&mut self) -> Pages {
+        Pages::new(&mut self.doc, self.pages_id)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.doc.get_pages().len()
+    }
 
-        let info = self
+    pub(crate) fn catalog_mut(&mut self) -> crate::Result<&mut Dictionary> {
+        let root_id = self
             .doc
             .trailer
-            .get(b"Info")
+            .get(b"Root")
             .and_then(Object::as_reference)?;
 
+        Ok(self.doc.get_dictionary_mut(root_id)?)
+    }
+
+    /// Append CONTENT to page PAGE_ID, isolating graphics state of original content
+    pub(crate) fn append_page_content(
+        &mut self,
+        page_id: ObjectId,
+        content: Vec<u8>,
+    ) -> crate::Result<()> {
+        let original = self.doc.get_page_content(page_id)?;
+        let content = [b"q\n".to_vec(), original, b"\nQ\n".to_vec(), content].concat();
+
+        self.doc.change_page_content(page_id, content)?;
+        Ok(())
+    }
+
+    /// Register ID as NAME in resource CATEGORY (Font, XObject, ...) of page PAGE_ID.
+    /// inherited or shared resources are copied into page, so other pages are not affected.
+    pub(crate) fn add_page_resource(
+        &mut self,
+        page_id: ObjectId,
+        category: &str,
+        name: &str,
+        id: ObjectId,
+    ) -> crate::Result<()> {
+        let mut resources = match page_attribute(&self.doc, page_id, b"Resources") {
+            Some(Object::Dictionary(dict)) => dict.clone(),
+            _ => Dictionary::new(),
+        };
+
+        let mut entries = match resources.get(category.as_bytes()) {
+            Ok(Object::Reference(id)) => self.doc.get_dictionary(*id)?.clone(),
+            Ok(Object::Dictionary(dict)) => dict.clone(),
+            _ => Dictionary::new(),
+        };
+        entries.set(name, id);
+        resources.set(category, entries);
+
         self.doc
-            .get_dictionary_mut(info)?
-            .set(
-                "Author",
-                Object::String(utfbe_str, StringFormat::Hexadecimal),
-            );
+            .get_dictionary_mut(page_id)?
+            .set("Resources", resources);
+        Ok(())
+    }
+
+    pub(crate) fn get_page_id(&self, num: u32) -> crate::Result<ObjectId> {
+        self.doc
+            .get_pages()
+            .get(&num)
+            .ok_or(Error::PageNotFound(num))
+            .copied()
+    }
+
+    pub fn set_author(&mut self, author: &str) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        self.info_dict_mut()?.set("Author", text_string(author));
 
         Ok(())
     }
 
-    pub fn add_link(&mut self, link: &str, page: u32) -> anyhow::Result<()> {
+    pub fn add_link(&mut self, link: &str, page: u32) -> crate::Result<()> {
+        self.check_permission(Permission::Annotate)?;
+
         let page_id = self.get_page_id(page)?;
 
-        let rect = self
-            .doc
-            .get_object_mut(page_id)
-            .and_then(Object::as_dict_mut)?
-            .get(b"MediaBox")?
-            .to_owned();
-
-        let url_id = self.doc.add_object(dictionary! {
-            "S" => "URI",
-            "URI" => Object::string_literal(link),
-        });
+        // link replaces existing annotations of page
+        self.doc
+            .get_dictionary_mut(page_id)?
+            .remove(b"Annots");
 
-        let annot_id = self.doc.add_object(dictionary! {
-            "Type" => "Annot",
-            "Subtype" => "Link",
-            "A" => url_id,
-            "Rect" => rect,
-            "Border" => vec![0.into(), 0.into(), 0.into()],
-            "F" => 4,
-        });
+        let link = LinkAnnotation {
+            rect: media_box(&self.doc, page_id),
+            target: LinkTarget::Uri(link.to_string()),
+        };
+        self.push_annotation(page_id, &link.into())?;
 
-        let page = self
-            .doc
-            .get_object_mut(page_id)
-            .and_then(Object::as_dict_mut)?;
+        Ok(())
+    }
 
-        page.set("Annots", vec![annot_id.into()]);
+    /// Add link on PAGE which jumps to TARGET page showing DEST
+    pub fn add_goto_link(
+        &mut self,
+        page: u32,
+        target: u32,
+        dest: &Destination,
+    ) -> crate::Result<()> {
+        self.check_permission(Permission::Annotate)?;
+
+        let page_id = self.get_page_id(page)?;
+        let target_id = self.get_page_id(target)?;
+
+        let link = LinkAnnotation {
+            rect: media_box(&self.doc, page_id),
+            target: LinkTarget::Dest(dest.to_object(target_id)),
+        };
+        self.push_annotation(page_id, &link.into())?;
 
         Ok(())
     }
 
-    pub fn remove_link(&mut self, page: u32) -> anyhow::Result<()> {
+    pub fn remove_link(&mut self, page: u32) -> crate::Result<()> {
+        self.check_permission(Permission::Annotate)?;
+
         let page_id = self.get_page_id(page)?;
 
         self.doc
@@ -253,25 +655,52 @@ impl Pdf {
         Ok(())
     }
 
-    pub fn move_link(&mut self, from: u32, to: u32) -> anyhow::Result<()> {
+    /// Move link annotations of FROM to TO, after existing annotations of TO.
+    /// other annotations like highlights and form widgets stay on FROM.
+    pub fn move_link(&mut self, from: u32, to: u32) -> crate::Result<()> {
+        self.check_permission(Permission::Annotate)?;
+
         let from_id = self.get_page_id(from)?;
         let to_id = self.get_page_id(to)?;
 
-        let annots = self
-            .doc
-            .get_dictionary_mut(from_id)?
-            .remove(b"Annots");
+        let (links, rest): (Vec<Object>, Vec<Object>) = annots_array(&self.doc, from_id)
+            .into_iter()
+            .partition(|x| is_link(&self.doc, x));
+        if links.is_empty() {
+            return Ok(());
+        }
 
-        if let Some(annots) = annots {
-            self.doc
-                .get_dictionary_mut(to_id)?
-                .set("Annots", annots);
+        let from_dict = self.doc.get_dictionary_mut(from_id)?;
+        if rest.is_empty() {
+            from_dict.remove(b"Annots");
+        } else {
+            from_dict.set("Annots", rest);
         }
 
+        for link in &links {
+            if let Ok(dict) = link
+                .as_reference()
+                .and_then(|id| self.doc.get_dictionary_mut(id))
+            {
+                dict.set("P", to_id);
+            }
+        }
+
+        let mut annots = annots_array(&self.doc, to_id);
+        annots.extend(links);
+        self.doc.get_dictionary_mut(to_id)?.set("Annots", annots);
+
         Ok(())
     }
 
-    pub fn add_page(&mut self, width: u32, height: u32) -> anyhow::Result<ObjectId> {
+    /// Add PAGE_ID, page object whose Parent is root of page tree, as last page
+    pub(crate) fn push_page(&mut self, page_id: ObjectId) -> crate::Result<()> {
+        self.get_pages().push(page_id)
+    }
+
+    pub fn add_page(&mut self, width: u32, height: u32) -> crate::Result<ObjectId> {
+        self.check_permission(Permission::Assemble)?;
+
         let page_id = self.doc.new_object_id();
         let contents_id = self.doc.add_object(Stream::new(dictionary! {}, vec![]));
 
@@ -286,20 +715,189 @@ impl Pdf {
             .into(),
         );
 
-        self.get_pages().push(page_id);
+        self.get_pages().push(page_id)?;
+
+        Ok(page_id)
+    }
+
+    /// Append blank page if page count is odd, so that following document starts on
+    /// front side in duplex printing. blank page has SIZE, or size of last page if None.
+    /// returns whether page was added.
+    pub fn pad_even(&mut self, size: Option<PageSize>) -> crate::Result<bool> {
+        let count = self.page_count();
+        if count % 2 == 0 {
+            return Ok(false);
+        }
+
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let last = self.get_page_id(count as u32)?;
+                let [x0, y0, x1, y1] = media_box(&self.doc, last);
+                PageSize::new(x1 - x0, y1 - y0)
+            }
+        };
+
+        self.add_page(size.width.round() as u32, size.height.round() as u32)?;
+        Ok(true)
+    }
+
+    /// Add page showing image. multi-page TIFF and animated GIF with `all_frames`
+    /// get page per frame, returning id of first one.
+    pub fn add_image(&mut self, bytes: &[u8]) -> crate::Result<ObjectId> {
+        // formats like HEIC and JPEG 2000 are not known to guess_format
+        let pages = match image::guess_format(bytes).ok() {
+            Some(ImageFormat::Jpeg) => return self.add_jpeg(bytes),
+            Some(ImageFormat::Tiff) => self.add_tiff(bytes)?,
+            Some(ImageFormat::Gif) => self.add_gif(bytes)?,
+            _ => vec![],
+        };
+        if let Some(&first) = pages.first() {
+            return Ok(first);
+        }
+
+        let img_stream = self.image_xobject(bytes)?;
+        self.add_image_page(img_stream, density(bytes))
+    }
+
+    /// Add page showing JPEG image. EXIF rotation is applied by page /Rotate,
+    /// keeping image data as is.
+    pub fn add_jpeg(&mut self, bytes: &[u8]) -> crate::Result<ObjectId> {
+        let rotate = match exif::orientation(bytes) {
+            Some(3) => 180,
+            Some(6) => 90,
+            Some(8) => 270,
+            _ => {
+                let img_stream = self.jpeg_xobject(bytes)?;
+                return self.add_image_page(img_stream, density(bytes));
+            }
+        };
+
+        let img_stream = self.jpeg_passthrough(bytes)?;
+        self.add_rotated_image_page(img_stream, density(bytes), rotate)
+    }
+
+    pub fn add_png(&mut self, bytes: &[u8]) -> crate::Result<ObjectId> {
+        let img_stream = self.png_xobject(bytes)?;
+        self.add_image_page(img_stream, density(bytes))
+    }
+
+    /// Get size in points of image XObject IMG_STREAM. resolution is taken from
+    /// `image_options.dpi`, DENSITY recorded in image or 72 DPI, in this order.
+    pub(crate) fn image_size(
+        &self,
+        img_stream: &Stream,
+        density: Option<(f32, f32)>,
+    ) -> crate::Result<(f32, f32)> {
+        let width = img_stream.dict.get(b"Width")?.as_i64()? as f32;
+        let height = img_stream.dict.get(b"Height")?.as_i64()? as f32;
+
+        let (x_dpi, y_dpi) = self
+            .image_options
+            .dpi
+            .filter(|&dpi| dpi > 0.0)
+            .map(|dpi| (dpi, dpi))
+            .or(density)
+            .unwrap_or((72.0, 72.0));
+
+        Ok((width * 72.0 / x_dpi, height * 72.0 / y_dpi))
+    }
+
+    /// Add new page showing image XObject IMG_STREAM at its actual size, or placed on
+    /// page of `image_options.page_size` if set. DENSITY is passed to image_size.
+    pub(crate) fn add_image_page(
+        &mut self,
+        img_stream: Stream,
+        density: Option<(f32, f32)>,
+    ) -> crate::Result<ObjectId> {
+        self.add_rotated_image_page(img_stream, density, 0)
+    }
+
+    /// Add image page like `add_image_page`, showing image turned ROTATE degrees
+    /// clockwise. page of actual size is turned by /Rotate, while image is turned
+    /// inside page of `image_options.page_size` so that page keeps its orientation.
+    fn add_rotated_image_page(
+        &mut self,
+        img_stream: Stream,
+        density: Option<(f32, f32)>,
+        rotate: i64,
+    ) -> crate::Result<ObjectId> {
+        let img_stream = self.bilevel_stream(img_stream)?;
+        let (width, height) = self.image_size(&img_stream, density)?;
+
+        if let Some(size) = self.image_options.page_size {
+            return Ok(self.add_placed_image_page(img_stream, (width, height), size, rotate)?);
+        }
+
+        let width = width.round().max(1.0);
+        let height = height.round().max(1.0);
+
+        let page_id = self.add_page(width as u32, height as u32)?;
+        log::debug!("added image page of {}x{} pt", width, height);
+
+        self.doc
+            .insert_image(page_id, img_stream, (0.0, 0.0), (width, height))?;
+        if rotate != 0 {
+            self.doc.get_dictionary_mut(page_id)?.set("Rotate", rotate);
+        }
 
         Ok(page_id)
     }
 
-    pub fn add_image(&mut self, bytes: &[u8]) -> anyhow::Result<ObjectId> {
-        match image::guess_format(bytes)? {
-            ImageFormat::Jpeg => self.add_jpeg(bytes),
-            ImageFormat::Png => self.add_png(bytes),
-            _ => anyhow::bail!("unsupported image format"),
+    /// Build image XObject from PNG, JPEG, JPEG 2000, WebP, BMP, PNM, AVIF, HEIC
+    /// or first frame of TIFF or GIF data
+    pub(crate) fn image_xobject(&mut self, bytes: &[u8]) -> crate::Result<Stream> {
+        if let Some(kind) = heif::detect(bytes) {
+            return Ok(self.heif_xobject(bytes, kind)?);
+        }
+        if jpx::is_jpx(bytes) {
+            return Ok(self.jpx_xobject(bytes)?);
+        }
+
+        let format = image::guess_format(bytes)
+            .map_err(|_| Error::UnsupportedImage("unknown image format".into()))?;
+        match format {
+            ImageFormat::Jpeg => self.jpeg_xobject(bytes),
+            ImageFormat::Png => self.png_xobject(bytes),
+            ImageFormat::WebP => Ok(self.webp_xobject(bytes)?),
+            ImageFormat::Bmp | ImageFormat::Pnm => Ok(self.bitmap_xobject(bytes, format)?),
+            ImageFormat::Tiff => self
+                .tiff_xobjects(bytes)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::UnsupportedImage("TIFF has no frame".into())),
+            ImageFormat::Gif => self
+                .gif_xobjects(bytes)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::UnsupportedImage("GIF has no frame".into())),
+            _ => Err(Error::UnsupportedImage(format!("{:?} format", format))),
         }
     }
 
-    pub fn add_jpeg(&mut self, bytes: &[u8]) -> anyhow::Result<ObjectId> {
+    /// Build image XObject from JPEG data, turning pixels upright according to
+    /// EXIF orientation if needed
+    pub(crate) fn jpeg_xobject(&mut self, bytes: &[u8]) -> crate::Result<Stream> {
+        match exif::orientation(bytes) {
+            Some(orientation) if orientation != 1 => {
+                self.check_encoded_image(bytes)?;
+
+                let img = image::load_from_memory_with_format(bytes, ImageFormat::Jpeg)?;
+                let img = exif::apply_orientation(img, orientation);
+                let jpeg = xobject::jpeg_stream(&img, self.image_options.quality())?.content;
+                self.jpeg_passthrough(&jpeg)
+            }
+            _ => self.jpeg_passthrough(bytes),
+        }
+    }
+
+    /// Build image XObject embedding JPEG data as is
+    fn jpeg_passthrough(&mut self, bytes: &[u8]) -> crate::Result<Stream> {
+        self.check_encoded_image(bytes)?;
+
+        let processed = self.preprocess(bytes, ImageFormat::Jpeg)?;
+        let bytes = processed.as_deref().unwrap_or(bytes);
+
         let img = image::load_from_memory(bytes)?;
         let (width, height) = img.dimensions();
 
@@ -308,12 +906,15 @@ impl Pdf {
             image::ColorType::L16 => ("DeviceGray", 16),
             image::ColorType::Rgb8 => ("DeviceRGB", 8),
             image::ColorType::Rgb16 => ("DeviceRGB", 16),
-            _ => anyhow::bail!("unsupported color type: {:?}", img.color()),
+            color => {
+                return Err(Error::UnsupportedImage(format!(
+                    "JPEG of color type {:?}",
+                    color
+                )))
+            }
         };
 
-        let page_id = self.add_page(width, height)?;
-
-        let img_stream = Stream::new(
+        Ok(Stream::new(
             dictionary! {
                 "Type" => "XObject",
                 "Subtype" => "Image",
@@ -325,46 +926,72 @@ impl Pdf {
                 "Height" =>  height,
             },
             bytes.into(),
-        );
+        ))
+    }
 
-        self.doc.insert_image(
-            page_id,
-            img_stream,
-            (0.0, 0.0),
-            (width as f32, height as f32),
-        )?;
+    pub(crate) fn png_xobject(&mut self, bytes: &[u8]) -> crate::Result<Stream> {
+        self.check_encoded_image(bytes)?;
 
-        Ok(page_id)
-    }
+        let processed = self.preprocess(bytes, ImageFormat::Png)?;
+        let bytes = processed.as_deref().unwrap_or(bytes);
 
-    pub fn add_png(&mut self, bytes: &[u8]) -> anyhow::Result<ObjectId> {
-        let info = crate::png::get_info(bytes)?;
+        let mut info = crate::png::get_info(bytes)?;
+        let reduce = info.depth == 16 && self.image_options.depth_reduce == Some(8);
 
-        let bytes = if info.interlace || info.color_type >= 4 {
-            let img = image::load_from_memory(bytes)?;
-            let mut result = Vec::new();
+        let reencode = info.interlace || info.color_type >= 4 || reduce;
+        let decoded = if reencode || info.transparency.is_some() {
+            Some(image::load_from_memory(bytes)?)
+        } else {
+            None
+        };
+
+        // alpha plane of gray or RGB image with alpha, or transparency given by tRNS as
+        // alpha of palette entries or color key, drawn as soft mask
+        let alpha = decoded.as_ref().and_then(|img| {
+            let rgba = img.to_rgba8();
+            // fully opaque images need no mask
+            rgba.pixels().any(|x| x.0[3] != 255).then(|| {
+                image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                    image::Luma([rgba.get_pixel(x, y).0[3]])
+                })
+            })
+        });
 
+        // 16-bit samples are big-endian in both PNG and PDF, so IDAT can be passed through as is
+        let bytes = if let Some(img) = decoded.filter(|_| reencode) {
+            let mut result = Vec::new();
             let mut writer = Cursor::new(&mut result);
 
-            match info.color_type {
-                4 => match info.depth {
-                    8 => DynamicImage::ImageLuma8(img.into_luma8()),
+            let img = match info.color_type {
+                0 | 4 => match info.depth {
                     16 => DynamicImage::ImageLuma16(img.into_luma16()),
-                    _ => anyhow::bail!(""),
+                    _ => DynamicImage::ImageLuma8(img.into_luma8()),
                 },
-                6 => match info.depth {
-                    8 => DynamicImage::ImageRgb8(img.into_rgb8()),
+                2 | 6 => match info.depth {
                     16 => DynamicImage::ImageRgb16(img.into_rgb16()),
-                    _ => anyhow::bail!(""),
+                    _ => DynamicImage::ImageRgb8(img.into_rgb8()),
                 },
-                _ => img,
-            }
-            .write_to(&mut writer, ImageFormat::Png)?;
+                _ => DynamicImage::ImageRgb8(img.into_rgb8()),
+            };
+
+            if reduce { dither_to_8bit(&img) } else { img }
+                .write_to(&mut writer, ImageFormat::Png)?;
+
+            // re-encoded image may differ in depth and color type from the original one
+            let reencoded = crate::png::get_info(&result)?;
+            info.depth = reencoded.depth;
+            info.color_type = reencoded.color_type;
+            info.palette = None;
+
             result
         } else {
             bytes.into()
         };
 
+        if info.depth == 16 && self.doc.version.as_str() < "1.5" {
+            self.doc.version = "1.5".into();
+        }
+
         let colors = if let 0 | 3 | 4 = info.color_type {
             1
         } else {
@@ -388,6 +1015,8 @@ impl Pdf {
                         )
                     );
                     vec!["ICCBased".into(), icc_id.into()].into()
+                } else if let Some(cal) = calibrated_color_space(&info) {
+                    cal
                 } else {
                     if let 0 | 4 = info.color_type {
                         "DeviceGray"
@@ -409,12 +1038,15 @@ impl Pdf {
                 .into()
             }
 
-            _ => anyhow::bail!("unexpected color type found: {}", info.color_type),
+            _ => {
+                return Err(Error::UnsupportedImage(format!(
+                    "PNG of color type {}",
+                    info.color_type
+                )))
+            }
         };
 
-        let page_id = self.add_page(info.width, info.height)?;
-
-        let img_stream = Stream::new(
+        let mut stream = Stream::new(
             dictionary! {
                 "Type" => "XObject",
                 "Subtype" => "Image",
@@ -434,45 +1066,243 @@ impl Pdf {
             idat,
         );
 
+        if let Some(alpha) = alpha {
+            // soft masks are introduced in PDF 1.4
+            if self.doc.version.as_str() < "1.4" {
+                self.doc.version = "1.4".into();
+            }
+
+            let smask = xobject::flate_stream(&DynamicImage::ImageLuma8(alpha))?;
+            stream.dict.set("SMask", self.doc.add_object(smask));
+        }
+
+        Ok(stream)
+    }
+
+    /// Draw PNG or JPEG image on existing PAGE with its lower left corner at POSITION.
+    /// image is drawn at SCALE points per pixel.
+    pub fn stamp_image(
+        &mut self,
+        page: u32,
+        bytes: &[u8],
+        position: (f32, f32),
+        scale: f32,
+    ) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        let img_stream = self.image_xobject(bytes)?;
+
+        let width = img_stream.dict.get(b"Width")?.as_i64()? as f32;
+        let height = img_stream.dict.get(b"Height")?.as_i64()? as f32;
+
         self.doc.insert_image(
             page_id,
             img_stream,
-            (0.0, 0.0),
-            (info.width as f32, info.height as f32),
+            position,
+            (width * scale, height * scale),
         )?;
 
-        Ok(page_id)
+        Ok(())
     }
 
-    pub fn move_page(&mut self, from: usize, to: usize) -> anyhow::Result<()> {
+    pub fn move_page(&mut self, from: usize, to: usize) -> crate::Result<()> {
+        self.check_permission(Permission::Assemble)?;
+
         let mut pages = self.get_pages();
 
-        let Some(removed) = pages.remove(from) else {
-            anyhow::bail!("page {} not found in document", from);
+        let Some(removed) = pages.remove(from)? else {
+            return Err(Error::PageNotFound(from as u32));
         };
-        pages.insert(to, removed);
+        pages.insert(to, removed)?;
 
         Ok(())
     }
 
-    pub fn remove_pages(&mut self, pages: &[u32]) {
-        self.doc.delete_pages(pages)
+    /// Reorder pages so that pages listed in ORDER come first in that order,
+    /// followed by remaining pages in their original order.
+    /// page tree is flattened, with inherited attributes copied into pages.
+    pub fn reorder_pages(&mut self, order: &[u32]) -> crate::Result<()> {
+        self.check_permission(Permission::Assemble)?;
+
+        let pages = self.doc.get_pages();
+        let mut seen = BTreeSet::new();
+        for num in order {
+            if !pages.contains_key(num) {
+                return Err(Error::PageNotFound(*num));
+            }
+            if !seen.insert(*num) {
+                return Err(Error::InvalidArgument(format!(
+                    "page {} appears more than once in order",
+                    num
+                )));
+            }
+        }
+
+        let new_order: Vec<ObjectId> = order
+            .iter()
+            .chain(pages.keys().filter(|num| !seen.contains(num)))
+            .map(|num| pages[num])
+            .collect();
+
+        self.set_flat_page_tree(new_order)
+    }
+
+    /// Make PAGE_IDS in order direct kids of root Pages node, copying inherited attributes
+    /// into pages. intermediate Pages nodes are left unreferenced until `prune`.
+    fn set_flat_page_tree(&mut self, page_ids: Vec<ObjectId>) -> crate::Result<()> {
+        for &page_id in &page_ids {
+            for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+                if self.doc.get_dictionary(page_id)?.has(key) {
+                    continue;
+                }
+
+                if let Some(value) = inherited_attribute(&self.doc, page_id, key).cloned() {
+                    self.doc
+                        .get_dictionary_mut(page_id)?
+                        .set(key.to_vec(), value);
+                }
+            }
+
+            self.doc
+                .get_dictionary_mut(page_id)?
+                .set("Parent", self.pages_id);
+        }
+
+        let root = self.doc.get_dictionary_mut(self.pages_id)?;
+        root.set("Count", page_ids.len() as i64);
+        root.set(
+            "Kids",
+            page_ids
+                .into_iter()
+                .map(Object::Reference)
+                .collect::<Vec<_>>(),
+        );
+
+        Ok(())
+    }
+
+    /// Collapse nested Pages nodes, so that all pages are direct kids of root node
+    pub fn flatten_page_tree(&mut self) -> crate::Result<()> {
+        self.check_permission(Permission::Assemble)?;
+
+        let pages = self.doc.get_pages().into_values().collect();
+        self.set_flat_page_tree(pages)
+    }
+
+    /// Rebuild page tree as balanced tree whose nodes have at most FANOUT kids, so that
+    /// looking up page by number visits few nodes even in documents of thousands of pages
+    pub fn rebalance_page_tree(&mut self, fanout: usize) -> crate::Result<()> {
+        if fanout < 2 {
+            return Err(Error::InvalidArgument(format!(
+                "page tree nodes must have at least 2 kids: {}",
+                fanout
+            )));
+        }
+        self.flatten_page_tree()?;
+
+        // build levels bottom up as pairs of node and number of pages under it
+        let mut level: Vec<(ObjectId, i64)> = self
+            .doc
+            .get_pages()
+            .into_values()
+            .map(|id| (id, 1))
+            .collect();
+        let total = level.len() as i64;
+
+        while level.len() > fanout {
+            let mut next = Vec::with_capacity(level.len() / fanout + 1);
+
+            for chunk in level.chunks(fanout) {
+                let count: i64 = chunk.iter().map(|(_, count)| count).sum();
+                let kids: Vec<Object> = chunk.iter().map(|&(id, _)| id.into()).collect();
+                let node_id = self.doc.add_object(dictionary! {
+                    "Type" => "Pages",
+                    "Parent" => self.pages_id,
+                    "Kids" => kids,
+                    "Count" => count,
+                });
+
+                for &(id, _) in chunk {
+                    self.doc.get_dictionary_mut(id)?.set("Parent", node_id);
+                }
+                next.push((node_id, count));
+            }
+
+            level = next;
+        }
+
+        let root = self.doc.get_dictionary_mut(self.pages_id)?;
+        root.set("Count", total);
+        root.set(
+            "Kids",
+            level
+                .into_iter()
+                .map(|(id, _)| Object::Reference(id))
+                .collect::<Vec<_>>(),
+        );
+
+        Ok(())
+    }
+
+    /// Remove PAGES. objects only they used, like content streams, are left for `prune`
+    /// to collect, as other parts of document may still refer to them.
+    pub fn remove_pages(&mut self, pages: &[u32]) -> crate::Result<()> {
+        self.check_permission(Permission::Assemble)?;
+
+        self.doc.delete_pages(pages);
+        Ok(())
     }
 
     pub fn prune(&mut self) {
+        let _ = self.prune_resources();
+        let _ = self.dedup_images();
         let _ = self.doc.prune_objects();
         let _ = self.doc.renumber_objects();
     }
 
-    pub fn save<P: AsRef<Path>>(mut self, path: P) -> anyhow::Result<()> {
-        self.doc.save(path)?;
+    pub fn save<P: AsRef<Path>>(self, path: P) -> crate::Result<()> {
+        self.save_with_options(path, &SaveOptions::default())
+    }
+
+    pub fn save_with_options<P: AsRef<Path>>(
+        self,
+        path: P,
+        options: &SaveOptions,
+    ) -> crate::Result<()> {
+        std::fs::write(path, self.to_bytes_with_options(options)?)?;
         Ok(())
     }
 
-    pub fn to_bytes(mut self) -> anyhow::Result<Vec<u8>> {
-        let mut result = Vec::new();
-        self.doc.save_to(&mut result)?;
+    pub fn to_bytes(self) -> crate::Result<Vec<u8>> {
+        self.to_bytes_with_options(&SaveOptions::default())
+    }
+
+    pub fn to_bytes_with_options(mut self, options: &SaveOptions) -> crate::Result<Vec<u8>> {
+        self.update_mod_date()?;
+        if let Some(version) = options.version {
+            self.set_output_version(version, options)?;
+        }
+        if options.deterministic {
+            self.make_deterministic()?;
+        }
+
+        if let Some(password) = &options.attachment_password {
+            encryption::encrypt_attachments(&mut self.doc, password)?;
+        }
+
+        self.check_object_count()?;
+
+        let result = if options.object_streams {
+            writer::write_with_object_streams(&self.doc)?
+        } else {
+            let mut result = Vec::new();
+            self.doc.save_to(&mut result)?;
+            result
+        };
 
+        self.check_output_size(result.len())?;
+        log::info!("serialized document into {} bytes", result.len());
         Ok(result)
     }
 }
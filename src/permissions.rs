@@ -0,0 +1,94 @@
+use std::fmt;
+
+use lopdf::{Dictionary, Document, Object};
+
+/// User access permission of encrypted document (Table 22 of ISO 32000-1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    Print,
+    Modify,
+    Copy,
+    Annotate,
+    FillForms,
+    Extract,
+    Assemble,
+    PrintHighQuality,
+}
+
+impl Permission {
+    fn bit(&self) -> u32 {
+        let pos = match self {
+            Permission::Print => 3,
+            Permission::Modify => 4,
+            Permission::Copy => 5,
+            Permission::Annotate => 6,
+            Permission::FillForms => 9,
+            Permission::Extract => 10,
+            Permission::Assemble => 11,
+            Permission::PrintHighQuality => 12,
+        };
+        1 << (pos - 1)
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Permission::Print => "printing",
+            Permission::Modify => "modification",
+            Permission::Copy => "copying",
+            Permission::Annotate => "annotation",
+            Permission::FillForms => "form filling",
+            Permission::Extract => "content extraction",
+            Permission::Assemble => "document assembly",
+            Permission::PrintHighQuality => "high quality printing",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Permission flags (/P) of encrypted document
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permissions(u32);
+
+impl Permissions {
+    /// Get permissions of DOC, or None if not encrypted
+    pub fn from_document(doc: &Document) -> Option<Self> {
+        let p = encrypt_dict(doc)?.get(b"P").and_then(Object::as_i64).ok()?;
+        Some(Self(p as u32))
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            // assembly is also allowed by general modification bit
+            Permission::Assemble => {
+                self.0 & (Permission::Assemble.bit() | Permission::Modify.bit()) != 0
+            }
+            _ => self.0 & permission.bit() != 0,
+        }
+    }
+}
+
+pub(crate) fn encrypt_dict(doc: &Document) -> Option<&Dictionary> {
+    match doc.trailer.get(b"Encrypt").ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+/// Error returned when operation is forbidden by document permissions
+#[derive(Debug)]
+pub struct PermissionDenied(pub Permission);
+
+impl fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is forbidden by document permissions; owner password is required to override",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
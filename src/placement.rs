@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use lopdf::{ObjectId, Stream};
+
+use crate::{paper::PageSize, Pdf};
+
+/// How image is scaled into area of fixed size page
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fit {
+    /// Scale to fit whole image in area, keeping aspect ratio
+    #[default]
+    Contain,
+    /// Scale to fill whole area, keeping aspect ratio and cropping overflow
+    Cover,
+    /// Stretch to area, ignoring aspect ratio
+    Fill,
+    /// Keep actual size and center in area, cropping overflow
+    Center,
+}
+
+impl FromStr for Fit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "contain" => Ok(Self::Contain),
+            "cover" => Ok(Self::Cover),
+            "fill" => Ok(Self::Fill),
+            "center" => Ok(Self::Center),
+            _ => anyhow::bail!("unknown fit mode: {}", s),
+        }
+    }
+}
+
+/// Rectangle in points given by lower left corner and size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Fit {
+    /// Get rectangle to draw image of WIDTH x HEIGHT points in AREA.
+    /// result may extend beyond area for Cover and Center.
+    pub fn place(&self, width: f32, height: f32, area: Rect) -> Rect {
+        let (width, height) = match self {
+            Self::Fill => return area,
+            Self::Center => (width, height),
+            Self::Contain | Self::Cover => {
+                let x_scale = area.width / width;
+                let y_scale = area.height / height;
+                let scale = if *self == Self::Contain {
+                    x_scale.min(y_scale)
+                } else {
+                    x_scale.max(y_scale)
+                };
+                (width * scale, height * scale)
+            }
+        };
+
+        Rect::new(
+            area.x + (area.width - width) / 2.0,
+            area.y + (area.height - height) / 2.0,
+            width,
+            height,
+        )
+    }
+}
+
+/// Build content drawing XObject NAME at PLACED, clipped to AREA
+pub(crate) fn draw_clipped(name: &str, placed: Rect, area: Rect) -> String {
+    draw_rotated(name, placed, area, 0)
+}
+
+/// Build content drawing XObject NAME turned ROTATE degrees clockwise, a multiple of 90,
+/// so that it fills PLACED, clipped to AREA
+pub(crate) fn draw_rotated(name: &str, placed: Rect, area: Rect, rotate: i64) -> String {
+    let Rect {
+        x,
+        y,
+        width: w,
+        height: h,
+    } = placed;
+    // unit square of image is mapped so that its top row comes along the turned edge
+    let [a, b, c, d, e, f] = match rotate.rem_euclid(360) {
+        90 => [0.0, -h, w, 0.0, x, y + h],
+        180 => [-w, 0.0, 0.0, -h, x + w, y + h],
+        270 => [0.0, h, -w, 0.0, x + w, y],
+        _ => [w, 0.0, 0.0, h, x, y],
+    };
+
+    format!(
+        "q {} {} {} {} re W n {} {} {} {} {} {} cm /{} Do Q\n",
+        area.x, area.y, area.width, area.height, a, b, c, d, e, f, name
+    )
+}
+
+impl Pdf {
+    /// Add page of SIZE showing image XObject IMG_STREAM of WIDTH x HEIGHT points,
+    /// turned ROTATE degrees clockwise as told by EXIF orientation, and placed inside
+    /// page margin according to `image_options.fit`
+    pub(crate) fn add_placed_image_page(
+        &mut self,
+        img_stream: Stream,
+        (width, height): (f32, f32),
+        size: PageSize,
+        rotate: i64,
+    ) -> anyhow::Result<ObjectId> {
+        let margin = self.image_options.margin;
+        anyhow::ensure!(
+            size.width > margin * 2.0 && size.height > margin * 2.0,
+            "margin {} is too large for page size {}x{}",
+            margin,
+            size.width,
+            size.height
+        );
+
+        let area = Rect::new(
+            margin,
+            margin,
+            size.width - margin * 2.0,
+            size.height - margin * 2.0,
+        );
+        // turned image is fitted with its displayed size
+        let (width, height) = if rotate % 180 == 0 {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        let placed = self.image_options.fit.place(width, height, area);
+
+        let page_id = self.add_page(size.width.round() as u32, size.height.round() as u32)?;
+        let image_id = self.doc.add_object(img_stream);
+        self.add_page_resource(page_id, "XObject", "Image", image_id)?;
+
+        let content = draw_rotated("Image", placed, area, rotate);
+        self.doc
+            .change_page_content(page_id, content.into_bytes())?;
+
+        Ok(page_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_rotated_turns_top_of_image_clockwise() {
+        let placed = Rect::new(10.0, 20.0, 30.0, 40.0);
+
+        assert_eq!(
+            draw_rotated("Im", placed, placed, 90),
+            "q 10 20 30 40 re W n 0 -40 30 0 10 60 cm /Im Do Q\n"
+        );
+        assert_eq!(
+            draw_rotated("Im", placed, placed, 0),
+            draw_clipped("Im", placed, placed)
+        );
+    }
+}
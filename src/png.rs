@@ -8,13 +8,39 @@ pub struct PngInfo {
     pub interlace: bool,
     pub palette: Option<(Vec<u8>, u32)>,
     pub icc: Option<Vec<u8>>,
+    /// Image gamma from gAMA chunk
+    pub gamma: Option<f32>,
+    /// White point and red, green, blue primaries (x, y pairs) from cHRM chunk
+    pub chromaticities: Option<[f32; 8]>,
+    /// Rendering intent from sRGB chunk
+    pub srgb: Option<u8>,
+    /// Horizontal and vertical resolution in DPI from pHYs chunk
+    pub dpi: Option<(f32, f32)>,
+    /// Alpha of palette entries, or transparent color key, from tRNS chunk
+    pub transparency: Option<Vec<u8>>,
 }
 
 fn read_be_u32(input: &[u8], pos: usize) -> u32 {
     u32::from_be_bytes(input[pos..pos + 4].try_into().expect("not enough byte"))
 }
 
+/// Return data of the chunk starting at POS, checking that it fits in BYTES
+fn chunk_data(bytes: &[u8], pos: usize) -> anyhow::Result<(&str, &[u8])> {
+    if pos + 8 > bytes.len() {
+        anyhow::bail!("truncated PNG chunk");
+    }
+    let size = read_be_u32(bytes, pos) as usize;
+    let name = std::str::from_utf8(&bytes[pos + 4..pos + 8])?;
+    if size > bytes.len() - pos - 8 {
+        anyhow::bail!("PNG chunk {} exceeds file size", name);
+    }
+    Ok((name, &bytes[pos + 8..pos + 8 + size]))
+}
+
 pub fn get_info(bytes: &[u8]) -> anyhow::Result<PngInfo> {
+    if bytes.len() < 33 {
+        anyhow::bail!("truncated PNG header");
+    }
     let width = read_be_u32(bytes, 16);
     let height = read_be_u32(bytes, 20);
     let depth = bytes[24];
@@ -27,28 +53,44 @@ pub fn get_info(bytes: &[u8]) -> anyhow::Result<PngInfo> {
 
     let mut palette: Option<(Vec<u8>, u32)> = None;
     let mut icc: Option<Vec<u8>> = None;
+    let mut gamma: Option<f32> = None;
+    let mut chromaticities: Option<[f32; 8]> = None;
+    let mut srgb: Option<u8> = None;
+    let mut dpi: Option<(f32, f32)> = None;
+    let mut transparency: Option<Vec<u8>> = None;
     let mut pos = 33;
 
     loop {
-        let size = read_be_u32(bytes, pos);
-        let name = std::str::from_utf8(&bytes[pos + 4..pos + 8])?;
+        let (name, data) = chunk_data(bytes, pos)?;
 
         match name {
-            "PLTE" => {
-                palette = Some((bytes[pos + 8..pos + 8 + (size as usize)].into(), size / 3));
-                pos += 8 + size as usize + 4;
-            }
+            "PLTE" => palette = Some((data.into(), data.len() as u32 / 3)),
             "iCCP" => {
-                let icc_start = bytes[pos + 8..pos + 8 + (size as usize)]
-                    .into_iter()
-                    .position(|&x| x == b'\x00');
-                icc = icc_start
-                    .map(|start| bytes[pos + 8 + start + 1..pos + 8 + (size as usize)].into());
-                pos += 8 + size as usize + 4;
+                let icc_start = data.iter().position(|&x| x == b'\x00');
+                icc = icc_start.map(|start| data[start + 1..].into());
+            }
+            "gAMA" if data.len() >= 4 => {
+                gamma = Some(read_be_u32(data, 0) as f32 / 100000.0);
+            }
+            "cHRM" if data.len() >= 32 => {
+                let mut values = [0.0; 8];
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = read_be_u32(data, i * 4) as f32 / 100000.0;
+                }
+                chromaticities = Some(values);
             }
+            "sRGB" if !data.is_empty() => srgb = Some(data[0]),
+            // unit 1 is meter, 0 gives only aspect ratio
+            "pHYs" if data.len() >= 9 && data[8] == 1 => {
+                let x = read_be_u32(data, 0) as f32 * 0.0254;
+                let y = read_be_u32(data, 4) as f32 * 0.0254;
+                dpi = Some((x, y));
+            }
+            "tRNS" => transparency = Some(data.into()),
             "IDAT" => break,
-            _ => pos += 8 + size as usize + 4,
+            _ => {}
         }
+        pos += 8 + data.len() + 4;
     }
 
     Ok(PngInfo {
@@ -59,6 +101,11 @@ pub fn get_info(bytes: &[u8]) -> anyhow::Result<PngInfo> {
         interlace,
         palette,
         icc,
+        gamma,
+        chromaticities,
+        srgb,
+        dpi,
+        transparency,
     })
 }
 
@@ -67,17 +114,14 @@ pub fn get_idat(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
     let mut result = Vec::new();
 
     loop {
-        let size = read_be_u32(bytes, pos);
-        let name = std::str::from_utf8(&bytes[pos + 4..pos + 8])?;
+        let (name, data) = chunk_data(bytes, pos)?;
 
         match name {
-            "IDAT" => {
-                result.push(&bytes[pos + 8..pos + 8 + (size as usize)]);
-                pos += 8 + size as usize + 4;
-            }
+            "IDAT" => result.push(data),
             "IEND" => break,
-            _ => pos += 8 + size as usize + 4,
+            _ => {}
         }
+        pos += 8 + data.len() + 4;
     }
 
     Ok(result.concat())
@@ -0,0 +1,229 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::{
+    attributes::{media_box, page_attribute, page_box},
+    xobject, Pdf,
+};
+
+/// Minimum effective resolution of images for print
+const PRINT_MIN_DPI: f32 = 300.0;
+
+/// Fonts every viewer has, which do not have to be embedded outside print
+const STANDARD_FONTS: [&[u8]; 14] = [
+    b"Times-Roman",
+    b"Times-Bold",
+    b"Times-Italic",
+    b"Times-BoldItalic",
+    b"Helvetica",
+    b"Helvetica-Bold",
+    b"Helvetica-Oblique",
+    b"Helvetica-BoldOblique",
+    b"Courier",
+    b"Courier-Bold",
+    b"Courier-Oblique",
+    b"Courier-BoldOblique",
+    b"Symbol",
+    b"ZapfDingbats",
+];
+
+/// Set of preflight rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Commercial print: 300 DPI images, CMYK only, embedded fonts and bleed
+    Print,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "print" => Ok(Self::Print),
+            _ => anyhow::bail!("unknown preflight profile: {}", s),
+        }
+    }
+}
+
+/// Rule broken by document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// Page number where violation is found
+    pub page: u32,
+    /// Short name of rule, like "image-resolution"
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: [{}] {}", self.page, self.rule, self.message)
+    }
+}
+
+pub(crate) fn deref<'a>(doc: &'a Document, obj: &'a Object) -> &'a Object {
+    match obj {
+        Object::Reference(id) => doc.get_object(*id).unwrap_or(obj),
+        _ => obj,
+    }
+}
+
+fn is_rgb(doc: &Document, cs: &Object) -> bool {
+    match deref(doc, cs) {
+        Object::Name(name) => matches!(name.as_slice(), b"DeviceRGB" | b"CalRGB" | b"RGB"),
+        Object::Array(arr) => match arr.first().and_then(|x| x.as_name().ok()) {
+            Some(b"CalRGB") => true,
+            Some(b"ICCBased") => {
+                arr.get(1)
+                    .and_then(|x| deref(doc, x).as_stream().ok())
+                    .and_then(|x| x.dict.get(b"N").and_then(Object::as_i64).ok())
+                    == Some(3)
+            }
+            Some(b"Indexed" | b"I") => arr.get(1).map_or(false, |x| is_rgb(doc, x)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Get resource dictionary CATEGORY of page, including inherited one
+pub(crate) fn page_resource<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    category: &[u8],
+) -> Option<&'a Dictionary> {
+    let resources = page_attribute(doc, page_id, b"Resources")?.as_dict().ok()?;
+    deref(doc, resources.get(category).ok()?).as_dict().ok()
+}
+
+pub(crate) fn is_embedded(doc: &Document, font: &Dictionary) -> bool {
+    match font.get(b"Subtype").and_then(Object::as_name) {
+        // glyphs of Type3 fonts are defined in document itself
+        Ok(b"Type3") => true,
+        Ok(b"Type0") => font
+            .get(b"DescendantFonts")
+            .map(|x| deref(doc, x))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|x| x.first())
+            .and_then(|x| deref(doc, x).as_dict().ok())
+            .map_or(false, |x| is_embedded(doc, x)),
+        _ => font
+            .get(b"FontDescriptor")
+            .ok()
+            .and_then(|x| deref(doc, x).as_dict().ok())
+            .map_or(false, |x| {
+                x.has(b"FontFile") || x.has(b"FontFile2") || x.has(b"FontFile3")
+            }),
+    }
+}
+
+impl Pdf {
+    /// Check document against rules of PROFILE, returning violations in page order
+    pub fn preflight(&self, profile: Profile) -> anyhow::Result<Vec<Violation>> {
+        let Profile::Print = profile;
+        let mut result = vec![];
+
+        for (num, page_id) in self.doc.get_pages() {
+            let mut violation = |rule, message| {
+                result.push(Violation {
+                    page: num,
+                    rule,
+                    message,
+                })
+            };
+
+            let mut checked = BTreeSet::new();
+            for placement in xobject::image_placements(&self.doc, page_id)? {
+                let stream = self.doc.get_object(placement.id)?.as_stream()?;
+                let (w_pt, h_pt) = placement.size();
+                let width = stream.dict.get(b"Width").and_then(Object::as_i64)? as f32;
+                let height = stream.dict.get(b"Height").and_then(Object::as_i64)? as f32;
+
+                if w_pt > 0.0 && h_pt > 0.0 {
+                    let dpi = (width / (w_pt / 72.0)).min(height / (h_pt / 72.0));
+                    if dpi < PRINT_MIN_DPI {
+                        violation(
+                            "image-resolution",
+                            format!(
+                                "image {} {} is {:.0} DPI at placed size, below {}",
+                                placement.id.0, placement.id.1, dpi, PRINT_MIN_DPI
+                            ),
+                        );
+                    }
+                }
+
+                if checked.insert(placement.id) {
+                    if let Ok(cs) = stream.dict.get(b"ColorSpace") {
+                        if is_rgb(&self.doc, cs) {
+                            violation(
+                                "rgb-image",
+                                format!(
+                                    "image {} {} uses RGB color space",
+                                    placement.id.0, placement.id.1
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+
+            let content = self.doc.get_and_decode_page_content(page_id)?;
+            if content
+                .operations
+                .iter()
+                .any(|op| op.operator == "rg" || op.operator == "RG")
+            {
+                violation(
+                    "rgb-content",
+                    "page content paints with RGB colors".to_string(),
+                );
+            }
+
+            if let Some(fonts) = page_resource(&self.doc, page_id, b"Font") {
+                for (name, font) in fonts.iter() {
+                    let Ok(font) = deref(&self.doc, font).as_dict() else {
+                        continue;
+                    };
+
+                    if !is_embedded(&self.doc, font) {
+                        let base = font
+                            .get(b"BaseFont")
+                            .and_then(Object::as_name)
+                            .unwrap_or(name.as_slice());
+                        let standard = if STANDARD_FONTS.contains(&base) {
+                            " (standard font)"
+                        } else {
+                            ""
+                        };
+
+                        violation(
+                            "font-not-embedded",
+                            format!(
+                                "font {} is not embedded{}",
+                                String::from_utf8_lossy(base),
+                                standard
+                            ),
+                        );
+                    }
+                }
+            }
+
+            let media = media_box(&self.doc, page_id);
+            let trim = page_box(&self.doc, page_id, b"TrimBox");
+            let bleed = page_box(&self.doc, page_id, b"BleedBox").unwrap_or(media);
+            let has_bleed = trim.map_or(false, |trim| {
+                bleed[0] < trim[0] && bleed[1] < trim[1] && bleed[2] > trim[2] && bleed[3] > trim[3]
+            });
+            if !has_bleed {
+                violation(
+                    "no-bleed",
+                    "page has no bleed area outside TrimBox".to_string(),
+                );
+            }
+        }
+
+        Ok(result)
+    }
+}
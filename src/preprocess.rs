@@ -0,0 +1,55 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+
+use crate::{xobject, Pdf};
+
+/// Transformation applied to decoded images before they are embedded by `add_image`,
+/// `add_jpeg` and `add_png`. it is Send, so that Pdf holding it can be moved to other threads.
+pub trait ImagePreprocessor: Send {
+    fn process(&self, img: DynamicImage) -> anyhow::Result<DynamicImage>;
+}
+
+impl<F> ImagePreprocessor for F
+where
+    F: Fn(DynamicImage) -> anyhow::Result<DynamicImage> + Send,
+{
+    fn process(&self, img: DynamicImage) -> anyhow::Result<DynamicImage> {
+        self(img)
+    }
+}
+
+impl Pdf {
+    /// Register PREPROCESSOR, which runs after ones registered before
+    pub fn add_preprocessor<P: ImagePreprocessor + 'static>(&mut self, preprocessor: P) {
+        self.preprocessors.push(Box::new(preprocessor));
+    }
+
+    /// Run registered preprocessors on BYTES and re-encode result as FORMAT.
+    /// returns None if there is no preprocessor, so original data can be embedded as is.
+    pub(crate) fn preprocess(
+        &self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.preprocessors.is_empty() {
+            return Ok(None);
+        }
+
+        let mut img = image::load_from_memory_with_format(bytes, format)?;
+        for preprocessor in &self.preprocessors {
+            img = preprocessor.process(img)?;
+        }
+
+        let result = match format {
+            ImageFormat::Jpeg => xobject::jpeg_stream(&img, self.image_options.quality())?.content,
+            _ => {
+                let mut result = Vec::new();
+                img.write_to(&mut Cursor::new(&mut result), ImageFormat::Png)?;
+                result
+            }
+        };
+
+        Ok(Some(result))
+    }
+}
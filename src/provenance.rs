@@ -0,0 +1,110 @@
+use lopdf::{dictionary, Dictionary, Object};
+
+use crate::{
+    pdf::{decode_text_string, text_string},
+    permissions::Permission,
+    Pdf,
+};
+
+/// Key of our data in /PieceInfo
+const PIECE_KEY: &[u8] = b"pdftool";
+
+/// Record of how document was generated, stored in catalog /PieceInfo
+#[derive(Clone, Debug)]
+pub struct Provenance {
+    /// Version of pdftool which wrote document
+    pub version: String,
+    /// Name of embedded manifest
+    pub manifest_name: Option<String>,
+    /// Content of embedded manifest
+    pub manifest: Option<Vec<u8>>,
+}
+
+impl Pdf {
+    fn piece_info(&self) -> Option<&Dictionary> {
+        let mut obj = self.doc.catalog().ok()?.get(b"PieceInfo").ok()?;
+        for key in [PIECE_KEY, &b"Private"[..]] {
+            if let Object::Reference(id) = obj {
+                obj = self.doc.get_object(*id).ok()?;
+            }
+            obj = obj.as_dict().ok()?.get(key).ok()?;
+        }
+
+        match obj {
+            Object::Reference(id) => self.doc.get_dictionary(*id).ok(),
+            _ => obj.as_dict().ok(),
+        }
+    }
+
+    /// Record tool version in /PieceInfo, and embed MANIFEST (file name and data)
+    /// used to generate document as source attachment
+    pub fn embed_provenance(&mut self, manifest: Option<(&str, &[u8])>) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let mut private = dictionary! {
+            "Version" => text_string(env!("CARGO_PKG_VERSION")),
+        };
+
+        if let Some((name, data)) = manifest {
+            let spec_id = self.add_attachment(name, data)?;
+            self.doc
+                .get_dictionary_mut(spec_id)?
+                .set("AFRelationship", "Source");
+            private.set("Manifest", text_string(name));
+        }
+
+        let data = dictionary! {
+            "LastModified" => self.now(),
+            "Private" => private,
+        };
+
+        // data of other applications in PieceInfo is kept, even when it is indirect
+        let info_id = match self.doc.catalog()?.get(b"PieceInfo") {
+            Ok(Object::Reference(id)) => Some(*id).filter(|&x| self.doc.get_dictionary(x).is_ok()),
+            _ => None,
+        };
+        match info_id {
+            Some(id) => self.doc.get_dictionary_mut(id)?.set(PIECE_KEY, data),
+            None => {
+                let catalog = self.catalog_mut()?;
+                match catalog.get_mut(b"PieceInfo") {
+                    Ok(Object::Dictionary(info)) => info.set(PIECE_KEY, data),
+                    _ => catalog.set("PieceInfo", dictionary! { PIECE_KEY => data }),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read back provenance recorded by `embed_provenance`
+    pub fn provenance(&self) -> anyhow::Result<Option<Provenance>> {
+        let Some(private) = self.piece_info() else {
+            return Ok(None);
+        };
+
+        let text = |key: &[u8]| {
+            private
+                .get(key)
+                .and_then(Object::as_str)
+                .ok()
+                .map(decode_text_string)
+        };
+
+        let manifest_name = text(b"Manifest");
+        let manifest = match &manifest_name {
+            Some(name) => self
+                .attachments()?
+                .into_iter()
+                .find(|x| &x.name == name)
+                .map(|x| x.data),
+            None => None,
+        };
+
+        Ok(Some(Provenance {
+            version: text(b"Version").unwrap_or_default(),
+            manifest_name,
+            manifest,
+        }))
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::Pdf;
+
+/// Resource categories pruned of entries unused by page content
+const CATEGORIES: [&[u8]; 3] = [b"Font", b"XObject", b"ExtGState"];
+
+/// Where resource dictionary of page is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ResourcesAt {
+    /// Indirect dictionary object
+    Object(ObjectId),
+    /// Direct dictionary in /Resources of page or Pages node
+    Holder(ObjectId),
+}
+
+/// Where dictionary of one resource category is stored
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum CategoryAt {
+    Object(ObjectId),
+    /// Direct dictionary under category name in resource dictionary
+    Resources(ResourcesAt, Vec<u8>),
+}
+
+/// Find where resources of page PAGE_ID are stored, looking up page tree
+fn resources_at(doc: &Document, page_id: ObjectId) -> Option<ResourcesAt> {
+    let mut id = page_id;
+    let mut visited = BTreeSet::new();
+
+    while visited.insert(id) {
+        let dict = doc.get_dictionary(id).ok()?;
+        match dict.get(b"Resources") {
+            Ok(Object::Reference(resources)) => return Some(ResourcesAt::Object(*resources)),
+            Ok(_) => return Some(ResourcesAt::Holder(id)),
+            Err(_) => id = dict.get(b"Parent").and_then(Object::as_reference).ok()?,
+        }
+    }
+
+    None
+}
+
+fn resources_dict(doc: &Document, at: ResourcesAt) -> Option<&Dictionary> {
+    match at {
+        ResourcesAt::Object(id) => doc.get_dictionary(id).ok(),
+        ResourcesAt::Holder(id) => doc
+            .get_dictionary(id)
+            .and_then(|x| x.get(b"Resources"))
+            .and_then(Object::as_dict)
+            .ok(),
+    }
+}
+
+fn resources_dict_mut(doc: &mut Document, at: ResourcesAt) -> Option<&mut Dictionary> {
+    match at {
+        ResourcesAt::Object(id) => doc.get_dictionary_mut(id).ok(),
+        ResourcesAt::Holder(id) => doc
+            .get_dictionary_mut(id)
+            .and_then(|x| x.get_mut(b"Resources"))
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    }
+}
+
+/// Names of resources in each category used by operators of page content,
+/// or None if content can't be decoded
+fn used_names(
+    doc: &Document,
+    page_id: ObjectId,
+) -> Option<BTreeMap<&'static [u8], BTreeSet<Vec<u8>>>> {
+    let content = doc.get_and_decode_page_content(page_id).ok()?;
+    let mut result: BTreeMap<&'static [u8], BTreeSet<Vec<u8>>> = BTreeMap::new();
+
+    for op in content.operations {
+        let category: &'static [u8] = match op.operator.as_str() {
+            "Tf" => b"Font",
+            "Do" => b"XObject",
+            "gs" => b"ExtGState",
+            _ => continue,
+        };
+        if let Some(Ok(name)) = op.operands.first().map(Object::as_name) {
+            result.entry(category).or_default().insert(name.to_vec());
+        }
+    }
+
+    Some(result)
+}
+
+/// Find indirect resource and category dictionaries referred to from objects other
+/// than pages, like form XObjects and Type3 fonts, which must be left as they are
+fn shared_outside_pages(doc: &Document) -> BTreeSet<ObjectId> {
+    let mut result = BTreeSet::new();
+
+    for obj in doc.objects.values() {
+        let dict = match obj {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => continue,
+        };
+        if matches!(dict.type_name(), Ok("Page" | "Pages")) {
+            continue;
+        }
+
+        let resources = match dict.get(b"Resources") {
+            Ok(Object::Reference(id)) => {
+                result.insert(*id);
+                doc.get_dictionary(*id).ok()
+            }
+            Ok(Object::Dictionary(dict)) => Some(dict),
+            _ => None,
+        };
+        for category in resources.into_iter().flat_map(|x| x.iter()) {
+            if let Object::Reference(id) = category.1 {
+                result.insert(*id);
+            }
+        }
+    }
+
+    result
+}
+
+impl Pdf {
+    /// Remove entries of Font, XObject and ExtGState resources which no page content
+    /// uses, so that `prune` can drop objects they refer to. resources shared by pages
+    /// keep entries used by any of them. returns number of removed entries.
+    pub(crate) fn prune_resources(&mut self) -> usize {
+        let shared = shared_outside_pages(&self.doc);
+
+        // None keeps all entries, when content of some page using them can't be read
+        let mut used: BTreeMap<CategoryAt, Option<BTreeSet<Vec<u8>>>> = BTreeMap::new();
+
+        for page_id in self.doc.get_pages().into_values() {
+            let Some(at) = resources_at(&self.doc, page_id) else {
+                continue;
+            };
+            let Some(resources) = resources_dict(&self.doc, at) else {
+                continue;
+            };
+            let names = used_names(&self.doc, page_id);
+
+            for category in CATEGORIES {
+                let location = match resources.get(category) {
+                    Ok(Object::Reference(id)) => CategoryAt::Object(*id),
+                    Ok(Object::Dictionary(_)) => CategoryAt::Resources(at, category.to_vec()),
+                    _ => continue,
+                };
+
+                let entry = used
+                    .entry(location)
+                    .or_insert_with(|| Some(BTreeSet::new()));
+                match (&names, entry) {
+                    (Some(names), Some(set)) => {
+                        set.extend(names.get(category).into_iter().flatten().cloned())
+                    }
+                    (None, entry) => *entry = None,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for (location, names) in used {
+            let is_shared = match &location {
+                CategoryAt::Object(id) => shared.contains(id),
+                CategoryAt::Resources(ResourcesAt::Object(id), _) => shared.contains(id),
+                CategoryAt::Resources(ResourcesAt::Holder(_), _) => false,
+            };
+            let (Some(names), false) = (names, is_shared) else {
+                continue;
+            };
+
+            let dict = match &location {
+                CategoryAt::Object(id) => self.doc.get_dictionary_mut(*id).ok(),
+                CategoryAt::Resources(at, category) => resources_dict_mut(&mut self.doc, *at)
+                    .and_then(|x| x.get_mut(category).ok())
+                    .and_then(|x| x.as_dict_mut().ok()),
+            };
+            let Some(dict) = dict else {
+                continue;
+            };
+
+            let unused: Vec<Vec<u8>> = dict
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| !names.contains(name))
+                .collect();
+            for name in unused {
+                log::debug!(
+                    "removing unused resource {}",
+                    String::from_utf8_lossy(&name)
+                );
+                dict.remove(&name);
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+}
@@ -0,0 +1,857 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use lopdf::{
+    content::{Content, Operation},
+    Dictionary, Document, Object, ObjectId, Stream,
+};
+
+use crate::{
+    appearance::{self, page_annotations},
+    attributes::page_attribute,
+    flatten::transform_rect,
+    limits::LimitExceeded,
+    permissions::Permission,
+    preflight::{deref, page_resource},
+    xobject::{self, as_number, multiply, IDENTITY},
+    Pdf,
+};
+
+/// Share of font size text is assumed to reach below baseline and above it
+const DESCENT: f32 = 0.25;
+const ASCENT: f32 = 1.0;
+
+/// Full keys of abbreviated keys of inline image dictionary
+const INLINE_KEYS: [(&[u8], &str); 10] = [
+    (b"BPC", "BitsPerComponent"),
+    (b"CS", "ColorSpace"),
+    (b"D", "Decode"),
+    (b"DP", "DecodeParms"),
+    (b"F", "Filter"),
+    (b"H", "Height"),
+    (b"IM", "ImageMask"),
+    (b"I", "Interpolate"),
+    (b"L", "Length"),
+    (b"W", "Width"),
+];
+
+/// Full names of abbreviated color spaces and filters of inline images
+const INLINE_NAMES: [(&[u8], &str); 11] = [
+    (b"G", "DeviceGray"),
+    (b"RGB", "DeviceRGB"),
+    (b"CMYK", "DeviceCMYK"),
+    (b"I", "Indexed"),
+    (b"AHx", "ASCIIHexDecode"),
+    (b"A85", "ASCII85Decode"),
+    (b"LZW", "LZWDecode"),
+    (b"Fl", "FlateDecode"),
+    (b"RL", "RunLengthDecode"),
+    (b"CCF", "CCITTFaxDecode"),
+    (b"DCT", "DCTDecode"),
+];
+
+fn is_space(x: u8) -> bool {
+    b" \t\r\n\x0c\x00".contains(&x)
+}
+
+/// Check whether CONTENT has operator OP at POS, delimited on both sides
+fn is_operator(content: &[u8], pos: usize, op: &[u8]) -> bool {
+    let before = pos == 0 || is_space(content[pos - 1]) || b")>]".contains(&content[pos - 1]);
+    let after = content
+        .get(pos + op.len())
+        .map_or(true, |&x| is_space(x) || b"()<>[]/%".contains(&x));
+    content[pos..].starts_with(op) && before && after
+}
+
+/// Position in CONTENT after string or comment starting at POS, or None if there is none
+fn skip_string(content: &[u8], pos: usize) -> Option<usize> {
+    match content[pos] {
+        b'%' => Some(
+            content[pos..]
+                .iter()
+                .position(|&x| x == b'\r' || x == b'\n')
+                .map_or(content.len(), |x| pos + x),
+        ),
+        b'(' => {
+            let mut depth = 0;
+            let mut i = pos;
+            while i < content.len() {
+                match content[i] {
+                    b'\\' => i += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            Some(content.len())
+        }
+        b'<' if content.get(pos + 1) != Some(&b'<') => Some(
+            content[pos..]
+                .iter()
+                .position(|&x| x == b'>')
+                .map_or(content.len(), |x| pos + x + 1),
+        ),
+        _ => None,
+    }
+}
+
+/// Expand abbreviated names of inline image, like /G for /DeviceGray
+fn expand_inline_name(obj: Object) -> Object {
+    match obj {
+        Object::Name(name) => match INLINE_NAMES
+            .iter()
+            .find(|(short, _)| *short == name.as_slice())
+        {
+            Some((_, full)) => Object::Name(full.as_bytes().to_vec()),
+            None => Object::Name(name),
+        },
+        Object::Array(arr) => Object::Array(arr.into_iter().map(expand_inline_name).collect()),
+        obj => obj,
+    }
+}
+
+/// Parse dictionary of inline image from text between BI and ID into that of image
+/// XObject, taking named color space from COLOR_SPACES of page
+fn inline_image_dict(text: &[u8], color_spaces: Option<&Dictionary>) -> Option<Dictionary> {
+    let mut wrapped = b"<<".to_vec();
+    wrapped.extend_from_slice(text);
+    wrapped.extend_from_slice(b">> BI");
+    let content = Content::decode(&wrapped).ok()?;
+    let Some(Object::Dictionary(inline)) = content.operations.first()?.operands.first() else {
+        return None;
+    };
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", "XObject");
+    dict.set("Subtype", "Image");
+    for (key, value) in inline.iter() {
+        let key = INLINE_KEYS
+            .iter()
+            .find(|(short, _)| *short == key.as_slice())
+            .map_or(&key[..], |(_, full)| full.as_bytes());
+        let mut value = expand_inline_name(value.clone());
+        if key == b"ColorSpace" {
+            if let Object::Name(name) = &value {
+                if let Some(cs) = color_spaces.and_then(|x| x.get(name).ok()) {
+                    value = cs.clone();
+                }
+            }
+        }
+        dict.set(key.to_vec(), value);
+    }
+
+    Some(dict)
+}
+
+/// Find inline images (BI ... ID ... EI) in CONTENT, returning their images and content
+/// with each of them replaced by `NAME Do` of image, where name is given by NAME_OF for
+/// its position. None if there are no inline images.
+fn extract_inline_images(
+    content: &[u8],
+    color_spaces: Option<&Dictionary>,
+    mut name_of: impl FnMut(usize) -> String,
+) -> Option<(Vec<u8>, Vec<(String, Stream)>)> {
+    let mut result = vec![];
+    let mut images = vec![];
+    let (mut pos, mut copied) = (0, 0);
+
+    while pos < content.len() {
+        if let Some(next) = skip_string(content, pos) {
+            pos = next;
+            continue;
+        }
+        if !is_operator(content, pos, b"BI") {
+            pos += 1;
+            continue;
+        }
+
+        let dict_start = pos + 2;
+        let mut i = dict_start;
+        while i < content.len() && !is_operator(content, i, b"ID") {
+            i = skip_string(content, i).unwrap_or(i + 1);
+        }
+        // single white space separates ID from data
+        let data_start = (i + 3).min(content.len());
+        let dict = inline_image_dict(&content[dict_start..i], color_spaces);
+
+        let length = dict
+            .as_ref()
+            .and_then(|x| x.get(b"Length").and_then(Object::as_i64).ok())
+            .and_then(|x| usize::try_from(x).ok())
+            .filter(|&x| data_start + x <= content.len());
+        let data_end = match length {
+            Some(length) => data_start + length,
+            None => (data_start..content.len())
+                .find(|&x| is_space(content[x]) && is_operator(content, x + 1, b"EI"))
+                .unwrap_or(content.len()),
+        };
+        let end = (data_end..content.len())
+            .find(|&x| is_operator(content, x, b"EI"))
+            .map_or(content.len(), |x| x + 2);
+
+        result.extend_from_slice(&content[copied..pos]);
+        if let Some(mut dict) = dict {
+            dict.remove(b"Length");
+            let name = name_of(images.len());
+            result.extend_from_slice(format!("/{} Do", name).as_bytes());
+            images.push((
+                name,
+                Stream::new(dict, content[data_start..data_end].to_vec()),
+            ));
+        } else {
+            log::warn!("removing inline image with invalid dictionary");
+        }
+        pos = end;
+        copied = end;
+    }
+
+    if copied == 0 {
+        return None;
+    }
+    result.extend_from_slice(&content[copied..]);
+    Some((result, images))
+}
+
+fn intersects(a: [f32; 4], b: [f32; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+fn numbers(operands: &[Object]) -> Vec<f32> {
+    operands.iter().filter_map(as_number).collect()
+}
+
+fn array_numbers(dict: &Dictionary, key: &[u8]) -> Vec<f32> {
+    dict.get(key)
+        .and_then(Object::as_array)
+        .map(|x| numbers(x))
+        .unwrap_or_default()
+}
+
+/// Widths of glyphs of font, used to estimate where text is drawn
+#[derive(Clone, Debug)]
+struct FontMetrics {
+    /// Composite font with 2 byte codes
+    two_byte: bool,
+    first_char: i64,
+    /// Widths in 1/1000 em from FirstChar
+    widths: Vec<f32>,
+    /// Width of codes out of Widths
+    missing: f32,
+}
+
+impl Default for FontMetrics {
+    fn default() -> Self {
+        Self {
+            two_byte: false,
+            first_char: 0,
+            widths: vec![],
+            missing: 1000.0,
+        }
+    }
+}
+
+impl FontMetrics {
+    fn new(doc: &Document, font: &Dictionary) -> Self {
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Type0"[..]) {
+            // CJK glyphs are usually full width, so widths are not read to stay on safe side
+            return Self {
+                two_byte: true,
+                missing: 1000.0,
+                ..Self::default()
+            };
+        }
+
+        let widths = font
+            .get(b"Widths")
+            .map(|x| deref(doc, x))
+            .and_then(Object::as_array)
+            .map(|x| x.iter().filter_map(|x| as_number(deref(doc, x))).collect())
+            .unwrap_or_default();
+        let descriptor = font
+            .get(b"FontDescriptor")
+            .map(|x| deref(doc, x))
+            .and_then(Object::as_dict)
+            .ok();
+        // glyphs of unknown width are taken as wide as font bbox, to stay on safe side
+        let conservative = match descriptor.map(|x| array_numbers(x, b"FontBBox")).as_deref() {
+            Some(&[x0, _, x1, _]) if (x1 - x0).abs() > 0.0 => (x1 - x0).abs(),
+            _ => 1000.0,
+        };
+        let missing = if widths.is_empty() {
+            conservative
+        } else {
+            descriptor
+                .and_then(|x| x.get(b"MissingWidth").ok())
+                .and_then(as_number)
+                .filter(|&x| x > 0.0)
+                .unwrap_or(conservative)
+        };
+
+        Self {
+            two_byte: false,
+            first_char: font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0),
+            widths,
+            missing,
+        }
+    }
+
+    fn codes(&self, bytes: &[u8]) -> Vec<u16> {
+        if self.two_byte {
+            bytes
+                .chunks(2)
+                .map(|x| u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)]))
+                .collect()
+        } else {
+            bytes.iter().map(|&x| x as u16).collect()
+        }
+    }
+
+    fn width(&self, code: u16) -> f32 {
+        usize::try_from(code as i64 - self.first_char)
+            .ok()
+            .and_then(|i| self.widths.get(i))
+            .copied()
+            .unwrap_or(self.missing)
+    }
+}
+
+/// Text state parameters, saved and restored with graphics state
+#[derive(Clone, Debug)]
+struct TextState {
+    font: FontMetrics,
+    size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    /// Horizontal scaling as ratio
+    scale: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            font: FontMetrics::default(),
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+impl TextState {
+    /// Horizontal displacement in text space by showing string BYTES
+    fn advance(&self, bytes: &[u8]) -> f32 {
+        self.font
+            .codes(bytes)
+            .into_iter()
+            .map(|code| {
+                let word = if !self.font.two_byte && code == 32 {
+                    self.word_spacing
+                } else {
+                    0.0
+                };
+                (self.font.width(code) / 1000.0 * self.size + self.char_spacing + word) * self.scale
+            })
+            .sum()
+    }
+
+    /// Horizontal displacement of operand of Tj or TJ
+    fn advance_of(&self, operand: &Object) -> f32 {
+        match operand {
+            Object::String(bytes, _) => self.advance(bytes),
+            Object::Array(arr) => arr
+                .iter()
+                .map(|x| match x {
+                    Object::String(bytes, _) => self.advance(bytes),
+                    _ => -as_number(x).unwrap_or(0.0) / 1000.0 * self.size * self.scale,
+                })
+                .sum(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Content of page with parts within redaction areas removed
+struct Redacted {
+    operations: Vec<Operation>,
+    /// Text showing operators removed
+    text: usize,
+    /// Images and forms removed or replaced, by resource name
+    xobjects: BTreeSet<Vec<u8>>,
+}
+
+impl Pdf {
+    /// Turn inline images of page PAGE_ID into image XObjects drawn at same place, so that
+    /// they are redacted like those
+    fn convert_inline_images(&mut self, page_id: ObjectId) -> anyhow::Result<()> {
+        let content = self.doc.get_page_content(page_id)?;
+        let color_spaces = page_resource(&self.doc, page_id, b"ColorSpace").cloned();
+        let next_id = self.doc.max_id + 1;
+        let converted = extract_inline_images(&content, color_spaces.as_ref(), |i| {
+            format!("PdftoolInline{}", next_id as usize + i)
+        });
+        let Some((content, images)) = converted else {
+            return Ok(());
+        };
+
+        for (name, image) in images {
+            let id = self.doc.add_object(image);
+            self.add_page_resource(page_id, "XObject", &name, id)?;
+        }
+        self.doc.change_page_content(page_id, content)?;
+        log::info!("converted inline images to XObjects for redaction");
+
+        Ok(())
+    }
+
+    /// Remove text showing operators drawn within RECTS of page PAGE_ID, black out areas
+    /// of images within them, and remove form XObjects overlapping them
+    fn redact_content(
+        &mut self,
+        page_id: ObjectId,
+        rects: &[[f32; 4]],
+    ) -> anyhow::Result<Redacted> {
+        let content = self.doc.get_and_decode_page_content(page_id)?;
+        let xobjects = xobject::page_xobjects(&self.doc, page_id);
+        let fonts: BTreeMap<Vec<u8>, FontMetrics> = page_resource(&self.doc, page_id, b"Font")
+            .map(|fonts| {
+                fonts
+                    .iter()
+                    .filter_map(|(name, font)| {
+                        let font = deref(&self.doc, font).as_dict().ok()?;
+                        Some((name.clone(), FontMetrics::new(&self.doc, font)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut result = Redacted {
+            operations: vec![],
+            text: 0,
+            xobjects: BTreeSet::new(),
+        };
+        let mut stack = vec![];
+        let mut ctm = IDENTITY;
+        let mut state = TextState::default();
+        let mut tm = IDENTITY;
+        let mut tlm = IDENTITY;
+
+        for op in content.operations {
+            let operands = numbers(&op.operands);
+
+            match op.operator.as_str() {
+                "q" => stack.push((ctm, state.clone())),
+                "Q" => (ctm, state) = stack.pop().unwrap_or((IDENTITY, TextState::default())),
+                "cm" => {
+                    if let [a, b, c, d, e, f] = operands[..] {
+                        ctm = multiply([a, b, c, d, e, f], ctm);
+                    }
+                }
+                "BT" => {
+                    tm = IDENTITY;
+                    tlm = IDENTITY;
+                }
+                "Tf" => {
+                    if let Some(Ok(name)) = op.operands.first().map(Object::as_name) {
+                        state.font = fonts.get(name).cloned().unwrap_or_default();
+                    }
+                    state.size = operands.first().copied().unwrap_or(0.0);
+                }
+                "Tc" => state.char_spacing = operands.first().copied().unwrap_or(0.0),
+                "Tw" => state.word_spacing = operands.first().copied().unwrap_or(0.0),
+                "Tz" => state.scale = operands.first().copied().unwrap_or(100.0) / 100.0,
+                "TL" => state.leading = operands.first().copied().unwrap_or(0.0),
+                "Ts" => state.rise = operands.first().copied().unwrap_or(0.0),
+                "Td" | "TD" => {
+                    if let [tx, ty] = operands[..] {
+                        if op.operator == "TD" {
+                            state.leading = -ty;
+                        }
+                        tlm = multiply([1.0, 0.0, 0.0, 1.0, tx, ty], tlm);
+                        tm = tlm;
+                    }
+                }
+                "Tm" => {
+                    if let [a, b, c, d, e, f] = operands[..] {
+                        tlm = [a, b, c, d, e, f];
+                        tm = tlm;
+                    }
+                }
+                "T*" => {
+                    tlm = multiply([1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], tlm);
+                    tm = tlm;
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let mut prefix = vec![];
+                    if op.operator == "\"" {
+                        if let [aw, ac, ..] = operands[..] {
+                            state.word_spacing = aw;
+                            state.char_spacing = ac;
+                            prefix.push(Operation::new("Tw", vec![aw.into()]));
+                            prefix.push(Operation::new("Tc", vec![ac.into()]));
+                        }
+                    }
+                    if op.operator == "'" || op.operator == "\"" {
+                        tlm = multiply([1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], tlm);
+                        tm = tlm;
+                        prefix.push(Operation::new("T*", vec![]));
+                    }
+
+                    let advance = op.operands.last().map_or(0.0, |x| state.advance_of(x));
+                    let text_box = [
+                        advance.min(0.0),
+                        state.rise - DESCENT * state.size.abs(),
+                        advance.max(0.0),
+                        state.rise + ASCENT * state.size.abs(),
+                    ];
+                    let area = transform_rect(text_box, multiply(tm, ctm));
+                    tm = multiply([1.0, 0.0, 0.0, 1.0, advance, 0.0], tm);
+
+                    if !rects.iter().any(|&x| intersects(area, x)) {
+                        result.operations.push(op);
+                        continue;
+                    }
+
+                    // text is replaced by empty move, so following text stays in place
+                    result.text += 1;
+                    result.operations.extend(prefix);
+                    let unit = state.size * state.scale;
+                    if unit != 0.0 {
+                        let shift = -advance / unit * 1000.0;
+                        result.operations.push(Operation::new(
+                            "TJ",
+                            vec![Object::Array(vec![Object::Real(shift)])],
+                        ));
+                    }
+                    continue;
+                }
+                "Do" => {
+                    let target = op
+                        .operands
+                        .first()
+                        .and_then(|x| x.as_name().ok())
+                        .and_then(|name| Some((name.to_vec(), *xobjects.get(name)?)));
+                    let Some((name, id)) = target else {
+                        result.operations.push(op);
+                        continue;
+                    };
+
+                    let dict = &self.doc.get_object(id)?.as_stream()?.dict;
+                    let is_image = xobject::is_image(&self.doc, id);
+                    let area = if is_image {
+                        transform_rect([0.0, 0.0, 1.0, 1.0], ctm)
+                    } else {
+                        let matrix = match array_numbers(dict, b"Matrix")[..] {
+                            [a, b, c, d, e, f] => [a, b, c, d, e, f],
+                            _ => IDENTITY,
+                        };
+                        let bbox = match array_numbers(dict, b"BBox")[..] {
+                            [x0, y0, x1, y1] => [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)],
+                            _ => [f32::MIN, f32::MIN, f32::MAX, f32::MAX],
+                        };
+                        transform_rect(bbox, multiply(matrix, ctm))
+                    };
+
+                    if !rects.iter().any(|&x| intersects(area, x)) {
+                        result.operations.push(op);
+                        continue;
+                    }
+
+                    result.xobjects.insert(name.clone());
+                    if is_image {
+                        if let Some(new_name) = self.redact_image(page_id, id, ctm, rects)? {
+                            result
+                                .operations
+                                .push(Operation::new("Do", vec![Object::Name(new_name)]));
+                        }
+                    } else {
+                        log::warn!(
+                            "removing form {} overlapping redaction area, as a whole",
+                            String::from_utf8_lossy(&name)
+                        );
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            result.operations.push(op);
+        }
+
+        Ok(result)
+    }
+
+    /// Copy image ID drawn with CTM, painting areas of it within RECTS black, and register
+    /// it to page PAGE_ID. returns resource name of copy, or None if image can't be
+    /// redacted partially and should be removed.
+    fn redact_image(
+        &mut self,
+        page_id: ObjectId,
+        id: ObjectId,
+        ctm: [f32; 6],
+        rects: &[[f32; 4]],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let [a, b, c, d, ..] = ctm;
+        if b != 0.0 || c != 0.0 || a == 0.0 || d == 0.0 {
+            log::warn!("removing rotated or skewed image overlapping redaction area");
+            return Ok(None);
+        }
+
+        let stream = self.doc.get_object(id)?.as_stream()?;
+        let mut img = match self.decode_image(stream) {
+            Ok(img) => img,
+            Err(err) if err.is::<LimitExceeded>() => return Err(err),
+            Err(err) => {
+                log::warn!("removing image overlapping redaction area: {}", err);
+                return Ok(None);
+            }
+        };
+        let smask = stream
+            .dict
+            .get(b"SMask")
+            .and_then(Object::as_reference)
+            .ok();
+        paint_rects(&mut img, ctm, rects, Rgba([0, 0, 0, 255]));
+
+        let mut redacted = xobject::flate_stream(&img)?;
+        if let Some(smask_id) = smask {
+            // soft mask keeps shape of redacted content, so it is made opaque there too
+            let mask = self
+                .doc
+                .get_object(smask_id)
+                .and_then(Object::as_stream)
+                .map_err(anyhow::Error::from)
+                .and_then(|x| self.decode_image(x));
+            match mask {
+                Ok(mut mask) => {
+                    paint_rects(&mut mask, ctm, rects, Rgba([255, 255, 255, 255]));
+                    let mask_id = self.doc.add_object(xobject::flate_stream(&mask)?);
+                    redacted.dict.set("SMask", mask_id);
+                }
+                Err(err) => log::warn!("dropping soft mask of redacted image: {}", err),
+            }
+        }
+        let new_id = self.doc.add_object(redacted);
+        let name = format!("PdftoolRedacted{}", new_id.0);
+        self.add_page_resource(page_id, "XObject", &name, new_id)?;
+
+        Ok(Some(name.into_bytes()))
+    }
+
+    /// Remove annotations of page PAGE_ID overlapping RECTS, as their contents can't be
+    /// redacted partially. form field widgets are detached from their fields, and popups
+    /// of removed annotations go with them. returns number of removed annotations.
+    fn redact_annotations(
+        &mut self,
+        page_id: ObjectId,
+        rects: &[[f32; 4]],
+    ) -> anyhow::Result<usize> {
+        let annots = page_annotations(&self.doc, page_id);
+        let mut removed: Vec<ObjectId> = annots
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let area = self.doc.get_dictionary(id).ok().and_then(appearance::rect);
+                area.map_or(false, |area| rects.iter().any(|&x| intersects(area, x)))
+            })
+            .collect();
+
+        let popups: Vec<ObjectId> = annots
+            .iter()
+            .copied()
+            .filter(|id| !removed.contains(id))
+            .filter(|&id| {
+                let Ok(annot) = self.doc.get_dictionary(id) else {
+                    return false;
+                };
+                let is_popup =
+                    annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Popup"[..]);
+                let parent = annot.get(b"Parent").and_then(Object::as_reference).ok();
+                is_popup && parent.map_or(false, |x| removed.contains(&x))
+            })
+            .collect();
+        removed.extend(popups);
+
+        for &id in &removed {
+            let annot = self.doc.get_dictionary(id)?;
+            if annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Widget"[..]) {
+                self.remove_form_field(id)?;
+            }
+        }
+        self.remove_annotations(page_id, &removed)?;
+
+        Ok(removed.len())
+    }
+
+    /// Remove content within RECTS of PAGE, given as [x0, y0, x1, y1] in default user
+    /// space, and paint them black. text showing operators drawn within rects are removed
+    /// as a whole, images including inline ones get their area within rects painted
+    /// black, and form XObjects and annotations overlapping rects are removed, as well
+    /// as page thumbnail. returns number of removed text operators.
+    pub fn redact(&mut self, page: u32, rects: &[[f32; 4]]) -> anyhow::Result<usize> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        let rects: Vec<[f32; 4]> = rects
+            .iter()
+            .map(|r| {
+                [
+                    r[0].min(r[2]),
+                    r[1].min(r[3]),
+                    r[0].max(r[2]),
+                    r[1].max(r[3]),
+                ]
+            })
+            .collect();
+        let mut old_contents = self.doc.get_page_contents(page_id);
+        self.convert_inline_images(page_id)?;
+        old_contents.extend(self.doc.get_page_contents(page_id));
+
+        let redacted = self.redact_content(page_id, &rects)?;
+        let content = Content {
+            operations: redacted.operations,
+        };
+        self.doc.change_page_content(page_id, content.encode()?)?;
+
+        // XObjects no longer drawn are taken out of page resources, so they are not kept
+        // in file with redacted content. ones also drawn out of rects stay.
+        let drawn: BTreeSet<&[u8]> = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "Do")
+            .filter_map(|op| op.operands.first()?.as_name().ok())
+            .collect();
+        let unused: Vec<Vec<u8>> = redacted
+            .xobjects
+            .iter()
+            .filter(|&x| !drawn.contains(&x[..]))
+            .cloned()
+            .collect();
+        let mut dropped = vec![];
+        let xobjects = xobject::page_xobjects(&self.doc, page_id);
+        let mut resources = match page_resource(&self.doc, page_id, b"XObject") {
+            Some(dict) => dict.clone(),
+            None => Dictionary::new(),
+        };
+        for name in &unused {
+            resources.remove(name);
+            dropped.extend(xobjects.get(name));
+        }
+        if !unused.is_empty() {
+            let mut page_resources = match page_attribute(&self.doc, page_id, b"Resources") {
+                Some(Object::Dictionary(dict)) => dict.clone(),
+                _ => Dictionary::new(),
+            };
+            page_resources.set("XObject", resources);
+            self.doc
+                .get_dictionary_mut(page_id)?
+                .set("Resources", page_resources);
+        }
+
+        // thumbnail would still show redacted content
+        if let Some(thumb) = self.doc.get_dictionary_mut(page_id)?.remove(b"Thumb") {
+            dropped.extend(thumb.as_reference());
+        }
+        let annotations = self.redact_annotations(page_id, &rects)?;
+
+        dropped.extend(old_contents);
+        for id in dropped {
+            if !is_referenced(&self.doc, id) {
+                self.doc.objects.remove(&id);
+            }
+        }
+
+        let mut ops = vec![Operation::new("g", vec![0.into()])];
+        for [x0, y0, x1, y1] in rects {
+            ops.push(Operation::new(
+                "re",
+                vec![x0.into(), y0.into(), (x1 - x0).into(), (y1 - y0).into()],
+            ));
+        }
+        ops.push(Operation::new("f", vec![]));
+        self.append_page_content(page_id, Content { operations: ops }.encode()?)?;
+
+        log::info!(
+            "redacted {} text operators, {} XObjects and {} annotations on page {}",
+            redacted.text,
+            redacted.xobjects.len(),
+            annotations,
+            page
+        );
+
+        Ok(redacted.text)
+    }
+}
+
+/// Paint areas of IMG drawn with CTM within RECTS with COLOR. CTM must not rotate or
+/// skew image.
+fn paint_rects(img: &mut DynamicImage, ctm: [f32; 6], rects: &[[f32; 4]], color: Rgba<u8>) {
+    let [a, _, _, d, e, f] = ctm;
+    let (w, h) = img.dimensions();
+
+    for rect in rects {
+        // position of rect in unit square of image, whose top row is drawn at v = 1
+        let (u0, u1) = ((rect[0] - e) / a, (rect[2] - e) / a);
+        let (v0, v1) = ((rect[1] - f) / d, (rect[3] - f) / d);
+        let clamp = |x: f32, max: u32| (x.max(0.0) as u32).min(max);
+
+        let x0 = clamp((u0.min(u1) * w as f32).floor(), w);
+        let x1 = clamp((u0.max(u1) * w as f32).ceil(), w);
+        let y0 = clamp(((1.0 - v0.max(v1)) * h as f32).floor(), h);
+        let y1 = clamp(((1.0 - v0.min(v1)) * h as f32).ceil(), h);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Check whether any object of DOC refers to ID
+pub(crate) fn is_referenced(doc: &Document, id: ObjectId) -> bool {
+    fn refers(obj: &Object, id: ObjectId) -> bool {
+        match obj {
+            Object::Reference(x) => *x == id,
+            Object::Array(arr) => arr.iter().any(|x| refers(x, id)),
+            Object::Dictionary(dict) => dict.iter().any(|(_, x)| refers(x, id)),
+            Object::Stream(stream) => stream.dict.iter().any(|(_, x)| refers(x, id)),
+            _ => false,
+        }
+    }
+
+    doc.objects.values().any(|x| refers(x, id)) || doc.trailer.iter().any(|(_, x)| refers(x, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_inline_images_expands_abbreviations() {
+        let content = b"q 8 0 0 1 0 0 cm BI /W 8 /H 1 /BPC 1 /CS /G ID \xf0 EI Q (BI ID EI) Tj";
+        let (content, images) =
+            extract_inline_images(content, None, |i| format!("Im{}", i)).unwrap();
+
+        assert_eq!(content, b"q 8 0 0 1 0 0 cm /Im0 Do Q (BI ID EI) Tj");
+        assert_eq!(images.len(), 1);
+        let (name, image) = &images[0];
+        assert_eq!(name, "Im0");
+        assert_eq!(image.content, [0xf0]);
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 8);
+        assert_eq!(
+            image.dict.get(b"ColorSpace").unwrap().as_name().unwrap(),
+            b"DeviceGray"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, ImageFormat};
+
+use crate::{attributes::media_box, selection::PageSelection, Pdf};
+
+/// Rasterize PAGES of PDF data BYTES at DPI with pdfium, passing each page number
+/// and image to CALLBACK
+#[cfg(feature = "render")]
+fn render_with<F>(bytes: &[u8], pages: &[u32], dpi: f32, mut callback: F) -> anyhow::Result<()>
+where
+    F: FnMut(u32, DynamicImage) -> anyhow::Result<()>,
+{
+    use pdfium_render::prelude::*;
+
+    let pdfium = Pdfium::new(Pdfium::bind_to_system_library()?);
+    let document = pdfium.load_pdf_from_byte_slice(bytes, None)?;
+    let config = PdfRenderConfig::new().scale_page_by_factor(dpi / 72.0);
+
+    for &page in pages {
+        let image = document
+            .pages()
+            .get((page - 1) as u16)?
+            .render_with_config(&config)?
+            .as_image();
+        callback(page, image)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "render"))]
+fn render_with<F>(_: &[u8], _: &[u32], _: f32, _: F) -> anyhow::Result<()>
+where
+    F: FnMut(u32, DynamicImage) -> anyhow::Result<()>,
+{
+    anyhow::bail!("rendering is not enabled, build with feature \"render\"")
+}
+
+impl Pdf {
+    /// Rasterize PAGES at DPI, passing each page number and image to CALLBACK in order
+    pub fn render_pages<F>(
+        &self,
+        pages: &PageSelection,
+        dpi: f32,
+        callback: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnMut(u32, DynamicImage) -> anyhow::Result<()>,
+    {
+        anyhow::ensure!(dpi > 0.0, "invalid resolution: {}", dpi);
+
+        let page_ids = self.doc.get_pages();
+        let pages = pages.page_set(page_ids.len() as u32)?;
+        for page in &pages {
+            let [x0, y0, x1, y1] = media_box(&self.doc, page_ids[page]);
+            let width = ((x1 - x0).abs() / 72.0 * dpi).ceil() as u32;
+            let height = ((y1 - y0).abs() / 72.0 * dpi).ceil() as u32;
+            self.check_image_pixels(width, height)?;
+        }
+
+        // pdfium reads serialized document, so pending changes are rendered as well
+        let mut doc = self.doc.clone();
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes)?;
+
+        render_with(&bytes, &pages, dpi, callback)
+    }
+
+    /// Rasterize PAGE at DPI
+    pub fn render_page(&self, page: u32, dpi: f32) -> anyhow::Result<DynamicImage> {
+        let selection = page.to_string().parse()?;
+
+        let mut result = None;
+        self.render_pages(&selection, dpi, |_, image| {
+            result = Some(image);
+            Ok(())
+        })?;
+
+        result.ok_or_else(|| anyhow::anyhow!("page {} was not rendered", page))
+    }
+
+    /// Write PAGES rasterized at DPI into directory DIR as PNG files named like
+    /// page003.png, returning paths of written files
+    pub fn render_pages_to<P: AsRef<Path>>(
+        &self,
+        pages: &PageSelection,
+        dpi: f32,
+        dir: P,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::new();
+        self.render_pages(pages, dpi, |page, image| {
+            let path = dir.join(format!("page{:03}.png", page));
+            log::info!("rendered page {} into {}", page, path.display());
+            image.save_with_format(&path, ImageFormat::Png)?;
+            paths.push(path);
+            Ok(())
+        })?;
+
+        Ok(paths)
+    }
+}
@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use flate2::read::ZlibDecoder;
+use lopdf::{dictionary, ObjectId};
+
+use crate::{
+    error::Error,
+    writer::{self, XrefEntry},
+    Pdf,
+};
+
+fn is_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(c: u8) -> bool {
+    is_whitespace(c) || b"()<>[]{}/%".contains(&c)
+}
+
+/// Find start of run of digits ending just before END, skipping whitespace before END
+fn digits_before(bytes: &[u8], end: usize) -> Option<(usize, u32)> {
+    let mut end = end;
+    while end > 0 && is_whitespace(bytes[end - 1]) {
+        end -= 1;
+    }
+
+    let mut start = end;
+    while start > 0 && bytes[start - 1].is_ascii_digit() {
+        start -= 1;
+    }
+    if start == end {
+        return None;
+    }
+
+    let value = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+    Some((start, value))
+}
+
+/// Find "N G obj" headers in BYTES, returning offset of each object. later
+/// definitions win, as they do in incremental updates.
+fn scan_objects(bytes: &[u8]) -> BTreeMap<ObjectId, usize> {
+    let mut objects = BTreeMap::new();
+
+    for pos in 0..bytes.len().saturating_sub(3) {
+        if &bytes[pos..pos + 3] != b"obj"
+            || pos == 0
+            || !is_whitespace(bytes[pos - 1])
+            || bytes.get(pos + 3).map_or(false, |&c| !is_delimiter(c))
+        {
+            continue;
+        }
+
+        let Some((gen_start, generation)) = digits_before(bytes, pos) else {
+            continue;
+        };
+        let Some((id_start, id)) = digits_before(bytes, gen_start) else {
+            continue;
+        };
+        if gen_start == id_start || !is_whitespace(bytes[gen_start - 1]) {
+            continue;
+        }
+        if id_start > 0 && !is_delimiter(bytes[id_start - 1]) {
+            continue;
+        }
+
+        objects.insert((id, generation as u16), id_start);
+    }
+
+    objects
+}
+
+/// Find last reference "KEY N G R" in BYTES, as in trailer or xref stream
+fn find_reference(bytes: &[u8], key: &[u8]) -> Option<ObjectId> {
+    let mut end = bytes.len();
+
+    while let Some(pos) = bytes[..end].windows(key.len()).rposition(|x| x == key) {
+        end = pos;
+
+        let rest = &bytes[pos + key.len()..];
+        let text = String::from_utf8_lossy(&rest[..rest.len().min(32)]);
+        let mut parts = text.split_ascii_whitespace();
+        let (Some(id), Some(generation), Some(r)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+
+        if let (Ok(id), Ok(generation), true) = (id.parse(), generation.parse(), r.starts_with('R'))
+        {
+            return Some((id, generation));
+        }
+    }
+
+    None
+}
+
+/// Get text of object at OFFSET in BYTES, up to endobj
+fn object_text(bytes: &[u8], offset: usize) -> &[u8] {
+    let rest = &bytes[offset..];
+    let end = rest
+        .windows(6)
+        .position(|x| x == b"endobj")
+        .unwrap_or(rest.len());
+
+    &rest[..end]
+}
+
+/// Check whether object at OFFSET is document catalog
+fn is_catalog(bytes: &[u8], offset: usize) -> bool {
+    object_text(bytes, offset)
+        .windows(8)
+        .any(|x| x == b"/Catalog")
+}
+
+/// Get integer following KEY in TEXT, as in "/N 10"
+fn integer_after(text: &[u8], key: &[u8]) -> Option<usize> {
+    text.windows(key.len())
+        .enumerate()
+        .filter(|(_, x)| *x == key)
+        .find_map(|(pos, _)| {
+            let rest = String::from_utf8_lossy(&text[pos + key.len()..]);
+            let digits: String = rest
+                .trim_start()
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            digits.parse().ok()
+        })
+}
+
+/// Get objects compressed in object stream at OFFSET, as pairs of object number and
+/// its text. None if object is not object stream, or its filter is not supported.
+fn object_stream_contents(bytes: &[u8], offset: usize) -> Option<Vec<(u32, Vec<u8>)>> {
+    let text = object_text(bytes, offset);
+    let stream_pos = text.windows(6).position(|x| x == b"stream")?;
+    let header = &text[..stream_pos];
+    if !header.windows(7).any(|x| x == b"/ObjStm") {
+        return None;
+    }
+
+    let count = integer_after(header, b"/N")?;
+    let first = integer_after(header, b"/First")?;
+    // predictors are not used for object streams in practice
+    let flate = header.windows(12).any(|x| x == b"/FlateDecode");
+    if header.windows(7).any(|x| x == b"/Filter") && !flate {
+        log::warn!("skipping object stream with unsupported filter");
+        return None;
+    }
+
+    let mut start = stream_pos + 6;
+    if text.get(start) == Some(&b'\r') {
+        start += 1;
+    }
+    if text.get(start) == Some(&b'\n') {
+        start += 1;
+    }
+    let end = text
+        .windows(9)
+        .rposition(|x| x == b"endstream")
+        .unwrap_or(text.len())
+        .max(start);
+
+    let data = if flate {
+        // damaged stream gives what could be decoded before damage
+        let mut data = Vec::new();
+        let _ = ZlibDecoder::new(&text[start..end]).read_to_end(&mut data);
+        data
+    } else {
+        text[start..end].to_vec()
+    };
+
+    let pairs = String::from_utf8_lossy(&data[..first.min(data.len())]).into_owned();
+    let numbers: Vec<usize> = pairs
+        .split_ascii_whitespace()
+        .map_while(|x| x.parse().ok())
+        .collect();
+    let entries: Vec<(u32, usize)> = numbers
+        .chunks_exact(2)
+        .take(count)
+        .filter_map(|x| Some((u32::try_from(x[0]).ok()?, first.checked_add(x[1])?)))
+        .collect();
+
+    let objects = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, start))| *start <= data.len())
+        .map(|(i, &(id, start))| {
+            let end = entries
+                .get(i + 1)
+                .map_or(data.len(), |x| x.1)
+                .clamp(start, data.len());
+            (id, data[start..end].to_vec())
+        })
+        .collect();
+
+    Some(objects)
+}
+
+/// Append objects compressed in object streams among OBJECTS found in OUT to OUT as
+/// plain objects, so that table can refer to them. objects defined out of streams win.
+fn expand_object_streams(
+    out: &mut Vec<u8>,
+    objects: &mut BTreeMap<ObjectId, usize>,
+    fixes: &mut Vec<String>,
+) {
+    // later streams win like later definitions
+    let mut streams: Vec<usize> = objects.values().copied().collect();
+    streams.sort_unstable();
+
+    let mut compressed = BTreeMap::new();
+    for offset in streams {
+        for (id, text) in object_stream_contents(out, offset).unwrap_or_default() {
+            if !objects.contains_key(&(id, 0)) {
+                compressed.insert(id, text);
+            }
+        }
+    }
+    if compressed.is_empty() {
+        return;
+    }
+
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    for (id, text) in &compressed {
+        objects.insert((*id, 0), out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+        out.extend_from_slice(text);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    fixes.push(format!(
+        "expanded {} objects compressed in object streams",
+        compressed.len()
+    ));
+}
+
+/// Append cross-reference table rebuilt from objects found in BYTES, describing
+/// what was done in FIXES
+pub(crate) fn rebuild_xref(bytes: &[u8], fixes: &mut Vec<String>) -> crate::Result<Vec<u8>> {
+    let mut objects = scan_objects(bytes);
+    if objects.is_empty() {
+        return Err(Error::InvalidDocument("no objects found".into()));
+    }
+    fixes.push(format!(
+        "rebuilt cross-reference table from {} objects found in file",
+        objects.len()
+    ));
+
+    let mut out = bytes.to_vec();
+    expand_object_streams(&mut out, &mut objects, fixes);
+
+    let root = match find_reference(bytes, b"/Root") {
+        Some(root) if objects.contains_key(&root) => root,
+        _ => {
+            let root = objects
+                .iter()
+                .rev()
+                .find(|(_, &offset)| is_catalog(&out, offset))
+                .map(|(&id, _)| id)
+                .ok_or_else(|| Error::InvalidDocument("document catalog not found".into()))?;
+            fixes.push(format!("found document catalog {} {}", root.0, root.1));
+            root
+        }
+    };
+
+    let mut entries = BTreeMap::new();
+    entries.insert(
+        0,
+        XrefEntry::Free {
+            next: 0,
+            generation: 65535,
+        },
+    );
+    for (&(id, generation), &offset) in &objects {
+        entries.insert(id, XrefEntry::InUse { offset, generation });
+    }
+    let size = entries.keys().max().map_or(1, |x| x + 1);
+
+    let mut trailer = dictionary! {
+        "Size" => size,
+        "Root" => root,
+    };
+    match find_reference(bytes, b"/Info") {
+        Some(info) if objects.contains_key(&info) => trailer.set("Info", info),
+        _ => fixes.push("document information dictionary not found, left out of trailer".into()),
+    }
+
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    let xref_offset = out.len();
+    writer::write_xref_table(&mut out, &entries);
+    out.extend_from_slice(b"trailer\n");
+    writer::write_dictionary(&mut out, &trailer);
+    out.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+
+    Ok(out)
+}
+
+impl Pdf {
+    /// Load document at PATH like `load`, rebuilding cross-reference table by scanning
+    /// for objects if it is broken. returns descriptions of fixes made along with
+    /// document, empty if file was loaded as is.
+    pub fn load_lenient<P: AsRef<Path>>(path: P) -> crate::Result<(Self, Vec<String>)> {
+        Self::from_bytes_lenient(&std::fs::read(path)?)
+    }
+
+    /// Load document from BYTES in memory like `load_lenient`
+    pub fn from_bytes_lenient(bytes: &[u8]) -> crate::Result<(Self, Vec<String>)> {
+        let err = match Self::from_bytes(bytes) {
+            Ok(pdf) => return Ok((pdf, Vec::new())),
+            Err(err) => err,
+        };
+
+        log::warn!("failed to load document ({}), trying to repair it", err);
+        let mut fixes = Vec::new();
+        let repaired = rebuild_xref(bytes, &mut fixes)?;
+
+        let pdf = Self::from_buffer(repaired)?;
+        for fix in &fixes {
+            log::info!("{}", fix);
+        }
+
+        Ok((pdf, fixes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_stream_contents_splits_objects() {
+        let bytes = b"5 0 obj\n<< /Type /ObjStm /N 2 /First 8 /Length 25 >>\nstream\n\
+            3 0 4 4 1 0 [2 3]\nendstream\nendobj\n";
+
+        let objects = object_stream_contents(bytes, 0).unwrap();
+        assert_eq!(objects, [(3, b"1 0 ".to_vec()), (4, b"[2 3]\n".to_vec())]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{offset::Utc, DateTime};
+use lopdf::{Document, Object, ObjectId, StringFormat};
+use md5::{Digest, Md5};
+
+use crate::{writer, Pdf};
+
+/// Get ids of objects of DOC reachable from trailer, in order they are first referred to
+pub(crate) fn canonical_order(doc: &Document) -> Vec<ObjectId> {
+    let mut order = Vec::new();
+    let mut visited = BTreeSet::new();
+    // trailer is walked in reverse, so that entries are popped in order
+    let mut stack: Vec<&Object> = doc.trailer.iter().rev().map(|(_, x)| x).collect();
+
+    while let Some(obj) = stack.pop() {
+        match obj {
+            Object::Reference(id) => {
+                if !visited.insert(*id) {
+                    continue;
+                }
+                if let Ok(target) = doc.get_object(*id) {
+                    order.push(*id);
+                    stack.push(target);
+                }
+            }
+            Object::Array(arr) => stack.extend(arr.iter().rev()),
+            Object::Dictionary(dict) => stack.extend(dict.iter().rev().map(|(_, x)| x)),
+            Object::Stream(stream) => stack.extend(stream.dict.iter().rev().map(|(_, x)| x)),
+            _ => {}
+        }
+    }
+
+    order
+}
+
+/// Replace references in OBJ by ids of MAP. references to missing objects become null.
+fn remap(obj: &mut Object, map: &BTreeMap<ObjectId, ObjectId>) {
+    match obj {
+        Object::Reference(id) => {
+            *obj = map.get(id).map_or(Object::Null, |&x| x.into());
+        }
+        Object::Array(arr) => arr.iter_mut().for_each(|x| remap(x, map)),
+        Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, x)| remap(x, map)),
+        Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, x)| remap(x, map)),
+        _ => {}
+    }
+}
+
+impl Pdf {
+    /// Record TIMESTAMP instead of current time in dates written from now on, such as
+    /// modification date and dates of attachments, for reproducible output. dates of
+    /// document made by `new` are reset to TIMESTAMP.
+    pub fn set_timestamp(&mut self, timestamp: DateTime<Utc>) -> crate::Result<()> {
+        self.timestamp = Some(timestamp);
+
+        if self.original.is_none() {
+            let info = self.info_dict_mut()?;
+            info.set("CreationDate", timestamp);
+            info.set("ModDate", timestamp);
+        }
+
+        Ok(())
+    }
+
+    /// Time to record in dates, fixed by `set_timestamp` or current time
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.timestamp.unwrap_or_else(Utc::now)
+    }
+
+    /// Renumber objects in order they are reached from trailer, dropping unreachable
+    /// ones, and derive document ID from content, so that same document is always
+    /// written as same bytes
+    pub(crate) fn make_deterministic(&mut self) -> crate::Result<()> {
+        // object numbers and document ID are part of encryption keys
+        if self.doc.trailer.has(b"Encrypt") {
+            log::warn!("encrypted document is written with its object numbers and ID");
+            return Ok(());
+        }
+
+        // permanent first element of existing ID is kept, as it identifies document
+        let permanent = self
+            .doc
+            .trailer
+            .get(b"ID")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|x| x.first())
+            .and_then(|x| x.as_str().ok())
+            .map(<[u8]>::to_vec);
+        self.doc.trailer.remove(b"ID");
+
+        let map: BTreeMap<ObjectId, ObjectId> = canonical_order(&self.doc)
+            .into_iter()
+            .zip(1..)
+            .map(|(old, new)| (old, (new, 0)))
+            .collect();
+
+        let mut objects = BTreeMap::new();
+        for (old, &new) in &map {
+            if let Some(mut obj) = self.doc.objects.remove(old) {
+                remap(&mut obj, &map);
+                objects.insert(new, obj);
+            }
+        }
+        self.doc.objects = objects;
+        self.doc.max_id = map.len() as u32;
+
+        for (_, value) in self.doc.trailer.iter_mut() {
+            remap(value, &map);
+        }
+        self.pages_id = self
+            .doc
+            .catalog()?
+            .get(b"Pages")
+            .and_then(Object::as_reference)?;
+
+        let mut md5 = Md5::new();
+        for (&id, obj) in &self.doc.objects {
+            let mut bytes = Vec::new();
+            writer::write_indirect_object(&mut bytes, id, obj);
+            md5.update(&bytes);
+        }
+        let hash = md5.finalize().to_vec();
+
+        let permanent = permanent.unwrap_or_else(|| hash.clone());
+        self.doc.trailer.set(
+            "ID",
+            vec![
+                Object::String(permanent, StringFormat::Hexadecimal),
+                Object::String(hash, StringFormat::Hexadecimal),
+            ],
+        );
+
+        Ok(())
+    }
+}
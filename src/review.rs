@@ -0,0 +1,152 @@
+use std::fmt;
+
+use image::imageops::FilterType;
+use lopdf::Object;
+
+use crate::{
+    destination::Destination, limits::LimitExceeded, outline::BookmarkStyle, xobject, Pdf,
+};
+
+/// Relative difference of image size from neighbors treated as suspicious
+const SIZE_TOLERANCE: f32 = 0.2;
+/// Difference of mean brightness (0 to 255) from neighbors treated as suspicious
+const BRIGHTNESS_TOLERANCE: f32 = 64.0;
+
+/// Page of scan batch which may need a look by human
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewItem {
+    pub page: u32,
+    pub reason: String,
+}
+
+impl fmt::Display for ReviewItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: {}", self.page, self.reason)
+    }
+}
+
+/// Pixel size and mean brightness of scanned image of page
+#[derive(Clone, Copy, Debug)]
+struct ScanStats {
+    width: f32,
+    height: f32,
+    brightness: f32,
+}
+
+fn differs(a: f32, b: f32) -> bool {
+    (a - b).abs() > a.max(b) * SIZE_TOLERANCE
+}
+
+impl Pdf {
+    fn scan_stats(&self, page: u32) -> anyhow::Result<Option<ScanStats>> {
+        let page_id = self.get_page_id(page)?;
+        let Some(id) = xobject::largest_image(&self.doc, page_id)? else {
+            return Ok(None);
+        };
+
+        let stream = self.doc.get_object(id).and_then(Object::as_stream)?;
+
+        // brightness can't be measured for images we can't decode, e.g. CCITT or JPX
+        let brightness = match self.decode_image(stream) {
+            Ok(img) => {
+                let small = img.resize(64, 64, FilterType::Triangle).to_luma8();
+                let sum: u64 = small.pixels().map(|x| x.0[0] as u64).sum();
+                sum as f32 / small.pixels().len().max(1) as f32
+            }
+            Err(err) if err.is::<LimitExceeded>() => return Err(err),
+            Err(_) => f32::NAN,
+        };
+
+        Ok(Some(ScanStats {
+            width: width as f32,
+            height: height as f32,
+            brightness,
+        }))
+    }
+
+    /// Find pages whose scanned image differs drastically in size or brightness from
+    /// both neighbors, which are likely double feeds, skipped or blank pages.
+    /// pages without image in document of scans are reported too.
+    pub fn review_scans(&self) -> anyhow::Result<Vec<ReviewItem>> {
+        let total = self.page_count() as u32;
+        let mut stats = Vec::new();
+        for page in 1..=total {
+            stats.push(self.scan_stats(page)?);
+        }
+
+        let mut result = Vec::new();
+        if stats.iter().all(Option::is_none) {
+            return Ok(result);
+        }
+
+        for (i, current) in stats.iter().enumerate() {
+            let page = i as u32 + 1;
+            let Some(current) = current else {
+                result.push(ReviewItem {
+                    page,
+                    reason: "no scanned image".to_string(),
+                });
+                continue;
+            };
+
+            let neighbors: Vec<&ScanStats> = [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|j| stats.get(j).and_then(Option::as_ref))
+                .collect();
+            if neighbors.is_empty() {
+                continue;
+            }
+
+            let mut reasons = Vec::new();
+            if neighbors
+                .iter()
+                .all(|x| differs(current.width, x.width) || differs(current.height, x.height))
+            {
+                reasons.push(format!(
+                    "image size {}x{} differs from neighbors",
+                    current.width, current.height
+                ));
+            }
+            // comparisons with NaN are false, so undecodable images are never flagged
+            if neighbors
+                .iter()
+                .all(|x| (current.brightness - x.brightness).abs() > BRIGHTNESS_TOLERANCE)
+            {
+                reasons.push(format!(
+                    "brightness {:.0} differs from neighbors",
+                    current.brightness
+                ));
+            }
+
+            if !reasons.is_empty() {
+                result.push(ReviewItem {
+                    page,
+                    reason: reasons.join(", "),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Add bookmark "Review: REASON" pointing to page of each item
+    pub fn add_review_bookmarks(&mut self, items: &[ReviewItem]) -> anyhow::Result<()> {
+        let style = BookmarkStyle {
+            color: Some([1.0, 0.0, 0.0]),
+            ..BookmarkStyle::default()
+        };
+
+        for item in items {
+            self.add_bookmark(
+                &format!("Review: {}", item.reason),
+                item.page,
+                &Destination::Fit,
+                None,
+                &style,
+            )?;
+        }
+
+        Ok(())
+    }
+}
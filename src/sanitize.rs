@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::{permissions::Permission, Pdf};
+
+fn is_dangerous_action(dict: &Dictionary) -> bool {
+    dict.has(b"JS")
+        || matches!(
+            dict.get(b"S").and_then(Object::as_name),
+            Ok(b"JavaScript" | b"Launch")
+        )
+}
+
+impl Pdf {
+    /// Remove Info dictionary, XMP metadata, PieceInfo, document ID and
+    /// author of annotations, for privacy before publishing.
+    /// removed objects are left unreferenced until `prune`.
+    pub fn strip_metadata(&mut self) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        self.doc.trailer.remove(b"Info");
+        self.doc.trailer.remove(b"ID");
+
+        for obj in self.doc.objects.values_mut() {
+            let dict = match obj {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &mut stream.dict,
+                _ => continue,
+            };
+
+            dict.remove(b"Metadata");
+            dict.remove(b"PieceInfo");
+
+            let is_annot = dict.type_name().ok() == Some("Annot")
+                || (dict.has(b"Subtype") && dict.has(b"Rect"));
+            // /T of widget is field name, not author
+            let is_widget =
+                dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Widget"[..]);
+
+            if is_annot && !is_widget {
+                dict.remove(b"T");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove JavaScript and launch actions, document level scripts and
+    /// additional actions (/AA) throughout document.
+    pub fn strip_actions(&mut self) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let dangerous: BTreeSet<ObjectId> = self
+            .doc
+            .objects
+            .iter()
+            .filter(|(_, obj)| obj.as_dict().map_or(false, is_dangerous_action))
+            .map(|(&id, _)| id)
+            .collect();
+
+        let is_dangerous = |obj: &Object| match obj {
+            Object::Reference(id) => dangerous.contains(id),
+            Object::Dictionary(dict) => is_dangerous_action(dict),
+            _ => false,
+        };
+
+        for obj in self.doc.objects.values_mut() {
+            let dict = match obj {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &mut stream.dict,
+                _ => continue,
+            };
+
+            dict.remove(b"AA");
+
+            for key in [&b"A"[..], b"OpenAction"] {
+                if dict.get(key).map_or(false, is_dangerous) {
+                    dict.remove(key);
+                }
+            }
+
+            // action chain may continue with single action or array of them
+            let drop_next = match dict.get_mut(b"Next") {
+                Ok(Object::Array(arr)) => {
+                    arr.retain(|x| !is_dangerous(x));
+                    false
+                }
+                Ok(next) => is_dangerous(next),
+                Err(_) => false,
+            };
+            if drop_next {
+                dict.remove(b"Next");
+            }
+
+            // document level scripts in name dictionary
+            dict.remove(b"JavaScript");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::Error;
+
+/// Range of 1-based page numbers; open end means up to last page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageRange {
+    pub start: u32,
+    pub end: Option<u32>,
+}
+
+/// List of page ranges like "1-4,7,10-", kept in given order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageSelection(pub Vec<PageRange>);
+
+impl Default for PageSelection {
+    /// Select all pages
+    fn default() -> Self {
+        Self(vec![PageRange {
+            start: 1,
+            end: None,
+        }])
+    }
+}
+
+impl PageSelection {
+    /// Expand selection into page numbers of document with TOTAL pages, in order of ranges.
+    /// pages out of document are dropped.
+    pub fn pages(&self, total: u32) -> Vec<u32> {
+        self.0
+            .iter()
+            .flat_map(|range| range.start..=range.end.unwrap_or(total).min(total))
+            .collect()
+    }
+
+    /// Expand selection into sorted page numbers without duplicates, failing if it refers
+    /// to page beyond TOTAL instead of dropping it
+    pub fn page_set(&self, total: u32) -> crate::Result<Vec<u32>> {
+        for range in &self.0 {
+            let last = range.end.unwrap_or(range.start);
+            if last > total {
+                return Err(Error::PageNotFound(last));
+            }
+        }
+
+        let mut pages = self.pages(total);
+        pages.sort_unstable();
+        pages.dedup();
+        Ok(pages)
+    }
+
+    pub fn contains(&self, page: u32, total: u32) -> bool {
+        page <= total
+            && self
+                .0
+                .iter()
+                .any(|range| range.start <= page && range.end.map_or(true, |end| page <= end))
+    }
+}
+
+impl FromStr for PageSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(Self::default());
+        }
+
+        let parse = |x: &str| -> anyhow::Result<u32> {
+            let page: u32 = x
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid page number {} in {}", x, s))?;
+            anyhow::ensure!(page > 0, "page number starts from 1: {}", s);
+            Ok(page)
+        };
+
+        let mut ranges = vec![];
+        for part in s.split(',') {
+            let range = match part.split_once('-') {
+                Some((start, "")) => PageRange {
+                    start: parse(start)?,
+                    end: None,
+                },
+                Some(("", end)) => PageRange {
+                    start: 1,
+                    end: Some(parse(end)?),
+                },
+                Some((start, end)) => PageRange {
+                    start: parse(start)?,
+                    end: Some(parse(end)?),
+                },
+                None => {
+                    let page = parse(part)?;
+                    PageRange {
+                        start: page,
+                        end: Some(page),
+                    }
+                }
+            };
+
+            anyhow::ensure!(
+                range.end.map_or(true, |end| range.start <= end),
+                "range {} is reversed",
+                part
+            );
+            ranges.push(range);
+        }
+
+        Ok(Self(ranges))
+    }
+}
+
+impl fmt::Display for PageSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|range| match range.end {
+                Some(end) if end == range.start => end.to_string(),
+                Some(end) => format!("{}-{}", range.start, end),
+                None => format!("{}-", range.start),
+            })
+            .collect();
+
+        write!(f, "{}", parts.join(","))
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use lopdf::{Document, Object, ObjectId};
+
+use crate::{permissions::Permission, reproducible::canonical_order, writer, Pdf};
+
+/// Bytes of xref entry written for each object
+const XREF_ENTRY_SIZE: u64 = 20;
+
+/// Push ids referred to by OBJ into OUT, except parents
+fn push_references(obj: &Object, out: &mut Vec<ObjectId>) {
+    let dict = match obj {
+        Object::Reference(id) => return out.push(*id),
+        Object::Array(arr) => return arr.iter().for_each(|x| push_references(x, out)),
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &stream.dict,
+        _ => return,
+    };
+    dict.iter()
+        .filter(|(key, _)| key.as_slice() != b"Parent")
+        .for_each(|(_, x)| push_references(x, out));
+}
+
+/// Get ids of objects page PAGE_ID needs, like its contents, resources and
+/// annotations. other pages, such as targets of links, are not followed.
+fn page_objects(doc: &Document, page_id: ObjectId) -> BTreeSet<ObjectId> {
+    let mut result = BTreeSet::new();
+    let mut stack = vec![page_id];
+
+    while let Some(id) = stack.pop() {
+        let Ok(obj) = doc.get_object(id) else {
+            continue;
+        };
+        let dict = match obj {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        };
+        let is_page = dict.map_or(false, |x| matches!(x.type_name(), Ok("Page" | "Pages")));
+        if (id != page_id && is_page) || !result.insert(id) {
+            continue;
+        }
+        push_references(obj, &mut stack);
+    }
+
+    result
+}
+
+/// Estimate bytes OBJ takes in written document
+fn object_size(id: ObjectId, obj: &Object) -> u64 {
+    let mut out = Vec::new();
+    // stream content is counted by its length instead of being copied
+    let content = match obj {
+        Object::Stream(stream) => {
+            writer::write_indirect_object(&mut out, id, &Object::Dictionary(stream.dict.clone()));
+            stream.content.len() + b"stream\n\nendstream".len()
+        }
+        _ => {
+            writer::write_indirect_object(&mut out, id, obj);
+            0
+        }
+    };
+    (out.len() + content) as u64 + XREF_ENTRY_SIZE
+}
+
+/// Sum sizes of objects IDS
+fn total_size<'a>(sizes: &BTreeMap<ObjectId, u64>, ids: impl Iterator<Item = &'a ObjectId>) -> u64 {
+    ids.filter_map(|id| sizes.get(id)).sum()
+}
+
+impl Pdf {
+    /// Group pages into consecutive parts, each estimated to take at most MAX_SIZE bytes
+    /// written as separate document. estimate adds up sizes of objects pages of part
+    /// use, counting shared ones like fonts once, and of objects outside pages. page
+    /// over MAX_SIZE by itself makes part of its own.
+    pub fn split_by_size(&self, max_size: u64) -> Vec<Vec<u32>> {
+        let sizes: BTreeMap<ObjectId, u64> = self
+            .doc
+            .objects
+            .iter()
+            .map(|(&id, obj)| (id, object_size(id, obj)))
+            .collect();
+        let pages: Vec<(u32, BTreeSet<ObjectId>)> = self
+            .doc
+            .get_pages()
+            .into_iter()
+            .map(|(num, id)| (num, page_objects(&self.doc, id)))
+            .collect();
+        let used: BTreeSet<ObjectId> = pages.iter().flat_map(|(_, x)| x).copied().collect();
+        // catalog, page tree, metadata and so on go into every part
+        let base = total_size(
+            &sizes,
+            canonical_order(&self.doc)
+                .iter()
+                .filter(|id| !used.contains(id)),
+        );
+
+        let mut parts = Vec::new();
+        let mut current = Vec::new();
+        let mut objects = BTreeSet::new();
+        let mut size = base;
+
+        for (num, page_objects) in pages {
+            let mut added = total_size(&sizes, page_objects.difference(&objects));
+            if !current.is_empty() && size + added > max_size {
+                parts.push(std::mem::take(&mut current));
+                objects.clear();
+                size = base;
+                added = total_size(&sizes, page_objects.iter());
+            }
+            if current.is_empty() && size + added > max_size {
+                log::warn!(
+                    "page {} alone is estimated to take {} bytes, over limit of {}",
+                    num,
+                    size + added,
+                    max_size
+                );
+            }
+
+            current.push(num);
+            objects.extend(page_objects);
+            size += added;
+        }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+
+        parts
+    }
+
+    /// Group pages into consecutive parts of EVERY pages, last one having rest
+    pub fn split_every(&self, every: u32) -> Vec<Vec<u32>> {
+        let pages: Vec<u32> = (1..=self.page_count() as u32).collect();
+        pages
+            .chunks(every.max(1) as usize)
+            .map(<[u32]>::to_vec)
+            .collect()
+    }
+
+    /// Make new document of PAGES of this one, keeping their order and dropping objects
+    /// only other pages use
+    pub fn extract_pages(&self, pages: &[u32]) -> crate::Result<Pdf> {
+        self.check_permission(Permission::Assemble)?;
+        for &page in pages {
+            self.get_page_id(page)?;
+        }
+
+        let removed: Vec<u32> = (1..=self.page_count() as u32)
+            .filter(|x| !pages.contains(x))
+            .collect();
+        let mut doc = self.doc.clone();
+        doc.delete_pages(&removed);
+        let _ = doc.prune_objects();
+
+        let mut pdf = Pdf::from_document(doc)?;
+        pdf.limits = self.limits.clone();
+        pdf.timestamp = self.timestamp;
+        Ok(pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::sample_document;
+
+    #[test]
+    fn split_every_leaves_rest_in_last_part() {
+        let pdf = sample_document(5, 100, 100).unwrap();
+
+        assert_eq!(pdf.split_every(2), vec![vec![1, 2], vec![3, 4], vec![5]]);
+        assert_eq!(pdf.split_every(5), vec![vec![1, 2, 3, 4, 5]]);
+        assert_eq!(
+            pdf.split_every(0),
+            (1..=5).map(|x| vec![x]).collect::<Vec<_>>()
+        );
+    }
+}
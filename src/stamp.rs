@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+
+use chrono::Local;
+use lopdf::ObjectId;
+
+use crate::{
+    font::Font, locale::Locale, permissions::Permission, selection::PageSelection, text::Position,
+    Pdf,
+};
+
+/// How page numbers are stamped by `stamp_page_numbers`
+#[derive(Clone, Debug)]
+pub struct PageNumberStyle {
+    /// Text of number. %d is replaced by page number, %0Nd by one zero padded to N digits
+    /// (Bates numbering), %r and %R by lower and upper case roman numeral,
+    /// and %t by count of numbered pages.
+    pub format: String,
+    /// Text put before formatted number
+    pub prefix: String,
+    /// Number of first selected page
+    pub start: u32,
+    /// Pages to be numbered, counting from START in order of selection
+    pub pages: PageSelection,
+    pub position: Position,
+    pub font: Font,
+    pub font_size: f32,
+    /// Distance from page edges in points
+    pub margin: f32,
+    pub locale: Locale,
+}
+
+impl Default for PageNumberStyle {
+    fn default() -> Self {
+        Self {
+            format: "%d".to_string(),
+            prefix: String::new(),
+            start: 1,
+            pages: PageSelection::default(),
+            position: Position::default(),
+            font: Font::default(),
+            font_size: 10.0,
+            margin: 36.0,
+            locale: Locale::default(),
+        }
+    }
+}
+
+/// How text of `stamp_text` is placed, for headers and footers
+#[derive(Clone, Debug)]
+pub struct TextStamp {
+    pub position: Position,
+    pub font: Font,
+    pub font_size: f32,
+    /// Distance from page edges in points
+    pub margin: f32,
+    /// Pages to draw text on
+    pub pages: PageSelection,
+    /// Value of {file} in template
+    pub file_name: String,
+    /// strftime format of {date} in template. if None, long date format of locale is used.
+    pub date_format: Option<String>,
+    pub locale: Locale,
+}
+
+impl TextStamp {
+    pub fn header() -> Self {
+        Self {
+            position: Position::TopCenter,
+            ..Default::default()
+        }
+    }
+
+    pub fn footer() -> Self {
+        Self {
+            position: Position::BottomCenter,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for TextStamp {
+    fn default() -> Self {
+        Self {
+            position: Position::TopCenter,
+            font: Font::default(),
+            font_size: 9.0,
+            margin: 24.0,
+            pages: PageSelection::default(),
+            file_name: String::new(),
+            date_format: None,
+            locale: Locale::default(),
+        }
+    }
+}
+
+/// Names of {NAME} variables in TEMPLATE
+pub(crate) fn template_names(template: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        if let Some(end) = rest.find(|c| c == '}' || c == '{') {
+            if rest[end..].starts_with('}') {
+                result.push(&rest[..end]);
+            }
+        }
+    }
+
+    result
+}
+
+/// Replace {NAME} in TEMPLATE with value of VARS. unknown names are kept as is.
+pub(crate) fn expand_template(template: &str, vars: &BTreeMap<&str, String>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest
+            .find('}')
+            .and_then(|end| Some((end, vars.get(&rest[1..end])?)));
+        match value {
+            Some((end, value)) => {
+                result.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Roman numeral of NUMBER in lower case; 0 has none, so empty string is returned
+pub(crate) fn roman(mut number: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+
+    let mut result = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            result.push_str(numeral);
+            number -= value;
+        }
+    }
+
+    result
+}
+
+/// Expand %d, %0Nd, %r, %R, %t and %% in FORMAT using conventions of LOCALE
+pub(crate) fn format_number(format: &str, number: u32, total: usize, locale: Locale) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let mut width = String::new();
+        while let Some(d) = chars.peek().filter(|x| x.is_ascii_digit()) {
+            width.push(*d);
+            chars.next();
+        }
+
+        match chars.next() {
+            Some('d') => {
+                // zero padded numbers are identifiers, so they are not grouped
+                match width.parse::<usize>() {
+                    Ok(width) => result.push_str(&locale.digits(&format!(
+                        "{:0width$}",
+                        number,
+                        width = width
+                    ))),
+                    Err(_) => result.push_str(&locale.format_number(number as u64)),
+                }
+            }
+            Some('r') => result.push_str(&roman(number)),
+            Some('R') => result.push_str(&roman(number).to_uppercase()),
+            Some('t') => result.push_str(&locale.format_number(total as u64)),
+            Some('%') => result.push('%'),
+            Some(other) => {
+                result.push('%');
+                result.push_str(&width);
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+
+    result
+}
+
+/// Fail if LOCALE has characters that standard FONT cannot draw
+fn check_font(font: &Font, locale: Locale) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !locale.needs_unicode_font() || matches!(font, Font::TrueType(_)),
+        "locale {:?} needs TrueType font given by --font",
+        locale
+    );
+
+    Ok(())
+}
+
+impl Pdf {
+    /// Write sequential numbers onto selected pages, as "Page 3 of 10" or Bates number like ABC000123
+    pub fn stamp_page_numbers(&mut self, style: &PageNumberStyle) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        check_font(&style.font, style.locale)?;
+
+        let page_ids = self.doc.get_pages();
+        let pages: Vec<ObjectId> = style
+            .pages
+            .pages(page_ids.len() as u32)
+            .iter()
+            .filter_map(|num| page_ids.get(num).copied())
+            .collect();
+        let total = pages.len();
+
+        for (i, page_id) in pages.into_iter().enumerate() {
+            self.check_deadline()?;
+            let number = style.start + i as u32;
+            let text = format!(
+                "{}{}",
+                style.prefix,
+                format_number(&style.format, number, total, style.locale)
+            );
+
+            self.draw_text_at(
+                page_id,
+                &text,
+                style.position,
+                &style.font,
+                style.font_size,
+                style.margin,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw TEMPLATE on selected pages as header or footer. {page}, {pages}, {file} and {date}
+    /// in TEMPLATE are replaced by page number, page count, STYLE's file name and today,
+    /// formatted for locale of STYLE. {title}, {author}, {subject}, {keywords}, {creator},
+    /// {producer} and {meta:KEY} are taken from Info dictionary or XMP metadata.
+    pub fn stamp_text(&mut self, template: &str, style: &TextStamp) -> anyhow::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        check_font(&style.font, style.locale)?;
+
+        let pages = self.doc.get_pages();
+
+        let mut vars = BTreeMap::new();
+        let total = pages.len() as u32;
+        vars.insert("pages", style.locale.format_number(total as u64));
+        vars.insert("file", style.file_name.clone());
+        let today = Local::now();
+        let date = match &style.date_format {
+            Some(format) => today.format(format).to_string(),
+            None => style.locale.format_date(today.date_naive()),
+        };
+        vars.insert("date", date);
+
+        // metadata is resolved now, so later edits by other operations are reflected
+        for name in template_names(template) {
+            if !vars.contains_key(name) {
+                if let Some(value) = self.metadata_var(name) {
+                    vars.insert(name, value);
+                }
+            }
+        }
+
+        for (num, page_id) in pages {
+            if !style.pages.contains(num, total) {
+                continue;
+            }
+
+            self.check_deadline()?;
+            vars.insert("page", style.locale.format_number(num as u64));
+
+            let text = expand_template(template, &vars);
+            self.draw_text_at(
+                page_id,
+                &text,
+                style.position,
+                &style.font,
+                style.font_size,
+                style.margin,
+            )?;
+        }
+
+        Ok(())
+    }
+}
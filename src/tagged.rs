@@ -0,0 +1,160 @@
+use lopdf::{dictionary, Dictionary, Object, ObjectId};
+
+use crate::{error::Error, pdf::text_string, permissions::Permission, Pdf};
+
+/// Append KID to kids (/K) of structure element or tree root DICT, normalizing them
+/// into array
+fn push_kid(dict: &mut Dictionary, kid: ObjectId) {
+    let mut kids = match dict.get(b"K") {
+        Ok(Object::Array(arr)) => arr.clone(),
+        Ok(kid) => vec![kid.clone()],
+        Err(_) => vec![],
+    };
+    kids.push(kid.into());
+    dict.set("K", kids);
+}
+
+impl Pdf {
+    /// Get id of structure tree root of catalog, creating it if missing and moving it
+    /// into its own object if it is direct. document is marked as tagged.
+    fn get_or_create_struct_tree(&mut self) -> crate::Result<ObjectId> {
+        let existing = self.doc.catalog()?.get(b"StructTreeRoot").ok().cloned();
+        let id = match existing {
+            Some(Object::Reference(id)) => id,
+            Some(Object::Dictionary(dict)) => self.doc.add_object(dict),
+            _ => self.doc.add_object(dictionary! {
+                "Type" => "StructTreeRoot",
+                "K" => vec![],
+            }),
+        };
+
+        let catalog = self.catalog_mut()?;
+        catalog.set("StructTreeRoot", id);
+        let mut mark_info = match catalog.get(b"MarkInfo") {
+            Ok(Object::Dictionary(dict)) => dict.clone(),
+            _ => Dictionary::new(),
+        };
+        mark_info.set("Marked", true);
+        catalog.set("MarkInfo", mark_info);
+
+        if !self.doc.get_dictionary(id)?.has(b"ParentTree") {
+            let parent_tree = self.doc.add_object(dictionary! { "Nums" => vec![] });
+            let root = self.doc.get_dictionary_mut(id)?;
+            root.set("ParentTree", parent_tree);
+            root.set("ParentTreeNextKey", 0);
+        }
+
+        Ok(id)
+    }
+
+    /// Get id of Document element directly under structure tree ROOT_ID, creating it if
+    /// missing
+    fn get_or_create_document_element(&mut self, root_id: ObjectId) -> crate::Result<ObjectId> {
+        let kids = match self.doc.get_dictionary(root_id)?.get(b"K") {
+            Ok(Object::Array(arr)) => arr.clone(),
+            Ok(kid) => vec![kid.clone()],
+            Err(_) => vec![],
+        };
+
+        let document = kids.iter().find_map(|kid| {
+            let id = kid.as_reference().ok()?;
+            let dict = self.doc.get_dictionary(id).ok()?;
+            (dict.get(b"S").and_then(Object::as_name).ok() == Some(&b"Document"[..])).then_some(id)
+        });
+        if let Some(id) = document {
+            return Ok(id);
+        }
+
+        let id = self.doc.add_object(dictionary! {
+            "Type" => "StructElem",
+            "S" => "Document",
+            "P" => root_id,
+            "K" => vec![],
+        });
+        push_kid(self.doc.get_dictionary_mut(root_id)?, id);
+
+        Ok(id)
+    }
+
+    /// Tag whole content of PAGE as Figure with alternate description ALT, read aloud
+    /// by screen readers in place of image. meant for pages made of single image, like
+    /// those added by `add_image`. returns id of structure element.
+    pub fn tag_figure(&mut self, page: u32, alt: &str) -> crate::Result<ObjectId> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        // marked content can't be nested, so content tagged already is left as is
+        if self.doc.get_dictionary(page_id)?.has(b"StructParents") {
+            return Err(Error::InvalidArgument(format!(
+                "page {} already has tagged content",
+                page
+            )));
+        }
+
+        let root_id = self.get_or_create_struct_tree()?;
+        let document_id = self.get_or_create_document_element(root_id)?;
+
+        let original = self.doc.get_page_content(page_id)?;
+        let content = [
+            b"/Figure <</MCID 0>> BDC\nq\n".to_vec(),
+            original,
+            b"\nQ\nEMC\n".to_vec(),
+        ]
+        .concat();
+        self.doc.change_page_content(page_id, content)?;
+
+        let figure_id = self.doc.add_object(dictionary! {
+            "Type" => "StructElem",
+            "S" => "Figure",
+            "P" => document_id,
+            "Pg" => page_id,
+            "K" => 0,
+            "Alt" => text_string(alt),
+        });
+        push_kid(self.doc.get_dictionary_mut(document_id)?, figure_id);
+
+        // parent tree maps marked content of page back to structure elements
+        let root = self.doc.get_dictionary(root_id)?;
+        let next_key = root.get(b"ParentTreeNextKey").and_then(Object::as_i64).ok();
+        let parent_tree = match root.get(b"ParentTree").ok().cloned() {
+            Some(Object::Reference(id)) => id,
+            Some(Object::Dictionary(dict)) => {
+                let id = self.doc.add_object(dict);
+                self.doc.get_dictionary_mut(root_id)?.set("ParentTree", id);
+                id
+            }
+            _ => {
+                return Err(Error::InvalidDocument(
+                    "invalid parent tree of structure tree root".into(),
+                ))
+            }
+        };
+        let parent_tree = self.doc.get_dictionary_mut(parent_tree)?;
+        if parent_tree.has(b"Kids") {
+            return Err(Error::InvalidDocument(
+                "parent tree with kids is not supported".into(),
+            ));
+        }
+        // next key may be missing or stale, so keys already in tree are skipped
+        let used_key = match parent_tree.get(b"Nums") {
+            Ok(Object::Array(nums)) => nums.iter().step_by(2).filter_map(|x| x.as_i64().ok()).max(),
+            _ => None,
+        };
+        let key = next_key.unwrap_or(0).max(used_key.map_or(0, |x| x + 1));
+        let entry = [key.into(), Object::Array(vec![figure_id.into()])];
+        match parent_tree.get_mut(b"Nums") {
+            Ok(Object::Array(nums)) => nums.extend(entry),
+            _ => parent_tree.set("Nums", entry.to_vec()),
+        }
+
+        self.doc
+            .get_dictionary_mut(root_id)?
+            .set("ParentTreeNextKey", key + 1);
+        self.doc
+            .get_dictionary_mut(page_id)?
+            .set("StructParents", key);
+
+        log::info!("tagged page {} as figure", page);
+        Ok(figure_id)
+    }
+}
@@ -0,0 +1,343 @@
+//! Helpers for regression tests of pipelines built on pdftool.
+//! comparisons ignore things which change on every run, such as dates, document ID and object numbers.
+
+use std::collections::BTreeSet;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, ObjectId, Stream,
+};
+
+use crate::{xobject, Pdf};
+
+/// Environment variable which makes golden comparisons overwrite golden files
+pub const UPDATE_GOLDEN_VAR: &str = "PDFTOOL_UPDATE_GOLDEN";
+
+/// Keys whose values differ between otherwise identical documents
+const VOLATILE_KEYS: [&[u8]; 7] = [
+    b"CreationDate",
+    b"ModDate",
+    b"ID",
+    b"Length",
+    b"Size",
+    b"Prev",
+    b"XRefStm",
+];
+
+/// Make document with PAGES pages of WIDTH x HEIGHT, each showing text "Page N"
+pub fn sample_document(pages: u32, width: u32, height: u32) -> anyhow::Result<Pdf> {
+    let mut pdf = Pdf::new();
+
+    let font_id = pdf.doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    for num in 1..=pages {
+        let page_id = pdf.add_page(width, height)?;
+
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                Operation::new("Td", vec![72.into(), (height as i64 - 72).into()]),
+                Operation::new("Tj", vec![Object::string_literal(format!("Page {}", num))]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let contents_id = pdf
+            .doc
+            .add_object(Stream::new(dictionary! {}, content.encode()?));
+
+        let page = pdf.doc.get_dictionary_mut(page_id)?;
+        page.set("Contents", contents_id);
+        page.set(
+            "Resources",
+            dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        );
+    }
+
+    Ok(pdf)
+}
+
+/// Make RGB gradient PNG of WIDTH x HEIGHT
+pub fn sample_png(width: u32, height: u32) -> anyhow::Result<Vec<u8>> {
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        Rgb([
+            (x * 255 / width.max(1)) as u8,
+            (y * 255 / height.max(1)) as u8,
+            128,
+        ])
+    });
+
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(img).write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+struct Comparer<'a> {
+    left: &'a Document,
+    right: &'a Document,
+    visited: BTreeSet<(ObjectId, ObjectId)>,
+    differences: Vec<String>,
+}
+
+impl<'a> Comparer<'a> {
+    fn compare(&mut self, path: &str, left: &Object, right: &Object) {
+        match (left, right) {
+            (Object::Reference(l), Object::Reference(r)) => {
+                if !self.visited.insert((*l, *r)) {
+                    return;
+                }
+
+                match (self.left.get_object(*l), self.right.get_object(*r)) {
+                    (Ok(lo), Ok(ro)) => self.compare(path, lo, ro),
+                    (lo, ro) => {
+                        if lo.is_ok() != ro.is_ok() {
+                            self.differences
+                                .push(format!("{}: dangling reference on one side", path));
+                        }
+                    }
+                }
+            }
+            (Object::Dictionary(l), Object::Dictionary(r)) => {
+                let keys: BTreeSet<&Vec<u8>> = l.iter().chain(r.iter()).map(|(k, _)| k).collect();
+
+                for key in keys {
+                    if VOLATILE_KEYS.contains(&key.as_slice()) {
+                        continue;
+                    }
+
+                    let sub = format!("{}/{}", path, String::from_utf8_lossy(key));
+                    match (l.get(key), r.get(key)) {
+                        (Ok(lv), Ok(rv)) => self.compare(&sub, lv, rv),
+                        (Ok(_), Err(_)) => self.differences.push(format!("{}: only in left", sub)),
+                        (Err(_), Ok(_)) => self.differences.push(format!("{}: only in right", sub)),
+                        _ => {}
+                    }
+                }
+            }
+            (Object::Array(l), Object::Array(r)) => {
+                if l.len() != r.len() {
+                    self.differences.push(format!(
+                        "{}: array length {} != {}",
+                        path,
+                        l.len(),
+                        r.len()
+                    ));
+                    return;
+                }
+
+                for (i, (lv, rv)) in l.iter().zip(r).enumerate() {
+                    self.compare(&format!("{}[{}]", path, i), lv, rv);
+                }
+            }
+            (Object::Stream(l), Object::Stream(r)) => {
+                let mut l_dict = l.dict.clone();
+                let mut r_dict = r.dict.clone();
+                // compare decoded data, so recompression alone is not a difference
+                for dict in [&mut l_dict, &mut r_dict] {
+                    dict.remove(b"Filter");
+                    dict.remove(b"DecodeParms");
+                }
+                self.compare(
+                    path,
+                    &Object::Dictionary(l_dict),
+                    &Object::Dictionary(r_dict),
+                );
+
+                let l_data = l
+                    .decompressed_content()
+                    .unwrap_or_else(|_| l.content.clone());
+                let r_data = r
+                    .decompressed_content()
+                    .unwrap_or_else(|_| r.content.clone());
+                if l_data != r_data {
+                    self.differences
+                        .push(format!("{}: stream data differs", path));
+                }
+            }
+            (Object::Integer(l), Object::Real(r)) | (Object::Real(r), Object::Integer(l)) => {
+                if *l as f32 != *r {
+                    self.differences.push(format!("{}: {} != {}", path, l, r));
+                }
+            }
+            (l, r) => {
+                if format!("{:?}", l) != format!("{:?}", r) {
+                    self.differences
+                        .push(format!("{}: {:?} != {:?}", path, l, r));
+                }
+            }
+        }
+    }
+}
+
+/// List structural differences between documents, walking both from trailer.
+/// empty result means documents are equivalent.
+pub fn structural_differences(left: &Pdf, right: &Pdf) -> Vec<String> {
+    let mut comparer = Comparer {
+        left: &left.doc,
+        right: &right.doc,
+        visited: BTreeSet::new(),
+        differences: vec![],
+    };
+
+    comparer.compare(
+        "trailer",
+        &Object::Dictionary(left.doc.trailer.clone()),
+        &Object::Dictionary(right.doc.trailer.clone()),
+    );
+
+    comparer.differences
+}
+
+/// Extract text of each page
+pub fn page_texts(pdf: &Pdf) -> anyhow::Result<Vec<String>> {
+    let count = pdf.doc.get_pages().len() as u32;
+
+    (1..=count)
+        .map(|num| Ok(pdf.doc.extract_text(&[num])?))
+        .collect()
+}
+
+/// Decode images drawn on each page, in drawing order
+pub fn page_images(pdf: &Pdf) -> anyhow::Result<Vec<Vec<DynamicImage>>> {
+    let mut result = vec![];
+
+    for page_id in pdf.doc.get_pages().into_values() {
+        let mut images = vec![];
+        for placement in xobject::image_placements(&pdf.doc, page_id)? {
+            let stream = pdf.doc.get_object(placement.id)?.as_stream()?;
+            images.push(xobject::decode_image(&pdf.doc, stream)?);
+        }
+        result.push(images);
+    }
+
+    Ok(result)
+}
+
+/// Panic if documents differ structurally
+pub fn assert_same_structure(left: &Pdf, right: &Pdf) {
+    let differences = structural_differences(left, right);
+
+    assert!(
+        differences.is_empty(),
+        "documents differ:\n{}",
+        differences.join("\n")
+    );
+}
+
+/// Panic if documents have different page count or page text
+pub fn assert_same_text(left: &Pdf, right: &Pdf) {
+    let left = page_texts(left).expect("failed to extract text of left document");
+    let right = page_texts(right).expect("failed to extract text of right document");
+
+    assert_eq!(left.len(), right.len(), "page count differs");
+    for (i, (l, r)) in left.iter().zip(&right).enumerate() {
+        assert_eq!(l, r, "text of page {} differs", i + 1);
+    }
+}
+
+/// Panic if images on pages differ in size or pixels by more than TOLERANCE per channel
+pub fn assert_same_images(left: &Pdf, right: &Pdf, tolerance: u8) {
+    let left = page_images(left).expect("failed to decode images of left document");
+    let right = page_images(right).expect("failed to decode images of right document");
+
+    assert_eq!(left.len(), right.len(), "page count differs");
+    for (page, (l, r)) in left.iter().zip(&right).enumerate() {
+        assert_eq!(l.len(), r.len(), "image count of page {} differs", page + 1);
+
+        for (i, (li, ri)) in l.iter().zip(r).enumerate() {
+            let (li, ri) = (li.to_rgb8(), ri.to_rgb8());
+            assert_eq!(
+                li.dimensions(),
+                ri.dimensions(),
+                "size of image {} on page {} differs",
+                i,
+                page + 1
+            );
+
+            let max_diff = li
+                .as_raw()
+                .iter()
+                .zip(ri.as_raw())
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+            assert!(
+                max_diff <= tolerance,
+                "pixels of image {} on page {} differ by {}",
+                i,
+                page + 1,
+                max_diff
+            );
+        }
+    }
+}
+
+/// Save PDF and load it again, for checking output survives serialization
+pub fn round_trip(pdf: Pdf) -> anyhow::Result<Pdf> {
+    let bytes = pdf.to_bytes()?;
+    Ok(Pdf::from_bytes(&bytes)?)
+}
+
+/// List structural differences between PDF and golden document at PATH.
+/// golden file is written from PDF instead if it is missing or UPDATE_GOLDEN_VAR is set.
+pub fn golden_differences<P: AsRef<Path>>(pdf: &Pdf, path: P) -> anyhow::Result<Vec<String>> {
+    let update = std::env::var_os(UPDATE_GOLDEN_VAR).is_some();
+    compare_golden(pdf, path.as_ref(), update)
+}
+
+fn compare_golden(pdf: &Pdf, path: &Path, update: bool) -> anyhow::Result<Vec<String>> {
+    if update || !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        pdf.doc.clone().save(path)?;
+        return Ok(vec![]);
+    }
+
+    let golden = Pdf::load(path)?;
+    Ok(structural_differences(&golden, pdf))
+}
+
+/// Panic if PDF differs structurally from golden document at PATH
+pub fn assert_golden<P: AsRef<Path>>(pdf: &Pdf, path: P) {
+    let path = path.as_ref();
+    let differences = golden_differences(pdf, path).expect("failed to compare with golden file");
+
+    assert!(
+        differences.is_empty(),
+        "document differs from {} (set {} to update):\n{}",
+        path.display(),
+        UPDATE_GOLDEN_VAR,
+        differences.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_file_is_written_then_compared() {
+        let dir = std::env::temp_dir().join(format!("pdftool-golden-{}", std::process::id()));
+        let path = dir.join("sample.pdf");
+        let _ = std::fs::remove_file(&path);
+
+        let pdf = sample_document(2, 200, 300).unwrap();
+        assert!(golden_differences(&pdf, &path).unwrap().is_empty());
+        assert!(path.exists());
+
+        assert_golden(&round_trip(pdf).unwrap(), &path);
+
+        let other = sample_document(3, 200, 300).unwrap();
+        let differences = compare_golden(&other, &path, false).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(!differences.is_empty());
+    }
+}
@@ -0,0 +1,427 @@
+use std::str::FromStr;
+
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Object, ObjectId, StringFormat,
+};
+
+use crate::{
+    attributes::display_space, font::Font, permissions::Permission, xobject::IDENTITY, Pdf,
+};
+
+/// Advance widths of Helvetica for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0' - '9'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556,
+    // ':' - '@'
+    278, 278, 584, 584, 584, 556, 1015,
+    // 'A' - 'Z'
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611,
+    // '[' - '`'
+    278, 278, 278, 469, 556, 333,
+    // 'a' - 'z'
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833,
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500,
+    // '{' - '~'
+    334, 260, 334, 584,
+];
+
+/// Advance widths of Helvetica-Bold for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0' - '9'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556,
+    // ':' - '@'
+    333, 333, 584, 584, 584, 611, 975,
+    // 'A' - 'Z'
+    722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611,
+    // '[' - '`'
+    333, 278, 333, 584, 556, 333,
+    // 'a' - 'z'
+    556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889,
+    611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500,
+    // '{' - '~'
+    389, 280, 389, 584,
+];
+
+/// Advance widths of Times-Roman for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const TIMES_ROMAN_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    // '0' - '9'
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    // ':' - '@'
+    278, 278, 564, 564, 564, 444, 921,
+    // 'A' - 'Z'
+    722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889,
+    722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611,
+    // '[' - '`'
+    333, 278, 333, 469, 500, 333,
+    // 'a' - 'z'
+    444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778,
+    500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444,
+    // '{' - '~'
+    480, 200, 480, 541,
+];
+
+/// Advance widths of Times-Bold for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const TIMES_BOLD_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    // '0' - '9'
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    // ':' - '@'
+    333, 333, 570, 570, 570, 500, 930,
+    // 'A' - 'Z'
+    722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944,
+    722, 778, 611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667,
+    // '[' - '`'
+    333, 278, 333, 581, 500, 333,
+    // 'a' - 'z'
+    500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833,
+    556, 500, 556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444,
+    // '{' - '~'
+    394, 220, 394, 520,
+];
+
+/// Advance widths of Times-Italic for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const TIMES_ITALIC_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    // '0' - '9'
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    // ':' - '@'
+    333, 333, 675, 675, 675, 500, 920,
+    // 'A' - 'Z'
+    611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833,
+    667, 722, 611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556,
+    // '[' - '`'
+    389, 278, 389, 422, 500, 333,
+    // 'a' - 'z'
+    500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722,
+    500, 500, 500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389,
+    // '{' - '~'
+    400, 275, 400, 541,
+];
+
+/// Advance widths of Times-BoldItalic for ' ' to '~' in 1/1000 em
+#[rustfmt::skip]
+const TIMES_BOLD_ITALIC_WIDTHS: [u16; 95] = [
+    // ' ' - '/'
+    250, 389, 555, 500, 500, 833, 778, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    // '0' - '9'
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    // ':' - '@'
+    333, 333, 570, 570, 570, 500, 832,
+    // 'A' - 'Z'
+    667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889,
+    722, 722, 611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611,
+    // '[' - '`'
+    333, 278, 333, 570, 500, 333,
+    // 'a' - 'z'
+    500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778,
+    556, 500, 500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389,
+    // '{' - '~'
+    348, 220, 348, 570,
+];
+
+/// Advance widths of Courier family, which is monospaced
+const COURIER_WIDTHS: [u16; 95] = [600; 95];
+
+/// Width of TEXT set in Helvetica at SIZE points
+pub(crate) fn text_width(text: &str, size: f32) -> f32 {
+    StandardFont::Helvetica.text_width(text, size)
+}
+
+/// Encode TEXT in WinAnsiEncoding, replacing characters out of Latin-1 with '?'
+pub(crate) fn win_ansi(text: &str) -> Object {
+    let bytes = text
+        .chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect();
+
+    Object::String(bytes, StringFormat::Literal)
+}
+
+/// One of standard 14 fonts every PDF viewer has, which need not be embedded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StandardFont {
+    #[default]
+    Helvetica,
+    HelveticaBold,
+    HelveticaOblique,
+    HelveticaBoldOblique,
+    TimesRoman,
+    TimesBold,
+    TimesItalic,
+    TimesBoldItalic,
+    Courier,
+    CourierBold,
+    CourierOblique,
+    CourierBoldOblique,
+    Symbol,
+    ZapfDingbats,
+}
+
+impl FromStr for StandardFont {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Helvetica" => Ok(Self::Helvetica),
+            "Helvetica-Bold" => Ok(Self::HelveticaBold),
+            "Helvetica-Oblique" => Ok(Self::HelveticaOblique),
+            "Helvetica-BoldOblique" => Ok(Self::HelveticaBoldOblique),
+            "Times-Roman" => Ok(Self::TimesRoman),
+            "Times-Bold" => Ok(Self::TimesBold),
+            "Times-Italic" => Ok(Self::TimesItalic),
+            "Times-BoldItalic" => Ok(Self::TimesBoldItalic),
+            "Courier" => Ok(Self::Courier),
+            "Courier-Bold" => Ok(Self::CourierBold),
+            "Courier-Oblique" => Ok(Self::CourierOblique),
+            "Courier-BoldOblique" => Ok(Self::CourierBoldOblique),
+            "Symbol" => Ok(Self::Symbol),
+            "ZapfDingbats" => Ok(Self::ZapfDingbats),
+            _ => anyhow::bail!("unknown standard font: {}", s),
+        }
+    }
+}
+
+impl StandardFont {
+    /// Advance widths for ' ' to '~' from AFM of font, and width assumed for other
+    /// characters
+    fn metrics(&self) -> (&'static [u16; 95], u16) {
+        match self {
+            Self::Helvetica | Self::HelveticaOblique => (&HELVETICA_WIDTHS, 556),
+            Self::HelveticaBold | Self::HelveticaBoldOblique => (&HELVETICA_BOLD_WIDTHS, 556),
+            Self::TimesRoman => (&TIMES_ROMAN_WIDTHS, 500),
+            Self::TimesBold => (&TIMES_BOLD_WIDTHS, 500),
+            Self::TimesItalic => (&TIMES_ITALIC_WIDTHS, 500),
+            Self::TimesBoldItalic => (&TIMES_BOLD_ITALIC_WIDTHS, 500),
+            Self::Courier | Self::CourierBold | Self::CourierOblique | Self::CourierBoldOblique => {
+                (&COURIER_WIDTHS, 600)
+            }
+            // built-in encodings don't follow ASCII, so Helvetica gives rough estimate
+            Self::Symbol | Self::ZapfDingbats => (&HELVETICA_WIDTHS, 556),
+        }
+    }
+
+    /// Width of TEXT set in this font at SIZE points
+    pub(crate) fn text_width(&self, text: &str, size: f32) -> f32 {
+        let (widths, other) = self.metrics();
+        let units: u32 = text
+            .chars()
+            .map(|c| match c {
+                ' '..='~' => widths[c as usize - 0x20] as u32,
+                // rough average for characters out of table
+                _ => other as u32,
+            })
+            .sum();
+
+        units as f32 * size / 1000.0
+    }
+
+    /// PostScript name used as /BaseFont
+    pub fn base_font(&self) -> &'static str {
+        match self {
+            Self::Helvetica => "Helvetica",
+            Self::HelveticaBold => "Helvetica-Bold",
+            Self::HelveticaOblique => "Helvetica-Oblique",
+            Self::HelveticaBoldOblique => "Helvetica-BoldOblique",
+            Self::TimesRoman => "Times-Roman",
+            Self::TimesBold => "Times-Bold",
+            Self::TimesItalic => "Times-Italic",
+            Self::TimesBoldItalic => "Times-BoldItalic",
+            Self::Courier => "Courier",
+            Self::CourierBold => "Courier-Bold",
+            Self::CourierOblique => "Courier-Oblique",
+            Self::CourierBoldOblique => "Courier-BoldOblique",
+            Self::Symbol => "Symbol",
+            Self::ZapfDingbats => "ZapfDingbats",
+        }
+    }
+
+    /// Name of font in page resources, after short names used by AcroForm
+    pub(crate) fn resource_name(&self) -> &'static str {
+        match self {
+            Self::Helvetica => "PdftoolHelv",
+            Self::HelveticaBold => "PdftoolHeBo",
+            Self::HelveticaOblique => "PdftoolHeOb",
+            Self::HelveticaBoldOblique => "PdftoolHeBO",
+            Self::TimesRoman => "PdftoolTiRo",
+            Self::TimesBold => "PdftoolTiBo",
+            Self::TimesItalic => "PdftoolTiIt",
+            Self::TimesBoldItalic => "PdftoolTiBI",
+            Self::Courier => "PdftoolCour",
+            Self::CourierBold => "PdftoolCoBo",
+            Self::CourierOblique => "PdftoolCoOb",
+            Self::CourierBoldOblique => "PdftoolCoBO",
+            Self::Symbol => "PdftoolSymb",
+            Self::ZapfDingbats => "PdftoolZaDb",
+        }
+    }
+
+    /// Symbol and ZapfDingbats have their own built-in encoding
+    fn is_symbolic(&self) -> bool {
+        matches!(self, Self::Symbol | Self::ZapfDingbats)
+    }
+}
+
+/// Place on page where text is anchored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Position {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomCenter,
+    BottomRight,
+}
+
+impl FromStr for Position {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Self::TopLeft),
+            "top-center" | "top" => Ok(Self::TopCenter),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-center" | "bottom" => Ok(Self::BottomCenter),
+            "bottom-right" => Ok(Self::BottomRight),
+            _ => anyhow::bail!("unknown position: {}", s),
+        }
+    }
+}
+
+impl Position {
+    /// Baseline origin of text WIDTH wide and SIZE high in BOX, keeping MARGIN from its edges
+    pub(crate) fn origin(
+        &self,
+        page_box: [f32; 4],
+        width: f32,
+        size: f32,
+        margin: f32,
+    ) -> (f32, f32) {
+        let [x0, y0, x1, y1] = page_box;
+
+        let x = match self {
+            Self::TopLeft | Self::BottomLeft => x0 + margin,
+            Self::TopCenter | Self::BottomCenter => (x0 + x1 - width) / 2.0,
+            Self::TopRight | Self::BottomRight => x1 - margin - width,
+        };
+        let y = match self {
+            Self::TopLeft | Self::TopCenter | Self::TopRight => y1 - margin - size,
+            _ => y0 + margin,
+        };
+
+        (x, y)
+    }
+}
+
+impl Pdf {
+    /// Get font object of FONT, adding it if document does not have one yet
+    pub(crate) fn standard_font(&mut self, font: StandardFont) -> ObjectId {
+        let encoding = (!font.is_symbolic()).then_some(&b"WinAnsiEncoding"[..]);
+
+        let existing = self.doc.objects.iter().find_map(|(id, obj)| {
+            let dict = obj.as_dict().ok()?;
+            let is_same = dict.type_name().ok() == Some("Font")
+                && dict.get(b"BaseFont").and_then(Object::as_name).ok()
+                    == Some(font.base_font().as_bytes())
+                && dict.get(b"Encoding").and_then(Object::as_name).ok() == encoding;
+
+            is_same.then_some(*id)
+        });
+
+        existing.unwrap_or_else(|| {
+            let mut dict = dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => font.base_font(),
+            };
+            if encoding.is_some() {
+                dict.set("Encoding", "WinAnsiEncoding");
+            }
+            self.doc.add_object(dict)
+        })
+    }
+
+    /// Append operators drawing single line TEXT with baseline origin at X, Y to page PAGE_ID.
+    /// X, Y are in space mapped into user space by CTM.
+    pub(crate) fn draw_text_line(
+        &mut self,
+        page_id: ObjectId,
+        text: &str,
+        (x, y): (f32, f32),
+        font: &Font,
+        size: f32,
+        ctm: [f32; 6],
+    ) -> crate::Result<()> {
+        let (font_id, name, encoded) = self.font_for_text(font, text)?;
+        self.add_page_resource(page_id, "Font", &name, font_id)?;
+
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![name.into(), size.into()]),
+            Operation::new("Td", vec![x.into(), y.into()]),
+            Operation::new("Tj", vec![encoded]),
+            Operation::new("ET", vec![]),
+        ];
+        if ctm != IDENTITY {
+            let matrix = ctm.iter().map(|&x| x.into()).collect();
+            operations.insert(0, Operation::new("q", vec![]));
+            operations.insert(1, Operation::new("cm", matrix));
+            operations.push(Operation::new("Q", vec![]));
+        }
+
+        let content = Content { operations };
+        self.append_page_content(page_id, content.encode()?)
+    }
+
+    /// Draw single line TEXT on PAGE above existing content, with baseline origin at
+    /// X, Y in points. with standard fonts, characters out of Latin-1 are replaced
+    /// with '?'; use TrueType font for other scripts like Japanese.
+    pub fn draw_text(
+        &mut self,
+        page: u32,
+        text: &str,
+        x: f32,
+        y: f32,
+        font: &Font,
+        size: f32,
+    ) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        self.draw_text_line(page_id, text, (x, y), font, size, IDENTITY)
+    }
+
+    /// Draw single line TEXT at POSITION of page PAGE_ID above existing content. position
+    /// is taken in visible area of page as displayed, after CropBox and Rotate.
+    pub(crate) fn draw_text_at(
+        &mut self,
+        page_id: ObjectId,
+        text: &str,
+        position: Position,
+        font: &Font,
+        size: f32,
+        margin: f32,
+    ) -> anyhow::Result<()> {
+        let (page_box, ctm) = display_space(&self.doc, page_id);
+        let origin = position.origin(page_box, font.text_width(text, size), size, margin);
+
+        Ok(self.draw_text_line(page_id, text, origin, font, size, ctm)?)
+    }
+}
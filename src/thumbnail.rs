@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat};
+use lopdf::{Object, ObjectId};
+
+use crate::{attributes::rotation, limits::LimitExceeded, permissions::Permission, xobject, Pdf};
+
+impl Pdf {
+    /// Decode image ID, scaled down to fit in MAX x MAX pixels
+    fn scaled_image(&self, id: ObjectId, max: u32) -> anyhow::Result<DynamicImage> {
+        let stream = self.doc.get_object(id).and_then(Object::as_stream)?;
+        let img = self.decode_image(stream)?;
+        if img.width() > max || img.height() > max {
+            Ok(img.thumbnail(max, max))
+        } else {
+            Ok(img)
+        }
+    }
+
+    /// Make thumbnail of PAGE fitting in MAX x MAX pixels.
+    /// thumbnail embedded in page (/Thumb) is used if present, otherwise largest image
+    /// drawn on page, which is whole page for scanned documents.
+    pub fn thumbnail(&self, page: u32, max: u32) -> anyhow::Result<DynamicImage> {
+        let page_id = self.get_page_id(page)?;
+        let page_dict = self.doc.get_dictionary(page_id)?;
+
+        let source = match page_dict.get(b"Thumb").and_then(Object::as_reference) {
+            Ok(id) => Some(id),
+            Err(_) => xobject::largest_image(&self.doc, page_id)?,
+        };
+        let id = source.ok_or_else(|| {
+            anyhow::anyhow!(
+                "page {} has no image to make thumbnail from; rendering is not supported",
+                page
+            )
+        })?;
+
+        let img = self.scaled_image(id, max)?;
+
+        Ok(match rotation(&self.doc, page_id) {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        })
+    }
+
+    /// Write thumbnail of PAGE fitting in MAX x MAX pixels into PATH as PNG
+    pub fn export_thumbnail<P: AsRef<Path>>(
+        &self,
+        page: u32,
+        path: P,
+        max: u32,
+    ) -> anyhow::Result<()> {
+        self.thumbnail(page, max)?
+            .save_with_format(path, ImageFormat::Png)?;
+        Ok(())
+    }
+
+    /// Embed thumbnail fitting in MAX_DIM x MAX_DIM pixels as /Thumb of each page which has
+    /// none, made from largest image drawn on page. pages without images are skipped.
+    /// returns numbers of pages which got thumbnail.
+    pub fn generate_thumbnails(&mut self, max_dim: u32) -> anyhow::Result<Vec<u32>> {
+        self.check_permission(Permission::Modify)?;
+
+        let pages = self.doc.get_pages();
+        let total = pages.len();
+        let mut result = Vec::new();
+
+        for (i, (page, page_id)) in pages.into_iter().enumerate() {
+            self.report_progress("generate_thumbnails", i, total);
+            if self.doc.get_dictionary(page_id)?.has(b"Thumb") {
+                continue;
+            }
+
+            let Some(id) = xobject::largest_image(&self.doc, page_id)? else {
+                log::debug!("page {} has no image to make thumbnail from", page);
+                continue;
+            };
+            let img = match self.scaled_image(id, max_dim) {
+                Ok(img) => img,
+                Err(err) if err.is::<LimitExceeded>() => return Err(err),
+                Err(err) => {
+                    log::warn!("skipping thumbnail of page {}: {}", page, err);
+                    continue;
+                }
+            };
+
+            // thumbnail is image stream without XObject type (8.3.4 of ISO 32000-1)
+            let mut thumb = xobject::flate_stream(&img)?;
+            thumb.dict.remove(b"Type");
+            thumb.dict.remove(b"Subtype");
+            let thumb_id = self.doc.add_object(thumb);
+
+            self.doc.get_dictionary_mut(page_id)?.set("Thumb", thumb_id);
+            result.push(page);
+        }
+        self.report_progress("generate_thumbnails", total, total);
+
+        Ok(result)
+    }
+}
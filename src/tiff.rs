@@ -0,0 +1,176 @@
+use std::io::Cursor;
+
+use ::tiff::{
+    decoder::{Decoder, DecodingResult},
+    tags::Tag,
+    ColorType,
+};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, ImageFormat, RgbImage};
+use lopdf::{ObjectId, Stream};
+
+use crate::{ccitt::g4_image_dict, Pdf};
+
+/// TIFF compression tag value of CCITT Group 4
+const COMPRESSION_G4: u32 = 4;
+
+/// Build image XObject of CCITT G4 compressed bilevel frame without decoding it.
+/// returns None if frame can't be passed through, e.g. split into multiple strips.
+fn g4_stream(decoder: &mut Decoder<Cursor<&[u8]>>, bytes: &[u8]) -> anyhow::Result<Option<Stream>> {
+    let (width, height) = decoder.dimensions()?;
+    let offsets = decoder.get_tag_u32_vec(Tag::StripOffsets)?;
+    let counts = decoder.get_tag_u32_vec(Tag::StripByteCounts)?;
+
+    // each strip is coded independently, so they can't be concatenated
+    let (&[offset], &[count]) = (&offsets[..], &counts[..]) else {
+        return Ok(None);
+    };
+    let data = bytes
+        .get(offset as usize..offset as usize + count as usize)
+        .ok_or_else(|| anyhow::anyhow!("TIFF strip out of file"))?;
+
+    let fill_order = decoder
+        .find_tag(Tag::FillOrder)?
+        .map(|x| x.into_u32())
+        .transpose()?
+        .unwrap_or(1);
+    // PDF expects most significant bit first
+    let data: Vec<u8> = match fill_order {
+        2 => data.iter().map(|x| x.reverse_bits()).collect(),
+        _ => data.to_vec(),
+    };
+
+    // BlackIsZero frames have inverted bits compared to usual WhiteIsZero ones
+    let black_is_1 = decoder.get_tag_u32(Tag::PhotometricInterpretation)? == 1;
+
+    let mut dict = g4_image_dict(width, height, black_is_1);
+    dict.set("Length", data.len() as u32);
+
+    Ok(Some(Stream::new(dict, data)))
+}
+
+/// Decode current frame of DECODER into pixels
+fn decode_frame(decoder: &mut Decoder<Cursor<&[u8]>>) -> anyhow::Result<DynamicImage> {
+    let (width, height) = decoder.dimensions()?;
+    let color = decoder.colortype()?;
+    let white_is_zero = decoder.get_tag_u32(Tag::PhotometricInterpretation)? == 0;
+    let invalid = || anyhow::anyhow!("TIFF frame data does not match its size");
+
+    let mut img = match (color, decoder.read_image()?) {
+        (ColorType::Gray(1), DecodingResult::U8(data)) => {
+            let row = (width as usize + 7) / 8;
+            anyhow::ensure!(
+                data.len() >= row * height as usize,
+                "TIFF frame data does not match its size"
+            );
+            GrayImage::from_fn(width, height, |x, y| {
+                let byte = data[y as usize * row + x as usize / 8];
+                let bit = (byte >> (7 - x % 8)) & 1;
+                image::Luma([bit * 255])
+            })
+            .into()
+        }
+        (ColorType::Gray(8), DecodingResult::U8(data)) => GrayImage::from_raw(width, height, data)
+            .ok_or_else(invalid)?
+            .into(),
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::<image::Luma<u16>, _>::from_raw(width, height, data)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            GrayAlphaImage::from_raw(width, height, data)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => RgbImage::from_raw(width, height, data)
+            .ok_or_else(invalid)?
+            .into(),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::<image::Rgb<u16>, _>::from_raw(width, height, data)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            image::RgbaImage::from_raw(width, height, data)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::<image::Rgba<u16>, _>::from_raw(width, height, data)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => {
+            anyhow::ensure!(
+                data.len() >= width as usize * height as usize * 4,
+                "TIFF frame data does not match its size"
+            );
+            let rgb = data
+                .chunks_exact(4)
+                .flat_map(|x| {
+                    let k = 255 - x[3] as u16;
+                    [0, 1, 2].map(|i| ((255 - x[i] as u16) * k / 255) as u8)
+                })
+                .collect();
+            RgbImage::from_raw(width, height, rgb)
+                .ok_or_else(invalid)?
+                .into()
+        }
+        (color, _) => anyhow::bail!("unsupported TIFF color type: {:?}", color),
+    };
+
+    if white_is_zero && matches!(color, ColorType::Gray(_)) {
+        img.invert();
+    }
+
+    Ok(img)
+}
+
+impl Pdf {
+    /// Build image XObject for each frame of TIFF data.
+    /// G4 compressed bilevel frames are passed through as CCITTFaxDecode,
+    /// and others are decoded and embedded like PNG.
+    pub(crate) fn tiff_xobjects(&mut self, bytes: &[u8]) -> anyhow::Result<Vec<Stream>> {
+        let mut decoder = Decoder::new(Cursor::new(bytes))?;
+        let mut result = Vec::new();
+
+        loop {
+            let (width, height) = decoder.dimensions()?;
+            self.check_image_pixels(width, height)?;
+
+            let g4 = if decoder.get_tag_u32(Tag::Compression)? == COMPRESSION_G4 {
+                g4_stream(&mut decoder, bytes)?
+            } else {
+                None
+            };
+
+            let stream = match g4 {
+                Some(stream) => stream,
+                None => {
+                    let img = decode_frame(&mut decoder)?;
+                    let mut png = Vec::new();
+                    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+                    self.png_xobject(&png)?
+                }
+            };
+            result.push(stream);
+
+            if !decoder.more_images() {
+                break;
+            }
+            decoder.next_image()?;
+        }
+
+        Ok(result)
+    }
+
+    /// Add page for each frame of TIFF data, returning ids of added pages
+    pub fn add_tiff(&mut self, bytes: &[u8]) -> anyhow::Result<Vec<ObjectId>> {
+        let density = crate::density::density(bytes);
+        Ok(self
+            .tiff_xobjects(bytes)?
+            .into_iter()
+            .map(|stream| self.add_image_page(stream, density))
+            .collect::<Result<_, _>>()?)
+    }
+}
@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use lopdf::{dictionary, Object};
+
+use crate::{error::Error, permissions::Permission, Pdf};
+
+/// Effect shown when moving to page in presentation (full screen) mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransitionStyle {
+    /// New page replaces old one without effect
+    #[default]
+    Replace,
+    /// Old page fades out into new one
+    Fade,
+    /// New page is dissolved in gradually
+    Dissolve,
+    /// Line sweeps across from left to right, revealing new page
+    Wipe,
+    /// Two lines sweep across from center, like curtain
+    Split,
+    /// Multiple lines sweep across, like window blinds
+    Blinds,
+    /// Rectangle grows from center, revealing new page
+    Box,
+    /// Like Dissolve, but sweeping from left to right
+    Glitter,
+    /// New page pushes old one out from right to left
+    Push,
+    /// New page slides in from right over old one
+    Cover,
+    /// Old page slides out to left, uncovering new one
+    Uncover,
+}
+
+impl TransitionStyle {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Replace => "R",
+            Self::Fade => "Fade",
+            Self::Dissolve => "Dissolve",
+            Self::Wipe => "Wipe",
+            Self::Split => "Split",
+            Self::Blinds => "Blinds",
+            Self::Box => "Box",
+            Self::Glitter => "Glitter",
+            Self::Push => "Push",
+            Self::Cover => "Cover",
+            Self::Uncover => "Uncover",
+        }
+    }
+}
+
+impl FromStr for TransitionStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "replace" => Ok(Self::Replace),
+            "fade" => Ok(Self::Fade),
+            "dissolve" => Ok(Self::Dissolve),
+            "wipe" => Ok(Self::Wipe),
+            "split" => Ok(Self::Split),
+            "blinds" => Ok(Self::Blinds),
+            "box" => Ok(Self::Box),
+            "glitter" => Ok(Self::Glitter),
+            "push" => Ok(Self::Push),
+            "cover" => Ok(Self::Cover),
+            "uncover" => Ok(Self::Uncover),
+            _ => anyhow::bail!("unknown transition style: {}", s),
+        }
+    }
+}
+
+impl Pdf {
+    /// Show STYLE effect taking DURATION seconds when moving to PAGE in presentation mode
+    pub fn set_transition(
+        &mut self,
+        page: u32,
+        style: TransitionStyle,
+        duration: f32,
+    ) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+        if duration.is_nan() || duration < 0.0 {
+            return Err(Error::InvalidArgument(format!(
+                "invalid transition duration: {}",
+                duration
+            )));
+        }
+
+        let page_id = self.get_page_id(page)?;
+        let trans = dictionary! {
+            "Type" => "Trans",
+            "S" => Object::Name(style.name().into()),
+            "D" => duration,
+        };
+        self.doc.get_dictionary_mut(page_id)?.set("Trans", trans);
+
+        Ok(())
+    }
+}
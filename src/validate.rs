@@ -0,0 +1,215 @@
+use std::{fmt, str::FromStr};
+
+use lopdf::{Dictionary, Object};
+
+use crate::{
+    preflight::{deref, is_embedded, page_resource},
+    Pdf,
+};
+
+/// Conformance level documents are validated against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conformance {
+    /// PDF/A-2b (ISO 19005-2, basic conformance)
+    PdfA2b,
+}
+
+impl FromStr for Conformance {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pdfa-2b" => Ok(Self::PdfA2b),
+            _ => anyhow::bail!("unknown validation profile: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Conformance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PdfA2b => write!(f, "PDF/A-2b"),
+        }
+    }
+}
+
+/// Result of checking one rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleResult {
+    /// Short name of rule, like "xmp-metadata"
+    pub rule: &'static str,
+    pub description: &'static str,
+    /// Places where rule is broken, empty if passed
+    pub failures: Vec<String>,
+}
+
+impl RuleResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Rule-by-rule result of validation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub conformance: Conformance,
+    pub rules: Vec<RuleResult>,
+}
+
+impl Report {
+    /// Whether document passed all rules checked
+    pub fn is_valid(&self) -> bool {
+        self.rules.iter().all(RuleResult::passed)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in &self.rules {
+            let status = if rule.passed() { "PASS" } else { "FAIL" };
+            writeln!(f, "{} [{}] {}", status, rule.rule, rule.description)?;
+            for failure in &rule.failures {
+                writeln!(f, "    {}", failure)?;
+            }
+        }
+
+        let result = if self.is_valid() { "passed" } else { "failed" };
+        write!(f, "{} validation {}", self.conformance, result)
+    }
+}
+
+/// Find JavaScript actions in OBJ and direct objects nested in it
+fn has_javascript(obj: &Object) -> bool {
+    let is_script = |dict: &Dictionary| {
+        dict.has(b"JS") || dict.get(b"S").and_then(Object::as_name).ok() == Some(&b"JavaScript"[..])
+    };
+
+    match obj {
+        Object::Dictionary(dict) => is_script(dict) || dict.iter().any(|(_, x)| has_javascript(x)),
+        Object::Stream(stream) => is_script(&stream.dict),
+        Object::Array(arr) => arr.iter().any(has_javascript),
+        _ => false,
+    }
+}
+
+impl Pdf {
+    /// Check document against key requirements of CONFORMANCE: XMP metadata with
+    /// identification, no encryption, embedded fonts, output intent and no JavaScript.
+    /// passing them does not prove full conformance.
+    pub fn validate(&self, conformance: Conformance) -> anyhow::Result<Report> {
+        let Conformance::PdfA2b = conformance;
+        let catalog = self.doc.catalog()?;
+
+        let mut xmp = vec![];
+        let mut identification = vec![];
+        match self.xmp() {
+            None => {
+                xmp.push("catalog has no XMP metadata stream".to_string());
+                identification.push("no XMP metadata to identify conformance".to_string());
+            }
+            Some(_) => {
+                // levels A and U of PDF/A-2 include requirements of B
+                let part = self.xmp_value("pdfaid:part");
+                let level = self.xmp_value("pdfaid:conformance");
+                let level_ok = level.as_deref().map_or(false, |x| {
+                    ["A", "B", "U"].contains(&x.to_ascii_uppercase().as_str())
+                });
+                if part.as_deref() != Some("2") || !level_ok {
+                    identification.push(format!(
+                        "pdfaid:part is {} and pdfaid:conformance is {}, expected 2 and A, B or U",
+                        part.as_deref().unwrap_or("missing"),
+                        level.as_deref().unwrap_or("missing")
+                    ));
+                }
+            }
+        }
+
+        let mut encryption = vec![];
+        if self.doc.trailer.has(b"Encrypt") {
+            encryption.push("trailer has Encrypt dictionary".to_string());
+        }
+
+        let mut fonts = vec![];
+        for (num, page_id) in self.doc.get_pages() {
+            let Some(resources) = page_resource(&self.doc, page_id, b"Font") else {
+                continue;
+            };
+            for (name, font) in resources.iter() {
+                let Ok(font) = deref(&self.doc, font).as_dict() else {
+                    continue;
+                };
+                if !is_embedded(&self.doc, font) {
+                    let base = font
+                        .get(b"BaseFont")
+                        .and_then(Object::as_name)
+                        .unwrap_or(name.as_slice());
+                    fonts.push(format!(
+                        "page {}: font {} is not embedded",
+                        num,
+                        String::from_utf8_lossy(base)
+                    ));
+                }
+            }
+        }
+
+        let mut output_intent = vec![];
+        let has_intent = catalog
+            .get(b"OutputIntents")
+            .map(|x| deref(&self.doc, x))
+            .and_then(Object::as_array)
+            .map_or(false, |intents| {
+                intents
+                    .iter()
+                    .filter_map(|x| deref(&self.doc, x).as_dict().ok())
+                    .any(|x| {
+                        x.get(b"S").and_then(Object::as_name).ok() == Some(&b"GTS_PDFA1"[..])
+                            && x.has(b"DestOutputProfile")
+                    })
+            });
+        if !has_intent {
+            output_intent
+                .push("catalog has no GTS_PDFA1 output intent with ICC profile".to_string());
+        }
+
+        let mut javascript = vec![];
+        let has_names_script = catalog
+            .get(b"Names")
+            .map(|x| deref(&self.doc, x))
+            .and_then(Object::as_dict)
+            .map_or(false, |x| x.has(b"JavaScript"));
+        if has_names_script {
+            javascript.push("name dictionary has document level JavaScript".to_string());
+        }
+        for (id, obj) in &self.doc.objects {
+            if has_javascript(obj) {
+                javascript.push(format!("object {} {} has JavaScript action", id.0, id.1));
+            }
+        }
+
+        let rule = |rule: &'static str, description: &'static str, failures| RuleResult {
+            rule,
+            description,
+            failures,
+        };
+
+        Ok(Report {
+            conformance,
+            rules: vec![
+                rule("xmp-metadata", "document has XMP metadata", xmp),
+                rule(
+                    "pdfa-identification",
+                    "XMP metadata identifies PDF/A-2b",
+                    identification,
+                ),
+                rule("no-encryption", "document is not encrypted", encryption),
+                rule("fonts-embedded", "all fonts are embedded", fonts),
+                rule(
+                    "output-intent",
+                    "document has PDF/A output intent",
+                    output_intent,
+                ),
+                rule("no-javascript", "document has no JavaScript", javascript),
+            ],
+        })
+    }
+}
@@ -0,0 +1,180 @@
+use std::{fmt, str::FromStr};
+
+use lopdf::{Dictionary, Object};
+
+use crate::{error::Error, writer::SaveOptions, Pdf};
+
+/// Version of PDF specification written in header of output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PdfVersion {
+    V1_4,
+    V1_5,
+    V1_7,
+    V2_0,
+}
+
+impl PdfVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::V1_4 => "1.4",
+            Self::V1_5 => "1.5",
+            Self::V1_7 => "1.7",
+            Self::V2_0 => "2.0",
+        }
+    }
+}
+
+impl fmt::Display for PdfVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PdfVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.4" => Ok(Self::V1_4),
+            "1.5" => Ok(Self::V1_5),
+            "1.7" => Ok(Self::V1_7),
+            "2.0" => Ok(Self::V2_0),
+            _ => anyhow::bail!("unsupported PDF version: {} (1.4, 1.5, 1.7 or 2.0)", s),
+        }
+    }
+}
+
+/// Page transition styles introduced in PDF 1.5
+const TRANSITIONS_1_5: &[&[u8]] = &[b"Fly", b"Push", b"Cover", b"Uncover", b"Fade"];
+
+/// Check whether stream DICT is encoded with filter NAME
+fn has_filter(dict: &Dictionary, name: &[u8]) -> bool {
+    match dict.get(b"Filter") {
+        Ok(Object::Name(filter)) => filter == name,
+        Ok(Object::Array(filters)) => filters.iter().any(|x| x.as_name().ok() == Some(name)),
+        _ => false,
+    }
+}
+
+/// Fail if entries of DICT, which is object of any kind, need newer version than VERSION
+fn check_entries(dict: &Dictionary, version: PdfVersion) -> crate::Result<()> {
+    if dict.has(b"AF") {
+        require(version, PdfVersion::V2_0, "associated files (/AF)")?;
+    }
+    // annotations and XObjects may refer to optional content without catalog having
+    // OCProperties
+    if dict.has(b"OC") {
+        require(version, PdfVersion::V1_5, "optional content (/OC)")?;
+    }
+    Ok(())
+}
+
+/// Fail with description of FEATURE if it needs newer version than VERSION
+fn require(version: PdfVersion, needed: PdfVersion, feature: &str) -> crate::Result<()> {
+    if version < needed {
+        return Err(Error::InvalidArgument(format!(
+            "{} requires PDF {}, but output version is {}",
+            feature, needed, version
+        )));
+    }
+    Ok(())
+}
+
+impl Pdf {
+    /// Make new empty document declaring VERSION in its header
+    pub fn with_version(version: PdfVersion) -> Self {
+        let mut pdf = Self::new();
+        pdf.doc.version = version.as_str().into();
+        pdf
+    }
+
+    /// Make document written as VERSION. features VERSION lacks are downgraded when
+    /// they can be without changing how document looks, like page transitions falling
+    /// back to plain replace, and otherwise refused.
+    pub(crate) fn set_output_version(
+        &mut self,
+        version: PdfVersion,
+        options: &SaveOptions,
+    ) -> crate::Result<()> {
+        if options.object_streams {
+            require(version, PdfVersion::V1_5, "object streams")?;
+        }
+        if options.attachment_password.is_some() {
+            // attachments are encrypted with AES, which needs crypt filters of 1.6
+            require(version, PdfVersion::V1_7, "attachment encryption")?;
+        }
+
+        if let Ok(encrypt) = self.doc.trailer.get(b"Encrypt") {
+            let encrypt = match encrypt {
+                Object::Reference(id) => self.doc.get_dictionary(*id)?,
+                obj => obj.as_dict()?,
+            };
+            match encrypt.get(b"V").and_then(Object::as_i64).unwrap_or(0) {
+                4 => require(version, PdfVersion::V1_7, "AES-128 encryption")?,
+                5.. => require(version, PdfVersion::V2_0, "AES-256 encryption")?,
+                _ => {}
+            }
+        }
+
+        let catalog = self.doc.catalog()?;
+        if catalog.has(b"OCProperties") {
+            require(version, PdfVersion::V1_5, "optional content (layers)")?;
+        }
+        match catalog.get(b"PageMode").and_then(Object::as_name) {
+            Ok(b"UseOC") => require(version, PdfVersion::V1_5, "page mode UseOC")?,
+            // introduced in 1.6, which is not written, so 1.7 is needed
+            Ok(b"UseAttachments") => {
+                require(version, PdfVersion::V1_7, "page mode UseAttachments")?
+            }
+            _ => {}
+        }
+
+        let mut downgraded = 0;
+        for obj in self.doc.objects.values_mut() {
+            match obj {
+                Object::Stream(stream) => {
+                    check_entries(&stream.dict, version)?;
+                    if has_filter(&stream.dict, b"JPXDecode") {
+                        require(version, PdfVersion::V1_5, "JPEG 2000 image")?;
+                    }
+                    if stream
+                        .dict
+                        .get(b"BitsPerComponent")
+                        .and_then(Object::as_i64)
+                        .ok()
+                        == Some(16)
+                    {
+                        require(version, PdfVersion::V1_5, "16-bit image")?;
+                    }
+                }
+                Object::Dictionary(dict) => {
+                    check_entries(dict, version)?;
+                    if version >= PdfVersion::V1_5 {
+                        continue;
+                    }
+                    let Ok(Object::Dictionary(trans)) = dict.get_mut(b"Trans") else {
+                        continue;
+                    };
+                    let style = trans.get(b"S").and_then(Object::as_name).unwrap_or(b"R");
+                    if TRANSITIONS_1_5.contains(&style) {
+                        trans.set("S", "R");
+                        downgraded += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if downgraded > 0 {
+            log::warn!(
+                "{} page transitions are replaced by plain one, as their style needs PDF 1.5",
+                downgraded
+            );
+        }
+
+        // version in catalog overrides header if newer
+        self.catalog_mut()?.remove(b"Version");
+        self.doc.version = version.as_str().into();
+
+        Ok(())
+    }
+}
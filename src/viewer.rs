@@ -0,0 +1,223 @@
+use std::str::FromStr;
+
+use lopdf::{Dictionary, Object};
+
+use crate::{destination::Destination, permissions::Permission, Pdf};
+
+/// Normalize name given like two-page-left or TwoPageLeft for matching
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// How pages are arranged when document is opened (/PageLayout)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageLayout {
+    /// One page at a time
+    #[default]
+    SinglePage,
+    /// Pages in one continuous column
+    OneColumn,
+    /// Pages in two columns, odd pages on left
+    TwoColumnLeft,
+    /// Pages in two columns, odd pages on right
+    TwoColumnRight,
+    /// Two pages at a time, odd pages on left
+    TwoPageLeft,
+    /// Two pages at a time, odd pages on right
+    TwoPageRight,
+}
+
+impl PageLayout {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::SinglePage => "SinglePage",
+            Self::OneColumn => "OneColumn",
+            Self::TwoColumnLeft => "TwoColumnLeft",
+            Self::TwoColumnRight => "TwoColumnRight",
+            Self::TwoPageLeft => "TwoPageLeft",
+            Self::TwoPageRight => "TwoPageRight",
+        }
+    }
+}
+
+impl FromStr for PageLayout {
+    type Err = anyhow::Error;
+
+    /// Parse layout given like single-page or SinglePage
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "singlepage" => Ok(Self::SinglePage),
+            "onecolumn" => Ok(Self::OneColumn),
+            "twocolumnleft" => Ok(Self::TwoColumnLeft),
+            "twocolumnright" => Ok(Self::TwoColumnRight),
+            "twopageleft" => Ok(Self::TwoPageLeft),
+            "twopageright" => Ok(Self::TwoPageRight),
+            _ => anyhow::bail!("unknown page layout: {}", s),
+        }
+    }
+}
+
+/// Which panel is shown when document is opened (/PageMode)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageMode {
+    /// No panel
+    #[default]
+    UseNone,
+    /// Outline (bookmarks)
+    UseOutlines,
+    /// Page thumbnails
+    UseThumbs,
+    /// Full screen, without menu bar and windows
+    FullScreen,
+    /// Optional content (layers)
+    UseOC,
+    /// Attachments
+    UseAttachments,
+}
+
+impl PageMode {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::UseNone => "UseNone",
+            Self::UseOutlines => "UseOutlines",
+            Self::UseThumbs => "UseThumbs",
+            Self::FullScreen => "FullScreen",
+            Self::UseOC => "UseOC",
+            Self::UseAttachments => "UseAttachments",
+        }
+    }
+}
+
+impl FromStr for PageMode {
+    type Err = anyhow::Error;
+
+    /// Parse mode given like use-outlines or UseOutlines
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "usenone" => Ok(Self::UseNone),
+            "useoutlines" => Ok(Self::UseOutlines),
+            "usethumbs" => Ok(Self::UseThumbs),
+            "fullscreen" => Ok(Self::FullScreen),
+            "useoc" => Ok(Self::UseOC),
+            "useattachments" => Ok(Self::UseAttachments),
+            _ => anyhow::bail!("unknown page mode: {}", s),
+        }
+    }
+}
+
+/// Flag of viewer window in /ViewerPreferences
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewerPreference {
+    HideToolbar,
+    HideMenubar,
+    /// Hide scroll bars and navigation controls
+    HideWindowUI,
+    /// Resize window to fit first page
+    FitWindow,
+    CenterWindow,
+    /// Show document title instead of file name in title bar
+    DisplayDocTitle,
+}
+
+impl ViewerPreference {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::HideToolbar => "HideToolbar",
+            Self::HideMenubar => "HideMenubar",
+            Self::HideWindowUI => "HideWindowUI",
+            Self::FitWindow => "FitWindow",
+            Self::CenterWindow => "CenterWindow",
+            Self::DisplayDocTitle => "DisplayDocTitle",
+        }
+    }
+}
+
+impl FromStr for ViewerPreference {
+    type Err = anyhow::Error;
+
+    /// Parse preference given like fit-window or FitWindow
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "hidetoolbar" => Ok(Self::HideToolbar),
+            "hidemenubar" => Ok(Self::HideMenubar),
+            "hidewindowui" => Ok(Self::HideWindowUI),
+            "fitwindow" => Ok(Self::FitWindow),
+            "centerwindow" => Ok(Self::CenterWindow),
+            "displaydoctitle" => Ok(Self::DisplayDocTitle),
+            _ => anyhow::bail!("unknown viewer preference: {}", s),
+        }
+    }
+}
+
+impl Pdf {
+    /// Set how pages are arranged when document is opened
+    pub fn set_page_layout(&mut self, layout: PageLayout) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        self.catalog_mut()?
+            .set("PageLayout", Object::Name(layout.name().into()));
+
+        Ok(())
+    }
+
+    /// Set which panel is shown when document is opened
+    pub fn set_page_mode(&mut self, mode: PageMode) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        self.catalog_mut()?
+            .set("PageMode", Object::Name(mode.name().into()));
+
+        Ok(())
+    }
+
+    /// Open document at PAGE with ZOOM factor like 1.5 for 150%, or fitting whole page
+    /// in window if None
+    pub fn set_open_action(&mut self, page: u32, zoom: Option<f32>) -> crate::Result<()> {
+        let dest = match zoom {
+            Some(zoom) => Destination::Xyz {
+                left: None,
+                top: None,
+                zoom: Some(zoom),
+            },
+            None => Destination::Fit,
+        };
+
+        self.set_open_destination(page, &dest)
+    }
+
+    /// Open document at PAGE displayed as DEST
+    pub fn set_open_destination(&mut self, page: u32, dest: &Destination) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        let page_id = self.get_page_id(page)?;
+        self.catalog_mut()?
+            .set("OpenAction", dest.to_object(page_id));
+
+        Ok(())
+    }
+
+    /// Set flag PREFERENCE of viewer window to VALUE
+    pub fn set_viewer_preference(
+        &mut self,
+        preference: ViewerPreference,
+        value: bool,
+    ) -> crate::Result<()> {
+        self.check_permission(Permission::Modify)?;
+
+        // preferences may be indirect object, which is replaced by direct one
+        let existing = match self.doc.catalog()?.get(b"ViewerPreferences") {
+            Ok(Object::Reference(id)) => self.doc.get_dictionary(*id).ok().cloned(),
+            Ok(Object::Dictionary(dict)) => Some(dict.clone()),
+            _ => None,
+        };
+        let mut preferences = existing.unwrap_or_else(Dictionary::new);
+        preferences.set(preference.name(), value);
+
+        self.catalog_mut()?.set("ViewerPreferences", preferences);
+
+        Ok(())
+    }
+}
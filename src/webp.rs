@@ -0,0 +1,45 @@
+use std::io::Cursor;
+
+use image::ImageFormat;
+use lopdf::Stream;
+
+use crate::{xobject, Pdf};
+
+/// Check whether WebP data is VP8L (lossless) coded, by looking for its chunk
+/// after RIFF header and optional VP8X and other extended chunks
+fn is_lossless(bytes: &[u8]) -> bool {
+    let mut pos = 12;
+
+    while let Some(header) = bytes.get(pos..pos + 8) {
+        match &header[..4] {
+            b"VP8L" => return true,
+            b"VP8 " => return false,
+            _ => {
+                let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                // chunks are padded to even size
+                pos += 8 + size as usize + (size as usize & 1);
+            }
+        }
+    }
+
+    false
+}
+
+impl Pdf {
+    /// Build image XObject from WebP data. lossless images are flate compressed like PNG,
+    /// and lossy ones re-encoded as JPEG, since PDF supports neither VP8 nor VP8L.
+    pub(crate) fn webp_xobject(&mut self, bytes: &[u8]) -> anyhow::Result<Stream> {
+        self.check_encoded_image(bytes)?;
+
+        let img = image::load_from_memory_with_format(bytes, ImageFormat::WebP)?;
+
+        if is_lossless(bytes) {
+            let mut png = Vec::new();
+            img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            Ok(self.png_xobject(&png)?)
+        } else {
+            let jpeg = xobject::jpeg_stream(&img, self.image_options.quality())?.content;
+            Ok(self.jpeg_xobject(&jpeg)?)
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, io::Write};
+
+use flate2::{write::ZlibEncoder, Compression};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::version::PdfVersion;
+
+/// Max number of objects packed into one object stream
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// Options for saving document
+#[derive(Clone, Debug, Default)]
+pub struct SaveOptions {
+    /// Pack non-stream objects into object streams and write xref stream (PDF 1.5+)
+    pub object_streams: bool,
+    /// Encrypt embedded files with this password, leaving rest of document readable
+    pub attachment_password: Option<String>,
+    /// Renumber objects canonically and derive document ID from content, so that same
+    /// document is always written as same bytes. use with `Pdf::set_timestamp`
+    pub deterministic: bool,
+    /// Write document as this version, refusing features it lacks. version of document
+    /// is kept if None
+    pub version: Option<PdfVersion>,
+}
+
+pub(crate) enum XrefEntry {
+    InUse { offset: usize, generation: u16 },
+    Free { next: u32, generation: u16 },
+    Compressed { stream: u32, index: usize },
+}
+
+/// Serialize OBJ in PDF syntax.
+/// Unlike lopdf's own writer this is usable for single objects, which is needed
+/// for incremental updates and object streams.
+pub fn write_object(out: &mut Vec<u8>, obj: &Object) {
+    match obj {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(b) => out.extend_from_slice(if *b { b"true" } else { b"false" }),
+        Object::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        Object::Real(r) => write_real(out, *r as f64),
+        Object::Name(name) => write_name(out, name),
+        Object::String(s, StringFormat::Literal) => write_literal_string(out, s),
+        Object::String(s, StringFormat::Hexadecimal) => write_hex_string(out, s),
+        Object::Array(arr) => {
+            out.push(b'[');
+            for (i, item) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict),
+        Object::Stream(stream) => write_stream(out, stream),
+        Object::Reference((id, gen)) => {
+            out.extend_from_slice(format!("{} {} R", id, gen).as_bytes())
+        }
+    }
+}
+
+/// Serialize OBJ as indirect object `id gen obj ... endobj`
+pub fn write_indirect_object(out: &mut Vec<u8>, id: ObjectId, obj: &Object) {
+    out.extend_from_slice(format!("{} {} obj\n", id.0, id.1).as_bytes());
+    write_object(out, obj);
+    out.extend_from_slice(b"\nendobj\n");
+}
+
+pub fn write_dictionary(out: &mut Vec<u8>, dict: &Dictionary) {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        write_name(out, key);
+        out.push(b' ');
+        write_object(out, value);
+    }
+    out.extend_from_slice(b">>");
+}
+
+fn write_stream(out: &mut Vec<u8>, stream: &Stream) {
+    let mut dict = stream.dict.clone();
+    dict.set("Length", stream.content.len() as i64);
+
+    write_dictionary(out, &dict);
+    out.extend_from_slice(b"\nstream\n");
+    out.extend_from_slice(&stream.content);
+    out.extend_from_slice(b"\nendstream");
+}
+
+fn write_real(out: &mut Vec<u8>, r: f64) {
+    let s = format!("{:.5}", r);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    out.extend_from_slice(if s.is_empty() || s == "-" { "0" } else { s }.as_bytes());
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for &b in name {
+        match b {
+            b'!'..=b'~'
+                if !matches!(
+                    b,
+                    b'#' | b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+                ) =>
+            {
+                out.push(b)
+            }
+            _ => out.extend_from_slice(format!("#{:02X}", b).as_bytes()),
+        }
+    }
+}
+
+fn write_literal_string(out: &mut Vec<u8>, s: &[u8]) {
+    out.push(b'(');
+    for &b in s {
+        match b {
+            b'(' | b')' | b'\\' => {
+                out.push(b'\\');
+                out.push(b);
+            }
+            b'\r' => out.extend_from_slice(b"\\r"),
+            _ => out.push(b),
+        }
+    }
+    out.push(b')');
+}
+
+fn write_hex_string(out: &mut Vec<u8>, s: &[u8]) {
+    out.push(b'<');
+    for b in s {
+        out.extend_from_slice(format!("{:02X}", b).as_bytes());
+    }
+    out.push(b'>');
+}
+
+pub(crate) fn deflate(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// Write classic cross-reference table with ENTRIES split into subsections
+pub(crate) fn write_xref_table(out: &mut Vec<u8>, entries: &BTreeMap<u32, XrefEntry>) {
+    out.extend_from_slice(b"xref\n");
+
+    let ids: Vec<u32> = entries.keys().copied().collect();
+    let mut start = 0;
+
+    while start < ids.len() {
+        let mut end = start + 1;
+        while end < ids.len() && ids[end] == ids[end - 1] + 1 {
+            end += 1;
+        }
+
+        out.extend_from_slice(format!("{} {}\n", ids[start], end - start).as_bytes());
+        for id in &ids[start..end] {
+            let line = match entries[id] {
+                XrefEntry::InUse { offset, generation } => {
+                    format!("{:010} {:05} n\r\n", offset, generation)
+                }
+                XrefEntry::Free { next, generation } => {
+                    format!("{:010} {:05} f\r\n", next, generation)
+                }
+                XrefEntry::Compressed { .. } => {
+                    unreachable!("compressed object in classic xref table")
+                }
+            };
+            out.extend_from_slice(line.as_bytes());
+        }
+
+        start = end;
+    }
+}
+
+/// Build xref stream covering object 0 to SIZE - 1.
+/// objects missing from ENTRIES are written as free.
+pub(crate) fn xref_stream(
+    entries: &BTreeMap<u32, XrefEntry>,
+    size: u32,
+    mut trailer: Dictionary,
+) -> anyhow::Result<Stream> {
+    let max_field = entries
+        .values()
+        .map(|entry| match *entry {
+            XrefEntry::InUse { offset, .. } => offset as u64,
+            XrefEntry::Free { next, .. } => next as u64,
+            XrefEntry::Compressed { stream, .. } => stream as u64,
+        })
+        .max()
+        .unwrap_or(0);
+    let width = ((64 - max_field.leading_zeros() as usize + 7) / 8).max(1);
+
+    let mut data = Vec::with_capacity(size as usize * (width + 3));
+    for id in 0..size {
+        let (kind, field2, field3) = match entries.get(&id) {
+            Some(XrefEntry::Free { next, generation }) => (0, *next as u64, *generation as u64),
+            Some(XrefEntry::InUse { offset, generation }) => {
+                (1, *offset as u64, *generation as u64)
+            }
+            Some(XrefEntry::Compressed { stream, index }) => (2, *stream as u64, *index as u64),
+            None if id == 0 => (0, 0, 65535),
+            None => (0, 0, 0),
+        };
+
+        data.push(kind);
+        data.extend_from_slice(&field2.to_be_bytes()[8 - width..]);
+        data.extend_from_slice(&(field3 as u16).to_be_bytes());
+    }
+
+    for key in [
+        &b"Prev"[..],
+        b"XRefStm",
+        b"Index",
+        b"DecodeParms",
+        b"Length",
+    ] {
+        trailer.remove(key);
+    }
+    trailer.set("Type", "XRef");
+    trailer.set("Size", size as i64);
+    trailer.set("W", vec![1.into(), (width as i64).into(), 2.into()]);
+    trailer.set("Filter", "FlateDecode");
+
+    Ok(Stream::new(trailer, deflate(&data)?))
+}
+
+/// Serialize whole DOC with non-stream objects packed into object streams
+/// and cross-reference written as xref stream.
+pub fn write_with_object_streams(doc: &Document) -> anyhow::Result<Vec<u8>> {
+    let version = if doc.version.as_str() < "1.5" {
+        "1.5"
+    } else {
+        doc.version.as_str()
+    };
+
+    let mut out = format!("%PDF-{}\n", version).into_bytes();
+    out.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+
+    let encrypt_id = doc
+        .trailer
+        .get(b"Encrypt")
+        .and_then(Object::as_reference)
+        .ok();
+
+    let mut entries = BTreeMap::new();
+    let mut packed = Vec::new();
+
+    for (&id, obj) in &doc.objects {
+        match obj {
+            // contents of existing object streams are already expanded into objects
+            Object::Stream(stream) if matches!(stream.dict.type_name(), Ok("ObjStm" | "XRef")) => {}
+            Object::Stream(_) => {
+                entries.insert(
+                    id.0,
+                    XrefEntry::InUse {
+                        offset: out.len(),
+                        generation: id.1,
+                    },
+                );
+                write_indirect_object(&mut out, id, obj);
+            }
+            _ if id.1 != 0 || Some(id) == encrypt_id => {
+                entries.insert(
+                    id.0,
+                    XrefEntry::InUse {
+                        offset: out.len(),
+                        generation: id.1,
+                    },
+                );
+                write_indirect_object(&mut out, id, obj);
+            }
+            _ => packed.push((id, obj)),
+        }
+    }
+
+    let mut next_id = doc.max_id + 1;
+
+    for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+        let stream_id = (next_id, 0);
+        next_id += 1;
+
+        let mut header = String::new();
+        let mut body = Vec::new();
+
+        for (index, (id, obj)) in chunk.iter().enumerate() {
+            header.push_str(&format!("{} {} ", id.0, body.len()));
+            write_object(&mut body, obj);
+            body.push(b'\n');
+
+            entries.insert(
+                id.0,
+                XrefEntry::Compressed {
+                    stream: stream_id.0,
+                    index,
+                },
+            );
+        }
+
+        let first = header.len();
+        let mut content = header.into_bytes();
+        content.extend_from_slice(&body);
+
+        let stream = Stream::new(
+            dictionary! {
+                "Type" => "ObjStm",
+                "N" => chunk.len() as i64,
+                "First" => first as i64,
+                "Filter" => "FlateDecode",
+            },
+            deflate(&content)?,
+        );
+
+        entries.insert(
+            stream_id.0,
+            XrefEntry::InUse {
+                offset: out.len(),
+                generation: 0,
+            },
+        );
+        write_indirect_object(&mut out, stream_id, &Object::Stream(stream));
+    }
+
+    let xref_id = (next_id, 0);
+    let startxref = out.len();
+    entries.insert(
+        xref_id.0,
+        XrefEntry::InUse {
+            offset: startxref,
+            generation: 0,
+        },
+    );
+
+    let xref = xref_stream(&entries, xref_id.0 + 1, doc.trailer.clone())?;
+    write_indirect_object(&mut out, xref_id, &Object::Stream(xref));
+
+    out.extend_from_slice(format!("startxref\n{}\n%%EOF\n", startxref).as_bytes());
+
+    Ok(out)
+}
@@ -0,0 +1,580 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageBuffer, ImageFormat};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::{optimize, writer};
+
+pub(crate) const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Image XObject drawn on page with its transformation matrix
+pub(crate) struct Placement {
+    pub id: ObjectId,
+    pub ctm: [f32; 6],
+}
+
+impl Placement {
+    /// Get size of placed image in points
+    pub fn size(&self) -> (f32, f32) {
+        let [a, b, c, d, _, _] = self.ctm;
+        ((a * a + b * b).sqrt(), (c * c + d * d).sqrt())
+    }
+}
+
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed(Vec<u8>),
+}
+
+impl ColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed(_) => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+        }
+    }
+}
+
+pub(crate) fn as_number(obj: &Object) -> Option<f32> {
+    match obj {
+        Object::Integer(i) => Some(*i as f32),
+        Object::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+fn deref<'a>(doc: &'a Document, obj: &'a Object) -> &'a Object {
+    match obj {
+        Object::Reference(id) => doc.get_object(*id).unwrap_or(obj),
+        _ => obj,
+    }
+}
+
+/// Multiply matrix M by N
+pub(crate) fn multiply(m: [f32; 6], n: [f32; 6]) -> [f32; 6] {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+/// Get XObject resources of page by name
+pub(crate) fn page_xobjects(doc: &Document, page_id: ObjectId) -> BTreeMap<Vec<u8>, ObjectId> {
+    let (dict, ids) = doc.get_page_resources(page_id);
+    let mut result = BTreeMap::new();
+
+    for resources in dict
+        .into_iter()
+        .chain(ids.iter().filter_map(|id| doc.get_dictionary(*id).ok()))
+    {
+        let Ok(Object::Dictionary(xobjects)) = resources.get(b"XObject").map(|x| deref(doc, x)) else {
+            continue;
+        };
+
+        for (name, obj) in xobjects.iter() {
+            if let Ok(id) = obj.as_reference() {
+                result.entry(name.clone()).or_insert(id);
+            }
+        }
+    }
+
+    result
+}
+
+pub(crate) fn is_image(doc: &Document, id: ObjectId) -> bool {
+    matches!(
+        doc.get_object(id),
+        Ok(Object::Stream(stream)) if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Image"[..])
+    )
+}
+
+/// Find image XObjects drawn directly by content stream of page
+pub(crate) fn image_placements(
+    doc: &Document,
+    page_id: ObjectId,
+) -> anyhow::Result<Vec<Placement>> {
+    let content = doc.get_and_decode_page_content(page_id)?;
+    let xobjects = page_xobjects(doc, page_id);
+
+    let mut result = Vec::new();
+    let mut stack = Vec::new();
+    let mut ctm = IDENTITY;
+
+    for op in content.operations {
+        match op.operator.as_str() {
+            "q" => stack.push(ctm),
+            "Q" => ctm = stack.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                let m: Vec<f32> = op.operands.iter().filter_map(as_number).collect();
+                if let [a, b, c, d, e, f] = m[..] {
+                    ctm = multiply([a, b, c, d, e, f], ctm);
+                }
+            }
+            "Do" => {
+                let Some(name) = op.operands.first().and_then(|x| x.as_name().ok()) else {
+                    continue;
+                };
+
+                if let Some(&id) = xobjects.get(name) {
+                    if is_image(doc, id) {
+                        result.push(Placement { id, ctm });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+/// Find image drawn largest on page, which is whole page for scanned documents
+pub(crate) fn largest_image(doc: &Document, page_id: ObjectId) -> anyhow::Result<Option<ObjectId>> {
+    Ok(largest_placement(doc, page_id)?.map(|x| x.id))
+}
+
+/// Find placement of image drawn largest on page, like `largest_image`
+pub(crate) fn largest_placement(
+    doc: &Document,
+    page_id: ObjectId,
+) -> anyhow::Result<Option<Placement>> {
+    Ok(image_placements(doc, page_id)?.into_iter().max_by(|a, b| {
+        let (aw, ah) = a.size();
+        let (bw, bh) = b.size();
+        (aw * ah).total_cmp(&(bw * bh))
+    }))
+}
+
+fn color_space(doc: &Document, obj: &Object) -> anyhow::Result<ColorSpace> {
+    match deref(doc, obj) {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"CalGray" | b"G" => Ok(ColorSpace::Gray),
+            b"DeviceRGB" | b"CalRGB" | b"RGB" => Ok(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => Ok(ColorSpace::Cmyk),
+            _ => anyhow::bail!("unsupported color space: {}", String::from_utf8_lossy(name)),
+        },
+        Object::Array(arr) => match arr.first().and_then(|x| x.as_name().ok()) {
+            Some(b"ICCBased") => {
+                let n = arr
+                    .get(1)
+                    .map(|x| deref(doc, x))
+                    .and_then(|x| x.as_stream().ok())
+                    .and_then(|x| x.dict.get(b"N").and_then(Object::as_i64).ok())
+                    .unwrap_or(3);
+
+                match n {
+                    1 => Ok(ColorSpace::Gray),
+                    4 => Ok(ColorSpace::Cmyk),
+                    _ => Ok(ColorSpace::Rgb),
+                }
+            }
+            Some(b"CalGray") => Ok(ColorSpace::Gray),
+            Some(b"CalRGB") => Ok(ColorSpace::Rgb),
+            Some(b"Indexed" | b"I") => {
+                let base = color_space(doc, arr.get(1).unwrap_or(&Object::Null))?;
+                anyhow::ensure!(
+                    matches!(base, ColorSpace::Rgb),
+                    "unsupported base color space of indexed image"
+                );
+
+                let palette = match arr.get(3).map(|x| deref(doc, x)) {
+                    Some(Object::String(bytes, _)) => bytes.clone(),
+                    Some(Object::Stream(stream)) => stream
+                        .decompressed_content()
+                        .unwrap_or_else(|_| stream.content.clone()),
+                    _ => anyhow::bail!("palette of indexed color space not found"),
+                };
+
+                Ok(ColorSpace::Indexed(palette))
+            }
+            _ => anyhow::bail!("unsupported color space"),
+        },
+        _ => anyhow::bail!("unsupported color space"),
+    }
+}
+
+/// Whether image XObject DICT has CMYK color space, directly or by ICC profile
+pub(crate) fn is_cmyk(doc: &Document, dict: &Dictionary) -> bool {
+    dict.get(b"ColorSpace").map_or(false, |x| {
+        matches!(color_space(doc, x), Ok(ColorSpace::Cmyk))
+    })
+}
+
+/// Get ids of images used by other images as /SMask or /Mask
+pub(crate) fn mask_images(doc: &Document) -> BTreeSet<ObjectId> {
+    doc.objects
+        .values()
+        .filter_map(|x| x.as_stream().ok())
+        .flat_map(|x| [x.dict.get(b"SMask"), x.dict.get(b"Mask")])
+        .filter_map(|x| x.and_then(Object::as_reference).ok())
+        .collect()
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverse PNG predictor applied on each row of RAW
+fn unpredict_png(raw: &[u8], row_len: usize, bpp: usize) -> anyhow::Result<Vec<u8>> {
+    let mut result: Vec<u8> = Vec::with_capacity(raw.len());
+    let mut prev = vec![0u8; row_len];
+
+    for row in raw.chunks(row_len + 1) {
+        anyhow::ensure!(row.len() == row_len + 1, "truncated predictor row");
+        let (kind, row) = (row[0], &row[1..]);
+        let mut cur = row.to_vec();
+
+        for i in 0..row_len {
+            let left = if i >= bpp { cur[i - bpp] } else { 0 };
+            let up = prev[i];
+            let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+
+            cur[i] = match kind {
+                0 => cur[i],
+                1 => cur[i].wrapping_add(left),
+                2 => cur[i].wrapping_add(up),
+                3 => cur[i].wrapping_add(((left as u16 + up as u16) / 2) as u8),
+                4 => cur[i].wrapping_add(paeth(left, up, up_left)),
+                _ => anyhow::bail!("invalid png predictor: {}", kind),
+            };
+        }
+
+        result.extend_from_slice(&cur);
+        prev = cur;
+    }
+
+    Ok(result)
+}
+
+/// Expand samples of less than 8 bits into one byte per sample
+fn unpack_bits(raw: &[u8], width: usize, height: usize, bpc: usize) -> Vec<u8> {
+    let row_len = (width * bpc + 7) / 8;
+    let max = (1u16 << bpc) - 1;
+    let mut result = Vec::with_capacity(width * height);
+
+    for row in raw.chunks(row_len).take(height) {
+        for x in 0..width {
+            let bit = x * bpc;
+            let byte = row.get(bit / 8).copied().unwrap_or(0);
+            let shift = 8 - bpc - bit % 8;
+            result.push(((byte >> shift) as u16 & max) as u8);
+        }
+    }
+
+    result
+}
+
+/// Get ranges of /Decode array of image XObject DICT, or None if it has none
+fn decode_ranges(doc: &Document, dict: &Dictionary) -> Option<Vec<[f32; 2]>> {
+    let arr = dict
+        .get(b"Decode")
+        .map(|x| deref(doc, x))
+        .ok()?
+        .as_array()
+        .ok()?;
+    let values: Vec<f32> = arr
+        .iter()
+        .filter_map(|x| as_number(deref(doc, x)))
+        .collect();
+
+    (!values.is_empty() && values.len() % 2 == 0)
+        .then(|| values.chunks_exact(2).map(|x| [x[0], x[1]]).collect())
+}
+
+/// Map SAMPLES through RANGES of /Decode, component by component. sample of MAX maps
+/// to upper end of range, and result is scaled by SCALE, which is MAX for color
+/// components and 1 for indexes of palette (8.9.5.2 of ISO 32000-1).
+fn apply_decode<T>(samples: &mut [T], ranges: &[[f32; 2]], max: f32, scale: f32)
+where
+    T: Copy + Into<f32> + TryFrom<u32>,
+{
+    for (i, x) in samples.iter_mut().enumerate() {
+        let [lo, hi] = ranges[i % ranges.len()];
+        let value = (lo + Into::<f32>::into(*x) / max * (hi - lo)) * scale;
+        if let Ok(value) = T::try_from(value.round().clamp(0.0, max) as u32) {
+            *x = value;
+        }
+    }
+}
+
+/// Get /Width and /Height of image XObject DICT, resolving references
+pub(crate) fn image_size(doc: &Document, dict: &Dictionary) -> anyhow::Result<(u32, u32)> {
+    let dimension = |key: &[u8]| -> anyhow::Result<u32> {
+        let value = dict
+            .get(key)
+            .map(|x| deref(doc, x))
+            .and_then(Object::as_i64)?;
+        u32::try_from(value).map_err(|_| anyhow::anyhow!("invalid image size: {}", value))
+    };
+
+    Ok((dimension(b"Width")?, dimension(b"Height")?))
+}
+
+/// Decode image XObject STREAM into pixels as displayed, with /Decode applied.
+/// `Pdf::decode_image` is used instead, to apply limits of document.
+pub(crate) fn decode_image(doc: &Document, stream: &Stream) -> anyhow::Result<DynamicImage> {
+    let dict = &stream.dict;
+
+    anyhow::ensure!(
+        !dict
+            .get(b"ImageMask")
+            .and_then(Object::as_bool)
+            .unwrap_or(false),
+        "stencil mask is not supported"
+    );
+
+    let ranges = decode_ranges(doc, dict);
+
+    let raw = match optimize::filter_names(dict).as_slice() {
+        [b"DCTDecode"] => {
+            let img = image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg)?;
+            // CMYK is converted by decoder, so only gray and RGB samples can be mapped
+            return Ok(match (ranges, img) {
+                (Some(ranges), DynamicImage::ImageLuma8(mut img)) if ranges.len() == 1 => {
+                    apply_decode(&mut *img, &ranges, 255.0, 255.0);
+                    DynamicImage::ImageLuma8(img)
+                }
+                (Some(ranges), DynamicImage::ImageRgb8(mut img)) if ranges.len() == 3 => {
+                    apply_decode(&mut *img, &ranges, 255.0, 255.0);
+                    DynamicImage::ImageRgb8(img)
+                }
+                (_, img) => img,
+            });
+        }
+        [] => stream.content.clone(),
+        [b"FlateDecode"] => optimize::inflate(&stream.content)?,
+        _ => anyhow::bail!("unsupported image filter"),
+    };
+
+    let (width, height) = image_size(doc, dict)?;
+    let (width, height) = (width as usize, height as usize);
+    let bpc = dict.get(b"BitsPerComponent").and_then(Object::as_i64)? as usize;
+    let cs = color_space(doc, dict.get(b"ColorSpace")?)?;
+    let colors = cs.components();
+
+    let raw = match dict.get(b"DecodeParms").map(|x| deref(doc, x)) {
+        Ok(Object::Dictionary(parms)) => {
+            match parms
+                .get(b"Predictor")
+                .and_then(Object::as_i64)
+                .unwrap_or(1)
+            {
+                1 => raw,
+                10..=15 => {
+                    let row_len = (width * colors * bpc + 7) / 8;
+                    unpredict_png(&raw, row_len, ((colors * bpc) / 8).max(1))?
+                }
+                predictor => anyhow::bail!("unsupported predictor: {}", predictor),
+            }
+        }
+        _ => raw,
+    };
+
+    let (w, h) = (width as u32, height as u32);
+    let mut samples = match bpc {
+        8 => raw,
+        1 | 2 | 4 if colors == 1 => {
+            let samples = unpack_bits(&raw, width, height, bpc);
+            match cs {
+                // scale gray levels to full 8 bit range
+                ColorSpace::Gray => {
+                    let max = (1u16 << bpc) - 1;
+                    samples
+                        .into_iter()
+                        .map(|x| (x as u16 * 255 / max) as u8)
+                        .collect()
+                }
+                _ => samples,
+            }
+        }
+        16 => {
+            let mut samples: Vec<u16> = raw
+                .chunks_exact(2)
+                .map(|x| u16::from_be_bytes([x[0], x[1]]))
+                .collect();
+            if let Some(ranges) = &ranges {
+                apply_decode(&mut samples, ranges, 65535.0, 65535.0);
+            }
+
+            return match cs {
+                ColorSpace::Gray => {
+                    ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageLuma16)
+                }
+                ColorSpace::Rgb => {
+                    ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgb16)
+                }
+                _ => None,
+            }
+            .ok_or(anyhow::anyhow!("invalid 16 bit image data"));
+        }
+        _ => anyhow::bail!("unsupported bits per component: {}", bpc),
+    };
+    if let Some(ranges) = &ranges {
+        match cs {
+            ColorSpace::Indexed(_) => {
+                let max = ((1u32 << bpc) - 1) as f32;
+                apply_decode(&mut samples, ranges, max, 1.0);
+            }
+            _ => apply_decode(&mut samples, ranges, 255.0, 255.0),
+        }
+    }
+
+    let img = match cs {
+        ColorSpace::Gray => ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageLuma8),
+        ColorSpace::Rgb => ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgb8),
+        ColorSpace::Cmyk => {
+            let rgb = samples
+                .chunks_exact(4)
+                .flat_map(|x| {
+                    let k = 255 - x[3] as u16;
+                    [0, 1, 2].map(|i| ((255 - x[i] as u16) * k / 255) as u8)
+                })
+                .collect();
+            ImageBuffer::from_raw(w, h, rgb).map(DynamicImage::ImageRgb8)
+        }
+        ColorSpace::Indexed(palette) => {
+            let rgb = samples
+                .iter()
+                .flat_map(|&x| {
+                    let i = x as usize * 3;
+                    [0, 1, 2].map(|c| palette.get(i + c).copied().unwrap_or(0))
+                })
+                .collect();
+            ImageBuffer::from_raw(w, h, rgb).map(DynamicImage::ImageRgb8)
+        }
+    };
+
+    img.ok_or(anyhow::anyhow!("invalid image data"))
+}
+
+/// Encode IMG as JPEG image XObject with QUALITY
+pub(crate) fn jpeg_stream(img: &DynamicImage, quality: u8) -> anyhow::Result<Stream> {
+    let img = match img.color().channel_count() {
+        1 | 2 => DynamicImage::ImageLuma8(img.to_luma8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    };
+
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, quality).encode(
+        img.as_bytes(),
+        img.width(),
+        img.height(),
+        img.color(),
+    )?;
+
+    let mut dict = image_dict(&img);
+    dict.set("Filter", "DCTDecode");
+
+    Ok(Stream::new(dict, bytes))
+}
+
+/// Encode IMG as flate compressed image XObject
+pub(crate) fn flate_stream(img: &DynamicImage) -> anyhow::Result<Stream> {
+    let img = match img.color().channel_count() {
+        1 | 2 => DynamicImage::ImageLuma8(img.to_luma8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    };
+
+    let mut dict = image_dict(&img);
+    dict.set("Filter", "FlateDecode");
+
+    Ok(Stream::new(dict, writer::deflate(img.as_bytes())?))
+}
+
+fn image_dict(img: &DynamicImage) -> Dictionary {
+    let cs = if img.color().channel_count() == 1 {
+        "DeviceGray"
+    } else {
+        "DeviceRGB"
+    };
+
+    lopdf::dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => img.width(),
+        "Height" => img.height(),
+        "BitsPerComponent" => 8,
+        "ColorSpace" => cs,
+    }
+}
+
+/// Replace image stream ID with NEW, keeping entries like /SMask of the original
+pub(crate) fn replace_image(doc: &mut Document, id: ObjectId, new: Stream) -> anyhow::Result<()> {
+    let Object::Stream(stream) = doc.get_object_mut(id)? else {
+        anyhow::bail!("object {:?} is not a stream", id);
+    };
+
+    let mut dict = stream.dict.clone();
+    for key in [
+        &b"DecodeParms"[..],
+        b"Decode",
+        b"Filter",
+        b"ColorSpace",
+        b"Length",
+    ] {
+        dict.remove(key);
+    }
+    for (key, value) in new.dict.iter() {
+        dict.set(key.clone(), value.clone());
+    }
+
+    *stream = Stream::new(dict, new.content);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make uncompressed 1 bit gray image of 8 x 1 pixels from ROW, with DECODE array
+    fn bilevel_image(row: u8, decode: Option<[i64; 2]>) -> Stream {
+        let mut dict = lopdf::dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 8,
+            "Height" => 1,
+            "BitsPerComponent" => 1,
+            "ColorSpace" => "DeviceGray",
+        };
+        if let Some([lo, hi]) = decode {
+            dict.set("Decode", vec![lo.into(), hi.into()]);
+        }
+        Stream::new(dict, vec![row])
+    }
+
+    #[test]
+    fn decode_image_bilevel() {
+        let img = decode_image(&Document::new(), &bilevel_image(0b1111_0000, None)).unwrap();
+
+        assert_eq!(img.to_luma8().into_raw(), [255, 255, 255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_image_applies_inverted_decode() {
+        let stream = bilevel_image(0b1111_0000, Some([1, 0]));
+        let img = decode_image(&Document::new(), &stream).unwrap();
+
+        assert_eq!(img.to_luma8().into_raw(), [0, 0, 0, 0, 255, 255, 255, 255]);
+    }
+}